@@ -1,5 +1,5 @@
 use monero_harness::Monero;
-use monero_rpc::monerod::MonerodRpc as _;
+use monero_rpc::monerod::{KeyImageSpentStatus, MonerodRpc as _};
 use std::time::Duration;
 use testcontainers::clients::Cli;
 use tokio::time;
@@ -29,3 +29,34 @@ async fn init_miner_and_mine_to_miner_address() {
 
     assert!(block_height > 70);
 }
+
+#[tokio::test]
+async fn monerod_reports_info_fees_and_pool() {
+    let _guard = tracing_subscriber::fmt()
+        .with_env_filter("warn,test=debug,monero_harness=debug,monero_rpc=debug")
+        .set_default();
+
+    let tc = Cli::default();
+    let (monero, _monerod_container, _wallet_containers) = Monero::new(&tc, vec![]).await.unwrap();
+
+    monero.init_and_start_miner().await.unwrap();
+
+    let client = monero.monerod().client();
+
+    let info = client.get_info().await.unwrap();
+    assert_eq!(info.status, "OK");
+    assert!(info.height > 0);
+
+    let fee_estimate = client.get_fee_estimate().await.unwrap();
+    assert!(fee_estimate.fee > 0);
+
+    let pool = client.get_transaction_pool().await.unwrap();
+    assert_eq!(pool.status, "OK");
+
+    let key_image = "0000000000000000000000000000000000000000000000000000000000000000";
+    let spent = client
+        .is_key_image_spent(vec![key_image.to_owned()])
+        .await
+        .unwrap();
+    assert_eq!(spent, vec![KeyImageSpentStatus::Unspent]);
+}
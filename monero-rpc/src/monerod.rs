@@ -4,6 +4,7 @@ use monero::util::ringct;
 use monero::PublicKey;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize, Serializer};
+use std::convert::TryFrom;
 
 #[jsonrpc_client::api(version = "2.0")]
 pub trait MonerodRpc {
@@ -12,6 +13,16 @@ pub trait MonerodRpc {
     async fn get_block_header_by_height(&self, height: u32) -> BlockHeader;
     async fn get_block_count(&self) -> BlockCount;
     async fn get_block(&self, height: u32) -> GetBlockResponse;
+    async fn get_output_distribution(
+        &self,
+        amounts: Vec<u64>,
+        cumulative: bool,
+        from_height: u64,
+        to_height: u64,
+        binary: bool,
+    ) -> GetOutputDistributionResponse;
+    async fn get_fee_estimate(&self) -> GetFeeEstimate;
+    async fn get_info(&self) -> GetInfo;
 }
 
 #[jsonrpc_client::implement(MonerodRpc)]
@@ -21,6 +32,10 @@ pub struct Client {
     base_url: reqwest::Url,
     get_o_indexes_bin_url: reqwest::Url,
     get_outs_bin_url: reqwest::Url,
+    get_transactions_url: reqwest::Url,
+    send_raw_transaction_url: reqwest::Url,
+    get_transaction_pool_url: reqwest::Url,
+    is_key_image_spent_url: reqwest::Url,
 }
 
 impl Client {
@@ -29,6 +44,13 @@ impl Client {
         Self::new("127.0.0.1".to_owned(), port)
     }
 
+    /// New monerod RPC client for an address of the form `host:port`.
+    pub fn from_daemon_address(address: &str) -> Result<Self> {
+        let (host, port) = parse_daemon_address(address)?;
+
+        Self::new(host, port)
+    }
+
     fn new(host: String, port: u16) -> Result<Self> {
         Ok(Self {
             inner: reqwest::ClientBuilder::new()
@@ -43,6 +65,18 @@ impl Client {
             get_outs_bin_url: format!("http://{}:{}/get_outs.bin", host, port)
                 .parse()
                 .context("url is well formed")?,
+            get_transactions_url: format!("http://{}:{}/get_transactions", host, port)
+                .parse()
+                .context("url is well formed")?,
+            send_raw_transaction_url: format!("http://{}:{}/send_raw_transaction", host, port)
+                .parse()
+                .context("url is well formed")?,
+            get_transaction_pool_url: format!("http://{}:{}/get_transaction_pool", host, port)
+                .parse()
+                .context("url is well formed")?,
+            is_key_image_spent_url: format!("http://{}:{}/is_key_image_spent", host, port)
+                .parse()
+                .context("url is well formed")?,
         })
     }
 
@@ -59,6 +93,106 @@ impl Client {
             .await
     }
 
+    /// Fetches the given transactions, decoded as JSON.
+    pub async fn get_transactions(&self, txids: Vec<Hash>) -> Result<GetTransactionsResponse> {
+        let response: GetTransactionsResponse = self
+            .json_request(
+                self.get_transactions_url.clone(),
+                GetTransactionsPayload {
+                    txs_hashes: txids
+                        .iter()
+                        .map(|txid| hex::encode(txid.as_bytes()))
+                        .collect(),
+                    decode_as_json: true,
+                },
+            )
+            .await?;
+
+        if response.status != "OK" {
+            anyhow::bail!("Failed to get transactions: {}", response.status)
+        }
+
+        Ok(response)
+    }
+
+    /// Submits a serialized transaction to the network. Fails with the reason
+    /// given by monerod if the transaction was rejected.
+    pub async fn send_raw_transaction(
+        &self,
+        tx_as_hex: String,
+        do_not_relay: bool,
+    ) -> Result<SendRawTransactionResponse> {
+        let response: SendRawTransactionResponse = self
+            .json_request(
+                self.send_raw_transaction_url.clone(),
+                SendRawTransactionPayload {
+                    tx_as_hex,
+                    do_not_relay,
+                },
+            )
+            .await?;
+
+        if response.status != "OK" {
+            anyhow::bail!(
+                "Transaction was rejected: {} ({})",
+                response.status,
+                response.reason
+            )
+        }
+
+        Ok(response)
+    }
+
+    /// Fetches the transactions currently in the pool of the daemon.
+    pub async fn get_transaction_pool(&self) -> Result<GetTransactionPoolResponse> {
+        let response: GetTransactionPoolResponse = self
+            .json_request(self.get_transaction_pool_url.clone(), Empty {})
+            .await?;
+
+        if response.status != "OK" {
+            anyhow::bail!("Failed to get transaction pool: {}", response.status)
+        }
+
+        Ok(response)
+    }
+
+    /// Checks whether the given key images were spent, in the same order.
+    pub async fn is_key_image_spent(
+        &self,
+        key_images: Vec<String>,
+    ) -> Result<Vec<KeyImageSpentStatus>> {
+        let response: IsKeyImageSpentResponse = self
+            .json_request(
+                self.is_key_image_spent_url.clone(),
+                IsKeyImageSpentPayload { key_images },
+            )
+            .await?;
+
+        if response.status != "OK" {
+            anyhow::bail!("Failed to check key images: {}", response.status)
+        }
+
+        response
+            .spent_status
+            .into_iter()
+            .map(KeyImageSpentStatus::try_from)
+            .collect()
+    }
+
+    async fn json_request<Req, Res>(&self, url: reqwest::Url, request: Req) -> Result<Res>
+    where
+        Req: Serialize,
+        Res: DeserializeOwned,
+    {
+        let response = self.inner.post(url).json(&request).send().await?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Request failed with status code {}", response.status())
+        }
+
+        Ok(response.json::<Res>().await?)
+    }
+
     async fn binary_request<Req, Res>(&self, url: reqwest::Url, request: Req) -> Result<Res>
     where
         Req: Serialize,
@@ -117,6 +251,163 @@ pub struct GetBlockResponse {
     pub blob: monero::Block,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetFeeEstimate {
+    /// Fee per byte in piconero.
+    pub fee: u64,
+    /// Fees per byte for the four priorities, if supported by the daemon.
+    #[serde(default)]
+    pub fees: Vec<u64>,
+    /// Transaction fees must be a multiple of this.
+    pub quantization_mask: u64,
+    pub status: String,
+    #[serde(default)]
+    pub untrusted: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetInfo {
+    pub height: u64,
+    /// The height the daemon is syncing to, 0 if it is not syncing.
+    pub target_height: u64,
+    /// One of `mainnet`, `testnet`, `stagenet` or `fakechain`.
+    pub nettype: String,
+    pub synchronized: bool,
+    #[serde(default)]
+    pub busy_syncing: bool,
+    #[serde(default)]
+    pub offline: bool,
+    pub top_block_hash: String,
+    pub tx_pool_size: u64,
+    pub incoming_connections_count: u64,
+    pub outgoing_connections_count: u64,
+    #[serde(default)]
+    pub version: String,
+    pub status: String,
+    #[serde(default)]
+    pub untrusted: bool,
+}
+
+impl GetInfo {
+    /// The network of the daemon, `None` for a local `fakechain`.
+    pub fn network(&self) -> Option<monero::Network> {
+        match self.nettype.as_str() {
+            "mainnet" => Some(monero::Network::Mainnet),
+            "testnet" => Some(monero::Network::Testnet),
+            "stagenet" => Some(monero::Network::Stagenet),
+            _ => None,
+        }
+    }
+
+    /// Whether the daemon is synced to the tip of the chain it knows about.
+    pub fn is_synced(&self) -> bool {
+        self.synchronized && !self.busy_syncing && self.target_height <= self.height
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct SendRawTransactionPayload {
+    tx_as_hex: String,
+    do_not_relay: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SendRawTransactionResponse {
+    pub status: String,
+    #[serde(default)]
+    pub reason: String,
+    #[serde(default)]
+    pub double_spend: bool,
+    #[serde(default)]
+    pub fee_too_low: bool,
+    #[serde(default)]
+    pub invalid_input: bool,
+    #[serde(default)]
+    pub invalid_output: bool,
+    #[serde(default)]
+    pub low_mixin: bool,
+    #[serde(default)]
+    pub not_relayed: bool,
+    #[serde(default)]
+    pub overspend: bool,
+    #[serde(default)]
+    pub too_big: bool,
+    #[serde(default)]
+    pub untrusted: bool,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+struct Empty {}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetTransactionPoolResponse {
+    pub status: String,
+    /// Missing in the response if the pool is empty.
+    #[serde(default)]
+    pub transactions: Vec<PoolTransaction>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PoolTransaction {
+    pub id_hash: String,
+    pub blob_size: u64,
+    #[serde(default)]
+    pub weight: u64,
+    pub fee: u64,
+    pub receive_time: u64,
+    pub double_spend_seen: bool,
+    pub relayed: bool,
+    /// The transaction decoded as JSON, see [`TransactionJson`].
+    pub tx_json: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct IsKeyImageSpentPayload {
+    key_images: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct IsKeyImageSpentResponse {
+    status: String,
+    #[serde(default)]
+    spent_status: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyImageSpentStatus {
+    Unspent,
+    SpentInBlockchain,
+    SpentInPool,
+}
+
+impl TryFrom<u8> for KeyImageSpentStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(status: u8) -> Result<Self> {
+        match status {
+            0 => Ok(KeyImageSpentStatus::Unspent),
+            1 => Ok(KeyImageSpentStatus::SpentInBlockchain),
+            2 => Ok(KeyImageSpentStatus::SpentInPool),
+            other => anyhow::bail!("Unknown key image spent status {}", other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetOutputDistributionResponse {
+    pub distributions: Vec<OutputDistribution>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OutputDistribution {
+    pub amount: u64,
+    pub start_height: u64,
+    pub base: u64,
+    /// Number of outputs per block starting at `start_height`, summed up if
+    /// the distribution was requested as cumulative.
+    pub distribution: Vec<u64>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GetIndexesResponse {
     pub o_indexes: Vec<u32>,
@@ -128,6 +419,77 @@ struct GetOIndexesPayload {
     txid: Hash,
 }
 
+#[derive(Clone, Debug, Serialize)]
+struct GetTransactionsPayload {
+    txs_hashes: Vec<String>,
+    decode_as_json: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetTransactionsResponse {
+    pub status: String,
+    #[serde(default)]
+    pub txs: Vec<TransactionEntry>,
+    #[serde(default)]
+    pub missed_tx: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransactionEntry {
+    pub tx_hash: String,
+    /// The transaction decoded as JSON, see [`TransactionJson`].
+    pub as_json: String,
+    #[serde(default)]
+    pub block_height: u64,
+    pub in_pool: bool,
+}
+
+/// The parts of a transaction decoded as JSON by monerod that are needed to
+/// find the outputs sent to an address.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransactionJson {
+    pub vout: Vec<TransactionOutput>,
+    pub extra: Vec<u8>,
+    pub rct_signatures: RctSignatures,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransactionOutput {
+    pub amount: u64,
+    pub target: OutputTarget,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputTarget {
+    Key(String),
+    TaggedKey { key: String, view_tag: String },
+}
+
+impl OutputTarget {
+    pub fn key(&self) -> &str {
+        match self {
+            OutputTarget::Key(key) => key,
+            OutputTarget::TaggedKey { key, .. } => key,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RctSignatures {
+    #[serde(rename = "type")]
+    pub rct_type: u8,
+    #[serde(rename = "ecdhInfo", default)]
+    pub ecdh_info: Vec<EcdhInfo>,
+    #[serde(rename = "outPk", default)]
+    pub out_pk: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EcdhInfo {
+    pub amount: String,
+}
+
 #[derive(Clone, Debug, Serialize)]
 struct GetOutsPayload {
     outputs: Vec<GetOutputsOut>,
@@ -258,3 +620,213 @@ mod byte_array {
         })
     }
 }
+
+/// Splits a monerod address of the form `[http://]host:port[/]` into its host
+/// and port.
+pub fn parse_daemon_address(address: &str) -> Result<(String, u16)> {
+    let url = if address.contains("://") {
+        reqwest::Url::parse(address)
+    } else {
+        reqwest::Url::parse(&format!("http://{}", address))
+    }
+    .with_context(|| format!("Invalid monerod address `{}`", address))?;
+
+    if url.scheme() != "http" {
+        anyhow::bail!(
+            "Unsupported scheme `{}` in monerod address `{}`, expected `http`",
+            url.scheme(),
+            address
+        )
+    }
+
+    let host = url
+        .host_str()
+        .with_context(|| format!("Expected monerod address `{}` to contain a host", address))?;
+    let port = url
+        .port()
+        .with_context(|| format!("Expected monerod address `{}` to contain a port", address))?;
+
+    Ok((host.to_owned(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc_client::Response;
+
+    #[test]
+    fn parses_daemon_addresses() {
+        assert_eq!(
+            parse_daemon_address("node.example.com:18081").unwrap(),
+            ("node.example.com".to_owned(), 18081)
+        );
+        assert_eq!(
+            parse_daemon_address("http://node.example.com:18081/").unwrap(),
+            ("node.example.com".to_owned(), 18081)
+        );
+        assert_eq!(
+            parse_daemon_address("http://[::1]:18081").unwrap(),
+            ("[::1]".to_owned(), 18081)
+        );
+
+        assert!(parse_daemon_address("node.example.com").is_err());
+        assert!(parse_daemon_address("https://node.example.com:18081").is_err());
+        assert!(parse_daemon_address("node.example.com:port").is_err());
+    }
+
+    #[test]
+    fn can_deserialize_get_info_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "adjusted_time": 1700000000,
+            "alt_blocks_count": 0,
+            "busy_syncing": false,
+            "credits": 0,
+            "database_size": 235295744,
+            "difficulty": 2390573,
+            "height": 1576352,
+            "incoming_connections_count": 0,
+            "mainnet": false,
+            "nettype": "stagenet",
+            "offline": false,
+            "outgoing_connections_count": 8,
+            "stagenet": true,
+            "status": "OK",
+            "synchronized": true,
+            "target_height": 0,
+            "testnet": false,
+            "top_block_hash": "a3ba4f4e0b9b6e4b3b66eb0a2bb1b6dd4b7d2e4f87e0f3b1a8a0ad0bc1f7e2d1",
+            "tx_pool_size": 3,
+            "untrusted": false,
+            "version": "0.18.3.1-release"
+          }
+        }"#;
+
+        let _: Response<GetInfo> = serde_json::from_str(response).unwrap();
+
+        let info: GetInfo = serde_json::from_str(
+            r#"{
+              "height": 100,
+              "target_height": 0,
+              "nettype": "stagenet",
+              "synchronized": true,
+              "top_block_hash": "",
+              "tx_pool_size": 0,
+              "incoming_connections_count": 0,
+              "outgoing_connections_count": 1,
+              "status": "OK"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(info.network(), Some(monero::Network::Stagenet));
+        assert!(info.is_synced());
+    }
+
+    #[test]
+    fn can_deserialize_get_fee_estimate_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "credits": 0,
+            "fee": 20000,
+            "fees": [20000, 80000, 320000, 4000000],
+            "quantization_mask": 10000,
+            "status": "OK",
+            "top_hash": "",
+            "untrusted": false
+          }
+        }"#;
+
+        let _: Response<GetFeeEstimate> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_send_raw_transaction_rejection() {
+        let response: SendRawTransactionResponse = serde_json::from_str(
+            r#"{
+              "double_spend": true,
+              "fee_too_low": false,
+              "invalid_input": false,
+              "invalid_output": false,
+              "low_mixin": false,
+              "not_relayed": false,
+              "overspend": false,
+              "reason": "double spend",
+              "status": "Failed",
+              "too_big": false,
+              "untrusted": false
+            }"#,
+        )
+        .unwrap();
+
+        assert!(response.double_spend);
+        assert_eq!(response.reason, "double spend");
+    }
+
+    #[test]
+    fn can_deserialize_get_transaction_pool_response() {
+        let response: GetTransactionPoolResponse = serde_json::from_str(
+            r#"{
+              "credits": 0,
+              "spent_key_images": [],
+              "status": "OK",
+              "transactions": [{
+                "blob_size": 1533,
+                "do_not_relay": false,
+                "double_spend_seen": false,
+                "fee": 30880000,
+                "id_hash": "9f2e8b3c3e2a4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5",
+                "kept_by_block": false,
+                "last_failed_height": 0,
+                "last_failed_id_hash": "0000000000000000000000000000000000000000000000000000000000000000",
+                "last_relayed_time": 1700000000,
+                "max_used_block_height": 1576340,
+                "max_used_block_id_hash": "a3ba4f4e0b9b6e4b3b66eb0a2bb1b6dd4b7d2e4f87e0f3b1a8a0ad0bc1f7e2d1",
+                "receive_time": 1700000000,
+                "relayed": true,
+                "tx_blob": "",
+                "tx_json": "{}",
+                "weight": 1533
+              }],
+              "untrusted": false
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(response.transactions.len(), 1);
+
+        let empty: GetTransactionPoolResponse =
+            serde_json::from_str(r#"{"status": "OK", "untrusted": false}"#).unwrap();
+
+        assert!(empty.transactions.is_empty());
+    }
+
+    #[test]
+    fn maps_key_image_spent_status() {
+        let response: IsKeyImageSpentResponse = serde_json::from_str(
+            r#"{"credits": 0, "spent_status": [0, 1, 2], "status": "OK", "untrusted": false}"#,
+        )
+        .unwrap();
+
+        let statuses = response
+            .spent_status
+            .into_iter()
+            .map(KeyImageSpentStatus::try_from)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            statuses,
+            vec![
+                KeyImageSpentStatus::Unspent,
+                KeyImageSpentStatus::SpentInBlockchain,
+                KeyImageSpentStatus::SpentInPool
+            ]
+        );
+        assert!(KeyImageSpentStatus::try_from(3).is_err());
+    }
+}
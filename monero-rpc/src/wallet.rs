@@ -1,8 +1,11 @@
 use std::fmt;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use jsonrpc_client::Response;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
 use rust_decimal::Decimal;
-use serde::de::Error;
+use serde::de::{DeserializeOwned, Error};
 use serde::{Deserialize, Deserializer, Serialize};
 
 #[jsonrpc_client::api(version = "2.0")]
@@ -36,13 +39,51 @@ pub trait MoneroWalletRpc {
     async fn refresh(&self) -> Refreshed;
     async fn sweep_all(&self, address: String) -> SweepAll;
     async fn get_version(&self) -> Version;
+    async fn set_daemon(&self, address: String, trusted: bool) -> DaemonSet;
+    async fn create_address(&self, account_index: u32, label: String) -> CreateAddress;
+    async fn label_address(&self, index: SubaddressIndex, label: String) -> AddressLabeled;
+    async fn get_address_index(&self, address: String) -> GetAddressIndex;
+    async fn get_transfer_by_txid(&self, txid: String) -> GetTransferByTxid;
+    async fn incoming_transfers(
+        &self,
+        transfer_type: String,
+        account_index: u32,
+    ) -> IncomingTransfers;
+    async fn relay_tx(&self, hex: String) -> RelayTx;
+    async fn describe_transfer(&self, unsigned_txset: String) -> DescribeTransfer;
 }
 
-#[jsonrpc_client::implement(MoneroWalletRpc)]
 #[derive(Debug, Clone)]
 pub struct Client {
     inner: reqwest::Client,
     base_url: reqwest::Url,
+    credentials: Option<Credentials>,
+}
+
+/// Username and password for a monero-wallet-rpc started with `--rpc-login`.
+#[derive(Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl MoneroWalletRpc<reqwest::Client> for Client {
+    async fn send_request<P>(&self, request: String) -> Result<Response<P>, reqwest::Error>
+    where
+        P: DeserializeOwned,
+    {
+        self.send(request).await?.json().await
+    }
 }
 
 impl Client {
@@ -62,9 +103,132 @@ impl Client {
                 .connection_verbose(true)
                 .build()?,
             base_url: url,
+            credentials: None,
         })
     }
 
+    /// Constructs a monero-wallet-rpc client with `url` endpoint which
+    /// answers HTTP digest challenges with `credentials`.
+    pub fn with_digest_auth(url: reqwest::Url, credentials: Credentials) -> Result<Self> {
+        Ok(Self {
+            credentials: Some(credentials),
+            ..Self::new(url)?
+        })
+    }
+
+    /// Lists the transfers of an account.
+    ///
+    /// Not part of [`MoneroWalletRpc`] because one of its parameters is called
+    /// `in`.
+    pub async fn get_transfers(&self, request: GetTransfersRequest) -> Result<GetTransfers> {
+        self.call("get_transfers", request).await
+    }
+
+    async fn call<P, R>(&self, method: &str, params: P) -> Result<R>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": "0",
+            "method": method,
+            "params": params,
+        });
+
+        let response = self
+            .send(request.to_string())
+            .await?
+            .json::<RawResponse<R>>()
+            .await?;
+
+        match response {
+            RawResponse {
+                result: Some(result),
+                ..
+            } => Ok(result),
+            RawResponse {
+                error: Some(error), ..
+            } => bail!(
+                "{} failed with code {}: {}",
+                method,
+                error.code,
+                error.message
+            ),
+            _ => bail!("{} returned neither a result nor an error", method),
+        }
+    }
+
+    /// Posts `request`, answering a digest challenge with the configured
+    /// credentials. Fails with a `401 Unauthorized` status error if the
+    /// challenge cannot be answered or the credentials are rejected.
+    async fn send(&self, request: String) -> Result<reqwest::Response, reqwest::Error> {
+        let response = self.post(request.clone(), None).await?;
+
+        let response = match &self.credentials {
+            Some(credentials) if response.status() == StatusCode::UNAUTHORIZED => {
+                match self.digest_authorization(&response, credentials, &request) {
+                    Ok(authorization) => self.post(request, Some(authorization)).await?,
+                    Err(error) => {
+                        tracing::warn!("Failed to answer digest challenge: {:#}", error);
+                        response
+                    }
+                }
+            }
+            _ => response,
+        };
+
+        if response.status() == StatusCode::UNAUTHORIZED {
+            tracing::warn!("monero-wallet-rpc rejected the request as unauthorized");
+            return response.error_for_status();
+        }
+
+        Ok(response)
+    }
+
+    async fn post(
+        &self,
+        body: String,
+        authorization: Option<String>,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = self
+            .inner
+            .post(self.base_url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body);
+
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        request.send().await
+    }
+
+    /// Computes the `Authorization` header answering the digest challenge of
+    /// an unauthorized `response`.
+    fn digest_authorization(
+        &self,
+        response: &reqwest::Response,
+        credentials: &Credentials,
+        body: &str,
+    ) -> Result<String> {
+        let challenge = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .context("Unauthorized response without digest challenge")?
+            .to_str()?;
+
+        let mut prompt = digest_auth::parse(challenge)?;
+        let context = digest_auth::AuthContext::new_post(
+            credentials.username.as_str(),
+            credentials.password.as_str(),
+            self.base_url.path(),
+            Some(body.as_bytes()),
+        );
+
+        Ok(prompt.respond(&context)?.to_header_string())
+    }
+
     /// Transfers `amount` monero from `account_index` to `address`.
     pub async fn transfer_single(
         &self,
@@ -84,6 +248,38 @@ impl Client {
 #[derive(Deserialize, Debug, Clone)]
 pub struct GetAddress {
     pub address: String,
+    /// The subaddresses of the account.
+    #[serde(default)]
+    pub addresses: Vec<SubaddressInfo>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SubaddressInfo {
+    pub address: String,
+    pub address_index: u32,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub used: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreateAddress {
+    pub address: String,
+    pub address_index: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubaddressIndex {
+    /// The account index.
+    pub major: u32,
+    /// The subaddress index within the account.
+    pub minor: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct GetAddressIndex {
+    pub index: SubaddressIndex,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -138,7 +334,7 @@ pub struct SubAddressAccount {
     pub unlocked_balance: u64,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Destination {
     pub amount: u64,
     pub address: String,
@@ -221,9 +417,138 @@ pub struct Version {
     pub version: u32,
 }
 
+/// Parameters of [`Client::get_transfers`]. Only the selected kinds of
+/// transfers are returned.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct GetTransfersRequest {
+    #[serde(rename = "in")]
+    pub incoming: bool,
+    pub out: bool,
+    pub pending: bool,
+    pub failed: bool,
+    pub pool: bool,
+    pub filter_by_height: bool,
+    pub min_height: u64,
+    pub max_height: u64,
+    pub account_index: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct GetTransfers {
+    #[serde(rename = "in", default)]
+    pub incoming: Vec<TransferEntry>,
+    #[serde(default)]
+    pub out: Vec<TransferEntry>,
+    #[serde(default)]
+    pub pending: Vec<TransferEntry>,
+    #[serde(default)]
+    pub failed: Vec<TransferEntry>,
+    #[serde(default)]
+    pub pool: Vec<TransferEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransferEntry {
+    pub address: String,
+    pub amount: u64,
+    #[serde(default)]
+    pub confirmations: u64,
+    pub double_spend_seen: bool,
+    pub fee: u64,
+    pub height: u64,
+    #[serde(default)]
+    pub note: String,
+    pub payment_id: String,
+    pub subaddr_index: SubaddressIndex,
+    pub timestamp: u64,
+    pub txid: String,
+    /// One of `in`, `out`, `pending`, `failed` or `pool`.
+    #[serde(rename = "type")]
+    pub transfer_type: String,
+    pub unlock_time: u64,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub destinations: Vec<Destination>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetTransferByTxid {
+    pub transfer: TransferEntry,
+    /// All transfers of the transaction if it has more than one, e.g. to
+    /// several subaddresses.
+    #[serde(default)]
+    pub transfers: Vec<TransferEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct IncomingTransfers {
+    /// Missing in the response if there are no transfers.
+    #[serde(default)]
+    pub transfers: Vec<IncomingTransfer>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct IncomingTransfer {
+    pub amount: u64,
+    pub global_index: u64,
+    #[serde(default)]
+    pub key_image: String,
+    pub spent: bool,
+    pub subaddr_index: SubaddressIndex,
+    pub tx_hash: String,
+    #[serde(default)]
+    pub frozen: bool,
+    #[serde(default)]
+    pub unlocked: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RelayTx {
+    pub tx_hash: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DescribeTransfer {
+    pub desc: Vec<TransferDescription>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransferDescription {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub recipients: Vec<Destination>,
+    #[serde(default)]
+    pub change_address: String,
+    pub change_amount: u64,
+    pub fee: u64,
+    pub ring_size: u32,
+    pub unlock_time: u64,
+    #[serde(default)]
+    pub dummy_outputs: u32,
+    #[serde(default)]
+    pub payment_id: String,
+    #[serde(default)]
+    pub extra: String,
+}
+
+#[derive(Deserialize)]
+struct RawResponse<T> {
+    result: Option<T>,
+    error: Option<RawError>,
+}
+
+#[derive(Deserialize)]
+struct RawError {
+    code: i64,
+    message: String,
+}
+
 pub type WalletCreated = Empty;
 pub type WalletClosed = Empty;
 pub type WalletOpened = Empty;
+pub type DaemonSet = Empty;
+pub type AddressLabeled = Empty;
 
 /// Zero-sized struct to allow serde to deserialize an empty JSON object.
 ///
@@ -251,7 +576,110 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use jsonrpc_client::Response;
+    use mockito::{Matcher, Server, ServerGuard};
+
+    const CHALLENGE: &str = r#"Digest realm="monero-rpc", qop="auth", algorithm=MD5, nonce="dcd98b7102dd2f0e8b11d0f600bfb0c0""#;
+
+    fn credentials() -> Credentials {
+        Credentials {
+            username: "swap".to_owned(),
+            password: "secret".to_owned(),
+        }
+    }
+
+    fn client(server: &ServerGuard) -> Client {
+        Client::with_digest_auth(
+            format!("{}/json_rpc", server.url()).parse().unwrap(),
+            credentials(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn answers_digest_challenge() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/json_rpc")
+            .match_header("authorization", Matcher::Missing)
+            .with_status(401)
+            .with_header("www-authenticate", CHALLENGE)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/json_rpc")
+            .match_header(
+                "authorization",
+                Matcher::Regex(r#"^Digest username="swap", .*realm="monero-rpc""#.to_owned()),
+            )
+            .with_body(r#"{"id":"0","jsonrpc":"2.0","result":{"version":65562}}"#)
+            .create_async()
+            .await;
+
+        let version = client(&server).get_version().await.unwrap();
+
+        assert_eq!(version.version, 65562);
+    }
+
+    #[tokio::test]
+    async fn fails_with_unauthorized_if_credentials_are_rejected() {
+        let mut server = Server::new_async().await;
+        let challenged = server
+            .mock("POST", "/json_rpc")
+            .with_status(401)
+            .with_header("www-authenticate", CHALLENGE)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let error = client(&server).get_version().await.unwrap_err();
+
+        challenged.assert_async().await;
+        assert!(
+            matches!(&error, jsonrpc_client::Error::Client(error) if error.status() == Some(StatusCode::UNAUTHORIZED)),
+            "unexpected error {:?}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_with_unauthorized_if_challenge_is_missing() {
+        let mut server = Server::new_async().await;
+        let challenged = server
+            .mock("POST", "/json_rpc")
+            .with_status(401)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let error = client(&server).get_version().await.unwrap_err();
+
+        challenged.assert_async().await;
+        assert!(
+            matches!(&error, jsonrpc_client::Error::Client(error) if error.status() == Some(StatusCode::UNAUTHORIZED)),
+            "unexpected error {:?}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn call_fails_with_unauthorized_if_challenge_is_missing() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/json_rpc")
+            .with_status(401)
+            .create_async()
+            .await;
+
+        let error = client(&server)
+            .get_transfers(GetTransfersRequest::default())
+            .await
+            .unwrap_err();
+
+        let status = error
+            .downcast_ref::<reqwest::Error>()
+            .and_then(reqwest::Error::status);
+        assert_eq!(status, Some(StatusCode::UNAUTHORIZED));
+    }
 
     #[test]
     fn can_deserialize_sweep_all_response() {
@@ -271,6 +699,29 @@ mod tests {
         let _: Response<SweepAll> = serde_json::from_str(response).unwrap();
     }
 
+    #[test]
+    fn can_deserialize_get_address_with_subaddresses() {
+        let result = r#"{
+            "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+            "addresses": [{
+              "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+              "address_index": 0,
+              "label": "Primary account",
+              "used": true
+            }, {
+              "address": "7BnERTpvL5MbCLtj5n9No7J5oE5hHiB3tVCK5cjSvCsYWD2WRJLFuWeKTLiXo5QJqt2ZwUaLy2Vh1Ad51K7FNgqcHgjW85o",
+              "address_index": 1,
+              "label": "",
+              "used": false
+            }]
+        }"#;
+
+        let get_address: GetAddress = serde_json::from_str(result).unwrap();
+
+        assert_eq!(get_address.addresses.len(), 2);
+        assert_eq!(get_address.addresses[1].address_index, 1);
+    }
+
     #[test]
     fn can_deserialize_create_wallet() {
         let response = r#"{
@@ -282,4 +733,244 @@ mod tests {
 
         let _: Response<WalletCreated> = serde_json::from_str(response).unwrap();
     }
+
+    #[test]
+    fn can_deserialize_get_transfers_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "in": [{
+              "address": "77Vx9cs1VPicFndSVgYUvTdLCJEZw9h81hXLMYsjBCXSJfUehLa9TDW3Ffh45SQa7xb6dUs18mpNxfUhQGqfwXPSMrvKhVp",
+              "amount": 200000000000,
+              "confirmations": 1,
+              "double_spend_seen": false,
+              "fee": 21650200000,
+              "height": 153624,
+              "locked": false,
+              "note": "",
+              "payment_id": "0000000000000000",
+              "subaddr_index": {"major": 0, "minor": 0},
+              "subaddr_indices": [{"major": 0, "minor": 0}],
+              "suggested_confirmations_threshold": 1,
+              "timestamp": 1535918400,
+              "txid": "c36258a276018c3a4bc1f195a7fb530f50cd63a4fa765fb7c6f7f49fc051762a",
+              "type": "in",
+              "unlock_time": 0
+            }],
+            "out": [{
+              "address": "77Vx9cs1VPicFndSVgYUvTdLCJEZw9h81hXLMYsjBCXSJfUehLa9TDW3Ffh45SQa7xb6dUs18mpNxfUhQGqfwXPSMrvKhVp",
+              "amount": 200000000000,
+              "confirmations": 10,
+              "destinations": [{
+                "address": "7BnERTpvL5MbCLtj5n9No7J5oE5hHiB3tVCK5cjSvCsYWD2WRJLFuWeKTLiXo5QJqt2ZwUaLy2Vh1Ad51K7FNgqcHgjW85o",
+                "amount": 100000000000
+              }],
+              "double_spend_seen": false,
+              "fee": 21650200000,
+              "height": 153620,
+              "note": "",
+              "payment_id": "0000000000000000",
+              "subaddr_index": {"major": 0, "minor": 0},
+              "timestamp": 1535918000,
+              "txid": "d2ed3fe4d7cb9b8a5b91cd6a7bc6b3e1c9c1d5d1ee1a1a0d76a3e9d0d2c0a2ce",
+              "type": "out",
+              "unlock_time": 0
+            }]
+          }
+        }"#;
+
+        let _: Response<GetTransfers> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_serialize_get_transfers_request_with_in_parameter() {
+        let request = GetTransfersRequest {
+            incoming: true,
+            ..GetTransfersRequest::default()
+        };
+
+        let request = serde_json::to_value(request).unwrap();
+
+        assert_eq!(request["in"], true);
+        assert_eq!(request["out"], false);
+    }
+
+    #[test]
+    fn can_deserialize_get_transfer_by_txid_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "transfer": {
+              "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+              "amount": 300000000000,
+              "confirmations": 1,
+              "destinations": [{
+                "address": "7BnERTpvL5MbCLtj5n9No7J5oE5hHiB3tVCK5cjSvCsYWD2WRJLFuWeKTLiXo5QJqt2ZwUaLy2Vh1Ad51K7FNgqcHgjW85o",
+                "amount": 100000000000
+              }],
+              "double_spend_seen": false,
+              "fee": 21650200000,
+              "height": 153624,
+              "locked": false,
+              "note": "",
+              "payment_id": "0000000000000000",
+              "subaddr_index": {"major": 0, "minor": 0},
+              "suggested_confirmations_threshold": 1,
+              "timestamp": 1535918400,
+              "txid": "765f7124d2a5b7b57c7e15e5b4b2f9c37d2c3b3d2d8e37e22ee9c4a6c0d7d9a3",
+              "type": "out",
+              "unlock_time": 0
+            },
+            "transfers": []
+          }
+        }"#;
+
+        let _: Response<GetTransferByTxid> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_incoming_transfers_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "transfers": [{
+              "amount": 60000000000000,
+              "block_height": 2758,
+              "frozen": false,
+              "global_index": 122405,
+              "key_image": "768f5144777eb23477ab7acf83562581d690abaf98ca897c03a9d2b900eb479b",
+              "pubkey": "7a8c6ae82bc61e9c5dd35ba2ec0a9d4de88d2e0d4e9a7d63a52e2a5f85b0d6f0",
+              "spent": true,
+              "subaddr_index": {"major": 0, "minor": 0},
+              "tx_hash": "7b5f8d6b8b0d0e8a8b0c5d0c0b5c3f3f5ba1d3b0e9d1a0c7e3a0c8d6e5f4a3b2",
+              "unlocked": true
+            }]
+          }
+        }"#;
+
+        let _: Response<IncomingTransfers> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_incoming_transfers_response_without_transfers() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+          }
+        }"#;
+
+        let _: Response<IncomingTransfers> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_create_address_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "address": "7BG5jr9QS5sGMdpbBrZEwVLZjSKJGJBsXdFLt6nUjvQvFEGFwMbUNyHEG9vHHzYQPvcdJdGgPjE3H1FhUcE9zt1tDd5uoxW",
+            "address_index": 1,
+            "address_indices": [1],
+            "addresses": ["7BG5jr9QS5sGMdpbBrZEwVLZjSKJGJBsXdFLt6nUjvQvFEGFwMbUNyHEG9vHHzYQPvcdJdGgPjE3H1FhUcE9zt1tDd5uoxW"]
+          }
+        }"#;
+
+        let _: Response<CreateAddress> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_label_address_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+          }
+        }"#;
+
+        let _: Response<AddressLabeled> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_get_address_index_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "index": {
+              "major": 0,
+              "minor": 1
+            }
+          }
+        }"#;
+
+        let _: Response<GetAddressIndex> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_relay_tx_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "tx_hash": "1c42dcc5672bb09bccf33fb1e9ab4a498af59a6dbd33b3d0cfb289b9e0e25fa5"
+          }
+        }"#;
+
+        let _: Response<RelayTx> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_set_daemon_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+          }
+        }"#;
+
+        let _: Response<DaemonSet> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_describe_transfer_response() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "desc": [{
+              "amount_in": 886489038634812,
+              "amount_out": 886455352051812,
+              "change_address": "9vGzMVdAuNWUZCgZ8b8GZvgxzB3y8zNRfcBZS8tsN6TpGsxrwRe6m2gCG3sT6n7SkNSGz8BkUpUGZ9Hxh9y3mH5wBLbVdZi",
+              "change_amount": 886445352051812,
+              "dummy_outputs": 0,
+              "extra": "01",
+              "fee": 33686583000,
+              "payment_id": "",
+              "recipients": [{
+                "address": "9yqvoDFbUuqfDvkWq4kQq3BQCHhuUVHnKJ6wkMdt4zR2WcZXwKYHTAPgPSr8BRqPSmEHMfRhk8YjL6KmvP1EMHeZ5xf7gxm",
+                "amount": 10000000000
+              }],
+              "ring_size": 16,
+              "unlock_time": 0
+            }],
+            "summary": {
+              "amount_in": 886489038634812,
+              "amount_out": 886455352051812,
+              "change_address": "9vGzMVdAuNWUZCgZ8b8GZvgxzB3y8zNRfcBZS8tsN6TpGsxrwRe6m2gCG3sT6n7SkNSGz8BkUpUGZ9Hxh9y3mH5wBLbVdZi",
+              "change_amount": 886445352051812,
+              "fee": 33686583000,
+              "recipients": [{
+                "address": "9yqvoDFbUuqfDvkWq4kQq3BQCHhuUVHnKJ6wkMdt4zR2WcZXwKYHTAPgPSr8BRqPSmEHMfRhk8YjL6KmvP1EMHeZ5xf7gxm",
+                "amount": 10000000000
+              }]
+            }
+          }
+        }"#;
+
+        let _: Response<DescribeTransfer> = serde_json::from_str(response).unwrap();
+    }
 }
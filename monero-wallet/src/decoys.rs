@@ -0,0 +1,594 @@
+//! Selection of decoys for the ring of a transaction input.
+//!
+//! This mirrors the gamma picker of the reference wallet (`wallet2`): The age
+//! of a decoy in seconds is drawn from `exp(Gamma(19.28, 1 / 1.61))` and
+//! converted to an output index using the average time between outputs of
+//! the last year. Outputs of the last [`SPENDABLE_AGE`] blocks are never
+//! picked and outputs which monerod reports as locked (e.g. recent coinbase
+//! outputs) are rejected.
+
+use anyhow::{bail, Context, Result};
+use monero::consensus::encode::VarInt;
+use monero::cryptonote::hash::Hash;
+use monero::util::ringct;
+use monero::PublicKey;
+use monero_rpc::monerod;
+use monero_rpc::monerod::{GetOutputsOut, MonerodRpc as _, OutKey};
+use rand::Rng;
+use rand_distr::{Distribution, Gamma};
+use std::collections::{BTreeMap, HashSet};
+
+/// Number of members of a ring, including the real output.
+pub const RING_SIZE: usize = 16;
+
+/// Number of blocks an output has to be buried under before it can be spent
+/// (`CRYPTONOTE_DEFAULT_TX_SPENDABLE_AGE`).
+pub const SPENDABLE_AGE: u64 = 10;
+
+/// Target time between two blocks in seconds.
+const TARGET_BLOCK_TIME: u64 = 120;
+
+const BLOCKS_IN_A_YEAR: u64 = 86400 * 365 / TARGET_BLOCK_TIME;
+
+/// Parameters of the gamma distribution fitted to real spends.
+const GAMMA_SHAPE: f64 = 19.28;
+const GAMMA_SCALE: f64 = 1.0 / 1.61;
+
+/// Picks younger than this are replaced by a uniform pick from the
+/// [`RECENT_SPEND_WINDOW`], see `wallet2::gamma_picker`.
+const DEFAULT_UNLOCK_TIME: f64 = (SPENDABLE_AGE * TARGET_BLOCK_TIME) as f64;
+const RECENT_SPEND_WINDOW: f64 = (15 * TARGET_BLOCK_TIME) as f64;
+
+/// Upper bound of picks before giving up, picks can repeatedly miss if the
+/// chain contains few outputs.
+const MAX_PICKS: usize = 100 * RING_SIZE;
+
+/// Picks global output indices following the spending pattern of the
+/// reference wallet.
+#[derive(Debug, Clone)]
+pub struct GammaPicker {
+    /// Cumulative number of RingCT outputs per block, without the blocks
+    /// which are younger than [`SPENDABLE_AGE`].
+    rct_offsets: Vec<u64>,
+    num_spendable_outputs: u64,
+    average_output_time: f64,
+    gamma: Gamma<f64>,
+}
+
+impl GammaPicker {
+    /// Creates a picker from the cumulative number of RingCT outputs per
+    /// block, starting at the genesis block.
+    pub fn new(rct_offsets: Vec<u64>) -> Result<Self> {
+        let num_blocks = rct_offsets.len() as u64;
+
+        if num_blocks <= SPENDABLE_AGE {
+            bail!(
+                "Need more than {} blocks to pick decoys, got {}",
+                SPENDABLE_AGE,
+                num_blocks
+            )
+        }
+
+        let blocks_to_consider = num_blocks.min(BLOCKS_IN_A_YEAR);
+        let last = *rct_offsets.last().expect("at least one block");
+        let outputs_to_consider = if blocks_to_consider < num_blocks {
+            last - rct_offsets[(num_blocks - blocks_to_consider - 1) as usize]
+        } else {
+            last
+        };
+
+        if outputs_to_consider == 0 {
+            bail!(
+                "No RingCT outputs in the last {} blocks",
+                blocks_to_consider
+            )
+        }
+
+        let average_output_time =
+            (TARGET_BLOCK_TIME * blocks_to_consider) as f64 / outputs_to_consider as f64;
+
+        let mut rct_offsets = rct_offsets;
+        rct_offsets.truncate((num_blocks - SPENDABLE_AGE) as usize);
+        let num_spendable_outputs = *rct_offsets.last().expect("at least one block");
+
+        if num_spendable_outputs == 0 {
+            bail!("No spendable RingCT outputs")
+        }
+
+        Ok(Self {
+            rct_offsets,
+            num_spendable_outputs,
+            average_output_time,
+            gamma: Gamma::new(GAMMA_SHAPE, GAMMA_SCALE).expect("valid gamma parameters"),
+        })
+    }
+
+    /// Number of outputs old enough to be spent.
+    pub fn num_spendable_outputs(&self) -> u64 {
+        self.num_spendable_outputs
+    }
+
+    /// Picks the global index of an output, `None` if the pick is older than
+    /// the first output or falls into a block without outputs.
+    pub fn pick<R: Rng>(&self, rng: &mut R) -> Option<u64> {
+        let mut age = self.gamma.sample(rng).exp();
+
+        if age > DEFAULT_UNLOCK_TIME {
+            age -= DEFAULT_UNLOCK_TIME;
+        } else {
+            age = rng.gen_range(0.0, RECENT_SPEND_WINDOW);
+        }
+
+        let outputs_ago = (age / self.average_output_time) as u64;
+
+        if outputs_ago >= self.num_spendable_outputs {
+            return None;
+        }
+
+        let target = self.num_spendable_outputs - 1 - outputs_ago;
+        let block = self.rct_offsets.partition_point(|offset| *offset < target);
+
+        let first_in_block = match block {
+            0 => 0,
+            block => self.rct_offsets[block - 1],
+        };
+        let outputs_in_block = self.rct_offsets[block] - first_in_block;
+
+        if outputs_in_block == 0 {
+            return None;
+        }
+
+        Some(first_in_block + rng.gen_range(0, outputs_in_block))
+    }
+}
+
+/// A member of a ring.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RingMember {
+    pub global_index: u64,
+    pub key: PublicKey,
+    pub commitment: ringct::Key,
+}
+
+impl RingMember {
+    fn new(global_index: u64, out: OutKey) -> Self {
+        Self {
+            global_index,
+            key: out.key,
+            commitment: out.mask,
+        }
+    }
+}
+
+/// The real output together with its decoys, sorted by global index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ring {
+    pub members: Vec<RingMember>,
+    /// Position of the real output within `members`.
+    pub real_position: usize,
+}
+
+impl Ring {
+    /// The key offsets as stored in a transaction input: The first offset is
+    /// the global index of the first member, every other offset is relative
+    /// to the previous member.
+    pub fn key_offsets(&self) -> Vec<VarInt> {
+        let mut previous = 0;
+
+        self.members
+            .iter()
+            .map(|member| {
+                let offset = member.global_index - previous;
+                previous = member.global_index;
+
+                VarInt(offset)
+            })
+            .collect()
+    }
+}
+
+/// Selects a ring for spending the output at `output_index` of the
+/// transaction `txid`.
+pub async fn select_ring<R: Rng>(
+    client: &monerod::Client,
+    txid: Hash,
+    output_index: usize,
+    rng: &mut R,
+) -> Result<Ring> {
+    let o_indexes = client.get_o_indexes(txid).await?.o_indexes;
+    let real_index = *o_indexes.get(output_index).with_context(|| {
+        format!(
+            "Transaction {:x} has no output with index {}",
+            txid, output_index
+        )
+    })?;
+
+    let block_count = client.get_block_count().await?.count as u64;
+    let distribution = client
+        .get_output_distribution(vec![0], true, 0, block_count - 1, false)
+        .await?
+        .distributions
+        .into_iter()
+        .find(|distribution| distribution.amount == 0)
+        .context("monerod did not return the RingCT output distribution")?;
+    let picker = GammaPicker::new(distribution.distribution)?;
+
+    if real_index >= picker.num_spendable_outputs() {
+        bail!("Output {} is not old enough to be spent", real_index)
+    }
+
+    let real = get_outs(client, &[real_index]).await?[0];
+    if !real.unlocked {
+        bail!("Output {} is locked", real_index)
+    }
+
+    let mut members = BTreeMap::new();
+    members.insert(real_index, RingMember::new(real_index, real));
+
+    let mut rejected = HashSet::new();
+    let mut picks = 0;
+
+    while members.len() < RING_SIZE {
+        let mut batch = Vec::new();
+
+        while members.len() + batch.len() < RING_SIZE {
+            picks += 1;
+            if picks > MAX_PICKS {
+                bail!(
+                    "Failed to select {} decoys out of {} spendable outputs",
+                    RING_SIZE - 1,
+                    picker.num_spendable_outputs()
+                )
+            }
+
+            match picker.pick(rng) {
+                Some(index)
+                    if !members.contains_key(&index)
+                        && !rejected.contains(&index)
+                        && !batch.contains(&index) =>
+                {
+                    batch.push(index)
+                }
+                _ => continue,
+            }
+        }
+
+        let outs = get_outs(client, &batch).await?;
+
+        for (index, out) in batch.into_iter().zip(outs) {
+            if out.unlocked {
+                members.insert(index, RingMember::new(index, out));
+            } else {
+                rejected.insert(index);
+            }
+        }
+    }
+
+    let real_position = members
+        .keys()
+        .position(|index| *index == real_index)
+        .expect("real output is a member");
+
+    Ok(Ring {
+        members: members.into_values().collect(),
+        real_position,
+    })
+}
+
+async fn get_outs(client: &monerod::Client, indices: &[u64]) -> Result<Vec<OutKey>> {
+    let outs = client
+        .get_outs(
+            indices
+                .iter()
+                .map(|index| GetOutputsOut {
+                    amount: 0,
+                    index: *index,
+                })
+                .collect(),
+        )
+        .await?
+        .outs;
+
+    if outs.len() != indices.len() {
+        bail!(
+            "Requested {} outputs from monerod but got {}",
+            indices.len(),
+            outs.len()
+        )
+    }
+
+    Ok(outs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curve25519_dalek::constants::ED25519_BASEPOINT_COMPRESSED;
+    use mockito::{Server, ServerGuard};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use serde::{Deserialize, Serialize, Serializer};
+    use serde_json::{json, Value};
+
+    /// Blocks with `outputs_per_block` outputs each, the first output of
+    /// every block is a coinbase output.
+    #[derive(Clone, Copy)]
+    struct Chain {
+        blocks: u64,
+        outputs_per_block: u64,
+    }
+
+    impl Chain {
+        const COINBASE_UNLOCK_WINDOW: u64 = 60;
+
+        fn rct_offsets(&self) -> Vec<u64> {
+            (1..=self.blocks)
+                .map(|block| block * self.outputs_per_block)
+                .collect()
+        }
+
+        fn height_of(&self, index: u64) -> u64 {
+            index / self.outputs_per_block
+        }
+
+        fn is_coinbase(&self, index: u64) -> bool {
+            index % self.outputs_per_block == 0
+        }
+
+        fn is_unlocked(&self, index: u64) -> bool {
+            let age = self.blocks - self.height_of(index);
+
+            if self.is_coinbase(index) {
+                age > Self::COINBASE_UNLOCK_WINDOW
+            } else {
+                age > SPENDABLE_AGE
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct GetOutsRequest {
+        outputs: Vec<GetOutsRequestOut>,
+    }
+
+    #[derive(Deserialize)]
+    struct GetOutsRequestOut {
+        #[allow(dead_code)]
+        amount: u64,
+        index: u64,
+    }
+
+    #[derive(Serialize)]
+    struct GetOutsResponse {
+        credits: u64,
+        status: String,
+        top_hash: String,
+        untrusted: bool,
+        outs: Vec<Out>,
+    }
+
+    #[derive(Serialize)]
+    struct Out {
+        height: u64,
+        #[serde(serialize_with = "bytes")]
+        key: [u8; 32],
+        #[serde(serialize_with = "bytes")]
+        mask: [u8; 32],
+        #[serde(serialize_with = "bytes")]
+        txid: [u8; 32],
+        unlocked: bool,
+    }
+
+    #[derive(Serialize)]
+    struct GetOIndexesResponse {
+        credits: u64,
+        status: String,
+        top_hash: String,
+        untrusted: bool,
+        o_indexes: Vec<u64>,
+    }
+
+    fn bytes<S: Serializer>(bytes: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    /// Serves `get_block_count`, `get_output_distribution`, `get_o_indexes`
+    /// and `get_outs` for the given chain. The transaction being spent has a
+    /// single output with global index `real_index`.
+    async fn monerod_stand_in(chain: Chain, real_index: u64) -> (ServerGuard, monerod::Client) {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/json_rpc")
+            .with_body_from_request(move |request| {
+                let request: Value = serde_json::from_slice(request.body().unwrap()).unwrap();
+                let result = match request["method"].as_str().unwrap() {
+                    "get_block_count" => json!({ "count": chain.blocks, "status": "OK" }),
+                    "get_output_distribution" => json!({
+                        "distributions": [{
+                            "amount": 0,
+                            "start_height": 0,
+                            "base": 0,
+                            "distribution": chain.rct_offsets(),
+                        }],
+                        "status": "OK",
+                    }),
+                    method => panic!("unexpected method {}", method),
+                };
+
+                json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+                    .to_string()
+                    .into_bytes()
+            })
+            .create_async()
+            .await;
+
+        server
+            .mock("POST", "/get_o_indexes.bin")
+            .with_body(
+                monero_epee_bin_serde::to_bytes(&GetOIndexesResponse {
+                    credits: 0,
+                    status: "OK".to_owned(),
+                    top_hash: String::new(),
+                    untrusted: false,
+                    o_indexes: vec![real_index],
+                })
+                .unwrap(),
+            )
+            .create_async()
+            .await;
+
+        server
+            .mock("POST", "/get_outs.bin")
+            .with_body_from_request(move |request| {
+                let request: GetOutsRequest =
+                    monero_epee_bin_serde::from_bytes(request.body().unwrap()).unwrap();
+                let outs = request
+                    .outputs
+                    .iter()
+                    .map(|out| Out {
+                        height: chain.height_of(out.index),
+                        key: ED25519_BASEPOINT_COMPRESSED.to_bytes(),
+                        mask: [0; 32],
+                        txid: [0; 32],
+                        unlocked: chain.is_unlocked(out.index),
+                    })
+                    .collect();
+
+                monero_epee_bin_serde::to_bytes(&GetOutsResponse {
+                    credits: 0,
+                    status: "OK".to_owned(),
+                    top_hash: String::new(),
+                    untrusted: false,
+                    outs,
+                })
+                .unwrap()
+            })
+            .create_async()
+            .await;
+
+        let client = monerod::Client::from_daemon_address(&server.host_with_port()).unwrap();
+
+        (server, client)
+    }
+
+    #[test]
+    fn never_picks_outputs_younger_than_spendable_age() {
+        let chain = Chain {
+            blocks: 1000,
+            outputs_per_block: 10,
+        };
+        let picker = GammaPicker::new(chain.rct_offsets()).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(picker.num_spendable_outputs(), 990 * 10);
+
+        for _ in 0..10_000 {
+            if let Some(index) = picker.pick(&mut rng) {
+                assert!(index < picker.num_spendable_outputs());
+            }
+        }
+    }
+
+    #[test]
+    fn picks_favour_recent_outputs() {
+        let chain = Chain {
+            blocks: 100_000,
+            outputs_per_block: 10,
+        };
+        let picker = GammaPicker::new(chain.rct_offsets()).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut ages = (0..10_000)
+            .filter_map(|_| picker.pick(&mut rng))
+            .map(|index| chain.blocks - chain.height_of(index))
+            .collect::<Vec<_>>();
+        ages.sort_unstable();
+        let median_age = ages[ages.len() / 2];
+
+        // half of the real spends are younger than about 1.5 days
+        assert!(
+            (900..1300).contains(&median_age),
+            "median age was {} blocks",
+            median_age
+        );
+    }
+
+    #[test]
+    fn needs_more_blocks_than_spendable_age() {
+        let chain = Chain {
+            blocks: SPENDABLE_AGE,
+            outputs_per_block: 10,
+        };
+
+        assert!(GammaPicker::new(chain.rct_offsets()).is_err());
+    }
+
+    #[test]
+    fn key_offsets_are_relative() {
+        let member = |global_index| RingMember {
+            global_index,
+            key: PublicKey::from_slice(ED25519_BASEPOINT_COMPRESSED.as_bytes()).unwrap(),
+            commitment: ringct::Key { key: [0; 32] },
+        };
+        let ring = Ring {
+            members: vec![member(5), member(7), member(20)],
+            real_position: 1,
+        };
+
+        assert_eq!(ring.key_offsets(), vec![VarInt(5), VarInt(2), VarInt(13)]);
+    }
+
+    #[tokio::test]
+    async fn selects_ring_of_unlocked_outputs() {
+        let chain = Chain {
+            blocks: 5000,
+            outputs_per_block: 4,
+        };
+        let real_index = 4 * 4000 + 1;
+        let (_server, client) = monerod_stand_in(chain, real_index).await;
+
+        let ring = select_ring(&client, Hash::new(b"tx"), 0, &mut StdRng::seed_from_u64(0))
+            .await
+            .unwrap();
+
+        assert_eq!(ring.members.len(), RING_SIZE);
+        assert_eq!(ring.members[ring.real_position].global_index, real_index);
+        assert!(ring
+            .members
+            .windows(2)
+            .all(|pair| pair[0].global_index < pair[1].global_index));
+        assert!(ring
+            .members
+            .iter()
+            .all(|member| chain.is_unlocked(member.global_index)));
+    }
+
+    #[tokio::test]
+    async fn fails_if_real_output_is_too_young() {
+        let chain = Chain {
+            blocks: 5000,
+            outputs_per_block: 4,
+        };
+        let real_index = 4 * 4995 + 1;
+        let (_server, client) = monerod_stand_in(chain, real_index).await;
+
+        let result = select_ring(&client, Hash::new(b"tx"), 0, &mut StdRng::seed_from_u64(0)).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn fails_if_there_are_not_enough_outputs() {
+        // only the 9 non-coinbase outputs of the first 10 blocks besides the
+        // real one are spendable
+        let chain = Chain {
+            blocks: 20,
+            outputs_per_block: 2,
+        };
+        let (_server, client) = monerod_stand_in(chain, 1).await;
+
+        let result = select_ring(&client, Hash::new(b"tx"), 0, &mut StdRng::seed_from_u64(0)).await;
+
+        assert!(result.is_err());
+    }
+}
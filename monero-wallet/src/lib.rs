@@ -1,92 +1,63 @@
-use anyhow::{Context, Result};
-use monero::consensus::encode::VarInt;
-use monero::cryptonote::hash::Hashable;
+pub mod decoys;
+pub mod transfer;
+
+use anyhow::Result;
+use decoys::Ring;
+use monero::cryptonote::hash::Hash;
 use monero_rpc::monerod;
-use monero_rpc::monerod::{GetBlockResponse, MonerodRpc as _};
-use rand::Rng;
+use rand::rngs::OsRng;
 
 pub struct Wallet {
     client: monerod::Client,
 }
 
 impl Wallet {
-    /// Chooses 10 random key offsets for use within a new confidential
-    /// transactions.
-    ///
-    /// Choosing these offsets randomly is not ideal for privacy, instead they
-    /// should be chosen in a way that mimics a real spending pattern as much as
-    /// possible.
-    pub async fn choose_ten_random_key_offsets(&self) -> Result<[VarInt; 10]> {
-        let latest_block = self.client.get_block_count().await?;
-        let latest_spendable_block = latest_block.count - 10;
-
-        let block: GetBlockResponse = self.client.get_block(latest_spendable_block).await?;
-
-        let tx_hash = block
-            .blob
-            .tx_hashes
-            .first()
-            .copied()
-            .unwrap_or_else(|| block.blob.miner_tx.hash());
-
-        let indices = self.client.get_o_indexes(tx_hash).await?;
-
-        let last_index = indices
-            .o_indexes
-            .into_iter()
-            .max()
-            .context("Expected at least one output index")?;
-        let oldest_index = last_index - (last_index / 100) * 40; // oldest index must be within last 40% TODO: CONFIRM THIS
-
-        let mut rng = rand::thread_rng();
-
-        Ok([
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-            VarInt(rng.gen_range(oldest_index, last_index)),
-        ])
+    /// Selects the decoys for spending the output at `output_index` of the
+    /// transaction `txid`, see [`decoys`].
+    pub async fn select_ring(&self, txid: Hash, output_index: usize) -> Result<Ring> {
+        decoys::select_ring(&self.client, txid, output_index, &mut OsRng).await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use decoys::GammaPicker;
     use monero_harness::image::Monerod;
-    use monero_rpc::monerod::{Client, GetOutputsOut};
+    use monero_rpc::monerod::{Client, GetOutputsOut, MonerodRpc as _};
     use testcontainers::clients::Cli;
 
     #[tokio::test]
-    async fn get_outs_for_key_offsets() {
+    async fn get_outs_for_picked_decoys() {
         let cli = Cli::default();
         let container = cli.run(Monerod);
         let rpc_client = Client::localhost(container.get_host_port_ipv4(18081)).unwrap();
         rpc_client.generateblocks(150, "498AVruCDWgP9Az9LjMm89VWjrBrSZ2W2K3HFBiyzzrRjUJWUcCVxvY1iitfuKoek2FdX6MKGAD9Qb1G1P8QgR5jPmmt3Vj".to_owned()).await.unwrap();
-        let wallet = Wallet {
-            client: rpc_client.clone(),
-        };
 
-        let key_offsets = wallet.choose_ten_random_key_offsets().await.unwrap();
+        let block_count = rpc_client.get_block_count().await.unwrap().count as u64;
+        let distribution = rpc_client
+            .get_output_distribution(vec![0], true, 0, block_count - 1, false)
+            .await
+            .unwrap()
+            .distributions
+            .remove(0);
+        let picker = GammaPicker::new(distribution.distribution).unwrap();
+
+        let mut rng = rand::thread_rng();
+        let picks = std::iter::repeat_with(|| picker.pick(&mut rng))
+            .flatten()
+            .take(decoys::RING_SIZE)
+            .collect::<Vec<_>>();
         let result = rpc_client
             .get_outs(
-                key_offsets
-                    .iter()
-                    .cloned()
-                    .map(|varint| GetOutputsOut {
-                        amount: 0,
-                        index: varint.0,
-                    })
+                picks
+                    .into_iter()
+                    .map(|index| GetOutputsOut { amount: 0, index })
                     .collect(),
             )
             .await
             .unwrap();
 
-        assert_eq!(result.outs.len(), 10);
+        assert_eq!(result.outs.len(), decoys::RING_SIZE);
     }
 }
@@ -0,0 +1,372 @@
+//! Verification of transfers using the secret key of a transaction.
+//!
+//! This is the equivalent of `check_tx_key` of `monero-wallet-rpc`: Given the
+//! transaction key `r` and the recipient's address `(A, B)`, the sender and
+//! the recipient share the derivation `8rA = 8aR`. For each output `i` the
+//! expected one-time key is `Hs(8rA || i)G + B`, the amount is decrypted with
+//! the same derivation and checked against the output's commitment.
+
+use anyhow::{bail, Context, Result};
+use curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use monero::consensus::encode::{serialize, VarInt};
+use monero::cryptonote::hash::{keccak_256, Hash};
+use monero::{Address, PrivateKey};
+use monero_rpc::monerod;
+use monero_rpc::monerod::{MonerodRpc as _, TransactionJson};
+use std::convert::TryInto;
+
+/// Tags of the fields within `tx_extra`.
+const TX_EXTRA_TAG_PUBKEY: u8 = 0x01;
+const TX_EXTRA_TAG_NONCE: u8 = 0x02;
+const TX_EXTRA_TAG_ADDITIONAL_PUBKEYS: u8 = 0x04;
+
+/// The first RingCT type with 8 byte encrypted amounts.
+const RCT_TYPE_BULLETPROOF2: u8 = 4;
+
+/// Generator `H` of the amount in Pedersen commitments.
+const H: [u8; 32] = [
+    0x8b, 0x65, 0x59, 0x70, 0x15, 0x37, 0x99, 0xaf, 0x2a, 0xea, 0xdc, 0x9f, 0xf1, 0xad, 0xd0, 0xea,
+    0x6c, 0x72, 0x51, 0xd5, 0x41, 0x54, 0xcf, 0xa9, 0x2c, 0x17, 0x3a, 0x0d, 0xd3, 0x9c, 0x1f, 0x94,
+];
+
+/// Result of checking a transaction against a transaction key and address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheckTxKey {
+    /// The amount in piconero the address received with the transaction.
+    pub received: u64,
+    /// Number of confirmations, zero while the transaction is in the pool.
+    pub confirmations: u64,
+    pub in_pool: bool,
+}
+
+/// Fetches the transaction from monerod and checks how much the given address
+/// received with it.
+pub async fn check_tx_key(
+    client: &monerod::Client,
+    txid: Hash,
+    tx_key: PrivateKey,
+    address: Address,
+) -> Result<CheckTxKey> {
+    let response = client.get_transactions(vec![txid]).await?;
+
+    let entry = match response.txs.as_slice() {
+        [entry] => entry,
+        [] => bail!("Transaction {:x} not found", txid),
+        _ => bail!("Expected exactly one transaction for {:x}", txid),
+    };
+
+    let tx = serde_json::from_str::<TransactionJson>(&entry.as_json)
+        .context("Failed to deserialize transaction")?;
+    let received = received_amount(&tx, tx_key, address)?;
+
+    let confirmations = if entry.in_pool {
+        0
+    } else {
+        let height = u64::from(client.get_block_count().await?.count);
+        height.saturating_sub(entry.block_height)
+    };
+
+    Ok(CheckTxKey {
+        received,
+        confirmations,
+        in_pool: entry.in_pool,
+    })
+}
+
+/// Sums up the amounts of all outputs of the transaction sent to `address`.
+pub fn received_amount(tx: &TransactionJson, tx_key: PrivateKey, address: Address) -> Result<u64> {
+    if tx.rct_signatures.rct_type < RCT_TYPE_BULLETPROOF2 {
+        bail!(
+            "Unsupported RingCT type {}, only types with compact amounts are supported",
+            tx.rct_signatures.rct_type
+        );
+    }
+
+    let r = Scalar::from_bytes_mod_order(tx_key.to_bytes());
+    let tx_public_key =
+        tx_public_key(&tx.extra).context("Transaction does not contain a public key")?;
+
+    if (&r * &ED25519_BASEPOINT_TABLE).compress() != tx_public_key {
+        bail!("Transaction key does not match the public key of the transaction");
+    }
+
+    let view = decompress(&address.public_view.to_bytes())?;
+    let spend = decompress(&address.public_spend.to_bytes())?;
+    let derivation = (r * view).mul_by_cofactor().compress();
+
+    let mut received = 0u64;
+
+    for (index, output) in tx.vout.iter().enumerate() {
+        let shared_secret = derivation_to_scalar(&derivation, index as u64);
+
+        let expected_key = &shared_secret * &ED25519_BASEPOINT_TABLE + spend;
+        if hex::encode(expected_key.compress().as_bytes()) != output.target.key() {
+            continue;
+        }
+
+        let ecdh_info = tx
+            .rct_signatures
+            .ecdh_info
+            .get(index)
+            .context("Missing encrypted amount for output")?;
+        let commitment = tx
+            .rct_signatures
+            .out_pk
+            .get(index)
+            .context("Missing commitment for output")?;
+
+        let amount = decrypt_amount(&ecdh_info.amount, &shared_secret)?;
+
+        if !commitment_matches(commitment, &shared_secret, amount)? {
+            bail!(
+                "Commitment of output {} does not match the decrypted amount",
+                index
+            );
+        }
+
+        received = received
+            .checked_add(amount)
+            .context("Received amount overflows")?;
+    }
+
+    Ok(received)
+}
+
+/// `Hs(derivation || varint(index))`
+fn derivation_to_scalar(derivation: &CompressedEdwardsY, index: u64) -> Scalar {
+    let mut bytes = derivation.as_bytes().to_vec();
+    bytes.extend(serialize(&VarInt(index)));
+
+    hash_to_scalar(&bytes)
+}
+
+fn hash_to_scalar(bytes: &[u8]) -> Scalar {
+    Scalar::from_bytes_mod_order(keccak_256(bytes))
+}
+
+fn decrypt_amount(encrypted: &str, shared_secret: &Scalar) -> Result<u64> {
+    let encrypted: [u8; 8] = hex::decode(encrypted)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Encrypted amount must be 8 bytes"))?;
+
+    let mut bytes = b"amount".to_vec();
+    bytes.extend(shared_secret.as_bytes());
+    let key = keccak_256(&bytes);
+
+    let mut amount = [0u8; 8];
+    for (i, byte) in amount.iter_mut().enumerate() {
+        *byte = encrypted[i] ^ key[i];
+    }
+
+    Ok(u64::from_le_bytes(amount))
+}
+
+/// Checks `C = mask * G + amount * H` with `mask = Hs("commitment_mask" ||
+/// shared secret)`.
+fn commitment_matches(commitment: &str, shared_secret: &Scalar, amount: u64) -> Result<bool> {
+    let mut bytes = b"commitment_mask".to_vec();
+    bytes.extend(shared_secret.as_bytes());
+    let mask = hash_to_scalar(&bytes);
+
+    let h = decompress(&H)?;
+    let expected = &mask * &ED25519_BASEPOINT_TABLE + Scalar::from(amount) * h;
+
+    Ok(hex::encode(expected.compress().as_bytes()) == commitment)
+}
+
+/// Extracts the transaction public key from `tx_extra`.
+fn tx_public_key(extra: &[u8]) -> Option<CompressedEdwardsY> {
+    let mut position = 0;
+
+    while position < extra.len() {
+        let tag = extra[position];
+        position += 1;
+
+        match tag {
+            TX_EXTRA_TAG_PUBKEY => {
+                let key = extra.get(position..position + 32)?;
+                return Some(CompressedEdwardsY::from_slice(key));
+            }
+            TX_EXTRA_TAG_NONCE => {
+                let length = usize::from(*extra.get(position)?);
+                position += 1 + length;
+            }
+            TX_EXTRA_TAG_ADDITIONAL_PUBKEYS => {
+                let count = usize::from(*extra.get(position)?);
+                position += 1 + count * 32;
+            }
+            // Padding and unknown fields can't be skipped
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+fn decompress(bytes: &[u8]) -> Result<EdwardsPoint> {
+    CompressedEdwardsY::from_slice(bytes)
+        .decompress()
+        .context("Invalid point")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monero::{Network, PublicKey};
+    use monero_rpc::monerod::{EcdhInfo, OutputTarget, RctSignatures, TransactionOutput};
+
+    fn random_key() -> PrivateKey {
+        PrivateKey::from_scalar(Scalar::random(&mut rand::thread_rng()))
+    }
+
+    fn random_address() -> Address {
+        Address::standard(
+            Network::Mainnet,
+            PublicKey::from_private_key(&random_key()),
+            PublicKey::from_private_key(&random_key()),
+        )
+    }
+
+    /// Builds the parts of a transaction paying `amounts` to `address`,
+    /// mirroring what a sending wallet does.
+    fn transaction(tx_key: PrivateKey, address: Address, amounts: &[u64]) -> TransactionJson {
+        let r = Scalar::from_bytes_mod_order(tx_key.to_bytes());
+        let view = decompress(&address.public_view.to_bytes()).unwrap();
+        let spend = decompress(&address.public_spend.to_bytes()).unwrap();
+        let derivation = (r * view).mul_by_cofactor().compress();
+        let h = decompress(&H).unwrap();
+
+        let mut vout = Vec::new();
+        let mut ecdh_info = Vec::new();
+        let mut out_pk = Vec::new();
+
+        for (index, amount) in amounts.iter().enumerate() {
+            let shared_secret = derivation_to_scalar(&derivation, index as u64);
+            let key = &shared_secret * &ED25519_BASEPOINT_TABLE + spend;
+
+            let mut bytes = b"amount".to_vec();
+            bytes.extend(shared_secret.as_bytes());
+            let amount_key = keccak_256(&bytes);
+            let encrypted = amount
+                .to_le_bytes()
+                .iter()
+                .zip(amount_key.iter())
+                .map(|(a, k)| a ^ k)
+                .collect::<Vec<_>>();
+
+            let mut bytes = b"commitment_mask".to_vec();
+            bytes.extend(shared_secret.as_bytes());
+            let mask = hash_to_scalar(&bytes);
+            let commitment = &mask * &ED25519_BASEPOINT_TABLE + Scalar::from(*amount) * h;
+
+            vout.push(TransactionOutput {
+                amount: 0,
+                target: OutputTarget::TaggedKey {
+                    key: hex::encode(key.compress().as_bytes()),
+                    view_tag: "00".to_owned(),
+                },
+            });
+            ecdh_info.push(EcdhInfo {
+                amount: hex::encode(encrypted),
+            });
+            out_pk.push(hex::encode(commitment.compress().as_bytes()));
+        }
+
+        let mut extra = vec![TX_EXTRA_TAG_PUBKEY];
+        extra.extend(PublicKey::from_private_key(&tx_key).to_bytes());
+
+        TransactionJson {
+            vout,
+            extra,
+            rct_signatures: RctSignatures {
+                rct_type: 6,
+                ecdh_info,
+                out_pk,
+            },
+        }
+    }
+
+    #[test]
+    fn sums_amounts_sent_to_address() {
+        let address = random_address();
+        let tx_key = random_key();
+        let tx = transaction(tx_key, address, &[1_000_000, 2_500_000]);
+
+        let received = received_amount(&tx, tx_key, address).unwrap();
+
+        assert_eq!(received, 3_500_000);
+    }
+
+    #[test]
+    fn ignores_outputs_sent_to_other_addresses() {
+        let address = random_address();
+        let tx_key = random_key();
+        let tx = transaction(tx_key, address, &[1_000_000]);
+
+        let received = received_amount(&tx, tx_key, random_address()).unwrap();
+
+        assert_eq!(received, 0);
+    }
+
+    #[test]
+    fn given_wrong_tx_key_fails() {
+        let address = random_address();
+        let tx = transaction(random_key(), address, &[1_000_000]);
+
+        assert!(received_amount(&tx, random_key(), address).is_err());
+    }
+
+    #[test]
+    fn given_manipulated_amount_fails() {
+        let address = random_address();
+        let tx_key = random_key();
+        let mut tx = transaction(tx_key, address, &[1_000_000]);
+        let other = transaction(tx_key, address, &[2_000_000]);
+        tx.rct_signatures.ecdh_info = other.rct_signatures.ecdh_info;
+
+        assert!(received_amount(&tx, tx_key, address).is_err());
+    }
+
+    #[test]
+    fn finds_tx_public_key_after_nonce() {
+        let key = PublicKey::from_private_key(&random_key()).to_bytes();
+
+        let mut extra = vec![TX_EXTRA_TAG_NONCE, 3, 0xaa, 0xbb, 0xcc, TX_EXTRA_TAG_PUBKEY];
+        extra.extend(key);
+
+        assert_eq!(
+            tx_public_key(&extra),
+            Some(CompressedEdwardsY::from_slice(&key))
+        );
+        assert_eq!(tx_public_key(&[TX_EXTRA_TAG_NONCE, 3, 0xaa]), None);
+    }
+
+    #[test]
+    fn deserializes_transaction_decoded_by_monerod() {
+        let json = r#"{
+            "version": 2,
+            "unlock_time": 0,
+            "vin": [],
+            "vout": [
+                { "amount": 0, "target": { "tagged_key": { "key": "aa", "view_tag": "f4" } } },
+                { "amount": 0, "target": { "key": "bb" } }
+            ],
+            "extra": [1, 2, 3],
+            "rct_signatures": {
+                "type": 6,
+                "txnFee": 30720000,
+                "ecdhInfo": [ { "amount": "c4e0f1b2a3d4e5f6" }, { "amount": "c4e0f1b2a3d4e5f6" } ],
+                "outPk": [ "cc", "dd" ]
+            }
+        }"#;
+
+        let tx = serde_json::from_str::<TransactionJson>(json).unwrap();
+
+        assert_eq!(tx.vout[0].target.key(), "aa");
+        assert_eq!(tx.vout[1].target.key(), "bb");
+        assert_eq!(tx.rct_signatures.rct_type, 6);
+        assert_eq!(tx.rct_signatures.out_pk, vec!["cc", "dd"]);
+    }
+}
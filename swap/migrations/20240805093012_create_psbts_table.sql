@@ -0,0 +1,6 @@
+CREATE TABLE if NOT EXISTS psbts
+(
+    swap_id         TEXT    PRIMARY KEY NOT NULL,
+    unsigned_psbt   TEXT                NOT NULL,
+    signed_psbt     TEXT
+);
@@ -0,0 +1,7 @@
+CREATE TABLE if NOT EXISTS bitcoin_script_statuses
+(
+    txid                TEXT    NOT NULL,
+    script              TEXT    NOT NULL,
+    inclusion_height    INTEGER NOT NULL,
+    PRIMARY KEY (txid, script)
+);
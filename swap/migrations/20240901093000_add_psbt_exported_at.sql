@@ -0,0 +1,3 @@
+-- Unix timestamp at which the unsigned PSBT was handed to the user, NULL for
+-- PSBTs exported before it was recorded
+ALTER TABLE psbts ADD COLUMN exported_at INTEGER;
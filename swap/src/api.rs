@@ -9,12 +9,13 @@ use crate::seed::Seed;
 use crate::{bitcoin, cli, monero};
 use anyhow::{bail, Context as AnyContext, Error, Result};
 use futures::future::try_join_all;
+use libp2p::PeerId;
 use std::fmt;
 use std::future::Future;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::{Arc, Once};
-use tokio::sync::{broadcast, broadcast::Sender, Mutex, RwLock};
+use tokio::sync::{broadcast, broadcast::Sender, watch, Mutex, RwLock};
 use tokio::task::JoinHandle;
 use url::Url;
 
@@ -64,16 +65,28 @@ impl PendingTaskList {
     }
 }
 
+/// Tracks the running swaps. Swaps run one at a time, unless they are with
+/// the same seller and share the event loop of the first swap.
 pub struct SwapLock {
-    current_swap: RwLock<Option<Uuid>>,
+    current_swaps: RwLock<Vec<Uuid>>,
+    shared_event_loop: Mutex<Option<SharedEventLoop>>,
     suspension_trigger: Sender<()>,
 }
 
+/// The seller of the running swaps and the event loop they share.
+struct SharedEventLoop {
+    seller: PeerId,
+    handle: cli::EventLoopHandle,
+    /// Closed once the event loop stopped.
+    stopped: watch::Receiver<()>,
+}
+
 impl SwapLock {
     pub fn new() -> Self {
         let (suspension_trigger, _) = broadcast::channel(10);
         SwapLock {
-            current_swap: RwLock::new(None),
+            current_swaps: RwLock::new(Vec::new()),
+            shared_event_loop: Mutex::new(None),
             suspension_trigger,
         }
     }
@@ -91,25 +104,80 @@ impl SwapLock {
     }
 
     pub async fn acquire_swap_lock(&self, swap_id: Uuid) -> Result<(), Error> {
-        let mut current_swap = self.current_swap.write().await;
-        if current_swap.is_some() {
+        let mut current_swaps = self.current_swaps.write().await;
+        if !current_swaps.is_empty() {
             bail!("There already exists an active swap lock");
         }
 
         tracing::debug!(swap_id = %swap_id, "Acquiring swap lock");
-        *current_swap = Some(swap_id);
+        current_swaps.push(swap_id);
         Ok(())
     }
 
+    /// Acquires the lock for a swap with the given seller.
+    ///
+    /// If swaps with the seller are running, the swap joins them and the
+    /// handle for the swap on their event loop is returned, together with a
+    /// receiver that is closed once the event loop stopped. Otherwise the
+    /// caller starts an event loop and shares it with
+    /// [`SwapLock::share_event_loop`].
+    pub async fn acquire_swap_lock_with_seller(
+        &self,
+        swap_id: Uuid,
+        seller: PeerId,
+    ) -> Result<Option<(cli::EventLoopHandle, watch::Receiver<()>)>, Error> {
+        let mut current_swaps = self.current_swaps.write().await;
+        let shared_event_loop = self.shared_event_loop.lock().await;
+
+        let handle = match shared_event_loop.as_ref() {
+            None if current_swaps.is_empty() => None,
+            Some(shared) if shared.seller == seller => Some((
+                shared.handle.new_swap(swap_id).await?,
+                shared.stopped.clone(),
+            )),
+            _ => bail!("There already exists an active swap lock"),
+        };
+
+        tracing::debug!(swap_id = %swap_id, "Acquiring swap lock");
+        current_swaps.push(swap_id);
+        Ok(handle)
+    }
+
+    /// Lets further swaps with the seller run on the given event loop, as long
+    /// as any swap is running. `stopped` must be closed once the event loop
+    /// stopped. Returns the handle for the swap that started the event loop.
+    pub async fn share_event_loop(
+        &self,
+        swap_id: Uuid,
+        seller: PeerId,
+        handle: cli::EventLoopHandle,
+        stopped: watch::Receiver<()>,
+    ) -> Result<cli::EventLoopHandle, Error> {
+        let current_swaps = self.current_swaps.read().await;
+        if !current_swaps.contains(&swap_id) {
+            bail!("There is no swap lock for swap {}", swap_id);
+        }
+
+        let swap_handle = handle.new_swap(swap_id).await?;
+        *self.shared_event_loop.lock().await = Some(SharedEventLoop {
+            seller,
+            handle,
+            stopped,
+        });
+
+        Ok(swap_handle)
+    }
+
+    /// The first of the running swaps.
     pub async fn get_current_swap_id(&self) -> Option<Uuid> {
-        *self.current_swap.read().await
+        self.current_swaps.read().await.first().copied()
     }
 
     /// Sends a signal to suspend all ongoing swap processes.
     ///
     /// This function performs the following steps:
     /// 1. Triggers the suspension by sending a unit `()` signal to all listeners via `self.suspension_trigger`.
-    /// 2. Polls the `current_swap` state every 50 milliseconds to check if it has been set to `None`, indicating that the swap processes have been suspended and the lock released.
+    /// 2. Polls the running swaps every 50 milliseconds to check if there are none left, indicating that the swap processes have been suspended and their locks released.
     /// 3. If the lock is not released within 10 seconds, the function returns an error.
     ///
     /// If we send a suspend signal while no swap is in progress, the function will not fail, but will return immediately.
@@ -136,18 +204,21 @@ impl SwapLock {
         bail!("Timed out waiting for swap lock to be released");
     }
 
-    pub async fn release_swap_lock(&self) -> Result<Uuid, Error> {
-        let mut current_swap = self.current_swap.write().await;
-        if let Some(swap_id) = current_swap.as_ref() {
-            tracing::debug!(swap_id = %swap_id, "Releasing swap lock");
-
-            let prev_swap_id = *swap_id;
-            *current_swap = None;
-            drop(current_swap);
-            Ok(prev_swap_id)
-        } else {
-            bail!("There is no current swap lock to release");
+    pub async fn release_swap_lock(&self, swap_id: Uuid) -> Result<(), Error> {
+        let mut current_swaps = self.current_swaps.write().await;
+        match current_swaps.iter().position(|id| *id == swap_id) {
+            Some(index) => {
+                tracing::debug!(swap_id = %swap_id, "Releasing swap lock");
+                current_swaps.remove(index);
+            }
+            None => bail!("There is no swap lock to release for swap {}", swap_id),
         }
+
+        if current_swaps.is_empty() {
+            *self.shared_event_loop.lock().await = None;
+        }
+
+        Ok(())
     }
 }
 
@@ -157,13 +228,14 @@ impl Default for SwapLock {
     }
 }
 
-// workaround for warning over monero_rpc_process which we must own but not read
+// workaround for warning over monero_rpc_supervisor which we must own but not read
 #[allow(dead_code)]
 pub struct Context {
     pub db: Arc<dyn Database + Send + Sync>,
     bitcoin_wallet: Option<Arc<bitcoin::Wallet>>,
     monero_wallet: Option<Arc<monero::Wallet>>,
-    monero_rpc_process: Option<monero::WalletRpcProcess>,
+    monero_rpc_supervisor: Option<monero::WalletRpcSupervisor>,
+    monero_daemon_status: Option<Arc<RwLock<monero::DaemonStatus>>>,
     pub swap_lock: Arc<SwapLock>,
     pub config: Config,
     pub tasks: Arc<PendingTaskList>,
@@ -193,41 +265,103 @@ impl Context {
 
         let bitcoin_wallet = {
             if let Some(bitcoin) = bitcoin {
+                let watch_only_descriptor = bitcoin.bitcoin_watch_only_descriptor.clone();
+                let fee_rate_sources = bitcoin::fee::sources(
+                    bitcoin.bitcoind_rpc_url.clone(),
+                    bitcoin.mempool_space_url.clone(),
+                );
+                let fee_limits = bitcoin::fee::FeeLimits::from_config(
+                    bitcoin.max_fee_rate,
+                    bitcoin.max_fee_percentage,
+                );
                 let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
                     bitcoin.apply_defaults(is_testnet)?;
-                Some(Arc::new(
-                    init_bitcoin_wallet(
-                        bitcoin_electrum_rpc_url,
-                        &seed,
-                        data_dir.clone(),
-                        env_config,
-                        bitcoin_target_block,
-                    )
-                    .await?,
-                ))
+
+                let bitcoin_wallet = init_bitcoin_wallet(
+                    bitcoin_electrum_rpc_url,
+                    &seed,
+                    watch_only_descriptor,
+                    data_dir.clone(),
+                    env_config,
+                    bitcoin_target_block,
+                )
+                .await?;
+                bitcoin_wallet
+                    .use_fee_rate_sources(fee_rate_sources, fee_limits)
+                    .await;
+
+                Some(Arc::new(bitcoin_wallet))
             } else {
                 None
             }
         };
 
-        let (monero_wallet, monero_rpc_process) = {
+        let (monero_wallet, monero_rpc_supervisor, monero_daemon_status) = {
             if let Some(monero) = monero {
-                let monero_daemon_address = monero.apply_defaults(is_testnet);
-                let (wlt, prc) =
-                    init_monero_wallet(data_dir.clone(), monero_daemon_address, env_config).await?;
-                (Some(Arc::new(wlt)), Some(prc))
+                let wallet_rpc_options = monero.wallet_rpc_options();
+                let remote_wallet_rpc = monero.remote_wallet_rpc()?;
+                let monero_daemons = monero.apply_defaults(is_testnet)?;
+                let (monero_daemon, probes) =
+                    monero::daemon::choose_monero_daemon(&monero_daemons).await?;
+
+                let status = Arc::new(RwLock::new(monero::DaemonStatus {
+                    current: monero_daemon.to_string(),
+                    probes,
+                }));
+
+                match remote_wallet_rpc {
+                    // The remote monero-wallet-rpc is managed by its operator, we neither
+                    // supervise it nor switch its daemon
+                    Some(remote_wallet_rpc) => {
+                        let wlt = init_remote_monero_wallet(
+                            remote_wallet_rpc,
+                            &monero_daemon,
+                            env_config,
+                        )
+                        .await?;
+
+                        (Some(Arc::new(wlt)), None, Some(status))
+                    }
+                    None => {
+                        let (wlt, prc) = init_monero_wallet(
+                            data_dir.clone(),
+                            &monero_daemon,
+                            wallet_rpc_options,
+                            env_config,
+                        )
+                        .await?;
+                        let wlt = Arc::new(wlt);
+
+                        tokio::spawn(monero::daemon::watch_daemon(
+                            Arc::downgrade(&wlt),
+                            monero_daemon,
+                            monero_daemons,
+                            status.clone(),
+                        ));
+                        let supervisor = prc.supervise(wlt.clone(), Some(status.clone()));
+
+                        (Some(wlt), Some(supervisor), Some(status))
+                    }
+                }
             } else {
-                (None, None)
+                (None, None, None)
             }
         };
 
         let tor_socks5_port = tor.map_or(9050, |tor| tor.tor_socks5_port);
 
+        let db = open_db(data_dir.join("sqlite"), AccessMode::ReadWrite).await?;
+
+        if let Some(bitcoin_wallet) = &bitcoin_wallet {
+            bitcoin_wallet.use_script_cache(db.clone()).await?;
+        }
+
         let context = Context {
-            db: open_db(data_dir.join("sqlite"), AccessMode::ReadWrite).await?,
+            db,
             bitcoin_wallet,
             monero_wallet,
-            monero_rpc_process,
+            monero_rpc_supervisor,
+            monero_daemon_status,
             config: Config {
                 tor_socks5_port,
                 namespace: XmrBtcNamespace::from_is_testnet(is_testnet),
@@ -262,7 +396,8 @@ impl Context {
             db: open_db(db_path, AccessMode::ReadWrite)
                 .await
                 .expect("Could not open sqlite database"),
-            monero_rpc_process: None,
+            monero_rpc_supervisor: None,
+            monero_daemon_status: None,
             swap_lock: Arc::new(SwapLock::new()),
             tasks: Arc::new(PendingTaskList::default()),
         }
@@ -282,40 +417,56 @@ impl fmt::Debug for Context {
 async fn init_bitcoin_wallet(
     electrum_rpc_url: Url,
     seed: &Seed,
+    watch_only_descriptor: Option<String>,
     data_dir: PathBuf,
     env_config: EnvConfig,
     bitcoin_target_block: usize,
 ) -> Result<bitcoin::Wallet> {
     let wallet_dir = data_dir.join("wallet");
 
-    let wallet = bitcoin::Wallet::new(
-        electrum_rpc_url.clone(),
-        &wallet_dir,
-        seed.derive_extended_private_key(env_config.bitcoin_network)?,
-        env_config,
-        bitcoin_target_block,
-    )
-    .await
-    .context("Failed to initialize Bitcoin wallet")?;
+    let wallet = match watch_only_descriptor {
+        Some(descriptor) => bitcoin::Wallet::new_watch_only(
+            electrum_rpc_url.clone(),
+            &wallet_dir,
+            &descriptor,
+            env_config,
+            bitcoin_target_block,
+        )
+        .await
+        .context("Failed to initialize watch-only Bitcoin wallet")?,
+        None => bitcoin::Wallet::new(
+            electrum_rpc_url.clone(),
+            &wallet_dir,
+            seed.derive_extended_private_key(env_config.bitcoin_network)?,
+            env_config,
+            bitcoin_target_block,
+        )
+        .await
+        .context("Failed to initialize Bitcoin wallet")?,
+    };
 
     wallet.sync().await?;
 
     Ok(wallet)
 }
 
+const MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME: &str = "swap-tool-blockchain-monitoring-wallet";
+
 async fn init_monero_wallet(
     data_dir: PathBuf,
-    monero_daemon_address: String,
+    monero_daemon: &monero::MoneroDaemon,
+    wallet_rpc_options: monero::WalletRpcOptions,
     env_config: EnvConfig,
 ) -> Result<(monero::Wallet, monero::WalletRpcProcess)> {
     let network = env_config.monero_network;
 
-    const MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME: &str = "swap-tool-blockchain-monitoring-wallet";
+    let monero_wallet_rpc =
+        monero::WalletRpc::new(data_dir.join("monero"), wallet_rpc_options).await?;
 
-    let monero_wallet_rpc = monero::WalletRpc::new(data_dir.join("monero")).await?;
+    let monerod = monero_daemon.monerod_client()?;
 
     let monero_wallet_rpc_process = monero_wallet_rpc
-        .run(network, Some(monero_daemon_address))
+        .run(network, Some(monero_daemon.to_string()))
         .await?;
 
     let monero_wallet = monero::Wallet::open_or_create(
@@ -323,11 +474,46 @@ async fn init_monero_wallet(
         MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME.to_string(),
         env_config,
     )
-    .await?;
+    .await?
+    .with_monerod(monerod);
 
     Ok((monero_wallet, monero_wallet_rpc_process))
 }
 
+async fn init_remote_monero_wallet(
+    remote_wallet_rpc: monero::RemoteWalletRpc,
+    monero_daemon: &monero::MoneroDaemon,
+    env_config: EnvConfig,
+) -> Result<monero::Wallet> {
+    let monero::RemoteWalletRpc {
+        url,
+        credentials,
+        wallet_prefix,
+    } = remote_wallet_rpc;
+
+    tracing::info!(%url, %wallet_prefix, "Using remote monero-wallet-rpc");
+
+    let client = match credentials {
+        Some(credentials) => monero_rpc::wallet::Client::with_digest_auth(url, credentials)?,
+        None => monero_rpc::wallet::Client::new(url)?,
+    };
+
+    let monero_wallet = monero::Wallet::open_or_create_with_client(
+        client,
+        format!(
+            "{}-{}",
+            wallet_prefix, MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME
+        ),
+        env_config,
+    )
+    .await
+    .context("Failed to open wallet on remote monero-wallet-rpc")?
+    .with_generated_wallet_prefix(wallet_prefix)
+    .with_monerod(monero_daemon.monerod_client()?);
+
+    Ok(monero_wallet)
+}
+
 mod data {
     use super::*;
 
@@ -440,12 +626,16 @@ pub mod api_test {
                 bitcoin_change_address: Some(bitcoin_change_address),
                 monero_receive_address,
                 swap_id: Uuid::new_v4(),
+                bitcoin_cancel_timelock: None,
+                bitcoin_punish_timelock: None,
+                skip_preflight: false,
             })
         }
 
         pub fn resume() -> Request {
             Request::new(Method::Resume {
                 swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                skip_preflight: false,
             })
         }
 
@@ -1,12 +1,14 @@
 use crate::api::Context;
-use crate::bitcoin::{Amount, ExpiredTimelocks, TxLock};
+use crate::bitcoin::{Amount, CancelTimelock, ExpiredTimelocks, PunishTimelock, TxLock};
+use crate::cli::evidence::Evidence;
 use crate::cli::{list_sellers, EventLoop, SellerStatus};
 use crate::libp2p_ext::MultiAddrExt;
 use crate::network::quote::{BidQuote, ZeroQuoteReceived};
+use crate::network::swap_setup::{TimelockBounds, Timelocks};
 use crate::network::swarm;
 use crate::protocol::bob::{BobState, Swap};
-use crate::protocol::{bob, State};
-use crate::{bitcoin, cli, monero, rpc};
+use crate::protocol::{bob, rescan, State};
+use crate::{bitcoin, cli, env, monero, rpc};
 use anyhow::{bail, Context as AnyContext, Result};
 use libp2p::core::Multiaddr;
 use qrcode::render::unicode;
@@ -16,9 +18,12 @@ use std::cmp::min;
 use std::convert::TryInto;
 use std::future::Future;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::watch;
 use tracing::{debug_span, field, Instrument, Span};
+use url::Url;
 use uuid::Uuid;
 
 #[derive(PartialEq, Debug)]
@@ -34,9 +39,13 @@ pub enum Method {
         bitcoin_change_address: Option<bitcoin::Address>,
         monero_receive_address: monero::Address,
         swap_id: Uuid,
+        bitcoin_cancel_timelock: Option<CancelTimelock>,
+        bitcoin_punish_timelock: Option<PunishTimelock>,
+        skip_preflight: bool,
     },
     Resume {
         swap_id: Uuid,
+        skip_preflight: bool,
     },
     CancelAndRefund {
         swap_id: Uuid,
@@ -57,7 +66,33 @@ pub enum Method {
         rendezvous_point: Multiaddr,
     },
     ExportBitcoinWallet,
+    Rescan {
+        bitcoin_electrum_rpc_url: Url,
+        bitcoin_target_block: usize,
+        gap_limit: u32,
+        min_lock_height: Option<u32>,
+    },
+    ExportPsbt {
+        swap_id: Uuid,
+    },
+    ImportPsbt {
+        swap_id: Uuid,
+        psbt: String,
+    },
+    ExportWatchtowerBundle {
+        swap_id: Uuid,
+        output: PathBuf,
+    },
+    ExportEvidence {
+        swap_id: Uuid,
+        output: PathBuf,
+        include_private_keys: bool,
+    },
+    VerifyEvidence {
+        path: PathBuf,
+    },
     SuspendCurrentSwap,
+    GetMoneroDaemon,
     StartDaemon {
         server_address: Option<SocketAddr>,
     },
@@ -84,7 +119,7 @@ impl Method {
             Method::CancelAndRefund { swap_id } => {
                 debug_span!("method", method_name="CancelAndRefund", swap_id=%swap_id, log_reference_id=field::Empty)
             }
-            Method::Resume { swap_id } => {
+            Method::Resume { swap_id, .. } => {
                 debug_span!("method", method_name="Resume", swap_id=%swap_id, log_reference_id=field::Empty)
             }
             Method::Config => {
@@ -101,6 +136,40 @@ impl Method {
                     log_reference_id = field::Empty
                 )
             }
+            Method::Rescan { .. } => {
+                debug_span!(
+                    "method",
+                    method_name = "Rescan",
+                    log_reference_id = field::Empty
+                )
+            }
+            Method::ExportPsbt { .. } => {
+                debug_span!(
+                    "method",
+                    method_name = "ExportPsbt",
+                    log_reference_id = field::Empty
+                )
+            }
+            Method::ImportPsbt { .. } => {
+                debug_span!(
+                    "method",
+                    method_name = "ImportPsbt",
+                    log_reference_id = field::Empty
+                )
+            }
+            Method::ExportWatchtowerBundle { swap_id, .. } => {
+                debug_span!("method", method_name="ExportWatchtowerBundle", swap_id=%swap_id, log_reference_id=field::Empty)
+            }
+            Method::ExportEvidence { swap_id, .. } => {
+                debug_span!("method", method_name="ExportEvidence", swap_id=%swap_id, log_reference_id=field::Empty)
+            }
+            Method::VerifyEvidence { .. } => {
+                debug_span!(
+                    "method",
+                    method_name = "VerifyEvidence",
+                    log_reference_id = field::Empty
+                )
+            }
             Method::GetCurrentSwap => {
                 debug_span!(
                     "method",
@@ -155,6 +224,13 @@ impl Method {
                     log_reference_id = field::Empty
                 )
             }
+            Method::GetMoneroDaemon => {
+                debug_span!(
+                    "method",
+                    method_name = "GetMoneroDaemon",
+                    log_reference_id = field::Empty
+                )
+            }
             Method::WithdrawBtc { .. } => {
                 debug_span!(
                     "method",
@@ -287,6 +363,7 @@ impl Request {
                     }
                     BobState::BtcPunished { .. } => Some(Ok(ExpiredTimelocks::Punish)),
                     BobState::BtcRefunded(_)
+                    | BobState::BtcEarlyRefunded(_)
                     | BobState::BtcRedeemed(_)
                     | BobState::XmrRedeemed { .. } => None,
                 };
@@ -319,6 +396,9 @@ impl Request {
                 bitcoin_change_address,
                 monero_receive_address,
                 swap_id,
+                bitcoin_cancel_timelock,
+                bitcoin_punish_timelock,
+                skip_preflight,
             } => {
                 let bitcoin_wallet = Arc::clone(
                     context
@@ -362,69 +442,125 @@ impl Request {
                     .insert_address(seller_peer_id, seller.clone())
                     .await?;
 
-                let behaviour = cli::Behaviour::new(
-                    seller_peer_id,
-                    env_config,
-                    bitcoin_wallet.clone(),
-                    (seed.derive_libp2p_identity(), context.config.namespace),
-                );
-                let mut swarm = swarm::cli(
-                    seed.derive_libp2p_identity(),
-                    context.config.tor_socks5_port,
-                    behaviour,
-                )
-                .await?;
-
-                swarm.behaviour_mut().add_address(seller_peer_id, seller);
-
                 context
                     .db
                     .insert_monero_address(swap_id, monero_receive_address)
                     .await?;
 
-                tracing::debug!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
-
-                context.swap_lock.acquire_swap_lock(swap_id).await?;
+                let shared_event_loop_handle = context
+                    .swap_lock
+                    .acquire_swap_lock_with_seller(swap_id, seller_peer_id)
+                    .await?;
 
                 let initialize_swap = tokio::select! {
                     biased;
                     _ = context.swap_lock.listen_for_swap_force_suspension() => {
                         tracing::debug!("Shutdown signal received, exiting");
-                        context.swap_lock.release_swap_lock().await.expect("Shutdown signal received but failed to release swap lock. The swap process has been terminated but the swap lock is still active.");
+                        context.swap_lock.release_swap_lock(swap_id).await.expect("Shutdown signal received but failed to release swap lock. The swap process has been terminated but the swap lock is still active.");
                         bail!("Shutdown signal received");
                     },
                     result = async {
-                        let (event_loop, mut event_loop_handle) =
-                            EventLoop::new(swap_id, swarm, seller_peer_id, context.db.clone())?;
-                        let event_loop = tokio::spawn(event_loop.run().in_current_span());
+                        let (event_loop, mut event_loop_handle, event_loop_stopped) = match shared_event_loop_handle {
+                            Some((event_loop_handle, event_loop_stopped)) => {
+                                tracing::info!(%seller_peer_id, "Running swap over the connection of the other swaps with the seller");
+                                (None, event_loop_handle, event_loop_stopped)
+                            }
+                            None => {
+                                let behaviour = cli::Behaviour::new(
+                                    seller_peer_id,
+                                    env_config,
+                                    bitcoin_wallet.clone(),
+                                    (seed.derive_libp2p_identity(), context.config.namespace),
+                                );
+                                let mut swarm = swarm::cli(
+                                    seed.derive_libp2p_identity(),
+                                    context.config.tor_socks5_port,
+                                    behaviour,
+                                )
+                                .await?;
+
+                                swarm.behaviour_mut().add_address(seller_peer_id, seller);
+
+                                tracing::debug!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
+
+                                let (event_loop, event_loop_handle) =
+                                    EventLoop::new(swap_id, swarm, seller_peer_id, context.db.clone())?;
+                                // Dropped once the event loop stopped, which closes the receiver of the joined swaps
+                                let (event_loop_running, event_loop_stopped) = watch::channel(());
+                                let event_loop = tokio::spawn(
+                                    async move {
+                                        event_loop.run().await;
+                                        drop(event_loop_running);
+                                    }
+                                    .in_current_span(),
+                                );
+                                let event_loop_handle = context
+                                    .swap_lock
+                                    .share_event_loop(swap_id, seller_peer_id, event_loop_handle, event_loop_stopped.clone())
+                                    .await?;
+
+                                (Some(event_loop), event_loop_handle, event_loop_stopped)
+                            }
+                        };
 
                         let bid_quote = event_loop_handle.request_quote().await?;
+                        match &bid_quote.signature {
+                            Some(signature) => tracing::info!(
+                                quote_id = %signature.id,
+                                expires_at = signature.expires_at,
+                                "Received signed quote"
+                            ),
+                            None => tracing::debug!("Seller does not sign its quotes"),
+                        }
 
-                        Ok::<_, anyhow::Error>((event_loop, event_loop_handle, bid_quote))
+                        let timelocks = choose_timelocks(
+                            bitcoin_cancel_timelock,
+                            bitcoin_punish_timelock,
+                            bid_quote.quote.timelock_bounds,
+                            env_config,
+                        )?;
+
+                        Ok::<_, anyhow::Error>((event_loop, event_loop_handle, event_loop_stopped, bid_quote, timelocks))
                     } => {
                         result
                     },
                 };
 
-                let (event_loop, event_loop_handle, bid_quote) = match initialize_swap {
-                    Ok(result) => result,
-                    Err(error) => {
-                        tracing::error!(%swap_id, "Swap initialization failed: {:#}", error);
-                        context
-                            .swap_lock
-                            .release_swap_lock()
-                            .await
-                            .expect("Could not release swap lock");
-                        bail!(error);
+                let (event_loop, event_loop_handle, mut event_loop_stopped, bid_quote, timelocks) =
+                    match initialize_swap {
+                        Ok(result) => result,
+                        Err(error) => {
+                            tracing::error!(%swap_id, "Swap initialization failed: {:#}", error);
+                            context
+                                .swap_lock
+                                .release_swap_lock(swap_id)
+                                .await
+                                .expect("Could not release swap lock");
+                            bail!(error);
+                        }
+                    };
+
+                // Only the swap that started the event loop drives it, the
+                // joined swaps stop once it stopped
+                let event_loop = async move {
+                    match event_loop {
+                        Some(event_loop) => event_loop.await.map_err(anyhow::Error::from),
+                        None => {
+                            // Nothing is ever sent, this only returns once the sender was dropped
+                            let _ = event_loop_stopped.changed().await;
+                            bail!("EventLoop shared with the other swaps stopped")
+                        }
                     }
                 };
 
+                let quote = json!(bid_quote);
+
                 context.tasks.clone().spawn(async move {
                     tokio::select! {
                         biased;
                         _ = context.swap_lock.listen_for_swap_force_suspension() => {
                             tracing::debug!("Shutdown signal received, exiting");
-                            context.swap_lock.release_swap_lock().await.expect("Shutdown signal received but failed to release swap lock. The swap process has been terminated but the swap lock is still active.");
+                            context.swap_lock.release_swap_lock(swap_id).await.expect("Shutdown signal received but failed to release swap lock. The swap process has been terminated but the swap lock is still active.");
                             bail!("Shutdown signal received");
                         },
                         event_loop_result = event_loop => {
@@ -443,7 +579,7 @@ impl Request {
 
                             let determine_amount = determine_btc_to_swap(
                                 context.config.json,
-                                bid_quote,
+                                bid_quote.quote,
                                 bitcoin_wallet.new_address(),
                                 || bitcoin_wallet.balance(),
                                 max_givable,
@@ -475,7 +611,10 @@ impl Request {
                                 monero_receive_address,
                                 bitcoin_change_address,
                                 amount,
-                            );
+                                timelocks,
+                                Some(bid_quote),
+                            )
+                            .with_skip_preflight(skip_preflight);
 
                             bob::run(swap).await
                         } => {
@@ -493,7 +632,7 @@ impl Request {
 
                     context
                         .swap_lock
-                        .release_swap_lock()
+                        .release_swap_lock(swap_id)
                         .await
                         .expect("Could not release swap lock");
                     Ok::<_, anyhow::Error>(())
@@ -501,10 +640,13 @@ impl Request {
 
                 Ok(json!({
                     "swapId": swap_id.to_string(),
-                    "quote": bid_quote,
+                    "quote": quote,
                 }))
             }
-            Method::Resume { swap_id } => {
+            Method::Resume {
+                swap_id,
+                skip_preflight,
+            } => {
                 context.swap_lock.acquire_swap_lock(swap_id).await?;
 
                 let seller_peer_id = context.db.get_peer_id(swap_id).await?;
@@ -562,7 +704,8 @@ impl Request {
                     event_loop_handle,
                     monero_receive_address,
                 )
-                .await?;
+                .await?
+                .with_skip_preflight(skip_preflight);
 
                 context.tasks.clone().spawn(
                     async move {
@@ -571,7 +714,7 @@ impl Request {
                             biased;
                             _ = context.swap_lock.listen_for_swap_force_suspension() => {
                                  tracing::debug!("Shutdown signal received, exiting");
-                                context.swap_lock.release_swap_lock().await.expect("Shutdown signal received but failed to release swap lock. The swap process has been terminated but the swap lock is still active.");
+                                context.swap_lock.release_swap_lock(swap_id).await.expect("Shutdown signal received but failed to release swap lock. The swap process has been terminated but the swap lock is still active.");
                                 bail!("Shutdown signal received");
                             },
 
@@ -599,7 +742,7 @@ impl Request {
                         }
                         context
                             .swap_lock
-                            .release_swap_lock()
+                            .release_swap_lock(swap_id)
                             .await
                             .expect("Could not release swap lock");
                         Ok::<(), anyhow::Error>(())
@@ -627,7 +770,7 @@ impl Request {
 
                 context
                     .swap_lock
-                    .release_swap_lock()
+                    .release_swap_lock(swap_id)
                     .await
                     .expect("Could not release swap lock");
 
@@ -652,6 +795,22 @@ impl Request {
 
                 Ok(json!({ "raw_states": raw_history }))
             }
+            Method::GetMoneroDaemon => {
+                let status = context
+                    .monero_daemon_status
+                    .as_ref()
+                    .context("Could not get Monero daemon status")?
+                    .read()
+                    .await
+                    .clone();
+
+                tracing::info!(daemon = %status.current, "Current Monero daemon");
+
+                Ok(json!({
+                    "current": status.current,
+                    "probes": status.probes,
+                }))
+            }
             Method::Config => {
                 let data_dir_display = context.config.data_dir.display();
                 tracing::info!(path=%data_dir_display, "Data directory");
@@ -686,6 +845,17 @@ impl Request {
                 let psbt = bitcoin_wallet
                     .send_to_address(address, amount, None)
                     .await?;
+
+                if bitcoin_wallet.is_watch_only() {
+                    let psbt = bitcoin::psbt_to_base64(&psbt);
+                    tracing::info!(%psbt, "Sign the withdraw transaction with your external wallet and broadcast it");
+
+                    return Ok(json!({
+                        "psbt": psbt,
+                        "amount": amount.to_sat(),
+                    }));
+                }
+
                 let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
 
                 bitcoin_wallet
@@ -798,6 +968,207 @@ impl Request {
                     "descriptor": wallet_export.to_string(),
                 }))
             }
+            Method::Rescan {
+                bitcoin_electrum_rpc_url,
+                bitcoin_target_block,
+                gap_limit,
+                min_lock_height,
+            } => {
+                let seed = context.config.seed.as_ref().context("Could not get seed")?;
+                let env_config = context.config.env_config;
+
+                let swap_addresses = rescan::swap_addresses(&*context.db).await?;
+                let bitcoin_wallet = bitcoin::Wallet::rescan(
+                    bitcoin_electrum_rpc_url,
+                    context.config.data_dir.join("wallet"),
+                    seed.derive_extended_private_key(env_config.bitcoin_network)?,
+                    env_config,
+                    bitcoin_target_block,
+                    gap_limit,
+                    &swap_addresses,
+                )
+                .await
+                .context("Failed to rescan Bitcoin wallet")?;
+
+                let bitcoin_balance = bitcoin_wallet.balance().await?;
+                tracing::info!(balance = %bitcoin_balance, "Bitcoin wallet rescanned");
+
+                let locked_swaps =
+                    rescan::locked_swaps(&*context.db, &bitcoin_wallet, min_lock_height).await?;
+                let bitcoin_wallet = Arc::new(bitcoin_wallet);
+                let mut recovered_swaps = Vec::new();
+
+                for locked_swap in &locked_swaps {
+                    let swap_id = locked_swap.swap_id;
+
+                    match rescan::recover(swap_id, bitcoin_wallet.clone(), context.db.clone()).await
+                    {
+                        Ok(state) => {
+                            tracing::info!(%swap_id, %state, "Recovered Bitcoin from the lock output of swap");
+                            recovered_swaps.push(swap_id);
+                        }
+                        Err(error) => {
+                            tracing::warn!(
+                                %swap_id,
+                                state = %locked_swap.state,
+                                tx_lock_id = %locked_swap.tx_lock_id,
+                                amount = %locked_swap.amount,
+                                "Bitcoin lock output of swap may still hold funds but they cannot be recovered yet: {:#}",
+                                error
+                            );
+                        }
+                    }
+                }
+
+                Ok(json!({
+                    "balance": bitcoin_balance.to_sat(),
+                    "locked_swaps": locked_swaps,
+                    "recovered_swaps": recovered_swaps,
+                }))
+            }
+            Method::ExportPsbt { swap_id } => {
+                let psbt = context
+                    .db
+                    .get_unsigned_psbt(swap_id)
+                    .await?
+                    .context("No PSBT was exported for this swap")?;
+                let psbt = bitcoin::psbt_to_base64(&psbt);
+
+                tracing::info!(%psbt, "Exported unsigned PSBT");
+
+                Ok(json!({
+                    "psbt": psbt,
+                }))
+            }
+            Method::ImportPsbt { swap_id, psbt } => {
+                if let State::Bob(BobState::SafelyAborted) = context.db.get_state(swap_id).await? {
+                    bail!(
+                        "Swap {} was aborted because the signed PSBT was not imported in time, do not broadcast it",
+                        swap_id
+                    );
+                }
+
+                let signed_psbt = bitcoin::psbt_from_base64(&psbt)?;
+                let unsigned_psbt = context
+                    .db
+                    .get_unsigned_psbt(swap_id)
+                    .await?
+                    .context("No PSBT was exported for this swap")?;
+
+                if signed_psbt.unsigned_tx.txid() != unsigned_psbt.unsigned_tx.txid() {
+                    bail!(
+                        "Imported PSBT spends a different transaction than the one exported for swap {}",
+                        swap_id
+                    );
+                }
+
+                context.db.insert_signed_psbt(swap_id, signed_psbt).await?;
+
+                tracing::info!("Imported signed PSBT, the swap will continue");
+
+                Ok(json!({
+                    "swap_id": swap_id,
+                }))
+            }
+            Method::ExportWatchtowerBundle { swap_id, output } => {
+                let swap_state: BobState = context.db.get_state(swap_id).await?.try_into()?;
+
+                let state6 = match &swap_state {
+                    BobState::BtcLocked {
+                        state3,
+                        monero_wallet_restore_blockheight,
+                    } => state3.cancel(*monero_wallet_restore_blockheight),
+                    BobState::XmrLockProofReceived {
+                        state,
+                        monero_wallet_restore_blockheight,
+                        ..
+                    } => state.cancel(*monero_wallet_restore_blockheight),
+                    BobState::XmrLocked(state4) => state4.clone().cancel(),
+                    // Once the encrypted signature was sent Alice can redeem,
+                    // cancelling is then left to the CLI
+                    _ => bail!(
+                        "Cannot export a watchtower bundle in state {}, only possible after locking the Bitcoin and before sending the encrypted signature",
+                        swap_state
+                    ),
+                };
+
+                let bundle = state6.watchtower_bundle(swap_id)?;
+                tokio::fs::write(&output, serde_json::to_vec_pretty(&bundle)?)
+                    .await
+                    .with_context(|| format!("Failed to write bundle to {}", output.display()))?;
+
+                tracing::info!(path = %output.display(), "Exported watchtower bundle, hand it to `swap-watchtower add`");
+                tracing::warn!("The bundle reveals your Monero key share to the seller, never share it with anyone but your watchtower");
+
+                Ok(json!({
+                    "swap_id": swap_id,
+                    "path": output,
+                }))
+            }
+            Method::ExportEvidence {
+                swap_id,
+                output,
+                include_private_keys,
+            } => {
+                let bitcoin_wallet = context
+                    .bitcoin_wallet
+                    .as_ref()
+                    .context("Could not get Bitcoin wallet")?;
+                let identity = context
+                    .config
+                    .seed
+                    .as_ref()
+                    .context("Could not get seed")?
+                    .derive_libp2p_identity();
+
+                let (evidence, log) = Evidence::collect(
+                    swap_id,
+                    Arc::clone(&context.db),
+                    bitcoin_wallet,
+                    &context.config.data_dir.join("logs"),
+                    include_private_keys,
+                )
+                .await?;
+
+                tokio::fs::write(&output, evidence.archive(&log, &identity)?)
+                    .await
+                    .with_context(|| format!("Failed to write evidence to {}", output.display()))?;
+
+                if include_private_keys {
+                    tracing::warn!("The evidence contains the private keys of the swap, only share it with parties you trust");
+                }
+                tracing::info!(path = %output.display(), "Exported evidence, verify it with `verify-evidence`");
+
+                Ok(json!({
+                    "swap_id": swap_id,
+                    "path": output,
+                }))
+            }
+            Method::VerifyEvidence { path } => {
+                let archive = tokio::fs::read(&path)
+                    .await
+                    .with_context(|| format!("Failed to read evidence from {}", path.display()))?;
+                let verified = Evidence::verify(&archive)?;
+                let evidence = verified.evidence;
+
+                tracing::info!(
+                    swap_id = %evidence.swap_id,
+                    signer = %verified.signer,
+                    seller = %evidence.peer_id,
+                    "Evidence is signed and unmodified"
+                );
+
+                Ok(json!({
+                    "swap_id": evidence.swap_id,
+                    "signer": verified.signer.to_string(),
+                    "seller": evidence.peer_id.to_string(),
+                    "exported_at": evidence.exported_at,
+                    "states": evidence.states.len(),
+                    "transactions": evidence.transactions.len(),
+                    "transfer_proof": evidence.transfer_proof.is_some(),
+                    "private_keys_redacted": evidence.private_keys_redacted,
+                }))
+            }
             Method::MoneroRecovery { swap_id } => {
                 let swap_state: BobState = context.db.get_state(swap_id).await?.try_into()?;
 
@@ -953,3 +1324,43 @@ where
 
     Ok((btc_swap_amount, fees))
 }
+
+/// Picks the timelocks to propose to the seller.
+///
+/// Returns `None` if the user did not ask for any timelocks, in which case the
+/// timelocks of the network are used. Timelocks that were not given default to
+/// the ones of the network.
+pub fn choose_timelocks(
+    cancel: Option<CancelTimelock>,
+    punish: Option<PunishTimelock>,
+    bounds: Option<TimelockBounds>,
+    env_config: env::Config,
+) -> Result<Option<Timelocks>> {
+    if cancel.is_none() && punish.is_none() {
+        return Ok(None);
+    }
+
+    let timelocks = Timelocks {
+        cancel: cancel.unwrap_or(env_config.bitcoin_cancel_timelock),
+        punish: punish.unwrap_or(env_config.bitcoin_punish_timelock),
+    };
+
+    timelocks.ensure_above_minimum()?;
+
+    let bounds = bounds.context(
+        "Seller does not support custom timelocks, retry without specifying any timelocks",
+    )?;
+    bounds
+        .validate()
+        .context("Seller advertises invalid timelock bounds")?;
+
+    if !bounds.contains(timelocks) {
+        bail!(
+            "Requested timelocks {:?} are outside of the bounds accepted by the seller {:?}",
+            timelocks,
+            bounds
+        );
+    }
+
+    Ok(Some(timelocks))
+}
@@ -4,6 +4,8 @@ mod event_loop;
 mod network;
 mod rate;
 mod recovery;
+pub mod rpc;
+mod scheduler;
 pub mod tracing;
 
 pub use event_loop::{EventLoop, EventLoopHandle, FixedRate, KrakenRate, LatestRate};
@@ -15,8 +17,9 @@ pub use recovery::cancel::cancel;
 pub use recovery::punish::punish;
 pub use recovery::redeem::{redeem, Finality};
 pub use recovery::refund::refund;
-pub use recovery::safely_abort::safely_abort;
+pub use recovery::safely_abort::{safely_abort, sign_early_refund};
 pub use recovery::{cancel, refund};
+pub use scheduler::{RunningSwaps, Scheduler, SCHEDULER_INTERVAL};
 
 #[cfg(test)]
 pub use network::rendezvous;
@@ -2,6 +2,8 @@ use crate::asb::config::GetDefaults;
 use crate::bitcoin::Amount;
 use crate::env;
 use crate::env::GetConfig;
+use crate::monero;
+use crate::monero::monero_address;
 use anyhow::{bail, Result};
 use bitcoin::Address;
 use serde::Serialize;
@@ -52,6 +54,17 @@ where
                 address: bitcoin_address(address, testnet)?,
             },
         },
+        RawCommand::WithdrawXmr { amount, address } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::WithdrawXmr {
+                amount,
+                address: monero_address::validate_is_testnet(address, testnet)?,
+            },
+        },
         RawCommand::Balance => Arguments {
             testnet,
             json,
@@ -76,6 +89,20 @@ where
             env_config: env_config(testnet),
             cmd: Command::ExportBitcoinWallet,
         },
+        RawCommand::Rescan {
+            gap_limit,
+            min_lock_height,
+        } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Rescan {
+                gap_limit,
+                min_lock_height,
+            },
+        },
         RawCommand::ManualRecovery(ManualRecovery::Redeem {
             redeem_params: RecoverCommandParams { swap_id },
             do_not_await_finality,
@@ -201,6 +228,10 @@ pub enum Command {
         amount: Option<Amount>,
         address: Address,
     },
+    WithdrawXmr {
+        amount: Option<monero::Amount>,
+        address: monero::Address,
+    },
     Balance,
     Redeem {
         swap_id: Uuid,
@@ -219,6 +250,10 @@ pub enum Command {
         swap_id: Uuid,
     },
     ExportBitcoinWallet,
+    Rescan {
+        gap_limit: u32,
+        min_lock_height: Option<u32>,
+    },
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -283,11 +318,44 @@ pub enum RawCommand {
         address: Address,
     },
     #[structopt(
-        about = "Prints the Bitcoin and Monero balance. Requires the monero-wallet-rpc to be running."
+        about = "Allows withdrawing XMR from the Monero wallet. Requires the monero-wallet-rpc to be running."
+    )]
+    WithdrawXmr {
+        #[structopt(
+            long = "amount",
+            help = "Optionally specify the amount of Monero to be withdrawn, e.g `--amount 0.5`. If not specified the whole unlocked balance is swept.",
+            parse(try_from_str = monero::Amount::parse_monero)
+        )]
+        amount: Option<monero::Amount>,
+        #[structopt(
+            long = "address",
+            help = "The address to receive the Monero.",
+            parse(try_from_str = monero_address::parse)
+        )]
+        address: monero::Address,
+    },
+    #[structopt(
+        about = "Prints the Bitcoin and Monero balance, including locked Monero and pending refunds. Requires the monero-wallet-rpc to be running."
     )]
     Balance,
     #[structopt(about = "Print the internal bitcoin wallet descriptor.")]
     ExportBitcoinWallet,
+    #[structopt(
+        about = "Recreates the internal Bitcoin wallet from the seed and rescans the blockchain. Recovers the Bitcoin of swaps whose lock output still holds funds once their timelocks allow it."
+    )]
+    Rescan {
+        #[structopt(
+            long = "gap-limit",
+            help = "Number of consecutive unused addresses after which the rescan stops looking for transactions.",
+            default_value = "100"
+        )]
+        gap_limit: u32,
+        #[structopt(
+            long = "min-lock-height",
+            help = "Optionally ignore swaps whose Bitcoin lock transaction was confirmed before this block height. The Electrum backend always returns the full history of the wallet, so this does not shorten the wallet sync."
+        )]
+        min_lock_height: Option<u32>,
+    },
     #[structopt(about = "Contains sub-commands for recovering a swap manually.")]
     ManualRecovery(ManualRecovery),
 }
@@ -328,7 +396,9 @@ pub enum ManualRecovery {
         #[structopt(flatten)]
         punish_params: RecoverCommandParams,
     },
-    #[structopt(about = "Safely Abort requires the swap to be in a state prior to locking XMR.")]
+    #[structopt(
+        about = "Safely Abort requires the swap to be in a state prior to locking XMR. The taker can then request an early refund of their Bitcoin."
+    )]
     SafelyAbort {
         #[structopt(
             long = "swap-id",
@@ -356,6 +426,8 @@ mod tests {
     const BITCOIN_MAINNET_ADDRESS: &str = "1KFHE7w8BhaENAswwryaoccDb6qcT6DbYY";
     const BITCOIN_TESTNET_ADDRESS: &str = "tb1qyccwk4yun26708qg5h6g6we8kxln232wclxf5a";
     const SWAP_ID: &str = "ea030832-3be9-454f-bb98-5ea9a788406b";
+    const MONERO_MAINNET_ADDRESS: &str = "44Ato7HveWidJYUAVw5QffEcEtSH1DwzSP3FPPkHxNAS4LX9CqgucphTisH978FLHE34YNEx7FcbBfQLQUU8m3NUC4VqsRa";
+    const MONERO_STAGENET_ADDRESS: &str = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a";
 
     #[test]
     fn ensure_start_command_mapping_mainnet() {
@@ -411,6 +483,56 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_rescan_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "rescan"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Rescan {
+                gap_limit: 100,
+                min_lock_height: None,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_rescan_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "rescan",
+            "--gap-limit",
+            "500",
+            "--min-lock-height",
+            "2500000",
+        ];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::Rescan {
+                gap_limit: 500,
+                min_lock_height: Some(2_500_000),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
     #[test]
     fn ensure_withdraw_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
@@ -436,6 +558,48 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_withdraw_xmr_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+        let raw_ars = vec![
+            BINARY_NAME,
+            "withdraw-xmr",
+            "--address",
+            MONERO_MAINNET_ADDRESS,
+            "--amount",
+            "0.5",
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::WithdrawXmr {
+                amount: Some(monero::Amount::parse_monero("0.5").unwrap()),
+                address: monero::Address::from_str(MONERO_MAINNET_ADDRESS).unwrap(),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_stagenet_address_on_mainnet_then_withdraw_xmr_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "withdraw-xmr",
+            "--address",
+            MONERO_STAGENET_ADDRESS,
+        ];
+
+        let err = parse_args(raw_ars).unwrap_err();
+        assert!(err
+            .downcast_ref::<monero_address::MoneroAddressNetworkMismatch>()
+            .is_some());
+    }
+
     #[test]
     fn ensure_cancel_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
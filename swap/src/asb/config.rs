@@ -1,5 +1,7 @@
+use crate::bitcoin::{CancelTimelock, PunishTimelock};
 use crate::env::{Mainnet, Testnet};
 use crate::fs::{ensure_directory_exists, system_config_dir, system_data_dir};
+use crate::network::swap_setup::{TimelockBounds, Timelocks};
 use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
 use anyhow::{bail, Context, Result};
 use config::ConfigError;
@@ -11,6 +13,7 @@ use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
 use std::fs;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use url::Url;
@@ -138,6 +141,30 @@ pub struct Network {
     pub rendezvous_point: Vec<Multiaddr>,
     #[serde(default, deserialize_with = "addr_list::deserialize")]
     pub external_addresses: Vec<Multiaddr>,
+    /// Address of the admin RPC server, disabled if not set. The server is not
+    /// authenticated, hence only loopback addresses are accepted.
+    #[serde(default, deserialize_with = "loopback_address::deserialize")]
+    pub admin_rpc_address: Option<SocketAddr>,
+}
+
+mod loopback_address {
+    use serde::{de, Deserialize, Deserializer};
+    use std::net::SocketAddr;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<SocketAddr>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let address = Option::<SocketAddr>::deserialize(deserializer)?;
+
+        match address {
+            Some(address) if !address.ip().is_loopback() => Err(de::Error::custom(format!(
+                "{} is not a loopback address, the admin RPC server must only be reachable by the operator",
+                address
+            ))),
+            address => Ok(address),
+        }
+    }
 }
 
 mod addr_list {
@@ -189,6 +216,14 @@ pub struct Bitcoin {
     pub finality_confirmations: Option<u32>,
     #[serde(with = "crate::bitcoin::network")]
     pub network: bitcoin::Network,
+    /// Additional fee-rate source, credentials can be part of the URL.
+    pub bitcoind_rpc_url: Option<Url>,
+    /// Additional fee-rate source serving a mempool.space compatible API.
+    pub mempool_space_url: Option<Url>,
+    /// Maximum fee rate in sat/vB.
+    pub max_fee_rate: Option<u64>,
+    /// Maximum fee in percent of the transferred amount.
+    pub max_fee_percentage: Option<Decimal>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
@@ -217,6 +252,47 @@ pub struct Maker {
     pub ask_spread: Decimal,
     pub price_ticker_ws_url: Url,
     pub external_bitcoin_redeem_address: Option<bitcoin::Address>,
+    #[serde(default)]
+    pub timelocks: MakerTimelocks,
+}
+
+/// The range of timelocks, in blocks, that takers may propose. Bounds that are
+/// not set default to the timelocks of the network.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MakerTimelocks {
+    pub min_cancel: Option<u32>,
+    pub max_cancel: Option<u32>,
+    pub min_punish: Option<u32>,
+    pub max_punish: Option<u32>,
+}
+
+impl MakerTimelocks {
+    pub fn bounds(&self, env_config: crate::env::Config) -> Result<TimelockBounds> {
+        let network = Timelocks::from(env_config);
+
+        let bounds = TimelockBounds {
+            min_cancel: self.min_cancel.map_or(network.cancel, CancelTimelock::new),
+            max_cancel: self.max_cancel.map_or(network.cancel, CancelTimelock::new),
+            min_punish: self.min_punish.map_or(network.punish, PunishTimelock::new),
+            max_punish: self.max_punish.map_or(network.punish, PunishTimelock::new),
+        };
+
+        bounds
+            .validate()
+            .context("Invalid timelock bounds in config")?;
+
+        // Takers that do not negotiate timelocks use the ones of the network
+        if !bounds.contains(network) {
+            bail!(
+                "Timelock bounds must include the cancel timelock of {} blocks and the punish timelock of {} blocks of the network",
+                u32::from(network.cancel),
+                u32::from(network.punish)
+            );
+        }
+
+        Ok(bounds)
+    }
 }
 
 impl Default for TorConf {
@@ -377,12 +453,17 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
             listen: listen_addresses,
             rendezvous_point: rendezvous_points, // keeping the singular key name for backcompat
             external_addresses: vec![],
+            admin_rpc_address: None,
         },
         bitcoin: Bitcoin {
             electrum_rpc_url,
             target_block,
             finality_confirmations: None,
             network: bitcoin_network,
+            bitcoind_rpc_url: None,
+            mempool_space_url: None,
+            max_fee_rate: None,
+            max_fee_percentage: None,
         },
         monero: Monero {
             wallet_rpc_url: monero_wallet_rpc_url,
@@ -399,6 +480,7 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
             ask_spread,
             price_ticker_ws_url: defaults.price_ticker_ws_url,
             external_bitcoin_redeem_address: None,
+            timelocks: MakerTimelocks::default(),
         },
     })
 }
@@ -406,6 +488,7 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::env::GetConfig;
     use serial_test::serial;
     use tempfile::tempdir;
 
@@ -427,11 +510,16 @@ mod tests {
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Testnet,
+                bitcoind_rpc_url: None,
+                mempool_space_url: None,
+                max_fee_rate: None,
+                max_fee_percentage: None,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
                 rendezvous_point: vec![],
                 external_addresses: vec![],
+                admin_rpc_address: None,
             },
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
@@ -445,6 +533,7 @@ mod tests {
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
                 external_bitcoin_redeem_address: None,
+                timelocks: MakerTimelocks::default(),
             },
         };
 
@@ -471,11 +560,16 @@ mod tests {
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Bitcoin,
+                bitcoind_rpc_url: None,
+                mempool_space_url: None,
+                max_fee_rate: None,
+                max_fee_percentage: None,
             },
             network: Network {
                 listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
                 rendezvous_point: vec![],
                 external_addresses: vec![],
+                admin_rpc_address: None,
             },
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
@@ -489,6 +583,7 @@ mod tests {
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
                 external_bitcoin_redeem_address: None,
+                timelocks: MakerTimelocks::default(),
             },
         };
 
@@ -525,11 +620,16 @@ mod tests {
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Bitcoin,
+                bitcoind_rpc_url: None,
+                mempool_space_url: None,
+                max_fee_rate: None,
+                max_fee_percentage: None,
             },
             network: Network {
                 listen,
                 rendezvous_point: vec![],
                 external_addresses,
+                admin_rpc_address: None,
             },
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
@@ -543,6 +643,7 @@ mod tests {
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
                 external_bitcoin_redeem_address: None,
+                timelocks: MakerTimelocks::default(),
             },
         };
 
@@ -554,4 +655,97 @@ mod tests {
         std::env::remove_var("ASB__NETWORK__EXTERNAL_ADDRESSES");
         std::env::remove_var("ASB__NETWORK__LISTEN");
     }
+
+    #[test]
+    fn admin_rpc_address_must_be_loopback() {
+        let network = |address: &str| {
+            serde_json::from_value::<Network>(serde_json::json!({
+                "listen": [],
+                "admin_rpc_address": address,
+            }))
+        };
+
+        assert_eq!(
+            network("127.0.0.1:9944").unwrap().admin_rpc_address,
+            Some("127.0.0.1:9944".parse().unwrap())
+        );
+        assert!(network("[::1]:9944").is_ok());
+        assert!(network("0.0.0.0:9944").is_err());
+        assert!(network("192.168.1.2:9944").is_err());
+    }
+
+    #[test]
+    fn timelock_bounds_default_to_network_timelocks() {
+        let env_config = Mainnet::get_config();
+
+        let bounds = MakerTimelocks::default().bounds(env_config).unwrap();
+
+        assert_eq!(bounds, TimelockBounds::fixed(Timelocks::from(env_config)));
+    }
+
+    #[test]
+    fn timelock_bounds_must_include_network_timelocks() {
+        let env_config = Mainnet::get_config();
+
+        let widened = MakerTimelocks {
+            max_cancel: Some(144),
+            max_punish: Some(144),
+            ..Default::default()
+        };
+        let bounds = widened.bounds(env_config).unwrap();
+        assert_eq!(bounds.min_cancel, CancelTimelock::new(72));
+        assert_eq!(bounds.max_cancel, CancelTimelock::new(144));
+
+        let excluding = MakerTimelocks {
+            min_cancel: Some(100),
+            max_cancel: Some(144),
+            ..Default::default()
+        };
+        assert!(excluding.bounds(env_config).is_err());
+    }
+
+    #[test]
+    fn timelock_bounds_must_not_be_inverted() {
+        let env_config = Mainnet::get_config();
+
+        let inverted = MakerTimelocks {
+            min_cancel: Some(144),
+            max_cancel: Some(72),
+            ..Default::default()
+        };
+        assert!(inverted.bounds(env_config).is_err());
+
+        let inverted = MakerTimelocks {
+            min_punish: Some(144),
+            max_punish: Some(72),
+            ..Default::default()
+        };
+        assert!(inverted.bounds(env_config).is_err());
+    }
+
+    #[test]
+    fn timelock_bounds_must_not_go_below_minimum() {
+        let env_config = Mainnet::get_config();
+
+        for timelock in [0, 1] {
+            let cancel = MakerTimelocks {
+                min_cancel: Some(timelock),
+                ..Default::default()
+            };
+            assert!(cancel.bounds(env_config).is_err());
+
+            let punish = MakerTimelocks {
+                min_punish: Some(timelock),
+                ..Default::default()
+            };
+            assert!(punish.bounds(env_config).is_err());
+        }
+
+        let at_minimum = MakerTimelocks {
+            min_cancel: Some(6),
+            min_punish: Some(6),
+            ..Default::default()
+        };
+        assert!(at_minimum.bounds(env_config).is_ok());
+    }
 }
@@ -1,10 +1,12 @@
-use crate::asb::{Behaviour, OutEvent, Rate};
+use crate::asb::{sign_early_refund, Behaviour, OutEvent, Rate, RunningSwaps};
 use crate::monero::Amount;
+use crate::network::cooperative_early_refund::CooperativeEarlyRefundRejectReason;
 use crate::network::cooperative_xmr_redeem_after_punish::CooperativeXmrRedeemRejectReason;
 use crate::network::cooperative_xmr_redeem_after_punish::Response::{Fullfilled, Rejected};
-use crate::network::quote::BidQuote;
+use crate::network::quote::{BidQuote, SignedBidQuote, QUOTE_VALIDITY};
 use crate::network::swap_setup::alice::WalletSnapshot;
-use crate::network::transfer_proof;
+use crate::network::swap_setup::TimelockBounds;
+use crate::network::{cooperative_early_refund, transfer_proof};
 use crate::protocol::alice::{AliceState, State3, Swap};
 use crate::protocol::{Database, State};
 use crate::{bitcoin, env, kraken, monero};
@@ -14,7 +16,7 @@ use futures::future::{BoxFuture, FutureExt};
 use futures::stream::{FuturesUnordered, StreamExt};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::SwarmEvent;
-use libp2p::{PeerId, Swarm};
+use libp2p::{identity, PeerId, Swarm};
 use rust_decimal::Decimal;
 use std::collections::HashMap;
 use std::convert::{Infallible, TryInto};
@@ -39,6 +41,8 @@ where
     LR: LatestRate + Send + 'static + Debug + Clone,
 {
     swarm: libp2p::Swarm<Behaviour<LR>>,
+    /// Signs the quotes we hand out.
+    identity: identity::Keypair,
     env_config: env::Config,
     bitcoin_wallet: Arc<bitcoin::Wallet>,
     monero_wallet: Arc<monero::Wallet>,
@@ -46,10 +50,15 @@ where
     latest_rate: LR,
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
+    timelock_bounds: TimelockBounds,
     external_redeem_address: Option<bitcoin::Address>,
 
     swap_sender: mpsc::Sender<Swap>,
 
+    /// Receives the ids of unfinished swaps that have to be resumed.
+    resume_sender: mpsc::Sender<Uuid>,
+    resume_receiver: mpsc::Receiver<Uuid>,
+
     /// Stores incoming [`EncryptedSignature`]s per swap.
     recv_encrypted_signature: HashMap<Uuid, bmrng::RequestSender<bitcoin::EncryptedSignature, ()>>,
     inflight_encrypted_signatures: FuturesUnordered<BoxFuture<'static, ResponseChannel<()>>>,
@@ -63,6 +72,12 @@ where
     /// Tracks [`transfer_proof::Request`]s which are currently inflight and
     /// awaiting an acknowledgement.
     inflight_transfer_proofs: HashMap<RequestId, bmrng::Responder<()>>,
+
+    /// The signed quotes whose price we honour until they expire.
+    issued_quotes: IssuedQuotes,
+
+    /// The swaps that currently have a task running them.
+    running_swaps: RunningSwaps,
 }
 
 impl<LR> EventLoop<LR>
@@ -72,6 +87,7 @@ where
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         swarm: Swarm<Behaviour<LR>>,
+        identity: identity::Keypair,
         env_config: env::Config,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
         monero_wallet: Arc<monero::Wallet>,
@@ -79,26 +95,34 @@ where
         latest_rate: LR,
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
+        timelock_bounds: TimelockBounds,
         external_redeem_address: Option<bitcoin::Address>,
     ) -> Result<(Self, mpsc::Receiver<Swap>)> {
         let swap_channel = MpscChannels::default();
+        let resume_channel = MpscChannels::default();
 
         let event_loop = EventLoop {
             swarm,
+            identity,
             env_config,
             bitcoin_wallet,
             monero_wallet,
             db,
             latest_rate,
             swap_sender: swap_channel.sender,
+            resume_sender: resume_channel.sender,
+            resume_receiver: resume_channel.receiver,
             min_buy,
             max_buy,
+            timelock_bounds,
             external_redeem_address,
             recv_encrypted_signature: Default::default(),
             inflight_encrypted_signatures: Default::default(),
             send_transfer_proof: Default::default(),
             buffered_transfer_proofs: Default::default(),
             inflight_transfer_proofs: Default::default(),
+            issued_quotes: Default::default(),
+            running_swaps: Default::default(),
         };
         Ok((event_loop, swap_channel.receiver))
     }
@@ -107,6 +131,17 @@ where
         *Swarm::local_peer_id(&self.swarm)
     }
 
+    /// Sender to ask the event loop to resume an unfinished swap.
+    pub fn resume_sender(&self) -> mpsc::Sender<Uuid> {
+        self.resume_sender.clone()
+    }
+
+    /// The swaps that currently have a task running them, tasks have to be
+    /// tracked by the caller.
+    pub fn running_swaps(&self) -> RunningSwaps {
+        self.running_swaps.clone()
+    }
+
     pub async fn run(mut self) {
         // ensure that these streams are NEVER empty, otherwise it will
         // terminate forever.
@@ -128,42 +163,17 @@ where
             .collect::<Vec<(Uuid, State)>>();
 
         for (swap_id, state) in unfinished_swaps {
-            let peer_id = match self.db.get_peer_id(swap_id).await {
-                Ok(peer_id) => peer_id,
-                Err(_) => {
-                    tracing::warn!(%swap_id, "Resuming swap skipped because no peer-id found for swap in database");
-                    continue;
-                }
-            };
-
-            let handle = self.new_handle(peer_id, swap_id);
-
-            let swap = Swap {
-                event_loop_handle: handle,
-                bitcoin_wallet: self.bitcoin_wallet.clone(),
-                monero_wallet: self.monero_wallet.clone(),
-                env_config: self.env_config,
-                db: self.db.clone(),
-                state: state.try_into().expect("Alice state loaded from db"),
-                swap_id,
-            };
-
-            match self.swap_sender.send(swap).await {
-                Ok(_) => tracing::info!(%swap_id, "Resuming swap"),
-                Err(_) => {
-                    tracing::warn!(%swap_id, "Failed to resume swap because receiver has been dropped")
-                }
-            }
+            self.resume_swap(swap_id, state).await;
         }
 
         loop {
             tokio::select! {
                 swarm_event = self.swarm.select_next_some() => {
                     match swarm_event {
-                        SwarmEvent::Behaviour(OutEvent::SwapSetupInitiated { mut send_wallet_snapshot }) => {
+                        SwarmEvent::Behaviour(OutEvent::SwapSetupInitiated { peer_id, mut send_wallet_snapshot }) => {
 
-                            let (btc, responder) = match send_wallet_snapshot.recv().await {
-                                Ok((btc, responder)) => (btc, responder),
+                            let ((btc, quote_id), responder) = match send_wallet_snapshot.recv().await {
+                                Ok((request, responder)) => (request, responder),
                                 Err(error) => {
                                     tracing::error!("Swap request will be ignored because of a failure when requesting information for the wallet snapshot: {:#}", error);
                                     continue;
@@ -178,8 +188,10 @@ where
                                 }
                             };
 
+                            let quoted_price = quote_id.and_then(|quote_id| self.quoted_price(quote_id, peer_id));
+
                             // Ignore result, we should never hit this because the receiver will alive as long as the connection is.
-                            let _ = responder.respond(wallet_snapshot);
+                            let _ = responder.respond(wallet_snapshot.with_quoted_price(quoted_price));
                         }
                         SwarmEvent::Behaviour(OutEvent::SwapSetupCompleted{peer_id, swap_id, state3}) => {
                             self.handle_execution_setup_done(peer_id, swap_id, state3).await;
@@ -196,6 +208,14 @@ where
                                 }
                             };
 
+                            let quote = match self.sign_quote(quote, peer) {
+                                Ok(quote) => quote,
+                                Err(error) => {
+                                    tracing::warn!(%peer, "Failed to sign quote: {:#}", error);
+                                    SignedBidQuote::unsigned(quote)
+                                }
+                            };
+
                             if self.swarm.behaviour_mut().quote.send_response(channel, quote).is_err() {
                                 tracing::debug!(%peer, "Failed to respond with quote");
                             }
@@ -308,6 +328,79 @@ where
 
                             tracing::info!(swap_id = %swap_id, peer = %peer, "Fullfilled cooperative XMR redeem request");
                         }
+                        SwarmEvent::Behaviour(OutEvent::CooperativeEarlyRefundRequested { swap_id, channel, peer }) => {
+                            let swap_peer = match self.db.get_peer_id(swap_id).await {
+                                Ok(swap_peer) => swap_peer,
+                                Err(_) => {
+                                    tracing::warn!(
+                                        swap_id = %swap_id,
+                                        received_from = %peer,
+                                        reason = "swap not found",
+                                        "Rejecting cooperative early refund request"
+                                    );
+                                    if self.swarm.behaviour_mut().cooperative_early_refund.send_response(channel, cooperative_early_refund::Response::Rejected { swap_id, reason: CooperativeEarlyRefundRejectReason::UnknownSwap }).is_err() {
+                                        tracing::error!(swap_id = %swap_id, "Failed to reject cooperative early refund request");
+                                    }
+                                    continue;
+                                }
+                            };
+
+                            if swap_peer != peer {
+                                tracing::warn!(
+                                    swap_id = %swap_id,
+                                    received_from = %peer,
+                                    expected_from = %swap_peer,
+                                    reason = "unexpected peer",
+                                    "Rejecting cooperative early refund request"
+                                );
+                                if self.swarm.behaviour_mut().cooperative_early_refund.send_response(channel, cooperative_early_refund::Response::Rejected { swap_id, reason: CooperativeEarlyRefundRejectReason::MaliciousRequest }).is_err() {
+                                    tracing::error!(swap_id = %swap_id, "Failed to reject cooperative early refund request");
+                                }
+                                continue;
+                            }
+
+                            // A running swap task may still lock the Monero, even if another process aborted the swap
+                            if self.running_swaps.contains(swap_id) {
+                                tracing::debug!(
+                                    swap_id = %swap_id,
+                                    reason = "swap is still running",
+                                    "Rejecting cooperative early refund request"
+                                );
+                                if self.swarm.behaviour_mut().cooperative_early_refund.send_response(channel, cooperative_early_refund::Response::Rejected { swap_id, reason: CooperativeEarlyRefundRejectReason::SwapInvalidState }).is_err() {
+                                    tracing::error!(swap_id = %swap_id, "Failed to reject cooperative early refund request");
+                                }
+                                continue;
+                            }
+
+                            let tx_early_refund_sig = match sign_early_refund(swap_id, self.db.as_ref()).await {
+                                Ok(Some(sig)) => sig,
+                                Ok(None) => {
+                                    tracing::debug!(
+                                        swap_id = %swap_id,
+                                        reason = "swap has not been safely aborted",
+                                        "Rejecting cooperative early refund request"
+                                    );
+                                    if self.swarm.behaviour_mut().cooperative_early_refund.send_response(channel, cooperative_early_refund::Response::Rejected { swap_id, reason: CooperativeEarlyRefundRejectReason::SwapInvalidState }).is_err() {
+                                        tracing::error!(swap_id = %swap_id, "Failed to reject cooperative early refund request");
+                                    }
+                                    continue;
+                                }
+                                Err(error) => {
+                                    tracing::error!(swap_id = %swap_id, "Failed to sign early refund transaction: {:#}", error);
+                                    if self.swarm.behaviour_mut().cooperative_early_refund.send_response(channel, cooperative_early_refund::Response::Rejected { swap_id, reason: CooperativeEarlyRefundRejectReason::SwapInvalidState }).is_err() {
+                                        tracing::error!(swap_id = %swap_id, "Failed to reject cooperative early refund request");
+                                    }
+                                    continue;
+                                }
+                            };
+
+                            if self.swarm.behaviour_mut().cooperative_early_refund.send_response(channel, cooperative_early_refund::Response::Fullfilled { swap_id, tx_early_refund_sig }).is_err() {
+                                tracing::error!(peer = %peer, "Failed to respond to cooperative early refund request");
+                                continue;
+                            }
+
+                            tracing::info!(swap_id = %swap_id, peer = %peer, "Fullfilled cooperative early refund request");
+                        }
                         SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::Registered { rendezvous_node, ttl, namespace })) => {
                             tracing::info!("Successfully registered with rendezvous node: {} with namespace: {} and TTL: {:?}", rendezvous_node, namespace, ttl);
                         }
@@ -369,6 +462,14 @@ where
                 Some(response_channel) = self.inflight_encrypted_signatures.next() => {
                     let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(response_channel, ());
                 }
+                Some(swap_id) = self.resume_receiver.recv() => {
+                    match self.db.get_state(swap_id).await {
+                        Ok(state) => self.resume_swap(swap_id, state).await,
+                        Err(error) => {
+                            tracing::warn!(%swap_id, "Failed to load swap to resume it: {:#}", error);
+                        }
+                    }
+                }
             }
         }
     }
@@ -406,6 +507,7 @@ where
                 price: ask_price,
                 min_quantity: bitcoin::Amount::ZERO,
                 max_quantity: bitcoin::Amount::ZERO,
+                timelock_bounds: Some(self.timelock_bounds),
             });
         }
 
@@ -418,6 +520,7 @@ where
                 price: ask_price,
                 min_quantity: min_buy,
                 max_quantity: max_bitcoin_for_monero,
+                timelock_bounds: Some(self.timelock_bounds),
             });
         }
 
@@ -425,9 +528,25 @@ where
             price: ask_price,
             min_quantity: min_buy,
             max_quantity: max_buy,
+            timelock_bounds: Some(self.timelock_bounds),
         })
     }
 
+    /// Signs the quote and remembers it so its price is honoured for the
+    /// requesting peer until it expires.
+    fn sign_quote(&mut self, quote: BidQuote, peer: PeerId) -> Result<SignedBidQuote> {
+        let signed = SignedBidQuote::sign(quote, &self.identity, QUOTE_VALIDITY, peer)?;
+        self.issued_quotes.insert(peer, signed.clone());
+
+        Ok(signed)
+    }
+
+    /// Consumes a quote we signed and returns its price if it was issued to
+    /// the given peer and did not expire yet.
+    fn quoted_price(&mut self, quote_id: Uuid, peer: PeerId) -> Option<bitcoin::Amount> {
+        self.issued_quotes.take_price(quote_id, peer)
+    }
+
     async fn handle_execution_setup_done(
         &mut self,
         bob_peer_id: PeerId,
@@ -465,6 +584,35 @@ where
         }
     }
 
+    async fn resume_swap(&mut self, swap_id: Uuid, state: State) {
+        let peer_id = match self.db.get_peer_id(swap_id).await {
+            Ok(peer_id) => peer_id,
+            Err(_) => {
+                tracing::warn!(%swap_id, "Resuming swap skipped because no peer-id found for swap in database");
+                return;
+            }
+        };
+
+        let handle = self.new_handle(peer_id, swap_id);
+
+        let swap = Swap {
+            event_loop_handle: handle,
+            bitcoin_wallet: self.bitcoin_wallet.clone(),
+            monero_wallet: self.monero_wallet.clone(),
+            env_config: self.env_config,
+            db: self.db.clone(),
+            state: state.try_into().expect("Alice state loaded from db"),
+            swap_id,
+        };
+
+        match self.swap_sender.send(swap).await {
+            Ok(_) => tracing::info!(%swap_id, "Resuming swap"),
+            Err(_) => {
+                tracing::warn!(%swap_id, "Failed to resume swap because receiver has been dropped")
+            }
+        }
+    }
+
     /// Create a new [`EventLoopHandle`] that is scoped for communication with
     /// the given peer.
     fn new_handle(&mut self, peer: PeerId, swap_id: Uuid) -> EventLoopHandle {
@@ -594,6 +742,51 @@ impl EventLoopHandle {
     }
 }
 
+/// Signed quotes by id, each one bound to the peer that requested it.
+///
+/// A quote is consumed by the first swap setup that refers to it, so its price
+/// is honoured for at most one swap.
+#[derive(Debug, Default)]
+struct IssuedQuotes(HashMap<Uuid, (PeerId, SignedBidQuote)>);
+
+impl IssuedQuotes {
+    /// Remembers a signed quote, forgetting all quotes that expired.
+    fn insert(&mut self, peer: PeerId, quote: SignedBidQuote) {
+        let quote_id = match quote.signature.as_ref() {
+            Some(signature) => signature.id,
+            None => return,
+        };
+
+        self.0.retain(|_, (_, quote)| {
+            matches!(
+                quote
+                    .signature
+                    .as_ref()
+                    .map(|signature| signature.is_expired()),
+                Some(Ok(false))
+            )
+        });
+        self.0.insert(quote_id, (peer, quote));
+    }
+
+    /// Returns the price of the quote and forgets it, as long as it was
+    /// issued to the given peer and did not expire.
+    fn take_price(&mut self, quote_id: Uuid, peer: PeerId) -> Option<bitcoin::Amount> {
+        let (issued_to, _) = self.0.get(&quote_id)?;
+        if *issued_to != peer {
+            tracing::warn!(%peer, %issued_to, %quote_id, "Ignoring quote that was issued to another peer");
+            return None;
+        }
+
+        let (_, quote) = self.0.remove(&quote_id)?;
+
+        match quote.signature.as_ref()?.is_expired() {
+            Ok(false) => Some(quote.quote.price),
+            _ => None,
+        }
+    }
+}
+
 #[allow(missing_debug_implementations)]
 struct MpscChannels<T> {
     sender: mpsc::Sender<T>,
@@ -606,3 +799,65 @@ impl<T> Default for MpscChannels<T> {
         MpscChannels { sender, receiver }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn signed_quote(validity: Duration) -> (Uuid, SignedBidQuote) {
+        let quote = BidQuote {
+            price: bitcoin::Amount::from_sat(500_000),
+            min_quantity: bitcoin::Amount::from_sat(10_000),
+            max_quantity: bitcoin::Amount::from_sat(1_000_000),
+            timelock_bounds: None,
+        };
+        let signed = SignedBidQuote::sign(
+            quote,
+            &identity::Keypair::generate_ed25519(),
+            validity,
+            PeerId::random(),
+        )
+        .unwrap();
+
+        (signed.signature.as_ref().unwrap().id, signed)
+    }
+
+    #[test]
+    fn quote_is_honoured_once_for_the_requesting_peer() {
+        let peer = PeerId::random();
+        let (quote_id, quote) = signed_quote(QUOTE_VALIDITY);
+        let mut issued_quotes = IssuedQuotes::default();
+        issued_quotes.insert(peer, quote);
+
+        assert_eq!(
+            issued_quotes.take_price(quote_id, peer),
+            Some(bitcoin::Amount::from_sat(500_000))
+        );
+        assert_eq!(issued_quotes.take_price(quote_id, peer), None);
+    }
+
+    #[test]
+    fn quote_is_not_honoured_for_other_peer() {
+        let peer = PeerId::random();
+        let (quote_id, quote) = signed_quote(QUOTE_VALIDITY);
+        let mut issued_quotes = IssuedQuotes::default();
+        issued_quotes.insert(peer, quote);
+
+        assert_eq!(issued_quotes.take_price(quote_id, PeerId::random()), None);
+        assert_eq!(
+            issued_quotes.take_price(quote_id, peer),
+            Some(bitcoin::Amount::from_sat(500_000))
+        );
+    }
+
+    #[test]
+    fn expired_quote_is_not_honoured() {
+        let peer = PeerId::random();
+        let (quote_id, quote) = signed_quote(Duration::ZERO);
+        let mut issued_quotes = IssuedQuotes::default();
+        issued_quotes.insert(peer, quote);
+
+        assert_eq!(issued_quotes.take_price(quote_id, peer), None);
+    }
+}
@@ -1,12 +1,14 @@
 use crate::asb::event_loop::LatestRate;
 use crate::env;
-use crate::network::quote::BidQuote;
+use crate::network::quote::SignedBidQuote;
 use crate::network::rendezvous::XmrBtcNamespace;
 use crate::network::swap_setup::alice;
-use crate::network::swap_setup::alice::WalletSnapshot;
+use crate::network::swap_setup::alice::{WalletSnapshot, WalletSnapshotRequest};
+use crate::network::swap_setup::TimelockBounds;
 use crate::network::transport::authenticate_and_multiplex;
 use crate::network::{
-    cooperative_xmr_redeem_after_punish, encrypted_signature, quote, transfer_proof,
+    compatibility, cooperative_early_refund, cooperative_xmr_redeem_after_punish,
+    encrypted_signature, quote, transfer_proof,
 };
 use crate::protocol::alice::State3;
 use anyhow::{anyhow, Error, Result};
@@ -54,7 +56,8 @@ pub mod behaviour {
     #[derive(Debug)]
     pub enum OutEvent {
         SwapSetupInitiated {
-            send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>,
+            peer_id: PeerId,
+            send_wallet_snapshot: bmrng::RequestReceiver<WalletSnapshotRequest, WalletSnapshot>,
         },
         SwapSetupCompleted {
             peer_id: PeerId,
@@ -66,7 +69,7 @@ pub mod behaviour {
             error: alice::Error,
         },
         QuoteRequested {
-            channel: ResponseChannel<BidQuote>,
+            channel: ResponseChannel<SignedBidQuote>,
             peer: PeerId,
         },
         TransferProofAcknowledged {
@@ -83,6 +86,11 @@ pub mod behaviour {
             swap_id: Uuid,
             peer: PeerId,
         },
+        CooperativeEarlyRefundRequested {
+            channel: ResponseChannel<cooperative_early_refund::Response>,
+            swap_id: Uuid,
+            peer: PeerId,
+        },
         Rendezvous(libp2p::rendezvous::client::Event),
         Failure {
             peer: PeerId,
@@ -122,6 +130,7 @@ pub mod behaviour {
         pub swap_setup: alice::Behaviour<LR>,
         pub transfer_proof: transfer_proof::Behaviour,
         pub cooperative_xmr_redeem: cooperative_xmr_redeem_after_punish::Behaviour,
+        pub cooperative_early_refund: cooperative_early_refund::Behaviour,
         pub encrypted_signature: encrypted_signature::Behaviour,
         pub identify: Identify,
 
@@ -135,9 +144,11 @@ pub mod behaviour {
     where
         LR: LatestRate + Send + 'static,
     {
+        #[allow(clippy::too_many_arguments)]
         pub fn new(
             min_buy: bitcoin::Amount,
             max_buy: bitcoin::Amount,
+            timelock_bounds: TimelockBounds,
             latest_rate: LR,
             resume_only: bool,
             env_config: env::Config,
@@ -146,7 +157,7 @@ pub mod behaviour {
         ) -> Self {
             let (identity, namespace) = identify_params;
             let agent_version = format!("asb/{} ({})", env!("CARGO_PKG_VERSION"), namespace);
-            let protocol_version = "/comit/xmr/btc/1.0.0".to_string();
+            let protocol_version = compatibility::PROTOCOL_VERSION.to_string();
             let identifyConfig = IdentifyConfig::new(protocol_version, identity.public())
                 .with_agent_version(agent_version);
 
@@ -162,6 +173,7 @@ pub mod behaviour {
                 swap_setup: alice::Behaviour::new(
                     min_buy,
                     max_buy,
+                    timelock_bounds,
                     env_config,
                     latest_rate,
                     resume_only,
@@ -169,6 +181,7 @@ pub mod behaviour {
                 transfer_proof: transfer_proof::alice(),
                 encrypted_signature: encrypted_signature::alice(),
                 cooperative_xmr_redeem: cooperative_xmr_redeem_after_punish::alice(),
+                cooperative_early_refund: cooperative_early_refund::alice(),
                 ping: Ping::new(PingConfig::new().with_keep_alive(true)),
                 identify: Identify::new(identifyConfig),
             }
@@ -47,7 +47,8 @@ impl Rate {
         Self::quote(self.ask()?, quote)
     }
 
-    fn quote(rate: bitcoin::Amount, quote: bitcoin::Amount) -> Result<monero::Amount> {
+    /// Calculate a sell quote for a given BTC amount at a fixed asking price.
+    pub fn quote(rate: bitcoin::Amount, quote: bitcoin::Amount) -> Result<monero::Amount> {
         // quote (btc) = rate * base (xmr)
         // base = quote / rate
 
@@ -75,7 +75,7 @@ pub async fn refund(
         .refund_xmr(
             &monero_wallet,
             monero_wallet_restore_blockheight,
-            swap_id.to_string(),
+            swap_id,
             spend_key,
             transfer_proof,
         )
@@ -1,3 +1,4 @@
+use crate::bitcoin;
 use crate::protocol::alice::AliceState;
 use crate::protocol::Database;
 use anyhow::{bail, Result};
@@ -5,6 +6,9 @@ use std::convert::TryInto;
 use std::sync::Arc;
 use uuid::Uuid;
 
+/// Marks the swap as aborted. From then on we will hand out our signature for
+/// the early refund transaction, which lets Bob take back his Bitcoin without
+/// waiting for the cancel timelock.
 pub async fn safely_abort(swap_id: Uuid, db: Arc<dyn Database>) -> Result<AliceState> {
     let state = db.get_state(swap_id).await?.try_into()?;
 
@@ -39,3 +43,39 @@ pub async fn safely_abort(swap_id: Uuid, db: Arc<dyn Database>) -> Result<AliceS
         ),
     }
 }
+
+/// Signs the early refund transaction of a swap that has been safely aborted.
+///
+/// Returns `None` if the swap has not been aborted before we locked the
+/// Monero.
+pub async fn sign_early_refund(
+    swap_id: Uuid,
+    db: &(dyn Database + Send + Sync),
+) -> Result<Option<bitcoin::Signature>> {
+    let state: AliceState = db.get_state(swap_id).await?.try_into()?;
+    if !matches!(state, AliceState::SafelyAborted) {
+        return Ok(None);
+    }
+
+    let states = db
+        .get_states(swap_id)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<AliceState>, _>>()?;
+
+    let mut state3 = None;
+    for state in &states {
+        match state {
+            AliceState::Started { state3: s }
+            | AliceState::BtcLockTransactionSeen { state3: s }
+            | AliceState::BtcLocked { state3: s } => state3 = Some(s),
+            AliceState::SafelyAborted => {}
+            // Once we have attempted to lock the Monero an early refund would
+            // allow Bob to walk away with both assets.
+            _ => return Ok(None),
+        }
+    }
+
+    Ok(state3.map(|state3| state3.sign_early_refund()))
+}
@@ -0,0 +1,142 @@
+use crate::monero;
+use crate::monero::monero_address;
+use crate::protocol::alice::AliceState;
+use crate::protocol::Database;
+use anyhow::Result;
+use jsonrpsee::server::{RpcModule, ServerBuilder, ServerHandle};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The Monero balance of the ASB, including swaps whose Monero may still be
+/// refunded.
+#[derive(Debug, Clone, Serialize)]
+pub struct MoneroBalance {
+    pub total: monero::Amount,
+    pub unlocked: monero::Amount,
+    pub locked: monero::Amount,
+    /// Sum of the pending refunds, not yet part of the wallet balance.
+    pub pending_refund_total: monero::Amount,
+    pub pending_refunds: Vec<PendingRefund>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingRefund {
+    pub swap_id: Uuid,
+    pub amount: monero::Amount,
+    pub state: String,
+}
+
+impl MoneroBalance {
+    pub async fn fetch(
+        monero_wallet: &monero::Wallet,
+        db: &(dyn Database + Send + Sync),
+    ) -> Result<Self> {
+        let balance = monero_wallet.get_balance().await?;
+
+        let mut pending_refunds = Vec::new();
+        for (swap_id, state) in db.all().await? {
+            let state: AliceState = state.try_into()?;
+
+            if let Some(amount) = state.pending_xmr_refund() {
+                pending_refunds.push(PendingRefund {
+                    swap_id,
+                    amount,
+                    state: state.to_string(),
+                });
+            }
+        }
+
+        let pending_refund_total = pending_refunds
+            .iter()
+            .fold(monero::Amount::ZERO, |total, refund| total + refund.amount);
+
+        Ok(Self {
+            total: monero::Amount::from_piconero(balance.balance),
+            unlocked: monero::Amount::from_piconero(balance.unlocked_balance),
+            locked: monero::Amount::from_piconero(
+                balance.balance.saturating_sub(balance.unlocked_balance),
+            ),
+            pending_refund_total,
+            pending_refunds,
+        })
+    }
+}
+
+/// Sends `amount` or, if not given, the whole unlocked balance to `address`.
+pub async fn withdraw_xmr(
+    monero_wallet: &monero::Wallet,
+    address: monero::Address,
+    amount: Option<monero::Amount>,
+    network: monero::Network,
+) -> Result<Vec<monero::TxHash>> {
+    let address = monero_address::validate(address, network)?;
+
+    let tx_hashes = monero_wallet.withdraw(address, amount).await?;
+
+    for tx_hash in &tx_hashes {
+        tracing::info!(%tx_hash, %address, "Withdrew Monero");
+    }
+
+    Ok(tx_hashes)
+}
+
+struct AdminContext {
+    monero_wallet: Arc<monero::Wallet>,
+    db: Arc<dyn Database + Send + Sync>,
+    network: monero::Network,
+}
+
+/// Serves the admin RPC methods of the ASB. It must only be reachable by the
+/// operator.
+pub async fn run_server(
+    server_address: SocketAddr,
+    monero_wallet: Arc<monero::Wallet>,
+    db: Arc<dyn Database + Send + Sync>,
+    network: monero::Network,
+) -> Result<(SocketAddr, ServerHandle)> {
+    let server = ServerBuilder::default().build(server_address).await?;
+    let mut module = RpcModule::new(AdminContext {
+        monero_wallet,
+        db,
+        network,
+    });
+
+    module.register_async_method("get_monero_balance", |_, context| async move {
+        let balance = MoneroBalance::fetch(&context.monero_wallet, context.db.as_ref())
+            .await
+            .map_err(|err| jsonrpsee_core::Error::Custom(format!("{:#}", err)))?;
+
+        Ok(balance)
+    })?;
+
+    module.register_async_method("withdraw_xmr", |params_raw, context| async move {
+        let params: HashMap<String, String> = params_raw.parse()?;
+
+        let amount = params
+            .get("amount")
+            .map(|amount| monero::Amount::parse_monero(amount))
+            .transpose()
+            .map_err(|_| jsonrpsee_core::Error::Custom("Unable to parse amount".to_string()))?;
+        let address = params
+            .get("address")
+            .ok_or_else(|| jsonrpsee_core::Error::Custom("Does not contain address".to_string()))?;
+        let address = monero_address::parse(address)?;
+
+        let tx_hashes = withdraw_xmr(&context.monero_wallet, address, amount, context.network)
+            .await
+            .map_err(|err| jsonrpsee_core::Error::Custom(format!("{:#}", err)))?;
+
+        Ok(tx_hashes)
+    })?;
+
+    let addr = server.local_addr()?;
+    let server_handle = server.start(module)?;
+
+    tracing::info!(%addr, "Admin RPC server listening");
+
+    Ok((addr, server_handle))
+}
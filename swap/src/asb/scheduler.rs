@@ -0,0 +1,336 @@
+use crate::bitcoin::{self, ExpiredTimelocks};
+use crate::protocol::alice::{AliceState, State3};
+use crate::protocol::Database;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// How often the database is scanned for swaps that are no longer running.
+pub const SCHEDULER_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The swaps that currently have a task running them.
+#[derive(Clone, Debug, Default)]
+pub struct RunningSwaps(Arc<Mutex<HashSet<Uuid>>>);
+
+impl RunningSwaps {
+    /// Marks the swap as running until the returned guard is dropped.
+    pub fn track(&self, swap_id: Uuid) -> RunningSwap {
+        self.0
+            .lock()
+            .expect("running swaps lock not poisoned")
+            .insert(swap_id);
+
+        RunningSwap {
+            running_swaps: self.clone(),
+            swap_id,
+        }
+    }
+
+    pub fn contains(&self, swap_id: Uuid) -> bool {
+        self.0
+            .lock()
+            .expect("running swaps lock not poisoned")
+            .contains(&swap_id)
+    }
+}
+
+#[derive(Debug)]
+pub struct RunningSwap {
+    running_swaps: RunningSwaps,
+    swap_id: Uuid,
+}
+
+impl Drop for RunningSwap {
+    fn drop(&mut self) {
+        self.running_swaps
+            .0
+            .lock()
+            .expect("running swaps lock not poisoned")
+            .remove(&self.swap_id);
+    }
+}
+
+/// Resumes swaps whose timelocks expired but that are not run by any task,
+/// e.g. because their task failed.
+///
+/// Resumed swaps are driven to a final state by the swap protocol itself,
+/// which cancels, refunds or punishes depending on the expired timelocks.
+#[allow(missing_debug_implementations)]
+pub struct Scheduler {
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    db: Arc<dyn Database + Send + Sync>,
+    running_swaps: RunningSwaps,
+    resume_sender: mpsc::Sender<Uuid>,
+    interval: Duration,
+}
+
+impl Scheduler {
+    pub fn new(
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        db: Arc<dyn Database + Send + Sync>,
+        running_swaps: RunningSwaps,
+        resume_sender: mpsc::Sender<Uuid>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            bitcoin_wallet,
+            db,
+            running_swaps,
+            resume_sender,
+            interval,
+        }
+    }
+
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+
+        // The first tick completes immediately. All unfinished swaps are
+        // resumed on startup, so there is nothing to do yet.
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            if let Err(error) = self.resume_expired_swaps().await {
+                tracing::warn!("Failed to check swaps for expired timelocks: {:#}", error);
+            }
+        }
+    }
+
+    async fn resume_expired_swaps(&self) -> Result<()> {
+        let bitcoin_wallet = self.bitcoin_wallet.as_ref();
+
+        resume_expired_swaps(
+            self.db.as_ref(),
+            &self.running_swaps,
+            &self.resume_sender,
+            |state3| async move { state3.expired_timelocks(bitcoin_wallet).await },
+        )
+        .await
+    }
+}
+
+/// Sends the id of every unfinished swap that is not running and has to be
+/// resumed to `resume_sender`. `expired_timelocks` looks up which timelocks
+/// of a swap expired.
+async fn resume_expired_swaps<F, Fut>(
+    db: &(dyn Database + Send + Sync),
+    running_swaps: &RunningSwaps,
+    resume_sender: &mpsc::Sender<Uuid>,
+    expired_timelocks: F,
+) -> Result<()>
+where
+    F: Fn(State3) -> Fut,
+    Fut: Future<Output = Result<ExpiredTimelocks>>,
+{
+    for (swap_id, state) in db.all().await? {
+        if state.swap_finished() || running_swaps.contains(swap_id) {
+            continue;
+        }
+
+        let state: AliceState = match state.try_into() {
+            Ok(state) => state,
+            Err(error) => {
+                tracing::warn!(%swap_id, "Skipping swap with unexpected state: {:#}", error);
+                continue;
+            }
+        };
+
+        let expired_timelocks = match state.state3() {
+            Some(state3) => match expired_timelocks(state3.clone()).await {
+                Ok(expired_timelocks) => expired_timelocks,
+                Err(error) => {
+                    tracing::warn!(%swap_id, "Failed to check timelocks of swap: {:#}", error);
+                    continue;
+                }
+            },
+            None => continue,
+        };
+
+        if !must_resume(&state, expired_timelocks) {
+            continue;
+        }
+
+        tracing::info!(
+            %swap_id,
+            %state,
+            ?expired_timelocks,
+            "Swap is not running although its timelock expired, resuming it"
+        );
+
+        resume_sender
+            .send(swap_id)
+            .await
+            .context("Failed to resume swap because the event loop stopped")?;
+    }
+
+    Ok(())
+}
+
+/// Whether a swap that is not running has to be resumed to reach a final
+/// state.
+fn must_resume(state: &AliceState, expired_timelocks: ExpiredTimelocks) -> bool {
+    match state {
+        // Waiting for the redeem transaction to be final does not depend on the timelocks
+        AliceState::BtcRedeemTransactionPublished { .. } => true,
+        _ => !matches!(expired_timelocks, ExpiredTimelocks::None { .. }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{AccessMode, SqliteDatabase};
+    use crate::protocol::swap_setup_states;
+    use std::collections::HashMap;
+
+    const NOT_EXPIRED: ExpiredTimelocks = ExpiredTimelocks::None { blocks_left: 10 };
+    const CANCEL_EXPIRED: ExpiredTimelocks = ExpiredTimelocks::Cancel { blocks_left: 10 };
+
+    async fn state3() -> Box<State3> {
+        let (state3, _) = swap_setup_states().await;
+
+        Box::new(state3)
+    }
+
+    async fn database() -> SqliteDatabase {
+        let temp_db = tempfile::tempdir().unwrap().into_path().join("tempdb");
+        std::fs::File::create(&temp_db).unwrap();
+
+        SqliteDatabase::open(temp_db, AccessMode::ReadWrite)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn resumes_swaps_once_a_timelock_expired() {
+        let state = AliceState::BtcLocked {
+            state3: state3().await,
+        };
+
+        assert!(!must_resume(&state, NOT_EXPIRED));
+        assert!(must_resume(&state, CANCEL_EXPIRED));
+        assert!(must_resume(&state, ExpiredTimelocks::Punish));
+    }
+
+    #[tokio::test]
+    async fn resumes_published_redeem_regardless_of_timelocks() {
+        let state = AliceState::BtcRedeemTransactionPublished {
+            state3: state3().await,
+        };
+
+        assert!(must_resume(&state, NOT_EXPIRED));
+    }
+
+    #[tokio::test]
+    async fn resumes_expired_swaps_that_are_not_running() {
+        let db = database().await;
+        let running_swaps = RunningSwaps::default();
+        let mut expiries = HashMap::new();
+        let mut insert = |state: AliceState, expired_timelocks: ExpiredTimelocks| {
+            let swap_id = Uuid::new_v4();
+            if let Some(state3) = state.state3() {
+                expiries.insert(state3.tx_lock.txid(), expired_timelocks);
+            }
+
+            (swap_id, state)
+        };
+
+        let expired = insert(
+            AliceState::BtcLocked {
+                state3: state3().await,
+            },
+            CANCEL_EXPIRED,
+        );
+        let not_expired = insert(
+            AliceState::BtcLocked {
+                state3: state3().await,
+            },
+            NOT_EXPIRED,
+        );
+        let running = insert(
+            AliceState::BtcLocked {
+                state3: state3().await,
+            },
+            CANCEL_EXPIRED,
+        );
+        let redeem_published = insert(
+            AliceState::BtcRedeemTransactionPublished {
+                state3: state3().await,
+            },
+            NOT_EXPIRED,
+        );
+        let finished = insert(AliceState::BtcRedeemed, CANCEL_EXPIRED);
+
+        for (swap_id, state) in [
+            &expired,
+            &not_expired,
+            &running,
+            &redeem_published,
+            &finished,
+        ] {
+            db.insert_latest_state(*swap_id, state.clone().into())
+                .await
+                .unwrap();
+        }
+        let _running = running_swaps.track(running.0);
+
+        let (resume_sender, mut resume_receiver) = mpsc::channel(10);
+        resume_expired_swaps(&db, &running_swaps, &resume_sender, |state3| {
+            let expired_timelocks = expiries[&state3.tx_lock.txid()];
+            async move { Ok(expired_timelocks) }
+        })
+        .await
+        .unwrap();
+        drop(resume_sender);
+
+        let mut resumed = HashSet::new();
+        while let Some(swap_id) = resume_receiver.recv().await {
+            resumed.insert(swap_id);
+        }
+
+        assert_eq!(resumed, HashSet::from([expired.0, redeem_published.0]));
+    }
+
+    #[tokio::test]
+    async fn skips_swaps_whose_timelocks_cannot_be_checked() {
+        let db = database().await;
+        let swap_id = Uuid::new_v4();
+        db.insert_latest_state(
+            swap_id,
+            AliceState::BtcLocked {
+                state3: state3().await,
+            }
+            .into(),
+        )
+        .await
+        .unwrap();
+
+        let (resume_sender, mut resume_receiver) = mpsc::channel(10);
+        resume_expired_swaps(&db, &RunningSwaps::default(), &resume_sender, |_| async {
+            Err::<ExpiredTimelocks, _>(anyhow::anyhow!("Electrum is unreachable"))
+        })
+        .await
+        .unwrap();
+        drop(resume_sender);
+
+        assert_eq!(resume_receiver.recv().await, None);
+    }
+
+    #[test]
+    fn swap_is_running_until_guard_is_dropped() {
+        let running_swaps = RunningSwaps::default();
+        let swap_id = Uuid::new_v4();
+
+        let running_swap = running_swaps.track(swap_id);
+        assert!(running_swaps.contains(swap_id));
+
+        drop(running_swap);
+        assert!(!running_swaps.contains(swap_id));
+    }
+}
@@ -28,12 +28,17 @@ use swap::asb::command::{parse_args, Arguments, Command};
 use swap::asb::config::{
     initial_setup, query_user_for_initial_config, read_config, Config, ConfigNotInitialized,
 };
-use swap::asb::{cancel, punish, redeem, refund, safely_abort, EventLoop, Finality, KrakenRate};
+use swap::asb::rpc::MoneroBalance;
+use swap::asb::{
+    cancel, punish, redeem, refund, safely_abort, EventLoop, Finality, KrakenRate, Scheduler,
+    SCHEDULER_INTERVAL,
+};
 use swap::common::check_latest_version;
 use swap::database::{open_db, AccessMode};
 use swap::network::rendezvous::XmrBtcNamespace;
 use swap::network::swarm;
 use swap::protocol::alice::{run, AliceState};
+use swap::protocol::rescan;
 use swap::seed::Seed;
 use swap::tor::AuthenticatedClient;
 use swap::{asb, bitcoin, kraken, monero, tor};
@@ -141,6 +146,7 @@ async fn main() -> Result<()> {
             }
 
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            bitcoin_wallet.use_script_cache(db.clone()).await?;
             let bitcoin_balance = bitcoin_wallet.balance().await?;
             tracing::info!(%bitcoin_balance, "Bitcoin wallet balance");
 
@@ -164,12 +170,15 @@ async fn main() -> Result<()> {
             };
 
             let kraken_rate = KrakenRate::new(config.maker.ask_spread, kraken_price_updates);
+            let timelock_bounds = config.maker.timelocks.bounds(env_config)?;
+            tracing::info!(?timelock_bounds, "Accepting timelocks within bounds");
             let namespace = XmrBtcNamespace::from_is_testnet(testnet);
 
             let mut swarm = swarm::asb(
                 &seed,
                 config.maker.min_buy_btc,
                 config.maker.max_buy_btc,
+                timelock_bounds,
                 kraken_rate.clone(),
                 resume_only,
                 env_config,
@@ -192,23 +201,54 @@ async fn main() -> Result<()> {
                 );
             }
 
+            let monero_wallet = Arc::new(monero_wallet);
+
+            let _admin_rpc_server = match config.network.admin_rpc_address {
+                Some(address) => Some(
+                    asb::rpc::run_server(
+                        address,
+                        monero_wallet.clone(),
+                        db.clone(),
+                        env_config.monero_network,
+                    )
+                    .await?,
+                ),
+                None => None,
+            };
+
+            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+
             let (event_loop, mut swap_receiver) = EventLoop::new(
                 swarm,
+                seed.derive_libp2p_identity(),
                 env_config,
-                Arc::new(bitcoin_wallet),
-                Arc::new(monero_wallet),
-                db,
+                bitcoin_wallet.clone(),
+                monero_wallet,
+                db.clone(),
                 kraken_rate.clone(),
                 config.maker.min_buy_btc,
                 config.maker.max_buy_btc,
+                timelock_bounds,
                 config.maker.external_bitcoin_redeem_address,
             )
             .unwrap();
 
+            let running_swaps = event_loop.running_swaps();
+            let scheduler = Scheduler::new(
+                bitcoin_wallet,
+                db,
+                running_swaps.clone(),
+                event_loop.resume_sender(),
+                SCHEDULER_INTERVAL,
+            );
+            tokio::spawn(scheduler.run());
+
             tokio::spawn(async move {
                 while let Some(swap) = swap_receiver.recv().await {
                     let rate = kraken_rate.clone();
+                    let running_swap = running_swaps.track(swap.swap_id);
                     tokio::spawn(async move {
+                        let _running_swap = running_swap;
                         let swap_id = swap.swap_id;
                         match run(swap, rate).await {
                             Ok(state) => {
@@ -261,10 +301,32 @@ async fn main() -> Result<()> {
 
             bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
         }
+        Command::WithdrawXmr { amount, address } => {
+            let monero_wallet = init_monero_wallet(&config, env_config).await?;
+
+            asb::rpc::withdraw_xmr(&monero_wallet, address, amount, env_config.monero_network)
+                .await?;
+        }
         Command::Balance => {
+            let db = open_db(config.data.dir.join("sqlite"), AccessMode::ReadOnly).await?;
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
-            let monero_balance = monero_wallet.get_balance().await?;
-            tracing::info!(%monero_balance);
+            let monero_balance = MoneroBalance::fetch(&monero_wallet, db.as_ref()).await?;
+            tracing::info!(
+                total = %monero_balance.total,
+                unlocked = %monero_balance.unlocked,
+                locked = %monero_balance.locked,
+                pending_refunds = %monero_balance.pending_refund_total,
+                "Monero balance"
+            );
+            for refund in &monero_balance.pending_refunds {
+                tracing::info!(
+                    swap_id = %refund.swap_id,
+                    amount = %refund.amount,
+                    state = %refund.state,
+                    "Pending Monero refund"
+                );
+            }
+            let monero_balance = monero_balance.total;
 
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
             let bitcoin_balance = bitcoin_wallet.balance().await?;
@@ -310,7 +372,9 @@ async fn main() -> Result<()> {
 
             safely_abort(swap_id, db).await?;
 
-            tracing::info!("Swap safely aborted");
+            tracing::info!(
+                "Swap safely aborted, the taker can now request an early refund of their Bitcoin"
+            );
         }
         Command::Redeem {
             swap_id,
@@ -335,6 +399,70 @@ async fn main() -> Result<()> {
             let wallet_export = bitcoin_wallet.wallet_export("asb").await?;
             println!("{}", wallet_export.to_string())
         }
+        Command::Rescan {
+            gap_limit,
+            min_lock_height,
+        } => {
+            let db = open_db(config.data.dir.join("sqlite"), AccessMode::ReadWrite).await?;
+
+            let swap_addresses = rescan::swap_addresses(&*db).await?;
+            let bitcoin_wallet = bitcoin::Wallet::rescan(
+                config.bitcoin.electrum_rpc_url.clone(),
+                &config.data.dir,
+                seed.derive_extended_private_key(env_config.bitcoin_network)?,
+                env_config,
+                config.bitcoin.target_block,
+                gap_limit,
+                &swap_addresses,
+            )
+            .await
+            .context("Failed to rescan Bitcoin wallet")?;
+
+            let bitcoin_balance = bitcoin_wallet.balance().await?;
+            tracing::info!(%bitcoin_balance, "Bitcoin wallet rescanned");
+
+            let locked_swaps = rescan::locked_swaps(&*db, &bitcoin_wallet, min_lock_height).await?;
+
+            if locked_swaps.is_empty() {
+                tracing::info!("No swaps with funds in a Bitcoin lock output found");
+            } else {
+                let bitcoin_wallet = Arc::new(bitcoin_wallet);
+                let mut table = Table::new();
+
+                table.set_header(vec!["SWAP ID", "STATE", "LOCK TXID", "AMOUNT", "RECOVERY"]);
+
+                for locked_swap in locked_swaps {
+                    let swap_id = locked_swap.swap_id;
+
+                    let recovery = match rescan::recover(
+                        swap_id,
+                        bitcoin_wallet.clone(),
+                        db.clone(),
+                    )
+                    .await
+                    {
+                        Ok(state) => {
+                            tracing::info!(%swap_id, %state, "Recovered Bitcoin from the lock output of swap");
+                            format!("now {}", state)
+                        }
+                        Err(error) => {
+                            tracing::warn!(%swap_id, "Cannot recover Bitcoin from the lock output of swap yet: {:#}", error);
+                            format!("{:#}", error)
+                        }
+                    };
+
+                    table.add_row(vec![
+                        swap_id.to_string(),
+                        locked_swap.state,
+                        locked_swap.tx_lock_id.to_string(),
+                        locked_swap.amount.to_string(),
+                        recovery,
+                    ]);
+                }
+
+                println!("{}", table);
+            }
+        }
     }
 
     Ok(())
@@ -357,6 +485,19 @@ async fn init_bitcoin_wallet(
     .await
     .context("Failed to initialize Bitcoin wallet")?;
 
+    wallet
+        .use_fee_rate_sources(
+            bitcoin::fee::sources(
+                config.bitcoin.bitcoind_rpc_url.clone(),
+                config.bitcoin.mempool_space_url.clone(),
+            ),
+            bitcoin::fee::FeeLimits::from_config(
+                config.bitcoin.max_fee_rate,
+                config.bitcoin.max_fee_percentage,
+            ),
+        )
+        .await;
+
     wallet.sync().await?;
 
     Ok(wallet)
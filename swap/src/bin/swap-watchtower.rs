@@ -0,0 +1,93 @@
+#![warn(
+    unused_extern_crates,
+    missing_copy_implementations,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![forbid(unsafe_code)]
+#![allow(non_snake_case)]
+
+use anyhow::{bail, Context, Result};
+use comfy_table::Table;
+use std::env;
+use structopt::clap;
+use structopt::clap::ErrorKind;
+use swap::asb;
+use swap::watchtower::command::{parse_args, Arguments, Command};
+use swap::watchtower::{self, Bundle};
+use tracing_subscriber::filter::LevelFilter;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let Arguments {
+        json,
+        data_dir,
+        env_config,
+        cmd,
+    } = match parse_args(env::args_os()) {
+        Ok(args) => args,
+        Err(e) => {
+            if let Some(clap_err) = e.downcast_ref::<clap::Error>() {
+                match clap_err.kind {
+                    ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => {
+                        println!("{}", clap_err.message);
+                        std::process::exit(0);
+                    }
+                    _ => {
+                        bail!(e);
+                    }
+                }
+            }
+            bail!(e);
+        }
+    };
+
+    asb::tracing::init(LevelFilter::DEBUG, json, true).expect("initialize tracing");
+
+    match cmd {
+        Command::Run { electrum_rpc_url } => {
+            tracing::info!(data_dir = %data_dir.display(), "Watching stored bundles");
+
+            watchtower::run(electrum_rpc_url, data_dir, env_config).await?;
+        }
+        Command::Add { bundle } => {
+            let bundle = tokio::fs::read(&bundle)
+                .await
+                .with_context(|| format!("Failed to read bundle from {}", bundle.display()))?;
+            let bundle: Bundle =
+                serde_json::from_slice(&bundle).context("Failed to parse bundle")?;
+
+            watchtower::add_bundle(&data_dir, &bundle, env_config).await?;
+
+            tracing::info!(swap_id = %bundle.swap_id, "Stored bundle, a running watchtower picks it up within a minute");
+        }
+        Command::List => {
+            let mut table = Table::new();
+
+            table.set_header(vec![
+                "SWAP ID",
+                "LOCK TXID",
+                "CANCEL TIMELOCK",
+                "PUNISH TIMELOCK",
+            ]);
+
+            for bundle in watchtower::load_bundles(&data_dir).await? {
+                table.add_row(vec![
+                    bundle.swap_id.to_string(),
+                    bundle.tx_lock_id.to_string(),
+                    bundle.cancel_timelock.to_string(),
+                    bundle.punish_timelock.to_string(),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+
+    Ok(())
+}
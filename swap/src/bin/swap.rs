@@ -41,8 +41,11 @@ mod tests {
     use ::bitcoin::Amount;
     use std::sync::{Arc, Mutex};
     use std::time::Duration;
-    use swap::api::request::determine_btc_to_swap;
+    use swap::api::request::{choose_timelocks, determine_btc_to_swap};
+    use swap::bitcoin::{CancelTimelock, PunishTimelock};
+    use swap::env::{GetConfig, Mainnet};
     use swap::network::quote::BidQuote;
+    use swap::network::swap_setup::{TimelockBounds, Timelocks};
     use swap::tracing_ext::capture_logs;
     use tracing::level_filters::LevelFilter;
 
@@ -376,6 +379,111 @@ mod tests {
         assert_eq!("Received quote of 0", determination_error);
     }
 
+    #[test]
+    fn given_no_timelocks_requested_does_not_propose_any() {
+        let timelocks = choose_timelocks(None, None, None, Mainnet::get_config()).unwrap();
+
+        assert_eq!(timelocks, None);
+    }
+
+    #[test]
+    fn given_timelocks_within_bounds_proposes_them() {
+        let env_config = Mainnet::get_config();
+        let bounds = TimelockBounds {
+            min_cancel: CancelTimelock::new(72),
+            max_cancel: CancelTimelock::new(144),
+            min_punish: PunishTimelock::new(72),
+            max_punish: PunishTimelock::new(144),
+        };
+
+        let timelocks = choose_timelocks(
+            Some(CancelTimelock::new(100)),
+            None,
+            Some(bounds),
+            env_config,
+        )
+        .unwrap();
+
+        assert_eq!(
+            timelocks,
+            Some(Timelocks {
+                cancel: CancelTimelock::new(100),
+                punish: env_config.bitcoin_punish_timelock,
+            })
+        );
+    }
+
+    #[test]
+    fn given_timelocks_outside_of_bounds_fails() {
+        let env_config = Mainnet::get_config();
+        let bounds = TimelockBounds::fixed(Timelocks::from(env_config));
+
+        let result = choose_timelocks(Some(CancelTimelock::new(1)), None, Some(bounds), env_config);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_timelocks_below_minimum_fails() {
+        let env_config = Mainnet::get_config();
+        let bounds = TimelockBounds {
+            min_cancel: CancelTimelock::new(0),
+            max_cancel: CancelTimelock::new(144),
+            min_punish: PunishTimelock::new(0),
+            max_punish: PunishTimelock::new(144),
+        };
+
+        for timelock in [0, 1] {
+            let cancel = choose_timelocks(
+                Some(CancelTimelock::new(timelock)),
+                None,
+                Some(bounds),
+                env_config,
+            );
+            assert!(cancel.is_err());
+
+            let punish = choose_timelocks(
+                None,
+                Some(PunishTimelock::new(timelock)),
+                Some(bounds),
+                env_config,
+            );
+            assert!(punish.is_err());
+        }
+    }
+
+    #[test]
+    fn given_seller_with_inverted_bounds_fails() {
+        let env_config = Mainnet::get_config();
+        let bounds = TimelockBounds {
+            min_cancel: CancelTimelock::new(144),
+            max_cancel: CancelTimelock::new(72),
+            min_punish: PunishTimelock::new(72),
+            max_punish: PunishTimelock::new(144),
+        };
+
+        let result = choose_timelocks(
+            Some(CancelTimelock::new(100)),
+            None,
+            Some(bounds),
+            env_config,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_seller_without_bounds_and_timelocks_requested_fails() {
+        let result = choose_timelocks(
+            None,
+            Some(PunishTimelock::new(100)),
+            None,
+            Mainnet::get_config(),
+        );
+
+        assert!(result.is_err());
+    }
+
     struct MaxGiveable {
         amounts: Vec<Amount>,
         call_counter: usize,
@@ -403,6 +511,7 @@ mod tests {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::from_btc(btc).unwrap(),
             min_quantity: Amount::ZERO,
+            timelock_bounds: None,
         }
     }
 
@@ -411,6 +520,7 @@ mod tests {
             price: Amount::from_btc(0.001).unwrap(),
             max_quantity: Amount::max_value(),
             min_quantity: Amount::from_btc(btc).unwrap(),
+            timelock_bounds: None,
         }
     }
 
@@ -1,6 +1,8 @@
+pub mod fee;
 pub mod wallet;
 
 mod cancel;
+mod early_refund;
 mod lock;
 mod punish;
 mod redeem;
@@ -8,6 +10,7 @@ mod refund;
 mod timelocks;
 
 pub use crate::bitcoin::cancel::{CancelTimelock, PunishTimelock, TxCancel};
+pub use crate::bitcoin::early_refund::TxEarlyRefund;
 pub use crate::bitcoin::lock::TxLock;
 pub use crate::bitcoin::punish::TxPunish;
 pub use crate::bitcoin::redeem::TxRedeem;
@@ -29,6 +32,8 @@ use crate::bitcoin::wallet::ScriptStatus;
 use ::bitcoin::hashes::Hash;
 use ::bitcoin::Sighash;
 use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose;
+use base64::Engine;
 use bdk::miniscript::descriptor::Wsh;
 use bdk::miniscript::{Descriptor, Segwitv0};
 use ecdsa_fun::adaptor::{Adaptor, HashTranscript};
@@ -223,6 +228,22 @@ pub fn build_shared_output_descriptor(
     Ok(Descriptor::Wsh(Wsh::new(miniscript)?))
 }
 
+/// Encodes a PSBT in the base64 format defined by BIP174, as understood by
+/// hardware and other external wallets.
+pub fn psbt_to_base64(psbt: &PartiallySignedTransaction) -> String {
+    general_purpose::STANDARD.encode(::bitcoin::consensus::encode::serialize(psbt))
+}
+
+pub fn psbt_from_base64(psbt: &str) -> Result<PartiallySignedTransaction> {
+    let bytes = general_purpose::STANDARD
+        .decode(psbt.trim())
+        .context("PSBT is not valid base64")?;
+    let psbt =
+        ::bitcoin::consensus::encode::deserialize(&bytes).context("Failed to decode PSBT")?;
+
+    Ok(psbt)
+}
+
 pub fn recover(S: PublicKey, sig: Signature, encsig: EncryptedSignature) -> Result<SecretKey> {
     let adaptor = Adaptor::<HashTranscript<Sha256>, Deterministic<Sha256>>::default();
 
@@ -392,6 +413,26 @@ mod tests {
     use std::matches;
     use uuid::Uuid;
 
+    #[test]
+    fn psbt_base64_roundtrip() {
+        let tx = Transaction {
+            version: 2,
+            lock_time: ::bitcoin::PackedLockTime(0),
+            input: vec![::bitcoin::TxIn::default()],
+            output: vec![::bitcoin::TxOut {
+                value: 1000,
+                script_pubkey: ::bitcoin::Script::new(),
+            }],
+        };
+        let psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+
+        let encoded = psbt_to_base64(&psbt);
+
+        assert!(encoded.starts_with("cHNidP8"));
+        assert_eq!(psbt_from_base64(&encoded).unwrap(), psbt);
+        assert!(psbt_from_base64("not a psbt").is_err());
+    }
+
     #[test]
     fn lock_confirmations_le_to_cancel_timelock_no_timelock_expired() {
         let tx_lock_status = ScriptStatus::from_confirmations(4);
@@ -460,7 +501,8 @@ mod tests {
         let alice_state0 = alice::State0::new(
             btc_amount,
             xmr_amount,
-            config,
+            config.bitcoin_cancel_timelock,
+            config.bitcoin_punish_timelock,
             redeem_address,
             punish_address,
             tx_redeem_fee,
@@ -501,6 +543,9 @@ mod tests {
         let alice_state3 = alice_state2.receive(bob_message4).unwrap();
 
         let (bob_state3, _tx_lock) = bob_state2.lock_btc().await.unwrap();
+        let early_refund_transaction = bob_state3
+            .signed_early_refund_transaction(alice_state3.sign_early_refund())
+            .unwrap();
         let bob_state4 = bob_state3.xmr_locked(monero_rpc::wallet::BlockHeight { height: 0 });
         let encrypted_signature = bob_state4.tx_redeem_encsig();
         let bob_state6 = bob_state4.cancel();
@@ -516,6 +561,11 @@ mod tests {
         assert_weight(cancel_transaction, TxCancel::weight(), "TxCancel");
         assert_weight(punish_transaction, TxPunish::weight(), "TxPunish");
         assert_weight(refund_transaction, TxRefund::weight(), "TxRefund");
+        assert_weight(
+            early_refund_transaction,
+            TxEarlyRefund::weight(),
+            "TxEarlyRefund",
+        );
     }
 
     // Weights fluctuate because of the length of the signatures. Valid ecdsa
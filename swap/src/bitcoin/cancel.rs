@@ -21,7 +21,7 @@ use std::ops::Add;
 /// [BIP68](https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki).
 /// E.g. The timelock expires 10 blocks after the reference transaction is
 /// mined.
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, PartialOrd, Ord)]
 #[serde(transparent)]
 pub struct CancelTimelock(u32);
 
@@ -67,7 +67,7 @@ impl fmt::Display for CancelTimelock {
 /// [BIP68](https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki).
 /// E.g. The timelock expires 10 blocks after the reference transaction is
 /// mined.
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, PartialOrd, Ord)]
 #[serde(transparent)]
 pub struct PunishTimelock(u32);
 
@@ -103,6 +103,12 @@ impl PartialEq<PunishTimelock> for u32 {
     }
 }
 
+impl fmt::Display for PunishTimelock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} blocks", self.0)
+    }
+}
+
 #[derive(Debug)]
 pub struct TxCancel {
     inner: Transaction,
@@ -0,0 +1,120 @@
+use crate::bitcoin;
+use crate::bitcoin::wallet::Watchable;
+use crate::bitcoin::{verify_sig, Address, Amount, PublicKey, Transaction, TxLock};
+use ::bitcoin::util::sighash::SighashCache;
+use ::bitcoin::{secp256k1, EcdsaSighashType, Script, Sighash, Txid};
+use anyhow::{Context, Result};
+use bdk::miniscript::Descriptor;
+use ecdsa_fun::Signature;
+use std::collections::HashMap;
+
+/// Spends the lock output straight back to Bob's refund address.
+///
+/// Unlike [`TxRefund`](crate::bitcoin::TxRefund) this transaction does not go
+/// through [`TxCancel`](crate::bitcoin::TxCancel) and is therefore not bound to
+/// the cancel timelock. Alice only signs it if she has safely aborted the swap
+/// before locking her Monero.
+#[derive(Clone, Debug)]
+pub struct TxEarlyRefund {
+    inner: Transaction,
+    digest: Sighash,
+    lock_output_descriptor: Descriptor<::bitcoin::PublicKey>,
+    watch_script: Script,
+}
+
+impl TxEarlyRefund {
+    pub fn new(tx_lock: &TxLock, refund_address: &Address, spending_fee: Amount) -> Self {
+        let tx_early_refund = tx_lock.build_spend_transaction(refund_address, None, spending_fee);
+
+        let digest = SighashCache::new(&tx_early_refund)
+            .segwit_signature_hash(
+                0, // Only one input: lock_input (lock transaction)
+                &tx_lock.output_descriptor.script_code().expect("scriptcode"),
+                tx_lock.lock_amount().to_sat(),
+                EcdsaSighashType::All,
+            )
+            .expect("sighash");
+
+        Self {
+            inner: tx_early_refund,
+            digest,
+            lock_output_descriptor: tx_lock.output_descriptor.clone(),
+            watch_script: refund_address.script_pubkey(),
+        }
+    }
+
+    pub fn txid(&self) -> Txid {
+        self.inner.txid()
+    }
+
+    pub fn digest(&self) -> Sighash {
+        self.digest
+    }
+
+    pub fn complete_as_bob(
+        self,
+        A: PublicKey,
+        b: bitcoin::SecretKey,
+        tx_early_refund_sig_A: Signature,
+    ) -> Result<Transaction> {
+        verify_sig(&A, &self.digest(), &tx_early_refund_sig_A)
+            .context("Invalid early refund signature received")?;
+
+        let sig_b = b.sign(self.digest());
+
+        let satisfier = {
+            let mut satisfier = HashMap::with_capacity(2);
+
+            let A = ::bitcoin::PublicKey {
+                compressed: true,
+                inner: secp256k1::PublicKey::from_slice(&A.0.to_bytes())?,
+            };
+            let B = ::bitcoin::PublicKey {
+                compressed: true,
+                inner: secp256k1::PublicKey::from_slice(&b.public.to_bytes())?,
+            };
+
+            let sig_a =
+                secp256k1::ecdsa::Signature::from_compact(&tx_early_refund_sig_A.to_bytes())?;
+            let sig_b = secp256k1::ecdsa::Signature::from_compact(&sig_b.to_bytes())?;
+            // The order in which these are inserted doesn't matter
+            satisfier.insert(
+                A,
+                ::bitcoin::EcdsaSig {
+                    sig: sig_a,
+                    hash_ty: EcdsaSighashType::All,
+                },
+            );
+            satisfier.insert(
+                B,
+                ::bitcoin::EcdsaSig {
+                    sig: sig_b,
+                    hash_ty: EcdsaSighashType::All,
+                },
+            );
+
+            satisfier
+        };
+
+        let mut tx_early_refund = self.inner;
+        self.lock_output_descriptor
+            .satisfy(&mut tx_early_refund.input[0], satisfier)
+            .context("Failed to sign Bitcoin early refund transaction")?;
+
+        Ok(tx_early_refund)
+    }
+
+    pub fn weight() -> usize {
+        548
+    }
+}
+
+impl Watchable for TxEarlyRefund {
+    fn id(&self) -> Txid {
+        self.txid()
+    }
+
+    fn script(&self) -> Script {
+        self.watch_script.clone()
+    }
+}
@@ -0,0 +1,446 @@
+//! Fee-rate estimates from multiple sources.
+//!
+//! The Electrum server we are connected to is not necessarily trustworthy. A
+//! compromised or buggy server could make us overpay hugely, hence its
+//! estimate is combined with the estimates of additional sources (bitcoind, a
+//! mempool.space compatible API) and bounded by an absolute maximum.
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use bdk::FeeRate;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+
+/// Default upper bound for the fee rate in sat/vB.
+pub const DEFAULT_MAX_FEE_RATE: u64 = 1_000;
+
+/// Default upper bound of the fee as a fraction of the transferred amount.
+///
+/// Assuming we add a spread of 3% we don't want to pay more than 3% of the
+/// amount for tx fees.
+pub const DEFAULT_MAX_RELATIVE_TX_FEE: Decimal = dec!(0.03);
+
+/// Estimates deviating from the median by more than this factor are flagged.
+const OUTLIER_FACTOR: f32 = 2.0;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Upper bounds for the fees we are willing to pay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeLimits {
+    /// Maximum fee rate in sat/vB.
+    pub max_fee_rate: u64,
+    /// Maximum fee as a fraction of the transferred amount.
+    pub max_relative_fee: Decimal,
+}
+
+impl FeeLimits {
+    /// Applies the defaults to the limits that were not configured.
+    ///
+    /// `max_fee_percentage` is given in percent of the transferred amount.
+    pub fn from_config(max_fee_rate: Option<u64>, max_fee_percentage: Option<Decimal>) -> Self {
+        Self {
+            max_fee_rate: max_fee_rate.unwrap_or(DEFAULT_MAX_FEE_RATE),
+            max_relative_fee: max_fee_percentage
+                .map(|percentage| percentage / dec!(100))
+                .unwrap_or(DEFAULT_MAX_RELATIVE_TX_FEE),
+        }
+    }
+}
+
+impl Default for FeeLimits {
+    fn default() -> Self {
+        Self {
+            max_fee_rate: DEFAULT_MAX_FEE_RATE,
+            max_relative_fee: DEFAULT_MAX_RELATIVE_TX_FEE,
+        }
+    }
+}
+
+/// A source of fee-rate estimates other than the Electrum server.
+#[async_trait]
+pub trait FeeRateSource: Send + Sync + 'static {
+    fn name(&self) -> String;
+    async fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate>;
+}
+
+/// Combines the estimate of the Electrum server with the estimates of
+/// additional sources.
+///
+/// The additional sources are queried in the background, the latest estimate
+/// of each source is used. The median of all estimates is bounded by
+/// [`FeeLimits::max_fee_rate`].
+pub struct FeeRateAggregator {
+    estimates: Arc<Mutex<BTreeMap<String, FeeRate>>>,
+    limits: FeeLimits,
+}
+
+impl FeeRateAggregator {
+    /// Only uses the estimate of the Electrum server, bounded by `limits`.
+    pub fn electrum_only(limits: FeeLimits) -> Self {
+        Self {
+            estimates: Default::default(),
+            limits,
+        }
+    }
+
+    /// Starts refreshing the estimates of the given sources in the background.
+    ///
+    /// Refreshing stops once the aggregator is dropped.
+    pub fn new(
+        sources: Vec<Box<dyn FeeRateSource>>,
+        target_block: usize,
+        limits: FeeLimits,
+    ) -> Self {
+        let aggregator = Self::electrum_only(limits);
+
+        if sources.is_empty() {
+            return aggregator;
+        }
+
+        let estimates = Arc::downgrade(&aggregator.estimates);
+
+        tokio::spawn(async move {
+            loop {
+                for source in &sources {
+                    let estimate = source.estimate_feerate(target_block).await;
+
+                    let estimates = match estimates.upgrade() {
+                        Some(estimates) => estimates,
+                        None => return,
+                    };
+                    let mut estimates = estimates.lock().expect("lock not poisoned");
+
+                    match estimate {
+                        Ok(fee_rate) => {
+                            estimates.insert(source.name(), fee_rate);
+                        }
+                        Err(error) => {
+                            tracing::warn!(source = %source.name(), "Failed to fetch fee rate estimate: {:#}", error);
+                            estimates.remove(&source.name());
+                        }
+                    }
+                }
+
+                tokio::time::sleep(REFRESH_INTERVAL).await;
+            }
+        });
+
+        aggregator
+    }
+
+    pub fn limits(&self) -> FeeLimits {
+        self.limits
+    }
+
+    /// Combines the estimate of the Electrum server with the latest estimates
+    /// of the other sources.
+    ///
+    /// Fails only if no source produced an estimate.
+    pub fn aggregate(&self, electrum: Result<FeeRate>) -> Result<FeeRate> {
+        let mut estimates = self
+            .estimates
+            .lock()
+            .expect("lock not poisoned")
+            .iter()
+            .map(|(name, fee_rate)| (name.clone(), *fee_rate))
+            .collect::<Vec<_>>();
+
+        match electrum {
+            Ok(fee_rate) => estimates.push(("electrum".to_string(), fee_rate)),
+            Err(error) if !estimates.is_empty() => {
+                tracing::warn!(
+                    "Failed to fetch fee rate estimate from Electrum: {:#}",
+                    error
+                )
+            }
+            Err(error) => return Err(error),
+        }
+
+        aggregate(&estimates, self.limits.max_fee_rate)
+    }
+}
+
+fn aggregate(estimates: &[(String, FeeRate)], max_fee_rate: u64) -> Result<FeeRate> {
+    let mut rates = estimates
+        .iter()
+        .map(|(_, fee_rate)| fee_rate.as_sat_per_vb())
+        .collect::<Vec<_>>();
+
+    if rates.is_empty() {
+        bail!("No fee rate estimate available");
+    }
+
+    rates.sort_by(|a, b| a.partial_cmp(b).expect("fee rates are not NaN"));
+    let median = if rates.len() % 2 == 0 {
+        (rates[rates.len() / 2 - 1] + rates[rates.len() / 2]) / 2.0
+    } else {
+        rates[rates.len() / 2]
+    };
+
+    for (source, fee_rate) in estimates {
+        let fee_rate = fee_rate.as_sat_per_vb();
+
+        if fee_rate > median * OUTLIER_FACTOR || fee_rate * OUTLIER_FACTOR < median {
+            tracing::warn!(%source, %fee_rate, %median, "Fee rate estimate deviates strongly from the other sources");
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let max_fee_rate = max_fee_rate as f32;
+
+    if median > max_fee_rate {
+        tracing::warn!(
+            fee_rate = %median,
+            %max_fee_rate,
+            "Fee rate estimate exceeds the maximum fee rate, falling back to the maximum"
+        );
+        return Ok(FeeRate::from_sat_per_vb(max_fee_rate));
+    }
+
+    Ok(FeeRate::from_sat_per_vb(median))
+}
+
+/// Builds the fee-rate sources that were configured in addition to Electrum.
+pub fn sources(
+    bitcoind_rpc_url: Option<Url>,
+    mempool_space_url: Option<Url>,
+) -> Vec<Box<dyn FeeRateSource>> {
+    let mut sources: Vec<Box<dyn FeeRateSource>> = Vec::new();
+
+    if let Some(url) = bitcoind_rpc_url {
+        sources.push(Box::new(Bitcoind::new(url)));
+    }
+    if let Some(url) = mempool_space_url {
+        sources.push(Box::new(MempoolSpace::new(url)));
+    }
+
+    sources
+}
+
+/// Fee-rate estimates of a bitcoind node via `estimatesmartfee`.
+///
+/// Credentials can be passed as part of the URL.
+pub struct Bitcoind {
+    url: Url,
+    client: reqwest::Client,
+}
+
+impl Bitcoind {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl FeeRateSource for Bitcoind {
+    fn name(&self) -> String {
+        "bitcoind".to_string()
+    }
+
+    async fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate> {
+        #[derive(Deserialize)]
+        struct Response {
+            result: Option<SmartFee>,
+            error: Option<serde_json::Value>,
+        }
+
+        #[derive(Deserialize)]
+        struct SmartFee {
+            feerate: Option<f32>,
+        }
+
+        let mut request = self.client.post(self.url.clone()).json(&serde_json::json!({
+            "jsonrpc": "1.0",
+            "id": "swap",
+            "method": "estimatesmartfee",
+            "params": [target_block],
+        }));
+
+        if !self.url.username().is_empty() {
+            request = request.basic_auth(self.url.username(), self.url.password());
+        }
+
+        let response = request
+            .send()
+            .await
+            .context("Failed to request fee estimate from bitcoind")?
+            .json::<Response>()
+            .await
+            .context("Failed to deserialize bitcoind response")?;
+
+        if let Some(error) = response.error.filter(|error| !error.is_null()) {
+            bail!("bitcoind returned an error: {}", error);
+        }
+
+        // Returned estimated fees are per BTC/kvB.
+        let fee_rate = response
+            .result
+            .and_then(|result| result.feerate)
+            .context("bitcoind has not enough data to estimate the fee rate")?;
+
+        Ok(FeeRate::from_btc_per_kvb(fee_rate))
+    }
+}
+
+/// Fee-rate estimates of a mempool.space compatible HTTP API.
+pub struct MempoolSpace {
+    url: Url,
+    client: reqwest::Client,
+}
+
+impl MempoolSpace {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecommendedFees {
+    fastest_fee: f32,
+    half_hour_fee: f32,
+    hour_fee: f32,
+    economy_fee: f32,
+}
+
+impl RecommendedFees {
+    fn for_target_block(&self, target_block: usize) -> f32 {
+        match target_block {
+            0 | 1 => self.fastest_fee,
+            2 | 3 => self.half_hour_fee,
+            4..=6 => self.hour_fee,
+            _ => self.economy_fee,
+        }
+    }
+}
+
+#[async_trait]
+impl FeeRateSource for MempoolSpace {
+    fn name(&self) -> String {
+        format!("mempool.space ({})", self.url)
+    }
+
+    async fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate> {
+        let url = self.url.join("api/v1/fees/recommended")?;
+
+        let fees = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .context("Failed to request recommended fees")?
+            .error_for_status()?
+            .json::<RecommendedFees>()
+            .await
+            .context("Failed to deserialize recommended fees")?;
+
+        Ok(FeeRate::from_sat_per_vb(
+            fees.for_target_block(target_block),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracing_ext::capture_logs;
+    use tracing::level_filters::LevelFilter;
+
+    fn estimates(rates: &[f32]) -> Vec<(String, FeeRate)> {
+        rates
+            .iter()
+            .enumerate()
+            .map(|(index, rate)| (format!("source-{}", index), FeeRate::from_sat_per_vb(*rate)))
+            .collect()
+    }
+
+    #[test]
+    fn aggregate_uses_median_of_estimates() {
+        assert_eq!(
+            aggregate(&estimates(&[10.0, 2000.0, 12.0]), 1000).unwrap(),
+            FeeRate::from_sat_per_vb(12.0)
+        );
+        assert_eq!(
+            aggregate(&estimates(&[10.0, 20.0]), 1000).unwrap(),
+            FeeRate::from_sat_per_vb(15.0)
+        );
+    }
+
+    #[test]
+    fn aggregate_is_bounded_by_max_fee_rate() {
+        assert_eq!(
+            aggregate(&estimates(&[5000.0]), 1000).unwrap(),
+            FeeRate::from_sat_per_vb(1000.0)
+        );
+    }
+
+    #[test]
+    fn aggregate_fails_without_estimates() {
+        assert!(aggregate(&[], 1000).is_err());
+    }
+
+    #[test]
+    fn outliers_are_logged() {
+        let writer = capture_logs(LevelFilter::WARN);
+
+        aggregate(&estimates(&[10.0, 2000.0, 12.0]), 1000).unwrap();
+
+        assert!(writer
+            .captured()
+            .contains("Fee rate estimate deviates strongly from the other sources"));
+        assert!(writer.captured().contains("source=source-1"));
+    }
+
+    #[test]
+    fn given_electrum_fails_falls_back_to_other_sources() {
+        let aggregator = FeeRateAggregator::electrum_only(FeeLimits::default());
+        aggregator
+            .estimates
+            .lock()
+            .unwrap()
+            .insert("bitcoind".to_string(), FeeRate::from_sat_per_vb(7.0));
+
+        let fee_rate = aggregator
+            .aggregate(Err(anyhow::anyhow!("electrum unreachable")))
+            .unwrap();
+
+        assert_eq!(fee_rate, FeeRate::from_sat_per_vb(7.0));
+        assert!(FeeRateAggregator::electrum_only(FeeLimits::default())
+            .aggregate(Err(anyhow::anyhow!("electrum unreachable")))
+            .is_err());
+    }
+
+    #[test]
+    fn max_fee_percentage_is_converted_to_fraction() {
+        let limits = FeeLimits::from_config(Some(200), Some(dec!(1.5)));
+
+        assert_eq!(limits.max_fee_rate, 200);
+        assert_eq!(limits.max_relative_fee, dec!(0.015));
+        assert_eq!(FeeLimits::from_config(None, None), FeeLimits::default());
+    }
+
+    #[test]
+    fn recommended_fees_are_mapped_to_target_block() {
+        let fees: RecommendedFees = serde_json::from_str(
+            r#"{"fastestFee":30,"halfHourFee":20,"hourFee":10,"economyFee":5,"minimumFee":1}"#,
+        )
+        .unwrap();
+
+        assert_eq!(fees.for_target_block(1), 30.0);
+        assert_eq!(fees.for_target_block(3), 20.0);
+        assert_eq!(fees.for_target_block(6), 10.0);
+        assert_eq!(fees.for_target_block(144), 5.0);
+    }
+}
@@ -130,6 +130,12 @@ impl TxLock {
         self.output_descriptor.script_pubkey()
     }
 
+    /// The descriptor of the lock output. It only contains the public keys of
+    /// both parties.
+    pub fn output_descriptor(&self) -> &Descriptor<::bitcoin::PublicKey> {
+        &self.output_descriptor
+    }
+
     /// Retreive the index of the locked output in the transaction outputs
     /// vector
     fn lock_output_vout(&self) -> usize {
@@ -1,10 +1,13 @@
+use crate::bitcoin::fee::{FeeLimits, FeeRateAggregator, FeeRateSource};
 use crate::bitcoin::timelocks::BlockHeight;
 use crate::bitcoin::{Address, Amount, Transaction};
 use crate::env;
+use crate::protocol::Database;
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
 use ::bitcoin::Txid;
 use anyhow::{bail, Context, Result};
-use bdk::blockchain::{Blockchain, ElectrumBlockchain, GetTx};
+use bdk::blockchain::electrum::ElectrumBlockchainConfig;
+use bdk::blockchain::{Blockchain, ConfigurableBlockchain, ElectrumBlockchain, GetTx};
 use bdk::database::BatchDatabase;
 use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
 use bdk::sled::Tree;
@@ -12,12 +15,13 @@ use bdk::wallet::export::FullyNodedExport;
 use bdk::wallet::AddressIndex;
 use bdk::{FeeRate, KeychainKind, SignOptions, SyncOptions};
 use bitcoin::util::bip32::ExtendedPrivKey;
-use bitcoin::{Network, Script};
+use bitcoin::{BlockHash, Network, Script};
 use reqwest::Url;
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
 use std::path::Path;
@@ -28,14 +32,26 @@ use tracing::{debug_span, Instrument};
 
 const SLED_TREE_NAME: &str = "default_tree";
 
-/// Assuming we add a spread of 3% we don't want to pay more than 3% of the
-/// amount for tx fees.
-const MAX_RELATIVE_TX_FEE: Decimal = dec!(0.03);
 const MAX_ABSOLUTE_TX_FEE: Decimal = dec!(100_000);
 const DUST_AMOUNT: u64 = 546;
 
 const WALLET: &str = "wallet";
 const WALLET_OLD: &str = "wallet-old";
+const WALLET_BACKUP: &str = "wallet-backup";
+const WALLET_WATCH_ONLY: &str = "wallet-watch-only";
+
+/// Number of consecutive unused addresses after which a sync stops looking
+/// for further transactions.
+const DEFAULT_STOP_GAP: usize = 20;
+
+/// Upper bound for the derivation index searched when looking for addresses
+/// of previous swaps during a rescan.
+const MAX_KNOWN_ADDRESS_INDEX: u32 = 10_000;
+
+/// Number of confirmations after which we no longer expect a transaction to be
+/// re-organized out of the chain. The inclusion height of such transactions is
+/// persisted and their scripts are no longer queried from Electrum.
+const SETTLED_CONFIRMATIONS: u32 = 6;
 
 pub struct Wallet<D = Tree, C = Client> {
     client: Arc<Mutex<C>>,
@@ -43,6 +59,7 @@ pub struct Wallet<D = Tree, C = Client> {
     finality_confirmations: u32,
     network: Network,
     target_block: usize,
+    watch_only: bool,
 }
 
 impl Wallet {
@@ -52,11 +69,178 @@ impl Wallet {
         xprivkey: ExtendedPrivKey,
         env_config: env::Config,
         target_block: usize,
+    ) -> Result<Self> {
+        let network = env_config.bitcoin_network;
+        let wallet = Self::open_database(data_dir.as_ref(), xprivkey, network)?;
+        let client = Client::new(
+            electrum_rpc_url,
+            env_config.bitcoin_sync_interval(),
+            DEFAULT_STOP_GAP,
+        )?;
+
+        Ok(Self::from_parts(client, wallet, env_config, target_block))
+    }
+
+    /// Opens a wallet that only knows the public descriptor of an external
+    /// wallet (e.g. a hardware wallet).
+    ///
+    /// Such a wallet can build transactions but cannot sign them, the PSBTs
+    /// have to be signed externally and handed back via
+    /// [`Wallet::finalize_psbt`].
+    pub async fn new_watch_only(
+        electrum_rpc_url: Url,
+        data_dir: impl AsRef<Path>,
+        descriptor: &str,
+        env_config: env::Config,
+        target_block: usize,
+    ) -> Result<Self> {
+        let network = env_config.bitcoin_network;
+        let wallet_dir = data_dir.as_ref().join(WALLET_WATCH_ONLY);
+        let database = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+
+        let wallet = bdk::Wallet::new(descriptor, None, network, database)
+            .context("Failed to open watch-only wallet from descriptor")?;
+        let client = Client::new(
+            electrum_rpc_url,
+            env_config.bitcoin_sync_interval(),
+            DEFAULT_STOP_GAP,
+        )?;
+
+        Ok(Self {
+            watch_only: true,
+            ..Self::from_parts(client, wallet, env_config, target_block)
+        })
+    }
+
+    /// Recreate the wallet database from the given key and rescan the chain.
+    ///
+    /// The existing database (if any) is kept as a backup next to the new
+    /// one and is moved back in place if the rescan fails. Fails if the
+    /// database is still opened by another process, e.g. a running ASB.
+    /// Addresses in `known_addresses` that belong to this wallet (e.g. refund
+    /// or redeem addresses of swaps) are searched for among the derived
+    /// addresses so that the rescan reaches them even if they lie beyond
+    /// `gap_limit`. The Electrum backend always returns the full history of
+    /// the wallet, the rescan cannot start at a later block.
+    pub async fn rescan(
+        electrum_rpc_url: Url,
+        data_dir: impl AsRef<Path>,
+        xprivkey: ExtendedPrivKey,
+        env_config: env::Config,
+        target_block: usize,
+        gap_limit: u32,
+        known_addresses: &[Address],
     ) -> Result<Self> {
         let data_dir = data_dir.as_ref();
         let wallet_dir = data_dir.join(WALLET);
-        let database = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+
+        let backup_dir = if wallet_dir.exists() {
+            // sled holds an exclusive lock on the database while it is open, e.g. by a
+            // running ASB or CLI. The database is closed again before it is moved.
+            if let Err(error) = bdk::sled::open(&wallet_dir) {
+                bail!(
+                    "Bitcoin wallet database at {} is in use or cannot be opened, stop the process using it before rescanning: {}",
+                    wallet_dir.display(),
+                    error
+                );
+            }
+
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs();
+            let backup_dir = data_dir.join(format!("{}-{}", WALLET_BACKUP, timestamp));
+            std::fs::rename(&wallet_dir, &backup_dir).with_context(|| {
+                format!(
+                    "Failed to move Bitcoin wallet database to {}",
+                    backup_dir.display()
+                )
+            })?;
+
+            tracing::info!(backup = %backup_dir.display(), "Moved existing Bitcoin wallet database aside");
+
+            Some(backup_dir)
+        } else {
+            None
+        };
+
+        let result = Self::rescan_database(
+            electrum_rpc_url,
+            data_dir,
+            xprivkey,
+            env_config,
+            target_block,
+            gap_limit,
+            known_addresses,
+        )
+        .await;
+
+        if let (Err(error), Some(backup_dir)) = (&result, backup_dir) {
+            tracing::warn!(%error, "Rescan failed, restoring the previous Bitcoin wallet database");
+
+            if wallet_dir.exists() {
+                std::fs::remove_dir_all(&wallet_dir).with_context(|| {
+                    format!(
+                        "Failed to remove partially rescanned Bitcoin wallet database at {}",
+                        wallet_dir.display()
+                    )
+                })?;
+            }
+            std::fs::rename(&backup_dir, &wallet_dir).with_context(|| {
+                format!(
+                    "Failed to restore Bitcoin wallet database from {}",
+                    backup_dir.display()
+                )
+            })?;
+        }
+
+        result
+    }
+
+    async fn rescan_database(
+        electrum_rpc_url: Url,
+        data_dir: &Path,
+        xprivkey: ExtendedPrivKey,
+        env_config: env::Config,
+        target_block: usize,
+        gap_limit: u32,
+        known_addresses: &[Address],
+    ) -> Result<Self> {
         let network = env_config.bitcoin_network;
+        let wallet = Self::open_database(data_dir, xprivkey, network)?;
+
+        let highest_known_index = highest_derivation_index(&wallet, known_addresses)?;
+        let stop_gap = match highest_known_index {
+            Some(index) => gap_limit.max(index + 1),
+            None => gap_limit,
+        };
+
+        if let Some(index) = highest_known_index {
+            tracing::debug!(%index, %stop_gap, "Found swap addresses owned by the Bitcoin wallet");
+        }
+
+        wallet.ensure_addresses_cached(stop_gap)?;
+
+        let client = Client::new(
+            electrum_rpc_url,
+            env_config.bitcoin_sync_interval(),
+            stop_gap as usize,
+        )?;
+
+        let wallet = Self::from_parts(client, wallet, env_config, target_block);
+
+        tracing::info!(%stop_gap, "Rescanning Bitcoin wallet, this may take a while");
+        wallet.sync().await?;
+
+        Ok(wallet)
+    }
+
+    fn open_database(
+        data_dir: &Path,
+        xprivkey: ExtendedPrivKey,
+        network: bitcoin::Network,
+    ) -> Result<bdk::Wallet<Tree>> {
+        let wallet_dir = data_dir.join(WALLET);
+        let database = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
 
         let wallet = match bdk::Wallet::new(
             bdk::template::Bip84(xprivkey, KeychainKind::External),
@@ -69,17 +253,25 @@ impl Wallet {
             err => err?,
         };
 
-        let client = Client::new(electrum_rpc_url, env_config.bitcoin_sync_interval())?;
+        Ok(wallet)
+    }
 
+    fn from_parts(
+        client: Client,
+        wallet: bdk::Wallet<Tree>,
+        env_config: env::Config,
+        target_block: usize,
+    ) -> Self {
         let network = wallet.network();
 
-        Ok(Self {
+        Self {
             client: Arc::new(Mutex::new(client)),
             wallet: Arc::new(Mutex::new(wallet)),
             finality_confirmations: env_config.bitcoin_finality_confirmations,
             network,
             target_block,
-        })
+            watch_only: false,
+        }
     }
 
     /// Create a new database for the wallet and rename the old one.
@@ -150,6 +342,56 @@ impl Wallet {
         self.client.lock().await.status_of_script(tx)
     }
 
+    /// Whether the output of the given transaction has been spent, i.e.
+    /// whether another transaction shows up in the history of its script.
+    pub async fn is_spent<T>(&self, tx: &T) -> Result<bool>
+    where
+        T: Watchable,
+    {
+        self.client.lock().await.is_spent(tx)
+    }
+
+    /// Use the given database to persist the status of deeply confirmed
+    /// transactions across restarts.
+    ///
+    /// Previously persisted transactions are loaded so their scripts don't have
+    /// to be queried from Electrum again.
+    pub async fn use_script_cache(&self, db: Arc<dyn Database + Send + Sync>) -> Result<()> {
+        let statuses = db
+            .get_script_statuses()
+            .await
+            .context("Failed to load cached Bitcoin script statuses")?;
+
+        let mut client = self.client.lock().await;
+        tracing::debug!(
+            count = statuses.len(),
+            "Loaded cached Bitcoin script statuses"
+        );
+        client.watch_set.settled.extend(
+            statuses
+                .into_iter()
+                .map(|(txid, script, inclusion_height)| ((txid, script), inclusion_height)),
+        );
+        client.watch_set.script_cache = Some(db);
+
+        Ok(())
+    }
+
+    /// Combine the fee-rate estimates of the Electrum server with the given
+    /// sources and bound them by `limits`.
+    pub async fn use_fee_rate_sources(
+        &self,
+        sources: Vec<Box<dyn FeeRateSource>>,
+        limits: FeeLimits,
+    ) {
+        self.client.lock().await.fee_rates =
+            FeeRateAggregator::new(sources, self.target_block, limits);
+    }
+
+    pub async fn latest_block_height(&self) -> BlockHeight {
+        self.client.lock().await.latest_block_height
+    }
+
     pub async fn subscribe_to(&self, tx: impl Watchable + Send + 'static) -> Subscription {
         let txid = tx.id();
         let script = tx.script();
@@ -158,6 +400,7 @@ impl Wallet {
             .client
             .lock()
             .await
+            .watch_set
             .subscriptions
             .entry((txid, script.clone()))
             .or_insert_with(|| {
@@ -166,6 +409,7 @@ impl Wallet {
 
                 tokio::spawn(async move {
                     let mut last_status = None;
+                    let mut persisted = false;
 
                     loop {
                         let new_status = match client.lock().await.status_of_script(&tx) {
@@ -180,11 +424,15 @@ impl Wallet {
                         {
                             last_status = Some(print_status_change(txid, last_status, new_status));
 
+                            if !persisted {
+                                persisted = persist_if_settled(&client, txid, &script, new_status).await;
+                            }
+
                             let all_receivers_gone = sender.send(new_status).is_err();
 
                             if all_receivers_gone {
                                 tracing::debug!(%txid, "All receivers gone, removing subscription");
-                                client.lock().await.subscriptions.remove(&(txid, script));
+                                client.lock().await.watch_set.unsubscribe(txid, &script);
                                 return;
                             }
                         }
@@ -217,6 +465,38 @@ impl Wallet {
     }
 }
 
+/// Stores the inclusion height of a transaction once it is deeply confirmed.
+///
+/// Returns whether the status is persisted, in which case it doesn't have to be
+/// stored again.
+async fn persist_if_settled(
+    client: &Mutex<Client>,
+    txid: Txid,
+    script: &Script,
+    status: ScriptStatus,
+) -> bool {
+    let (db, inclusion_height) = match client
+        .lock()
+        .await
+        .watch_set
+        .to_persist(txid, script, status)
+    {
+        Some(to_persist) => to_persist,
+        None => return false,
+    };
+
+    match db
+        .insert_script_status(txid, script.clone(), inclusion_height)
+        .await
+    {
+        Ok(()) => true,
+        Err(error) => {
+            tracing::warn!(%txid, "Failed to persist status of script: {:#}", error);
+            false
+        }
+    }
+}
+
 fn print_status_change(txid: Txid, old: Option<ScriptStatus>, new: ScriptStatus) -> ScriptStatus {
     match (old, new) {
         (None, new_status) => {
@@ -318,6 +598,23 @@ where
         Ok(tx)
     }
 
+    /// Finalizes a PSBT that was signed outside of this wallet.
+    pub async fn finalize_psbt(&self, mut psbt: PartiallySignedTransaction) -> Result<Transaction> {
+        let finalized = self
+            .wallet
+            .lock()
+            .await
+            .finalize_psbt(&mut psbt, SignOptions::default())?;
+
+        if !finalized {
+            bail!("PSBT is missing signatures")
+        }
+
+        let tx = psbt.extract_tx();
+
+        Ok(tx)
+    }
+
     /// Returns the total Bitcoin balance, which includes pending funds
     pub async fn balance(&self) -> Result<Amount> {
         let balance = self
@@ -469,8 +766,36 @@ where
         let client = self.client.lock().await;
         let fee_rate = client.estimate_feerate(self.target_block)?;
         let min_relay_fee = client.min_relay_fee()?;
+        let max_relative_fee = client.fee_limits().max_relative_fee;
+
+        estimate_fee(
+            weight,
+            transfer_amount,
+            fee_rate,
+            min_relay_fee,
+            max_relative_fee,
+        )
+    }
 
-        estimate_fee(weight, transfer_amount, fee_rate, min_relay_fee)
+    /// Like [`Wallet::estimate_fee`] but without bounding the fee relative to
+    /// the transferred amount. This is the fee a transaction needs at the
+    /// current fee levels to be mined within the target block.
+    pub async fn projected_fee(
+        &self,
+        weight: usize,
+        transfer_amount: bitcoin::Amount,
+    ) -> Result<bitcoin::Amount> {
+        let client = self.client.lock().await;
+        let fee_rate = client.estimate_feerate(self.target_block)?;
+        let min_relay_fee = client.min_relay_fee()?;
+
+        estimate_fee(
+            weight,
+            transfer_amount,
+            fee_rate,
+            min_relay_fee,
+            Decimal::ONE,
+        )
     }
 }
 
@@ -479,6 +804,7 @@ fn estimate_fee(
     transfer_amount: Amount,
     fee_rate: FeeRate,
     min_relay_fee: Amount,
+    max_relative_fee: Decimal,
 ) -> Result<Amount> {
     if transfer_amount.to_sat() <= 546 {
         bail!("Amounts needs to be greater than Bitcoin dust amount.")
@@ -512,7 +838,7 @@ fn estimate_fee(
     );
 
     let transfer_amount = Decimal::from(transfer_amount.to_sat());
-    let max_allowed_fee = transfer_amount * MAX_RELATIVE_TX_FEE;
+    let max_allowed_fee = transfer_amount * max_relative_fee;
     let min_relay_fee = Decimal::from(min_relay_fee.to_sat());
 
     let recommended_fee = if sats_per_vbyte < min_relay_fee {
@@ -555,6 +881,28 @@ where
         Ok(tx)
     }
 
+    /// The transactions that pay to or spend from the script of `tx`.
+    pub async fn script_history<T>(&self, tx: &T) -> Result<Vec<Txid>>
+    where
+        T: Watchable,
+    {
+        let history = self
+            .client
+            .lock()
+            .await
+            .electrum
+            .script_get_history(&tx.script())
+            .context("Failed to get script history")?;
+
+        Ok(history.into_iter().map(|entry| entry.tx_hash).collect())
+    }
+
+    /// The genesis block and the tip of the chain the Electrum server
+    /// follows.
+    pub async fn electrum_chain(&self) -> Result<ElectrumChain> {
+        self.client.lock().await.chain()
+    }
+
     pub async fn sync(&self) -> Result<()> {
         let client = self.client.lock().await;
         let blockchain = client.blockchain();
@@ -574,11 +922,56 @@ impl<D, C> Wallet<D, C> {
     pub fn get_network(&self) -> bitcoin::Network {
         self.network
     }
+
+    /// Whether the keys of this wallet are held externally.
+    pub fn is_watch_only(&self) -> bool {
+        self.watch_only
+    }
+
+    /// The number of blocks within which our transactions are expected to be
+    /// mined at the estimated fee rate.
+    pub fn target_block(&self) -> usize {
+        self.target_block
+    }
+}
+
+/// Returns the highest external derivation index of `addresses` that belongs
+/// to the given wallet, searching up to [`MAX_KNOWN_ADDRESS_INDEX`].
+fn highest_derivation_index<D>(
+    wallet: &bdk::Wallet<D>,
+    addresses: &[Address],
+) -> Result<Option<u32>>
+where
+    D: BatchDatabase,
+{
+    let mut remaining = addresses
+        .iter()
+        .map(|address| address.script_pubkey())
+        .collect::<HashSet<_>>();
+    let mut highest = None;
+
+    for index in 0..MAX_KNOWN_ADDRESS_INDEX {
+        if remaining.is_empty() {
+            break;
+        }
+
+        let address = wallet.get_address(AddressIndex::Peek(index))?;
+
+        if remaining.remove(&address.script_pubkey()) {
+            highest = Some(index);
+        }
+    }
+
+    Ok(highest)
 }
 
 pub trait EstimateFeeRate {
     fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate>;
     fn min_relay_fee(&self) -> Result<bitcoin::Amount>;
+
+    fn fee_limits(&self) -> FeeLimits {
+        FeeLimits::default()
+    }
 }
 
 #[cfg(test)]
@@ -686,6 +1079,7 @@ impl WalletBuilder {
             finality_confirmations: 1,
             network: Network::Regtest,
             target_block: 1,
+            watch_only: false,
         }
     }
 }
@@ -717,12 +1111,12 @@ pub struct Client {
     latest_block_height: BlockHeight,
     last_sync: Instant,
     sync_interval: Duration,
-    script_history: BTreeMap<Script, Vec<GetHistoryRes>>,
-    subscriptions: HashMap<(Txid, Script), Subscription>,
+    watch_set: WatchSet,
+    fee_rates: FeeRateAggregator,
 }
 
 impl Client {
-    fn new(electrum_rpc_url: Url, interval: Duration) -> Result<Self> {
+    fn new(electrum_rpc_url: Url, interval: Duration, stop_gap: usize) -> Result<Self> {
         let config = bdk::electrum_client::ConfigBuilder::default()
             .retry(5)
             .build();
@@ -734,9 +1128,15 @@ impl Client {
             .block_headers_subscribe()
             .context("Failed to subscribe to header notifications")?;
 
-        let client = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
-        let blockchain = ElectrumBlockchain::from(client);
+        let blockchain = ElectrumBlockchain::from_config(&ElectrumBlockchainConfig {
+            url: electrum_rpc_url.to_string(),
+            socks5: None,
+            retry: 0,
+            timeout: None,
+            stop_gap,
+            validate_domain: true,
+        })
+        .context("Failed to initialize Electrum RPC client")?;
         let last_sync = Instant::now()
             .checked_sub(interval)
             .expect("no underflow since block time is only 600 secs");
@@ -747,8 +1147,27 @@ impl Client {
             latest_block_height: BlockHeight::try_from(latest_block)?,
             last_sync,
             sync_interval: interval,
-            script_history: Default::default(),
-            subscriptions: Default::default(),
+            watch_set: WatchSet::default(),
+            fee_rates: FeeRateAggregator::electrum_only(FeeLimits::default()),
+        })
+    }
+
+    fn chain(&self) -> Result<ElectrumChain> {
+        let genesis = self
+            .electrum
+            .block_header(0)
+            .context("Failed to fetch genesis block header")?;
+        let tip = self
+            .electrum
+            .block_headers_subscribe()
+            .context("Failed to subscribe to header notifications")?;
+
+        let tip_time = tip.header.time;
+
+        Ok(ElectrumChain {
+            genesis_hash: genesis.block_hash(),
+            tip_height: u32::from(BlockHeight::try_from(tip)?),
+            tip_time,
         })
     }
 
@@ -781,9 +1200,7 @@ impl Client {
         let txid = tx.id();
         let script = tx.script();
 
-        if !self.script_history.contains_key(&script) {
-            self.script_history.insert(script.clone(), vec![]);
-
+        if !self.watch_set.is_settled(txid, &script) && self.watch_set.watch(script.clone()) {
             // When we first subscribe to a script we want to immediately fetch its status
             // Otherwise we would have to wait for the next sync interval, which can take a minute
             // This would result in potentially inaccurate status updates until that next sync interval is hit
@@ -792,32 +1209,21 @@ impl Client {
             self.update_state(false)?;
         }
 
-        let history = self.script_history.entry(script).or_default();
-
-        let history_of_tx = history
-            .iter()
-            .filter(|entry| entry.tx_hash == txid)
-            .collect::<Vec<_>>();
+        self.watch_set
+            .status_of_script(txid, script, self.latest_block_height)
+    }
 
-        match history_of_tx.as_slice() {
-            [] => Ok(ScriptStatus::Unseen),
-            [remaining @ .., last] => {
-                if !remaining.is_empty() {
-                    tracing::warn!("Found more than a single history entry for script. This is highly unexpected and those history entries will be ignored")
-                }
+    fn is_spent<T>(&self, tx: &T) -> Result<bool>
+    where
+        T: Watchable,
+    {
+        let txid = tx.id();
+        let history = self
+            .electrum
+            .script_get_history(&tx.script())
+            .context("Failed to get script history")?;
 
-                if last.height <= 0 {
-                    Ok(ScriptStatus::InMempool)
-                } else {
-                    Ok(ScriptStatus::Confirmed(
-                        Confirmed::from_inclusion_and_latest_block(
-                            u32::try_from(last.height)?,
-                            u32::from(self.latest_block_height),
-                        ),
-                    ))
-                }
-            }
-        }
+        Ok(history.iter().any(|entry| entry.tx_hash != txid))
     }
 
     fn update_latest_block(&mut self) -> Result<()> {
@@ -843,37 +1249,222 @@ impl Client {
         Ok(())
     }
 
+    /// Fetches the histories of the watched scripts that may have changed
+    /// since they were last synced.
     fn update_script_histories(&mut self) -> Result<()> {
+        let scripts = self.watch_set.scripts_to_sync(self.latest_block_height);
+
+        if scripts.is_empty() {
+            return Ok(());
+        }
+
         let histories = self
             .electrum
-            .batch_script_get_history(self.script_history.keys())
+            .batch_script_get_history(scripts.iter())
             .context("Failed to get script histories")?;
 
-        if histories.len() != self.script_history.len() {
+        if histories.len() != scripts.len() {
             bail!(
                 "Expected {} history entries, received {}",
-                self.script_history.len(),
+                scripts.len(),
                 histories.len()
             );
         }
 
-        let scripts = self.script_history.keys().cloned();
-        let histories = histories.into_iter();
-
-        self.script_history = scripts.zip(histories).collect::<BTreeMap<_, _>>();
+        self.watch_set
+            .update_histories(scripts.into_iter().zip(histories), self.latest_block_height);
 
         Ok(())
     }
 }
 
+/// The scripts watched on behalf of subscriptions together with their
+/// histories.
+///
+/// Transactions that are confirmed deeply enough are settled: their status is
+/// derived from their inclusion height and their script is no longer queried
+/// unless another subscription still needs it.
+#[derive(Default)]
+struct WatchSet {
+    script_history: BTreeMap<Script, Vec<GetHistoryRes>>,
+    /// Latest block height at which the history of a script was fetched.
+    synced_at: HashMap<Script, BlockHeight>,
+    subscriptions: HashMap<(Txid, Script), Subscription>,
+    /// Inclusion heights of transactions that are confirmed deeply enough to
+    /// no longer be watched.
+    settled: HashMap<(Txid, Script), u32>,
+    script_cache: Option<Arc<dyn Database + Send + Sync>>,
+}
+
+impl WatchSet {
+    /// Starts watching the given script, returns whether it was not watched
+    /// before.
+    fn watch(&mut self, script: Script) -> bool {
+        match self.script_history.entry(script) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(vec![]);
+                true
+            }
+        }
+    }
+
+    fn is_settled(&self, txid: Txid, script: &Script) -> bool {
+        self.settled.contains_key(&(txid, script.clone()))
+    }
+
+    /// Returns the watched scripts whose history has to be fetched again.
+    ///
+    /// Scripts without history or with unconfirmed entries may change at any
+    /// time. Scripts whose entries are all confirmed only change with a new
+    /// block, e.g. because of a reorg, so they are synced incrementally once
+    /// per block.
+    fn scripts_to_sync(&self, latest_block: BlockHeight) -> Vec<Script> {
+        self.script_history
+            .iter()
+            .filter(|(script, history)| {
+                let all_confirmed =
+                    !history.is_empty() && history.iter().all(|entry| entry.height > 0);
+                let synced_at_latest_block = self.synced_at.get(*script) == Some(&latest_block);
+
+                !(all_confirmed && synced_at_latest_block)
+            })
+            .map(|(script, _)| script.clone())
+            .collect()
+    }
+
+    fn update_histories(
+        &mut self,
+        histories: impl IntoIterator<Item = (Script, Vec<GetHistoryRes>)>,
+        latest_block: BlockHeight,
+    ) {
+        for (script, history) in histories {
+            if let Some(entry) = self.script_history.get_mut(&script) {
+                *entry = history;
+                self.synced_at.insert(script, latest_block);
+            }
+        }
+    }
+
+    /// Derives the status of a transaction from its settled inclusion height
+    /// or the last fetched history of its script, settling it once it is
+    /// confirmed deeply enough.
+    fn status_of_script(
+        &mut self,
+        txid: Txid,
+        script: Script,
+        latest_block: BlockHeight,
+    ) -> Result<ScriptStatus> {
+        if let Some(inclusion_height) = self.settled.get(&(txid, script.clone())).copied() {
+            return Ok(ScriptStatus::Confirmed(
+                Confirmed::from_inclusion_and_latest_block(
+                    inclusion_height,
+                    u32::from(latest_block),
+                ),
+            ));
+        }
+
+        let history = self.script_history.entry(script.clone()).or_default();
+
+        let history_of_tx = history
+            .iter()
+            .filter(|entry| entry.tx_hash == txid)
+            .collect::<Vec<_>>();
+
+        match history_of_tx.as_slice() {
+            [] => Ok(ScriptStatus::Unseen),
+            [remaining @ .., last] => {
+                if !remaining.is_empty() {
+                    tracing::warn!("Found more than a single history entry for script. This is highly unexpected and those history entries will be ignored")
+                }
+
+                if last.height <= 0 {
+                    Ok(ScriptStatus::InMempool)
+                } else {
+                    let inclusion_height = u32::try_from(last.height)?;
+                    let confirmed = Confirmed::from_inclusion_and_latest_block(
+                        inclusion_height,
+                        u32::from(latest_block),
+                    );
+
+                    if confirmed.confirmations() >= SETTLED_CONFIRMATIONS {
+                        self.settle(txid, script, inclusion_height);
+                    }
+
+                    Ok(ScriptStatus::Confirmed(confirmed))
+                }
+            }
+        }
+    }
+
+    /// Stops querying the script of a deeply confirmed transaction, its status
+    /// is derived from the inclusion height from now on.
+    fn settle(&mut self, txid: Txid, script: Script, inclusion_height: u32) {
+        self.settled
+            .insert((txid, script.clone()), inclusion_height);
+
+        if !self.is_watched(&script) {
+            self.forget(&script);
+        }
+    }
+
+    /// Removes the subscription and stops watching its script unless another
+    /// subscription still needs it.
+    fn unsubscribe(&mut self, txid: Txid, script: &Script) {
+        self.subscriptions.remove(&(txid, script.clone()));
+
+        if !self.is_watched(script) {
+            self.forget(script);
+        }
+    }
+
+    /// Whether a subscription for a transaction that is not yet settled needs
+    /// the history of the given script.
+    fn is_watched(&self, script: &Script) -> bool {
+        self.subscriptions.keys().any(|(txid, subscribed)| {
+            subscribed == script && !self.settled.contains_key(&(*txid, script.clone()))
+        })
+    }
+
+    fn forget(&mut self, script: &Script) {
+        self.script_history.remove(script);
+        self.synced_at.remove(script);
+    }
+
+    /// Returns the database and the inclusion height to persist for the given
+    /// transaction if it is settled and `status` confirms it deeply enough.
+    fn to_persist(
+        &self,
+        txid: Txid,
+        script: &Script,
+        status: ScriptStatus,
+    ) -> Option<(Arc<dyn Database + Send + Sync>, u32)> {
+        let db = self.script_cache.clone()?;
+        let inclusion_height = self.settled.get(&(txid, script.clone())).copied()?;
+
+        if !status.is_confirmed_with(SETTLED_CONFIRMATIONS) {
+            return None;
+        }
+
+        Some((db, inclusion_height))
+    }
+}
+
 impl EstimateFeeRate for Client {
     fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate> {
         // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L213
         // Returned estimated fees are per BTC/kb.
-        let fee_per_byte = self.electrum.estimate_fee(target_block)?;
-        // we do not expect fees being that high.
-        #[allow(clippy::cast_possible_truncation)]
-        Ok(FeeRate::from_btc_per_kvb(fee_per_byte as f32))
+        let electrum_fee_rate = self
+            .electrum
+            .estimate_fee(target_block)
+            .map_err(anyhow::Error::from)
+            // we do not expect fees being that high.
+            .map(|fee_per_byte| {
+                #[allow(clippy::cast_possible_truncation)]
+                FeeRate::from_btc_per_kvb(fee_per_byte as f32)
+            });
+
+        self.fee_rates.aggregate(electrum_fee_rate)
     }
 
     fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
@@ -882,6 +1473,19 @@ impl EstimateFeeRate for Client {
         let relay_fee = bitcoin::Amount::from_btc(self.electrum.relay_fee()?)?;
         Ok(relay_fee)
     }
+
+    fn fee_limits(&self) -> FeeLimits {
+        self.fee_rates.limits()
+    }
+}
+
+/// The chain an Electrum server follows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ElectrumChain {
+    pub genesis_hash: BlockHash,
+    pub tip_height: u32,
+    /// The timestamp of the tip in seconds since the unix epoch.
+    pub tip_time: u32,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -998,7 +1602,10 @@ impl fmt::Display for ScriptStatus {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bitcoin::fee::DEFAULT_MAX_RELATIVE_TX_FEE;
     use crate::bitcoin::{PublicKey, TxLock};
+    use crate::database::{AccessMode, SqliteDatabase};
+    use crate::env::{GetConfig, Regtest};
     use crate::tracing_ext::capture_logs;
     use bitcoin::hashes::Hash;
     use proptest::prelude::*;
@@ -1069,7 +1676,14 @@ mod tests {
         let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
         let relay_fee = bitcoin::Amount::ONE_SAT;
-        let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
+        let is_fee = estimate_fee(
+            weight,
+            amount,
+            fee_rate,
+            relay_fee,
+            DEFAULT_MAX_RELATIVE_TX_FEE,
+        )
+        .unwrap();
 
         // weight / 4.0 *  sat_per_vb
         let should_fee = bitcoin::Amount::from_sat(10_000);
@@ -1086,7 +1700,14 @@ mod tests {
         let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
         let relay_fee = bitcoin::Amount::from_sat(100_000);
-        let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
+        let is_fee = estimate_fee(
+            weight,
+            amount,
+            fee_rate,
+            relay_fee,
+            DEFAULT_MAX_RELATIVE_TX_FEE,
+        )
+        .unwrap();
 
         // weight / 4.0 *  sat_per_vb would be smaller than relay fee hence we take min
         // relay fee
@@ -1104,7 +1725,14 @@ mod tests {
         let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
         let relay_fee = bitcoin::Amount::ONE_SAT;
-        let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
+        let is_fee = estimate_fee(
+            weight,
+            amount,
+            fee_rate,
+            relay_fee,
+            DEFAULT_MAX_RELATIVE_TX_FEE,
+        )
+        .unwrap();
 
         // weight / 4.0 *  sat_per_vb would be greater than 3% hence we take max
         // relative fee.
@@ -1123,7 +1751,14 @@ mod tests {
         let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
         let relay_fee = bitcoin::Amount::ONE_SAT;
-        let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
+        let is_fee = estimate_fee(
+            weight,
+            amount,
+            fee_rate,
+            relay_fee,
+            DEFAULT_MAX_RELATIVE_TX_FEE,
+        )
+        .unwrap();
 
         // weight / 4.0 *  sat_per_vb would be greater than 3% hence we take total
         // max allowed fee.
@@ -1143,7 +1778,7 @@ mod tests {
             let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
             let relay_fee = bitcoin::Amount::from_sat(relay_fee);
-            let _is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
+            let _is_fee = estimate_fee(weight, amount, fee_rate, relay_fee, DEFAULT_MAX_RELATIVE_TX_FEE).unwrap();
 
         }
     }
@@ -1160,7 +1795,7 @@ mod tests {
             let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
             let relay_fee = bitcoin::Amount::ONE_SAT;
-            let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
+            let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee, DEFAULT_MAX_RELATIVE_TX_FEE).unwrap();
 
             // weight / 4 * 1_000 is always lower than MAX_ABSOLUTE_TX_FEE
             assert!(is_fee.to_sat() < MAX_ABSOLUTE_TX_FEE.to_u64().unwrap());
@@ -1179,7 +1814,7 @@ mod tests {
             let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
             let relay_fee = bitcoin::Amount::ONE_SAT;
-            let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
+            let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee, DEFAULT_MAX_RELATIVE_TX_FEE).unwrap();
 
             // weight / 4 * 1_000  is always higher than MAX_ABSOLUTE_TX_FEE
             assert!(is_fee.to_sat() >= MAX_ABSOLUTE_TX_FEE.to_u64().unwrap());
@@ -1197,7 +1832,7 @@ mod tests {
             let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
             let relay_fee = bitcoin::Amount::from_sat(1);
-            assert!(estimate_fee(weight, amount, fee_rate, relay_fee).is_err());
+            assert!(estimate_fee(weight, amount, fee_rate, relay_fee, DEFAULT_MAX_RELATIVE_TX_FEE).is_err());
 
         }
     }
@@ -1213,7 +1848,7 @@ mod tests {
             let fee_rate = FeeRate::from_sat_per_vb(1.0);
 
             let relay_fee = bitcoin::Amount::from_sat(relay_fee);
-            assert!(estimate_fee(weight, amount, fee_rate, relay_fee).is_err());
+            assert!(estimate_fee(weight, amount, fee_rate, relay_fee, DEFAULT_MAX_RELATIVE_TX_FEE).is_err());
         }
     }
 
@@ -1335,10 +1970,241 @@ DEBUG swap::bitcoin::wallet: Bitcoin transaction status changed txid=00000000000
         )
     }
 
+    #[tokio::test]
+    async fn finds_highest_derivation_index_of_own_addresses() {
+        let wallet = WalletBuilder::new(0).build();
+        let bdk_wallet = wallet.wallet.lock().await;
+
+        let own = bdk_wallet
+            .get_address(AddressIndex::Peek(42))
+            .unwrap()
+            .address;
+        let lower = bdk_wallet
+            .get_address(AddressIndex::Peek(3))
+            .unwrap()
+            .address;
+
+        let index = highest_derivation_index(&bdk_wallet, &[lower, own]).unwrap();
+        assert_eq!(index, Some(42));
+
+        let index = highest_derivation_index(&bdk_wallet, &[]).unwrap();
+        assert_eq!(index, None);
+    }
+
+    #[test]
+    fn settled_transaction_drops_out_of_the_watch_set() {
+        let mut watch_set = WatchSet::default();
+        let (txid, script) = (txid(1), script(1));
+        watch_set
+            .subscriptions
+            .insert((txid, script.clone()), subscription(txid));
+
+        assert!(watch_set.watch(script.clone()));
+        assert!(!watch_set.watch(script.clone()));
+        watch_set.update_histories(
+            vec![(script.clone(), vec![history_entry(txid, 100)])],
+            BlockHeight::new(104),
+        );
+
+        let status = watch_set
+            .status_of_script(txid, script.clone(), BlockHeight::new(104))
+            .unwrap();
+        assert_eq!(status, confs(5));
+        assert!(!watch_set.is_settled(txid, &script));
+        assert!(watch_set.script_history.contains_key(&script));
+
+        let status = watch_set
+            .status_of_script(txid, script.clone(), BlockHeight::new(105))
+            .unwrap();
+        assert_eq!(status, confs(6));
+        assert!(watch_set.is_settled(txid, &script));
+        assert!(!watch_set.script_history.contains_key(&script));
+        assert!(!watch_set.synced_at.contains_key(&script));
+
+        let status = watch_set
+            .status_of_script(txid, script.clone(), BlockHeight::new(110))
+            .unwrap();
+        assert_eq!(status, confs(11));
+        assert!(!watch_set.script_history.contains_key(&script));
+    }
+
+    #[test]
+    fn script_stays_watched_until_no_unsettled_subscription_needs_it() {
+        let mut watch_set = WatchSet::default();
+        let script = script(1);
+        let (settled, pending) = (txid(1), txid(2));
+        for txid in [settled, pending] {
+            watch_set
+                .subscriptions
+                .insert((txid, script.clone()), subscription(txid));
+        }
+        watch_set.watch(script.clone());
+
+        watch_set.settle(settled, script.clone(), 100);
+        assert!(watch_set.script_history.contains_key(&script));
+
+        watch_set.unsubscribe(settled, &script);
+        assert!(watch_set.script_history.contains_key(&script));
+
+        watch_set.unsubscribe(pending, &script);
+        assert!(!watch_set.script_history.contains_key(&script));
+        assert!(watch_set.subscriptions.is_empty());
+    }
+
+    #[test]
+    fn confirmed_scripts_are_only_synced_once_per_block() {
+        let mut watch_set = WatchSet::default();
+        let (unseen, in_mempool, confirmed) = (script(1), script(2), script(3));
+        for script in [&unseen, &in_mempool, &confirmed] {
+            watch_set.watch(script.clone());
+        }
+        assert_eq!(
+            watch_set.scripts_to_sync(BlockHeight::new(100)),
+            vec![unseen.clone(), in_mempool.clone(), confirmed.clone()]
+        );
+
+        watch_set.update_histories(
+            vec![
+                (unseen.clone(), vec![]),
+                (in_mempool.clone(), vec![history_entry(txid(2), 0)]),
+                (confirmed.clone(), vec![history_entry(txid(3), 99)]),
+            ],
+            BlockHeight::new(100),
+        );
+
+        assert_eq!(
+            watch_set.scripts_to_sync(BlockHeight::new(100)),
+            vec![unseen.clone(), in_mempool.clone()]
+        );
+        assert_eq!(
+            watch_set.scripts_to_sync(BlockHeight::new(101)),
+            vec![unseen, in_mempool, confirmed]
+        );
+    }
+
+    #[tokio::test]
+    async fn settled_status_is_persisted_once_confirmed_deeply_enough() {
+        let mut watch_set = WatchSet::default();
+        let (txid, other_txid, script) = (txid(1), txid(2), script(1));
+
+        watch_set.settle(txid, script.clone(), 100);
+        assert!(watch_set.to_persist(txid, &script, confs(6)).is_none());
+
+        let temp_db = tempfile::tempdir().unwrap().into_path().join("tempdb");
+        std::fs::File::create(&temp_db).unwrap();
+        let db = SqliteDatabase::open(temp_db, AccessMode::ReadWrite)
+            .await
+            .unwrap();
+        watch_set.script_cache = Some(Arc::new(db));
+
+        assert!(watch_set.to_persist(txid, &script, confs(5)).is_none());
+        assert!(watch_set
+            .to_persist(other_txid, &script, confs(6))
+            .is_none());
+
+        let (db, inclusion_height) = watch_set.to_persist(txid, &script, confs(6)).unwrap();
+        assert_eq!(inclusion_height, 100);
+
+        db.insert_script_status(txid, script.clone(), inclusion_height)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_script_statuses().await.unwrap(),
+            vec![(txid, script, 100)]
+        );
+    }
+
+    fn txid(byte: u8) -> Txid {
+        Txid::from_hash(bitcoin::hashes::sha256d::Hash::hash(&[byte]))
+    }
+
+    fn script(byte: u8) -> Script {
+        Script::from(vec![byte])
+    }
+
+    fn history_entry(tx_hash: Txid, height: i32) -> GetHistoryRes {
+        GetHistoryRes {
+            height,
+            tx_hash,
+            fee: None,
+        }
+    }
+
+    fn subscription(txid: Txid) -> Subscription {
+        let (_, receiver) = watch::channel(ScriptStatus::Unseen);
+
+        Subscription {
+            receiver,
+            finality_confirmations: 1,
+            txid,
+        }
+    }
+
     fn confs(confirmations: u32) -> ScriptStatus {
         ScriptStatus::from_confirmations(confirmations)
     }
 
+    fn rescan_key() -> ExtendedPrivKey {
+        ExtendedPrivKey::new_master(Network::Regtest, &[7u8; 32]).unwrap()
+    }
+
+    fn unreachable_electrum() -> Url {
+        Url::parse("tcp://127.0.0.1:1").unwrap()
+    }
+
+    #[tokio::test]
+    async fn rescan_refuses_to_run_while_database_is_open() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let _open = bdk::sled::open(data_dir.path().join(WALLET)).unwrap();
+
+        let result = Wallet::rescan(
+            unreachable_electrum(),
+            data_dir.path(),
+            rescan_key(),
+            Regtest::get_config(),
+            1,
+            20,
+            &[],
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(data_dir.path().join(WALLET).exists());
+        assert_eq!(std::fs::read_dir(data_dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_rescan_restores_the_previous_database() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let wallet =
+            Wallet::open_database(data_dir.path(), rescan_key(), Network::Regtest).unwrap();
+        let address = wallet.get_address(AddressIndex::New).unwrap().address;
+        drop(wallet);
+
+        let result = Wallet::rescan(
+            unreachable_electrum(),
+            data_dir.path(),
+            rescan_key(),
+            Regtest::get_config(),
+            1,
+            20,
+            &[],
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_dir(data_dir.path()).unwrap().count(), 1);
+        let wallet =
+            Wallet::open_database(data_dir.path(), rescan_key(), Network::Regtest).unwrap();
+        assert_eq!(
+            wallet
+                .get_address(AddressIndex::LastUnused)
+                .unwrap()
+                .address,
+            address
+        );
+    }
+
     proptest::proptest! {
         #[test]
         fn funding_never_fails_with_insufficient_funds(funding_amount in 3000u32.., num_utxos in 1..5u8, sats_per_vb in 1.0..500.0f32, key in crate::proptest::bitcoin::extended_priv_key(), alice in crate::proptest::ecdsa_fun::point(), bob in crate::proptest::ecdsa_fun::point()) {
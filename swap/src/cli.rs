@@ -2,6 +2,7 @@ mod behaviour;
 pub mod cancel_and_refund;
 pub mod command;
 mod event_loop;
+pub mod evidence;
 mod list_sellers;
 pub mod tracing;
 pub mod transport;
@@ -18,7 +19,7 @@ mod tests {
     use crate::asb::rendezvous::RendezvousNode;
     use crate::cli::list_sellers::{Seller, Status};
     use crate::network::quote;
-    use crate::network::quote::BidQuote;
+    use crate::network::quote::{BidQuote, SignedBidQuote};
     use crate::network::rendezvous::XmrBtcNamespace;
     use crate::network::test::{new_swarm, SwarmExt};
     use futures::StreamExt;
@@ -78,6 +79,7 @@ mod tests {
             price: bitcoin::Amount::from_sat(1337),
             min_quantity: bitcoin::Amount::from_sat(42),
             max_quantity: bitcoin::Amount::from_sat(9001),
+            timelock_bounds: None,
         };
 
         let mut asb = new_swarm(|_, identity| {
@@ -149,7 +151,7 @@ mod tests {
             } = event
             {
                 self.quote
-                    .send_response(channel, self.static_quote)
+                    .send_response(channel, SignedBidQuote::unsigned(self.static_quote))
                     .unwrap();
             }
         }
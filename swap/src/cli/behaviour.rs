@@ -1,10 +1,12 @@
 use crate::monero::Scalar;
+use crate::network::cooperative_early_refund::CooperativeEarlyRefundRejectReason;
 use crate::network::cooperative_xmr_redeem_after_punish::CooperativeXmrRedeemRejectReason;
-use crate::network::quote::BidQuote;
+use crate::network::quote::SignedBidQuote;
 use crate::network::rendezvous::XmrBtcNamespace;
 use crate::network::swap_setup::bob;
 use crate::network::{
-    cooperative_xmr_redeem_after_punish, encrypted_signature, quote, redial, transfer_proof,
+    compatibility, cooperative_early_refund, cooperative_xmr_redeem_after_punish,
+    encrypted_signature, quote, redial, transfer_proof,
 };
 use crate::protocol::bob::State2;
 use crate::{bitcoin, env};
@@ -21,7 +23,7 @@ use std::time::Duration;
 pub enum OutEvent {
     QuoteReceived {
         id: RequestId,
-        response: BidQuote,
+        response: SignedBidQuote,
     },
     SwapSetupCompleted(Box<Result<State2>>),
     TransferProofReceived {
@@ -42,9 +44,25 @@ pub enum OutEvent {
         reason: CooperativeXmrRedeemRejectReason,
         swap_id: uuid::Uuid,
     },
+    CooperativeEarlyRefundFulfilled {
+        id: RequestId,
+        tx_early_refund_sig: bitcoin::Signature,
+        swap_id: uuid::Uuid,
+    },
+    CooperativeEarlyRefundRejected {
+        id: RequestId,
+        reason: CooperativeEarlyRefundRejectReason,
+        swap_id: uuid::Uuid,
+    },
     AllRedialAttemptsExhausted {
         peer: PeerId,
     },
+    /// The peer told us which protocols it supports.
+    Identified {
+        peer: PeerId,
+        agent_version: String,
+        protocols: Vec<String>,
+    },
     Failure {
         peer: PeerId,
         error: Error,
@@ -79,6 +97,7 @@ pub struct Behaviour {
     pub swap_setup: bob::Behaviour,
     pub transfer_proof: transfer_proof::Behaviour,
     pub cooperative_xmr_redeem: cooperative_xmr_redeem_after_punish::Behaviour,
+    pub cooperative_early_refund: cooperative_early_refund::Behaviour,
     pub encrypted_signature: encrypted_signature::Behaviour,
     pub redial: redial::Behaviour,
     pub identify: Identify,
@@ -97,7 +116,7 @@ impl Behaviour {
         identify_params: (identity::Keypair, XmrBtcNamespace),
     ) -> Self {
         let agentVersion = format!("cli/{} ({})", env!("CARGO_PKG_VERSION"), identify_params.1);
-        let protocolVersion = "/comit/xmr/btc/1.0.0".to_string();
+        let protocolVersion = compatibility::PROTOCOL_VERSION.to_string();
         let identifyConfig = IdentifyConfig::new(protocolVersion, identify_params.0.public())
             .with_agent_version(agentVersion);
 
@@ -107,6 +126,7 @@ impl Behaviour {
             transfer_proof: transfer_proof::bob(),
             encrypted_signature: encrypted_signature::bob(),
             cooperative_xmr_redeem: cooperative_xmr_redeem_after_punish::bob(),
+            cooperative_early_refund: cooperative_early_refund::bob(),
             redial: redial::Behaviour::new(alice, Duration::from_secs(2)),
             ping: Ping::new(PingConfig::new().with_keep_alive(true)),
             identify: Identify::new(identifyConfig),
@@ -128,7 +148,14 @@ impl From<PingEvent> for OutEvent {
 }
 
 impl From<IdentifyEvent> for OutEvent {
-    fn from(_: IdentifyEvent) -> Self {
-        OutEvent::Other
+    fn from(event: IdentifyEvent) -> Self {
+        match event {
+            IdentifyEvent::Received { peer_id, info } => OutEvent::Identified {
+                peer: peer_id,
+                agent_version: info.agent_version,
+                protocols: info.protocols,
+            },
+            _ => OutEvent::Other,
+        }
     }
 }
@@ -1,10 +1,12 @@
 use crate::api::request::{Method, Request};
 use crate::api::Context;
-use crate::bitcoin::{bitcoin_address, Amount};
+use crate::bitcoin::{bitcoin_address, Amount, CancelTimelock, PunishTimelock};
 use crate::monero;
 use crate::monero::monero_address;
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use libp2p::core::Multiaddr;
+use monero_rpc::wallet::Credentials;
+use rust_decimal::Decimal;
 use std::ffi::OsString;
 use std::net::SocketAddr;
 use std::path::PathBuf;
@@ -14,12 +16,8 @@ use structopt::{clap, StructOpt};
 use url::Url;
 use uuid::Uuid;
 
-// See: https://moneroworld.com/
-pub const DEFAULT_MONERO_DAEMON_ADDRESS: &str = "node.community.rino.io:18081";
-pub const DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET: &str = "stagenet.community.rino.io:38081";
-
 // See: https://1209k.com/bitcoin-eye/ele.php?chain=btc
-const DEFAULT_ELECTRUM_RPC_URL: &str = "ssl://blockstream.info:700";
+pub const DEFAULT_ELECTRUM_RPC_URL: &str = "ssl://blockstream.info:700";
 // See: https://1209k.com/bitcoin-eye/ele.php?chain=tbtc
 pub const DEFAULT_ELECTRUM_RPC_URL_TESTNET: &str = "ssl://electrum.blockstream.info:60002";
 
@@ -67,6 +65,9 @@ where
             bitcoin_change_address,
             monero,
             monero_receive_address,
+            bitcoin_cancel_timelock,
+            bitcoin_punish_timelock,
+            skip_preflight,
             tor,
         } => {
             let context = Context::build(
@@ -92,6 +93,9 @@ where
                 bitcoin_change_address,
                 monero_receive_address,
                 swap_id: Uuid::new_v4(),
+                bitcoin_cancel_timelock: bitcoin_cancel_timelock.map(CancelTimelock::new),
+                bitcoin_punish_timelock: bitcoin_punish_timelock.map(PunishTimelock::new),
+                skip_preflight,
             });
 
             (context, request)
@@ -174,9 +178,13 @@ where
             swap_id: SwapId { swap_id },
             bitcoin,
             monero,
+            skip_preflight,
             tor,
         } => {
-            let request = Request::new(Method::Resume { swap_id });
+            let request = Request::new(Method::Resume {
+                swap_id,
+                skip_preflight,
+            });
 
             let context = Context::build(
                 Some(bitcoin),
@@ -238,6 +246,91 @@ where
             .await?;
             (context, request)
         }
+        CliCommand::Rescan {
+            bitcoin,
+            gap_limit,
+            min_lock_height,
+        } => {
+            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+            let request = Request::new(Method::Rescan {
+                bitcoin_electrum_rpc_url,
+                bitcoin_target_block,
+                gap_limit,
+                min_lock_height,
+            });
+
+            let context =
+                Context::build(None, None, None, data, is_testnet, debug, json, None).await?;
+
+            (context, request)
+        }
+        CliCommand::ExportPsbt {
+            swap_id: SwapId { swap_id },
+        } => {
+            let request = Request::new(Method::ExportPsbt { swap_id });
+
+            let context =
+                Context::build(None, None, None, data, is_testnet, debug, json, None).await?;
+
+            (context, request)
+        }
+        CliCommand::ImportPsbt {
+            swap_id: SwapId { swap_id },
+            psbt,
+        } => {
+            let request = Request::new(Method::ImportPsbt { swap_id, psbt });
+
+            let context =
+                Context::build(None, None, None, data, is_testnet, debug, json, None).await?;
+
+            (context, request)
+        }
+        CliCommand::ExportWatchtowerBundle {
+            swap_id: SwapId { swap_id },
+            output,
+        } => {
+            let request = Request::new(Method::ExportWatchtowerBundle { swap_id, output });
+
+            let context =
+                Context::build(None, None, None, data, is_testnet, debug, json, None).await?;
+
+            (context, request)
+        }
+        CliCommand::ExportEvidence {
+            swap_id: SwapId { swap_id },
+            bitcoin,
+            output,
+            include_private_keys,
+        } => {
+            let request = Request::new(Method::ExportEvidence {
+                swap_id,
+                output,
+                include_private_keys,
+            });
+
+            let context = Context::build(
+                Some(bitcoin),
+                None,
+                None,
+                data,
+                is_testnet,
+                debug,
+                json,
+                None,
+            )
+            .await?;
+
+            (context, request)
+        }
+        CliCommand::VerifyEvidence { evidence } => {
+            let request = Request::new(Method::VerifyEvidence { path: evidence });
+
+            let context =
+                Context::build(None, None, None, data, is_testnet, debug, json, None).await?;
+
+            (context, request)
+        }
         CliCommand::MoneroRecovery {
             swap_id: SwapId { swap_id },
         } => {
@@ -316,6 +409,24 @@ enum CliCommand {
         )]
         monero_receive_address: monero::Address,
 
+        #[structopt(
+            long = "bitcoin-cancel-timelock",
+            help = "The number of blocks after which the swap can be cancelled. Must be within the bounds accepted by the seller. If omitted the default of the network is used."
+        )]
+        bitcoin_cancel_timelock: Option<u32>,
+
+        #[structopt(
+            long = "bitcoin-punish-timelock",
+            help = "The number of blocks after cancelling after which the seller can punish. Must be within the bounds accepted by the seller. If omitted the default of the network is used."
+        )]
+        bitcoin_punish_timelock: Option<u32>,
+
+        #[structopt(
+            long = "skip-preflight",
+            help = "Lock the Bitcoin even if the pre-flight checks fail. They check that the Bitcoin and Monero nodes are synced, that the cancel timelock leaves enough time, that the seller locked the Monero in previous swaps and that the cancel and refund fees suffice."
+        )]
+        skip_preflight: bool,
+
         #[structopt(flatten)]
         tor: Tor,
     },
@@ -373,6 +484,12 @@ enum CliCommand {
         #[structopt(flatten)]
         monero: Monero,
 
+        #[structopt(
+            long = "skip-preflight",
+            help = "Lock the Bitcoin even if the pre-flight checks fail. They check that the Bitcoin and Monero nodes are synced, that the cancel timelock leaves enough time, that the seller locked the Monero in previous swaps and that the cancel and refund fees suffice."
+        )]
+        skip_preflight: bool,
+
         #[structopt(flatten)]
         tor: Tor,
     },
@@ -404,6 +521,81 @@ enum CliCommand {
         #[structopt(flatten)]
         bitcoin: Bitcoin,
     },
+    /// Recreate the internal bitcoin wallet from the seed and rescan the
+    /// blockchain. Recovers the Bitcoin of swaps whose lock output still holds
+    /// funds once their timelocks allow it.
+    Rescan {
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(
+            long = "gap-limit",
+            help = "Number of consecutive unused addresses after which the rescan stops looking for transactions",
+            default_value = "100"
+        )]
+        gap_limit: u32,
+
+        #[structopt(
+            long = "min-lock-height",
+            help = "Optionally ignore swaps whose Bitcoin lock transaction was confirmed before this block height. The Electrum backend always returns the full history of the wallet, so this does not shorten the wallet sync"
+        )]
+        min_lock_height: Option<u32>,
+    },
+    /// Print the unsigned PSBT of a swap that waits for an external signature
+    ExportPsbt {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+    },
+    /// Import a PSBT signed by an external wallet to continue a swap
+    ImportPsbt {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(long = "psbt", help = "The signed PSBT, base64 encoded")]
+        psbt: String,
+    },
+    /// Export the signed cancel and refund transactions of a swap for
+    /// `swap-watchtower`, which publishes them if the CLI stays offline
+    ExportWatchtowerBundle {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(
+            long = "output",
+            help = "The file the bundle is written to",
+            parse(from_os_str)
+        )]
+        output: PathBuf,
+    },
+    /// Export a signed archive with the states, Bitcoin transactions,
+    /// transfer proof, seller details and log of a swap, e.g. to hand to a
+    /// mediator in a dispute
+    ExportEvidence {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(
+            long = "output",
+            help = "The file the archive is written to",
+            parse(from_os_str)
+        )]
+        output: PathBuf,
+
+        #[structopt(
+            long = "include-private-keys",
+            help = "Keep the private keys of the swap in the exported states instead of redacting them"
+        )]
+        include_private_keys: bool,
+    },
+    /// Check the signature of an archive written by `export-evidence` and
+    /// print who signed it
+    VerifyEvidence {
+        #[structopt(long = "evidence", help = "The archive to verify", parse(from_os_str))]
+        evidence: PathBuf,
+    },
     /// Prints Monero information related to the swap in case the generated
     /// wallet fails to detect the funds. This can only be used for swaps
     /// that are in a `btc is redeemed` state.
@@ -417,20 +609,109 @@ enum CliCommand {
 pub struct Monero {
     #[structopt(
         long = "monero-daemon-address",
-        help = "Specify to connect to a monero daemon of your choice: <host>:<port>"
+        number_of_values = 1,
+        help = "Specify to connect to a monero daemon of your choice: <host>:<port>. Can be given multiple times, the first healthy daemon is used and the others serve as fallback"
+    )]
+    pub monero_daemon_address: Vec<String>,
+
+    #[structopt(
+        long = "monero-wallet-rpc-path",
+        help = "Use a locally installed monero-wallet-rpc instead of downloading it"
+    )]
+    pub monero_wallet_rpc_path: Option<PathBuf>,
+
+    #[structopt(
+        long = "monero-wallet-rpc-offline",
+        help = "Never download monero-wallet-rpc. Fails if it was not downloaded before and no --monero-wallet-rpc-path is given"
+    )]
+    pub monero_wallet_rpc_offline: bool,
+
+    #[structopt(
+        long = "monero-wallet-rpc-version",
+        help = "Download this monero-wallet-rpc release instead of the default one, e.g. v0.18.3.3. Its hash is taken from the signed hashes of Monero releases"
+    )]
+    pub monero_wallet_rpc_version: Option<String>,
+
+    #[structopt(
+        long = "monero-wallet-rpc-download-url",
+        help = "Download monero-wallet-rpc releases from this URL instead of downloads.getmonero.org"
     )]
-    pub monero_daemon_address: Option<String>,
+    pub monero_wallet_rpc_download_url: Option<Url>,
+
+    #[structopt(
+        long = "monero-wallet-rpc-url",
+        help = "Use the monero-wallet-rpc at this URL instead of starting one locally, e.g. http://wallet-rpc.example.com:18083/json_rpc"
+    )]
+    pub monero_wallet_rpc_url: Option<Url>,
+
+    #[structopt(
+        long = "monero-wallet-rpc-login",
+        help = "Credentials of the remote monero-wallet-rpc in the form <username>:<password>, as given to its --rpc-login",
+        requires = "monero-wallet-rpc-url"
+    )]
+    pub monero_wallet_rpc_login: Option<String>,
+
+    #[structopt(
+        long = "monero-wallet-rpc-wallet-prefix",
+        help = "Prefix of the file names of all wallets opened on the remote monero-wallet-rpc",
+        default_value = "xmr-btc-swap"
+    )]
+    pub monero_wallet_rpc_wallet_prefix: String,
 }
 
 impl Monero {
-    pub fn apply_defaults(self, testnet: bool) -> String {
-        if let Some(address) = self.monero_daemon_address {
-            address
-        } else if testnet {
-            DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string()
+    pub fn wallet_rpc_options(&self) -> monero::WalletRpcOptions {
+        monero::WalletRpcOptions {
+            binary: self.monero_wallet_rpc_path.clone(),
+            offline: self.monero_wallet_rpc_offline,
+            version: self.monero_wallet_rpc_version.clone(),
+            download_url: self.monero_wallet_rpc_download_url.clone(),
+        }
+    }
+
+    pub fn remote_wallet_rpc(&self) -> Result<Option<monero::RemoteWalletRpc>> {
+        let url = match &self.monero_wallet_rpc_url {
+            Some(url) => url.clone(),
+            None => return Ok(None),
+        };
+
+        let credentials = self
+            .monero_wallet_rpc_login
+            .as_deref()
+            .map(|login| {
+                let (username, password) = login
+                    .split_once(':')
+                    .context("monero-wallet-rpc login must be of the form <username>:<password>")?;
+
+                Ok::<_, anyhow::Error>(Credentials {
+                    username: username.to_owned(),
+                    password: password.to_owned(),
+                })
+            })
+            .transpose()?;
+
+        Ok(Some(monero::RemoteWalletRpc {
+            url,
+            credentials,
+            wallet_prefix: self.monero_wallet_rpc_wallet_prefix.clone(),
+        }))
+    }
+
+    pub fn apply_defaults(self, testnet: bool) -> Result<Vec<monero::MoneroDaemon>> {
+        let network = if testnet {
+            monero::Network::Stagenet
         } else {
-            DEFAULT_MONERO_DAEMON_ADDRESS.to_string()
+            monero::Network::Mainnet
+        };
+
+        if self.monero_daemon_address.is_empty() {
+            return Ok(monero::MoneroDaemon::defaults(network));
         }
+
+        self.monero_daemon_address
+            .iter()
+            .map(|address| monero::MoneroDaemon::from_address(address, network))
+            .collect()
     }
 }
 
@@ -444,6 +725,36 @@ pub struct Bitcoin {
         help = "Estimate Bitcoin fees such that transactions are confirmed within the specified number of blocks"
     )]
     pub bitcoin_target_block: Option<usize>,
+
+    #[structopt(
+        long = "bitcoin-watch-only-descriptor",
+        help = "Use a watch-only wallet for the given output descriptor instead of the internal wallet. Transactions spending from it are exported as PSBTs to be signed externally"
+    )]
+    pub bitcoin_watch_only_descriptor: Option<String>,
+
+    #[structopt(
+        long = "bitcoind-rpc",
+        help = "Additionally estimate Bitcoin fees using the given bitcoind RPC URL, credentials can be part of the URL"
+    )]
+    pub bitcoind_rpc_url: Option<Url>,
+
+    #[structopt(
+        long = "mempool-space-url",
+        help = "Additionally estimate Bitcoin fees using the given mempool.space compatible API"
+    )]
+    pub mempool_space_url: Option<Url>,
+
+    #[structopt(
+        long = "max-fee-rate",
+        help = "The maximum Bitcoin fee rate in sat/vB we are willing to pay"
+    )]
+    pub max_fee_rate: Option<u64>,
+
+    #[structopt(
+        long = "max-fee-percentage",
+        help = "The maximum Bitcoin fee in percent of the transferred amount we are willing to pay"
+    )]
+    pub max_fee_percentage: Option<Decimal>,
 }
 
 impl Bitcoin {
@@ -1235,5 +1546,62 @@ mod tests {
         ];
         let result = parse_args_and_apply_defaults(raw_ars).await.unwrap();
         assert!(matches!(result, ParseResult::Context(_, _)));
+
+        // given_rescan_on_testnet_with_gap_limit_then_gap_limit_set
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "rescan",
+            "--gap-limit",
+            "500",
+            "--min-lock-height",
+            "2500000",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).await.unwrap();
+        let (is_testnet, debug, json) = (true, false, false);
+
+        let (expected_config, expected_request) = (
+            Config::default(is_testnet, None, debug, json),
+            Request::new(Method::Rescan {
+                bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET).unwrap(),
+                bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                gap_limit: 500,
+                min_lock_height: Some(2_500_000),
+            }),
+        );
+
+        let (actual_config, actual_request) = match args {
+            ParseResult::Context(context, request) => (context.config.clone(), request),
+            _ => panic!("Couldn't parse result"),
+        };
+
+        assert_eq!(actual_config, expected_config);
+        assert_eq!(actual_request, Box::new(expected_request));
+
+        // given_import_psbt_on_testnet_then_psbt_passed_through
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "import-psbt",
+            "--swap-id",
+            SWAP_ID,
+            "--psbt",
+            "cHNidP8BAAoCAAAAAAAAAAAAAAA=",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).await.unwrap();
+
+        let expected_request = Request::new(Method::ImportPsbt {
+            swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+            psbt: "cHNidP8BAAoCAAAAAAAAAAAAAAA=".to_string(),
+        });
+
+        let actual_request = match args {
+            ParseResult::Context(_, request) => request,
+            _ => panic!("Couldn't parse result"),
+        };
+
+        assert_eq!(actual_request, Box::new(expected_request));
     }
 }
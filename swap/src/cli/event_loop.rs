@@ -1,14 +1,16 @@
 use crate::bitcoin::EncryptedSignature;
 use crate::cli::behaviour::{Behaviour, OutEvent};
 use crate::monero;
+use crate::network::compatibility::{self, Compatibility};
 use crate::network::cooperative_xmr_redeem_after_punish::{Request, Response};
-use crate::network::encrypted_signature;
-use crate::network::quote::BidQuote;
+use crate::network::quote::SignedBidQuote;
 use crate::network::swap_setup::bob::NewSwap;
+use crate::network::{cooperative_early_refund, encrypted_signature};
 use crate::protocol::bob::State2;
 use crate::protocol::Database;
-use anyhow::{Context, Result};
-use futures::future::{BoxFuture, OptionFuture};
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
 use futures::{FutureExt, StreamExt};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::dial_opts::DialOpts;
@@ -17,38 +19,57 @@ use libp2p::{PeerId, Swarm};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::{mpsc, watch};
 use uuid::Uuid;
 
+/// Relays the transfer proofs of a swap to its [`EventLoopHandle`].
+type TransferProofSender = bmrng::RequestSender<monero::TransferProof, ()>;
+
+/// Runs the network side of one or more swaps with the same seller over a
+/// single connection.
+///
+/// Messages that belong to a particular swap are routed by their swap id.
+/// Every swap has its own [`EventLoopHandle`], handles for additional swaps
+/// are created with [`EventLoopHandle::new_swap`].
 #[allow(missing_debug_implementations)]
 pub struct EventLoop {
-    swap_id: Uuid,
     swarm: libp2p::Swarm<Behaviour>,
     alice_peer_id: PeerId,
     db: Arc<dyn Database + Send + Sync>,
+    /// Whether Alice supports the protocols we need, known once she
+    /// identified herself. Shared with all handles.
+    alice_compatibility: watch::Sender<Option<Compatibility>>,
 
     // these streams represents outgoing requests that we have to make
-    quote_requests: bmrng::RequestReceiverStream<(), BidQuote>,
+    quote_requests: bmrng::RequestReceiverStream<(), SignedBidQuote>,
     cooperative_xmr_redeem_requests: bmrng::RequestReceiverStream<Uuid, Response>,
-    encrypted_signatures: bmrng::RequestReceiverStream<EncryptedSignature, ()>,
+    cooperative_early_refund_requests:
+        bmrng::RequestReceiverStream<Uuid, cooperative_early_refund::Response>,
+    encrypted_signatures: bmrng::RequestReceiverStream<(Uuid, EncryptedSignature), ()>,
     swap_setup_requests: bmrng::RequestReceiverStream<NewSwap, Result<State2>>,
 
+    /// Receives the swaps that are added to this event loop.
+    new_swaps: mpsc::Receiver<(Uuid, TransferProofSender)>,
+
     // these represents requests that are currently in-flight.
     // once we get a response to a matching [`RequestId`], we will use the responder to relay the
     // response.
-    inflight_quote_requests: HashMap<RequestId, bmrng::Responder<BidQuote>>,
+    inflight_quote_requests: HashMap<RequestId, bmrng::Responder<SignedBidQuote>>,
     inflight_encrypted_signature_requests: HashMap<RequestId, bmrng::Responder<()>>,
     inflight_swap_setup: Option<bmrng::Responder<Result<State2>>>,
     inflight_cooperative_xmr_redeem_requests: HashMap<RequestId, bmrng::Responder<Response>>,
-    /// The sender we will use to relay incoming transfer proofs.
-    transfer_proof: bmrng::RequestSender<monero::TransferProof, ()>,
-    /// The future representing the successful handling of an incoming transfer
-    /// proof.
+    inflight_cooperative_early_refund_requests:
+        HashMap<RequestId, bmrng::Responder<cooperative_early_refund::Response>>,
+    /// The senders we use to relay incoming transfer proofs, per swap.
+    transfer_proofs: HashMap<Uuid, TransferProofSender>,
+    /// The futures representing the successful handling of incoming transfer
+    /// proofs.
     ///
-    /// Once we've sent a transfer proof to the ongoing swap, this future waits
-    /// until the swap took it "out" of the `EventLoopHandle`. As this future
+    /// Once we've sent a transfer proof to its swap, the future waits until
+    /// the swap took it "out" of the `EventLoopHandle`. As the future
     /// resolves, we use the `ResponseChannel` returned from it to send an ACK
     /// to Alice that we have successfully processed the transfer proof.
-    pending_transfer_proof: OptionFuture<BoxFuture<'static, ResponseChannel<()>>>,
+    pending_transfer_proofs: FuturesUnordered<BoxFuture<'static, ResponseChannel<()>>>,
 }
 
 impl EventLoop {
@@ -63,29 +84,39 @@ impl EventLoop {
         let encrypted_signature = bmrng::channel(1);
         let quote = bmrng::channel_with_timeout(1, Duration::from_secs(60));
         let cooperative_xmr_redeem = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let cooperative_early_refund = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let new_swaps = mpsc::channel(100);
+        let alice_compatibility = watch::channel(None);
         let event_loop = EventLoop {
-            swap_id,
             swarm,
             alice_peer_id,
             swap_setup_requests: execution_setup.1.into(),
-            transfer_proof: transfer_proof.0,
+            new_swaps: new_swaps.1,
+            transfer_proofs: HashMap::from([(swap_id, transfer_proof.0)]),
             encrypted_signatures: encrypted_signature.1.into(),
             cooperative_xmr_redeem_requests: cooperative_xmr_redeem.1.into(),
+            cooperative_early_refund_requests: cooperative_early_refund.1.into(),
             quote_requests: quote.1.into(),
             inflight_quote_requests: HashMap::default(),
             inflight_swap_setup: None,
             inflight_encrypted_signature_requests: HashMap::default(),
             inflight_cooperative_xmr_redeem_requests: HashMap::default(),
-            pending_transfer_proof: OptionFuture::from(None),
+            inflight_cooperative_early_refund_requests: HashMap::default(),
+            pending_transfer_proofs: FuturesUnordered::new(),
             db,
+            alice_compatibility: alice_compatibility.0,
         };
 
         let handle = EventLoopHandle {
+            swap_id,
+            new_swaps: new_swaps.0,
             swap_setup: execution_setup.0,
             transfer_proof: transfer_proof.1,
             encrypted_signature: encrypted_signature.0,
             cooperative_xmr_redeem: cooperative_xmr_redeem.0,
+            cooperative_early_refund: cooperative_early_refund.0,
             quote: quote.0,
+            alice_compatibility: alice_compatibility.1,
         };
 
         Ok((event_loop, handle))
@@ -105,7 +136,15 @@ impl EventLoop {
             tokio::select! {
                 swarm_event = self.swarm.select_next_some() => {
                     match swarm_event {
-                        SwarmEvent::Behaviour(OutEvent::QuoteReceived { id, response }) => {
+                        SwarmEvent::Behaviour(OutEvent::QuoteReceived { id, mut response }) => {
+                            if response.signature.is_some() {
+                                let local_peer_id = *self.swarm.local_peer_id();
+                                if let Err(error) = response.verify(self.alice_peer_id, local_peer_id) {
+                                    tracing::warn!("Ignoring invalid quote signature: {:#}", error);
+                                    response.signature = None;
+                                }
+                            }
+
                             if let Some(responder) = self.inflight_quote_requests.remove(&id) {
                                 let _ = responder.respond(response);
                             }
@@ -118,7 +157,7 @@ impl EventLoop {
                         SwarmEvent::Behaviour(OutEvent::TransferProofReceived { msg, channel, peer }) => {
                             let swap_id = msg.swap_id;
 
-                            if swap_id == self.swap_id {
+                            if let Some(sender) = self.transfer_proofs.get(&swap_id) {
                                 if peer != self.alice_peer_id {
                                     tracing::warn!(
                                                 %swap_id,
@@ -128,19 +167,20 @@ impl EventLoop {
                                             continue;
                                 }
 
-                                let mut responder = match self.transfer_proof.send(msg.tx_lock_proof).await {
+                                let mut responder = match sender.send(msg.tx_lock_proof).await {
                                     Ok(responder) => responder,
                                     Err(e) => {
-                                        tracing::warn!("Failed to pass on transfer proof: {:#}", e);
+                                        tracing::warn!(%swap_id, "Failed to pass on transfer proof, the swap is no longer running: {:#}", e);
+                                        self.transfer_proofs.remove(&swap_id);
                                         continue;
                                     }
                                 };
 
-                                self.pending_transfer_proof = OptionFuture::from(Some(async move {
+                                self.pending_transfer_proofs.push(async move {
                                     let _ = responder.recv().await;
 
                                     channel
-                                }.boxed()));
+                                }.boxed());
                             }else {
                                 // Check if the transfer proof is sent from the correct peer and if we have a record of the swap
                                 match self.db.get_peer_id(swap_id).await {
@@ -150,7 +190,7 @@ impl EventLoop {
                                             // Save transfer proof in the database such that we can process it later when we resume the swap
                                             match self.db.insert_buffered_transfer_proof(swap_id, msg.tx_lock_proof).await {
                                                 Ok(_) => {
-                                                    tracing::info!("Received transfer proof for swap {} while running swap {}. Buffering this transfer proof in the database for later retrieval", swap_id, self.swap_id);
+                                                    tracing::info!("Received transfer proof for swap {} which is not running. Buffering this transfer proof in the database for later retrieval", swap_id);
                                                     let _ = self.swarm.behaviour_mut().transfer_proof.send_response(channel, ());
                                                 }
                                                 Err(e) => {
@@ -161,16 +201,16 @@ impl EventLoop {
                                             tracing::warn!(
                                                 %swap_id,
                                                 "Ignoring malicious transfer proof from {}, expected to receive it from {}",
-                                                self.swap_id,
+                                                peer,
                                                 buffer_swap_alice_peer_id);
                                         }
                                     },
                                     // We do not have a record of the swap or an error occurred while retrieving the peer id of Alice
                                     Err(e) => {
                                         if let Some(sqlx::Error::RowNotFound) = e.downcast_ref::<sqlx::Error>() {
-                                            tracing::warn!("Ignoring transfer proof for swap {} while running swap {}. We do not have a record of this swap", swap_id, self.swap_id);
+                                            tracing::warn!("Ignoring transfer proof for swap {} which is not running. We do not have a record of this swap", swap_id);
                                         } else {
-                                            tracing::error!("Ignoring transfer proof for swap {} while running swap {}. Failed to retrieve the peer id of Alice for the corresponding swap: {:#}", swap_id, self.swap_id, e);
+                                            tracing::error!("Ignoring transfer proof for swap {} which is not running. Failed to retrieve the peer id of Alice for the corresponding swap: {:#}", swap_id, e);
                                         }
                                     }
                                 }
@@ -191,10 +231,33 @@ impl EventLoop {
                                 let _ = responder.respond(Response::Rejected { reason, swap_id });
                             }
                         }
+                        SwarmEvent::Behaviour(OutEvent::CooperativeEarlyRefundFulfilled { id, swap_id, tx_early_refund_sig }) => {
+                            if let Some(responder) = self.inflight_cooperative_early_refund_requests.remove(&id) {
+                                let _ = responder.respond(cooperative_early_refund::Response::Fullfilled { swap_id, tx_early_refund_sig });
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::CooperativeEarlyRefundRejected { id, swap_id, reason }) => {
+                            if let Some(responder) = self.inflight_cooperative_early_refund_requests.remove(&id) {
+                                let _ = responder.respond(cooperative_early_refund::Response::Rejected { swap_id, reason });
+                            }
+                        }
                         SwarmEvent::Behaviour(OutEvent::AllRedialAttemptsExhausted { peer }) if peer == self.alice_peer_id => {
                             tracing::error!("Exhausted all re-dial attempts to Alice");
                             return;
                         }
+                        SwarmEvent::Behaviour(OutEvent::Identified { peer, agent_version, protocols }) if peer == self.alice_peer_id => {
+                            let compatibility = Compatibility::check(agent_version, &protocols, compatibility::ASB_PROTOCOLS);
+
+                            if !compatibility.is_compatible() {
+                                tracing::warn!("Alice can not be swapped with, {}", compatibility);
+                            } else if !compatibility.missing_features.is_empty() {
+                                tracing::warn!("Some recovery paths are unavailable, {}", compatibility);
+                            } else {
+                                tracing::debug!("Alice identified, {}", compatibility);
+                            }
+
+                            self.alice_compatibility.send_replace(Some(compatibility));
+                        }
                         SwarmEvent::Behaviour(OutEvent::Failure { peer, error }) => {
                             tracing::warn!(%peer, err = %error, "Communication error");
                             return;
@@ -231,13 +294,23 @@ impl EventLoop {
                     let id = self.swarm.behaviour_mut().quote.send_request(&self.alice_peer_id, ());
                     self.inflight_quote_requests.insert(id, responder);
                 },
-                Some((swap, responder)) = self.swap_setup_requests.next().fuse(), if self.is_connected_to_alice() => {
-                    self.swarm.behaviour_mut().swap_setup.start(self.alice_peer_id, swap).await;
-                    self.inflight_swap_setup = Some(responder);
+                // The swap setup handler runs one setup at a time, so we only start the next one once the previous one completed
+                Some((swap, responder)) = self.swap_setup_requests.next().fuse(), if self.is_connected_to_alice() && self.inflight_swap_setup.is_none() => {
+                    // Refuse to set up a swap, and therefore to lock any funds, with a peer that could not finish it
+                    let incompatible = self.alice_compatibility.borrow().clone().filter(|c| !c.is_compatible());
+                    match incompatible {
+                        Some(compatibility) => {
+                            let _ = responder.respond(Err(anyhow!("Refusing to swap, {}", compatibility)));
+                        }
+                        None => {
+                            self.swarm.behaviour_mut().swap_setup.start(self.alice_peer_id, swap).await;
+                            self.inflight_swap_setup = Some(responder);
+                        }
+                    }
                 },
-                Some((tx_redeem_encsig, responder)) = self.encrypted_signatures.next().fuse(), if self.is_connected_to_alice() => {
+                Some(((swap_id, tx_redeem_encsig), responder)) = self.encrypted_signatures.next().fuse(), if self.is_connected_to_alice() => {
                     let request = encrypted_signature::Request {
-                        swap_id: self.swap_id,
+                        swap_id,
                         tx_redeem_encsig
                     };
 
@@ -245,18 +318,39 @@ impl EventLoop {
                     self.inflight_encrypted_signature_requests.insert(id, responder);
                 },
 
-                Some(response_channel) = &mut self.pending_transfer_proof => {
+                Some(response_channel) = self.pending_transfer_proofs.next() => {
                     let _ = self.swarm.behaviour_mut().transfer_proof.send_response(response_channel, ());
+                },
 
-                    self.pending_transfer_proof = OptionFuture::from(None);
+                Some((swap_id, transfer_proof)) = self.new_swaps.recv() => {
+                    tracing::debug!(%swap_id, "Adding swap to event loop");
+                    self.transfer_proofs.insert(swap_id, transfer_proof);
                 },
 
                 Some((swap_id, responder)) = self.cooperative_xmr_redeem_requests.next().fuse(), if self.is_connected_to_alice() => {
+                    // Dropping the responder fails the request without sending it
+                    if !self.alice_supports(compatibility::COOPERATIVE_XMR_REDEEM) {
+                        tracing::debug!(%swap_id, "Not requesting a cooperative XMR redeem, Alice does not support it");
+                        continue;
+                    }
+
                     let id = self.swarm.behaviour_mut().cooperative_xmr_redeem.send_request(&self.alice_peer_id, Request {
                         swap_id
                     });
                     self.inflight_cooperative_xmr_redeem_requests.insert(id, responder);
                 },
+
+                Some((swap_id, responder)) = self.cooperative_early_refund_requests.next().fuse(), if self.is_connected_to_alice() => {
+                    if !self.alice_supports(compatibility::COOPERATIVE_EARLY_REFUND) {
+                        tracing::debug!(%swap_id, "Not requesting an early refund, Alice does not support it");
+                        continue;
+                    }
+
+                    let id = self.swarm.behaviour_mut().cooperative_early_refund.send_request(&self.alice_peer_id, cooperative_early_refund::Request {
+                        swap_id
+                    });
+                    self.inflight_cooperative_early_refund_requests.insert(id, responder);
+                },
             }
         }
     }
@@ -264,18 +358,64 @@ impl EventLoop {
     fn is_connected_to_alice(&self) -> bool {
         self.swarm.is_connected(&self.alice_peer_id)
     }
+
+    /// Whether Alice advertised the protocol. Requests are only sent before
+    /// she identified herself if she is connected, so we optimistically assume
+    /// support until then.
+    fn alice_supports(&self, protocol: &str) -> bool {
+        self.alice_compatibility
+            .borrow()
+            .as_ref()
+            .map_or(true, |compatibility| compatibility.supports(protocol))
+    }
 }
 
 #[derive(Debug)]
 pub struct EventLoopHandle {
+    swap_id: Uuid,
+    new_swaps: mpsc::Sender<(Uuid, TransferProofSender)>,
     swap_setup: bmrng::RequestSender<NewSwap, Result<State2>>,
     transfer_proof: bmrng::RequestReceiver<monero::TransferProof, ()>,
-    encrypted_signature: bmrng::RequestSender<EncryptedSignature, ()>,
-    quote: bmrng::RequestSender<(), BidQuote>,
+    encrypted_signature: bmrng::RequestSender<(Uuid, EncryptedSignature), ()>,
+    quote: bmrng::RequestSender<(), SignedBidQuote>,
     cooperative_xmr_redeem: bmrng::RequestSender<Uuid, Response>,
+    cooperative_early_refund: bmrng::RequestSender<Uuid, cooperative_early_refund::Response>,
+    alice_compatibility: watch::Receiver<Option<Compatibility>>,
 }
 
 impl EventLoopHandle {
+    /// Creates the handle for another swap with the same seller. The swap
+    /// shares the connection of the event loop with all its other swaps.
+    pub async fn new_swap(&self, swap_id: Uuid) -> Result<EventLoopHandle> {
+        let transfer_proof = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+
+        self.new_swaps
+            .send((swap_id, transfer_proof.0))
+            .await
+            .context("Failed to add swap because the event loop stopped")?;
+
+        Ok(EventLoopHandle {
+            swap_id,
+            new_swaps: self.new_swaps.clone(),
+            swap_setup: self.swap_setup.clone(),
+            transfer_proof: transfer_proof.1,
+            encrypted_signature: self.encrypted_signature.clone(),
+            quote: self.quote.clone(),
+            cooperative_xmr_redeem: self.cooperative_xmr_redeem.clone(),
+            cooperative_early_refund: self.cooperative_early_refund.clone(),
+            alice_compatibility: self.alice_compatibility.clone(),
+        })
+    }
+
+    /// Whether Alice advertised the protocol with the given name, `None` until
+    /// she identified herself.
+    pub fn alice_supports(&self, protocol: &str) -> Option<bool> {
+        self.alice_compatibility
+            .borrow()
+            .as_ref()
+            .map(|compatibility| compatibility.supports(protocol))
+    }
+
     pub async fn setup_swap(&mut self, swap: NewSwap) -> Result<State2> {
         self.swap_setup.send_receive(swap).await?
     }
@@ -293,7 +433,7 @@ impl EventLoopHandle {
         Ok(transfer_proof)
     }
 
-    pub async fn request_quote(&mut self) -> Result<BidQuote> {
+    pub async fn request_quote(&mut self) -> Result<SignedBidQuote> {
         tracing::debug!("Requesting quote");
         Ok(self.quote.send_receive(()).await?)
     }
@@ -301,12 +441,19 @@ impl EventLoopHandle {
         Ok(self.cooperative_xmr_redeem.send_receive(swap_id).await?)
     }
 
+    pub async fn request_cooperative_early_refund(
+        &mut self,
+        swap_id: Uuid,
+    ) -> Result<cooperative_early_refund::Response> {
+        Ok(self.cooperative_early_refund.send_receive(swap_id).await?)
+    }
+
     pub async fn send_encrypted_signature(
         &mut self,
         tx_redeem_encsig: EncryptedSignature,
-    ) -> Result<(), bmrng::error::RequestError<EncryptedSignature>> {
+    ) -> Result<(), bmrng::error::RequestError<(Uuid, EncryptedSignature)>> {
         self.encrypted_signature
-            .send_receive(tx_redeem_encsig)
+            .send_receive((self.swap_id, tx_redeem_encsig))
             .await
     }
 }
@@ -0,0 +1,512 @@
+//! Evidence of what happened in a swap, to be shared with support or a
+//! mediator when a swap went wrong.
+//!
+//! The evidence is a zip archive holding the stored states of the swap, the
+//! quote the swap was started at, the transactions on the Bitcoin chain, the
+//! transfer proof of the Monero lock, the peer id and addresses of the seller
+//! and the log of the swap. The archive is signed with the libp2p identity of
+//! the CLI, which the seller knows as the peer id of its counterparty. Only the
+//! fields of the states that are known to hold no secrets are exported, unless
+//! private keys are explicitly included.
+
+use crate::bitcoin::{self, Txid};
+use crate::monero::TransferProof;
+use crate::network::quote::SignedBidQuote;
+use crate::protocol::bob::BobState;
+use crate::protocol::Database;
+use crate::signed::Signed;
+use anyhow::{bail, Context, Result};
+use libp2p::identity::Keypair;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const EVIDENCE_FILE: &str = "evidence.json";
+const LOG_FILE: &str = "swap.log";
+const SIGNATURE_FILE: &str = "signature.json";
+
+/// The file the CLI logs to, in its log directory.
+const CLI_LOG_FILE: &str = "swap-all.log";
+
+const SIGNATURE_DOMAIN: &str = "xmr-btc-swap/evidence";
+
+/// Keys of the stored states whose values are searched for public fields: the
+/// states of Bob and the structs holding the keys of the swap.
+const STATE_KEYS: &[&str] = &[
+    "Bob",
+    "Started",
+    "ExecutionSetupDone",
+    "BtcLocked",
+    "XmrLockProofReceived",
+    "XmrLocked",
+    "EncSigSent",
+    "BtcPunished",
+    "BtcRedeemed",
+    "CancelTimelockExpired",
+    "BtcCancelled",
+    "Done",
+    "XmrRedeemed",
+    "BtcRefunded",
+    "BtcEarlyRefunded",
+    "state",
+    "state2",
+    "state3",
+    "state4",
+];
+
+/// Fields of the stored states that hold no secrets and are exported as they
+/// are. All other fields are redacted, including fields added to the states
+/// later on.
+const PUBLIC_FIELDS: &[&str] = &[
+    "btc_amount",
+    "change_address",
+    "timelocks",
+    "quote",
+    "monero_wallet_restore_blockheight",
+    "lock_transfer_proof",
+    "tx_lock_id",
+    "A",
+    "S_a_monero",
+    "S_a_bitcoin",
+    "xmr",
+    "cancel_timelock",
+    "punish_timelock",
+    "refund_address",
+    "redeem_address",
+    "punish_address",
+    "tx_lock",
+    "tx_cancel_sig_a",
+    "tx_refund_encsig",
+    "min_monero_confirmations",
+    "tx_redeem_fee",
+    "tx_punish_fee",
+    "tx_refund_fee",
+    "tx_cancel_fee",
+];
+const REDACTED: &str = "<redacted>";
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub swap_id: Uuid,
+    pub exported_at: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub peer_id: PeerId,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub peer_addresses: Vec<Multiaddr>,
+    /// Every state of the swap in the order it was entered.
+    pub states: Vec<StoredState>,
+    /// The quote the swap was started at, its signature proves the price the
+    /// seller committed to.
+    #[serde(default)]
+    pub quote: Option<SignedBidQuote>,
+    /// The transactions paying to or spending from the lock and cancel
+    /// outputs.
+    pub transactions: Vec<EvidenceTransaction>,
+    /// The proof of the Monero lock sent by the seller, if it was received.
+    pub transfer_proof: Option<TransferProof>,
+    pub private_keys_redacted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredState {
+    pub entered_at: String,
+    pub state: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvidenceTransaction {
+    pub txid: Txid,
+    /// The hex encoded transaction, absent if the Electrum server does not
+    /// know it.
+    pub raw: Option<String>,
+}
+
+/// Evidence whose signature was verified.
+#[derive(Debug)]
+pub struct Verified {
+    /// The peer id of the CLI that exported the evidence.
+    pub signer: PeerId,
+    pub evidence: Evidence,
+    pub log: String,
+}
+
+impl Evidence {
+    /// Collects the evidence of a swap from the database, the Electrum server
+    /// and the log directory. Returns the evidence and the log of the swap.
+    pub async fn collect(
+        swap_id: Uuid,
+        db: Arc<dyn Database + Send + Sync>,
+        bitcoin_wallet: &bitcoin::Wallet,
+        log_dir: &Path,
+        include_private_keys: bool,
+    ) -> Result<(Self, String)> {
+        let mut states = db
+            .get_state_history(swap_id)
+            .await?
+            .into_iter()
+            .map(|(entered_at, state)| StoredState { entered_at, state })
+            .collect::<Vec<_>>();
+
+        if states.is_empty() {
+            bail!("No states stored for swap {}", swap_id);
+        }
+
+        if !include_private_keys {
+            for stored in &mut states {
+                redact(&mut stored.state);
+            }
+        }
+
+        let history = db
+            .get_states(swap_id)
+            .await?
+            .into_iter()
+            .map(|state| state.try_into())
+            .collect::<Result<Vec<BobState>, _>>()?;
+
+        let quote = history.iter().find_map(|state| match state {
+            BobState::Started { quote, .. } => quote.clone(),
+            _ => None,
+        });
+
+        let transfer_proof = match history.iter().find_map(|state| match state {
+            BobState::XmrLockProofReceived {
+                lock_transfer_proof,
+                ..
+            } => Some(lock_transfer_proof.clone()),
+            _ => None,
+        }) {
+            Some(transfer_proof) => Some(transfer_proof),
+            None => db.get_buffered_transfer_proof(swap_id).await?,
+        };
+
+        let peer_id = db.get_peer_id(swap_id).await?;
+        let peer_addresses = db.get_addresses(peer_id).await?;
+
+        let transactions = transactions(&history, bitcoin_wallet).await?;
+
+        let log = read_log(&log_dir.join(CLI_LOG_FILE), swap_id).await?;
+
+        let evidence = Self {
+            swap_id,
+            exported_at: OffsetDateTime::now_utc().to_string(),
+            peer_id,
+            peer_addresses,
+            states,
+            quote,
+            transactions,
+            transfer_proof,
+            private_keys_redacted: !include_private_keys,
+        };
+
+        Ok((evidence, log))
+    }
+
+    /// Writes the evidence and the log into a zip archive signed with the
+    /// given identity.
+    pub fn archive(&self, log: &str, identity: &Keypair) -> Result<Vec<u8>> {
+        let mut files = BTreeMap::from([
+            (EVIDENCE_FILE.to_owned(), serde_json::to_vec_pretty(self)?),
+            (LOG_FILE.to_owned(), log.as_bytes().to_vec()),
+        ]);
+
+        // The SHA-256 hashes of all files bind them to the identity of the CLI
+        let signature = Signed::sign(SIGNATURE_DOMAIN, &hashes(&files), identity)
+            .context("Failed to sign evidence")?;
+
+        files.insert(
+            SIGNATURE_FILE.to_owned(),
+            serde_json::to_vec_pretty(&signature)?,
+        );
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in &files {
+            writer.start_file(name.as_str(), FileOptions::default())?;
+            writer.write_all(content)?;
+        }
+
+        Ok(writer.finish()?.into_inner())
+    }
+
+    /// Checks that the archive is complete, unmodified and signed, and that
+    /// the transactions match their ids.
+    pub fn verify(archive: &[u8]) -> Result<Verified> {
+        let mut archive =
+            ZipArchive::new(Cursor::new(archive)).context("Evidence is not a zip archive")?;
+
+        let mut files = BTreeMap::new();
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)?;
+            files.insert(file.name().to_owned(), content);
+        }
+
+        let signature: Signed = serde_json::from_slice(
+            &files
+                .remove(SIGNATURE_FILE)
+                .context("Evidence is not signed")?,
+        )
+        .context("Failed to parse evidence signature")?;
+
+        let (signer, signed_hashes) = signature
+            .verify::<BTreeMap<String, String>>(SIGNATURE_DOMAIN)
+            .context("Evidence signature is invalid")?;
+        if signed_hashes != hashes(&files) {
+            bail!("Evidence does not match its signature, the archive was modified");
+        }
+
+        let evidence: Evidence = serde_json::from_slice(
+            files
+                .get(EVIDENCE_FILE)
+                .context("Evidence archive does not contain the evidence")?,
+        )
+        .context("Failed to parse evidence")?;
+        let log = String::from_utf8(
+            files
+                .remove(LOG_FILE)
+                .context("Evidence archive does not contain the log")?,
+        )?;
+
+        for transaction in &evidence.transactions {
+            if let Some(raw) = &transaction.raw {
+                let tx: bitcoin::Transaction = ::bitcoin::consensus::encode::deserialize(
+                    &hex::decode(raw)?,
+                )
+                .with_context(|| format!("Failed to decode transaction {}", transaction.txid))?;
+
+                if tx.txid() != transaction.txid {
+                    bail!(
+                        "Transaction {} does not match its id, it hashes to {}",
+                        transaction.txid,
+                        tx.txid()
+                    );
+                }
+            }
+        }
+
+        Ok(Verified {
+            signer,
+            evidence,
+            log,
+        })
+    }
+}
+
+fn hashes(files: &BTreeMap<String, Vec<u8>>) -> BTreeMap<String, String> {
+    files
+        .iter()
+        .map(|(name, content)| (name.clone(), hex::encode(Sha256::digest(content))))
+        .collect()
+}
+
+/// Replaces every field of a stored state that is not known to be public.
+fn redact(value: &mut serde_json::Value) {
+    if let serde_json::Value::Object(map) = value {
+        for (key, value) in map.iter_mut() {
+            if STATE_KEYS.contains(&key.as_str()) {
+                redact(value);
+            } else if !PUBLIC_FIELDS.contains(&key.as_str()) {
+                *value = serde_json::Value::String(REDACTED.to_owned());
+            }
+        }
+    }
+}
+
+/// All transactions that pay to or spend from the lock output and, if the swap
+/// got that far, the cancel output.
+async fn transactions(
+    history: &[BobState],
+    bitcoin_wallet: &bitcoin::Wallet,
+) -> Result<Vec<EvidenceTransaction>> {
+    let mut txids = BTreeSet::new();
+
+    if let Some(tx_lock) = history.iter().rev().find_map(|state| state.tx_lock()) {
+        txids.insert(tx_lock.txid());
+        txids.extend(bitcoin_wallet.script_history(tx_lock).await?);
+    }
+
+    let state6 = history.iter().rev().find_map(|state| match state {
+        BobState::BtcLocked {
+            state3,
+            monero_wallet_restore_blockheight,
+        }
+        | BobState::XmrLockProofReceived {
+            state: state3,
+            monero_wallet_restore_blockheight,
+            ..
+        } => Some(state3.cancel(*monero_wallet_restore_blockheight)),
+        BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => Some(state4.clone().cancel()),
+        BobState::CancelTimelockExpired(state6)
+        | BobState::BtcCancelled(state6)
+        | BobState::BtcRefunded(state6)
+        | BobState::BtcPunished { state: state6, .. } => Some(state6.clone()),
+        _ => None,
+    });
+
+    if let Some(state6) = state6 {
+        let tx_cancel = state6.construct_tx_cancel()?;
+        txids.extend(bitcoin_wallet.script_history(&tx_cancel).await?);
+    }
+
+    let mut transactions = Vec::new();
+    for txid in txids {
+        let raw = bitcoin_wallet
+            .get_tx(txid)
+            .await?
+            .map(|tx| ::bitcoin::consensus::encode::serialize_hex(&tx));
+
+        transactions.push(EvidenceTransaction { txid, raw });
+    }
+
+    Ok(transactions)
+}
+
+/// The lines of the log that mention the swap.
+async fn read_log(path: &Path, swap_id: Uuid) -> Result<String> {
+    let log = match tokio::fs::read_to_string(path).await {
+        Ok(log) => log,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            tracing::warn!(path = %path.display(), "No log file found, exporting evidence without log");
+            return Ok(String::new());
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("Failed to read log {}", path.display()))
+        }
+    };
+
+    let swap_id = swap_id.to_string();
+
+    Ok(log
+        .lines()
+        .filter(|line| line.contains(&swap_id))
+        .map(|line| format!("{}\n", line))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Swap;
+    use crate::protocol::State;
+    use monero_rpc::wallet::BlockHeight;
+    use serde_json::json;
+
+    fn evidence() -> Evidence {
+        Evidence {
+            swap_id: Uuid::new_v4(),
+            exported_at: "2024-08-01 12:00:00.0 +00:00:00".to_owned(),
+            peer_id: PeerId::random(),
+            peer_addresses: vec!["/ip4/127.0.0.1/tcp/9939".parse().unwrap()],
+            states: vec![StoredState {
+                entered_at: "2024-08-01 11:00:00.0 +00:00:00".to_owned(),
+                state: json!({ "Bob": "SafelyAborted" }),
+            }],
+            quote: None,
+            transactions: vec![],
+            transfer_proof: None,
+            private_keys_redacted: true,
+        }
+    }
+
+    #[test]
+    fn archive_verifies_for_signer() {
+        let identity = Keypair::generate_ed25519();
+        let evidence = evidence();
+
+        let archive = evidence.archive("log line\n", &identity).unwrap();
+        let verified = Evidence::verify(&archive).unwrap();
+
+        assert_eq!(verified.signer, identity.public().to_peer_id());
+        assert_eq!(verified.evidence, evidence);
+        assert_eq!(verified.log, "log line\n");
+    }
+
+    #[test]
+    fn modified_archive_does_not_verify() {
+        let identity = Keypair::generate_ed25519();
+        let archive = evidence().archive("log line\n", &identity).unwrap();
+
+        let mut files = ZipArchive::new(Cursor::new(archive.as_slice())).unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for index in 0..files.len() {
+            let mut file = files.by_index(index).unwrap();
+            let mut content = Vec::new();
+            file.read_to_end(&mut content).unwrap();
+            if file.name() == LOG_FILE {
+                content = b"another log line\n".to_vec();
+            }
+            writer
+                .start_file(file.name(), FileOptions::default())
+                .unwrap();
+            writer.write_all(&content).unwrap();
+        }
+        let modified = writer.finish().unwrap().into_inner();
+
+        assert!(Evidence::verify(&modified).is_err());
+    }
+
+    #[tokio::test]
+    async fn only_public_fields_of_states_are_exported() {
+        let (_, state2) = crate::protocol::swap_setup_states().await;
+        let (state3, _) = state2.lock_btc().await.unwrap();
+        let state = BobState::BtcLocked {
+            state3,
+            monero_wallet_restore_blockheight: BlockHeight { height: 10 },
+        };
+        let mut value = serde_json::to_value(Swap::from(State::Bob(state))).unwrap();
+
+        redact(&mut value);
+
+        let state3 = &value["Bob"]["BtcLocked"]["state3"];
+        for private_key in ["b", "s_b", "v"] {
+            assert_eq!(state3[private_key], json!(REDACTED));
+        }
+        for public in ["A", "xmr", "tx_lock", "cancel_timelock"] {
+            assert_ne!(state3[public], json!(REDACTED));
+        }
+        assert_eq!(
+            value["Bob"]["BtcLocked"]["monero_wallet_restore_blockheight"],
+            json!({ "height": 10 })
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_redacted() {
+        let mut state = json!({
+            "Bob": {
+                "Done": "SafelyAborted",
+                "BtcLocked": {
+                    "state3": { "new_secret": "secret", "xmr": 100 },
+                    "unknown": { "b": "secret" }
+                }
+            }
+        });
+
+        redact(&mut state);
+
+        assert_eq!(
+            state,
+            json!({
+                "Bob": {
+                    "Done": "SafelyAborted",
+                    "BtcLocked": {
+                        "state3": { "new_secret": REDACTED, "xmr": 100 },
+                        "unknown": REDACTED
+                    }
+                }
+            })
+        );
+    }
+}
@@ -234,7 +234,7 @@ impl EventLoop {
                                 RequestResponseEvent::Message { peer, message } => {
                                     match message {
                                         RequestResponseMessage::Response { response, .. } => {
-                                            if self.asb_quote_status.insert(peer, QuoteStatus::Received(Status::Online(response))).is_none() {
+                                            if self.asb_quote_status.insert(peer, QuoteStatus::Received(Status::Online(response.quote))).is_none() {
                                                 tracing::error!(%peer, "Received bid quote from unexpected peer, this record will be removed!");
                                                 self.asb_quote_status.remove(&peer);
                                             }
@@ -344,6 +344,7 @@ mod tests {
                     price: Default::default(),
                     min_quantity: Default::default(),
                     max_quantity: Default::default(),
+                    timelock_bounds: None,
                 }),
             },
         ];
@@ -359,6 +360,7 @@ mod tests {
                         price: Default::default(),
                         min_quantity: Default::default(),
                         max_quantity: Default::default(),
+                        timelock_bounds: None,
                     })
                 },
                 Seller {
@@ -1,4 +1,6 @@
 use crate::monero::TransferProof;
+use crate::network::quote::SignedBidQuote;
+use crate::network::swap_setup::Timelocks;
 use crate::protocol::bob;
 use crate::protocol::bob::BobState;
 use monero_rpc::wallet::BlockHeight;
@@ -14,6 +16,10 @@ pub enum Bob {
         btc_amount: bitcoin::Amount,
         #[serde_as(as = "DisplayFromStr")]
         change_address: bitcoin::Address,
+        #[serde(default)]
+        timelocks: Option<Timelocks>,
+        #[serde(default)]
+        quote: Option<SignedBidQuote>,
     },
     ExecutionSetupDone {
         state2: bob::State2,
@@ -48,6 +54,7 @@ pub enum BobEndState {
     SafelyAborted,
     XmrRedeemed { tx_lock_id: bitcoin::Txid },
     BtcRefunded(Box<bob::State6>),
+    BtcEarlyRefunded(Box<bob::State3>),
 }
 
 impl From<BobState> for Bob {
@@ -56,9 +63,13 @@ impl From<BobState> for Bob {
             BobState::Started {
                 btc_amount,
                 change_address,
+                timelocks,
+                quote,
             } => Bob::Started {
                 btc_amount,
                 change_address,
+                timelocks,
+                quote,
             },
             BobState::SwapSetupCompleted(state2) => Bob::ExecutionSetupDone { state2 },
             BobState::BtcLocked {
@@ -84,6 +95,9 @@ impl From<BobState> for Bob {
             BobState::BtcCancelled(state6) => Bob::BtcCancelled(state6),
             BobState::BtcPunished { state, tx_lock_id } => Bob::BtcPunished { state, tx_lock_id },
             BobState::BtcRefunded(state6) => Bob::Done(BobEndState::BtcRefunded(Box::new(state6))),
+            BobState::BtcEarlyRefunded(state3) => {
+                Bob::Done(BobEndState::BtcEarlyRefunded(Box::new(state3)))
+            }
             BobState::XmrRedeemed { tx_lock_id } => {
                 Bob::Done(BobEndState::XmrRedeemed { tx_lock_id })
             }
@@ -98,9 +112,13 @@ impl From<Bob> for BobState {
             Bob::Started {
                 btc_amount,
                 change_address,
+                timelocks,
+                quote,
             } => BobState::Started {
                 btc_amount,
                 change_address,
+                timelocks,
+                quote,
             },
             Bob::ExecutionSetupDone { state2 } => BobState::SwapSetupCompleted(state2),
             Bob::BtcLocked {
@@ -129,6 +147,7 @@ impl From<Bob> for BobState {
                 BobEndState::SafelyAborted => BobState::SafelyAborted,
                 BobEndState::XmrRedeemed { tx_lock_id } => BobState::XmrRedeemed { tx_lock_id },
                 BobEndState::BtcRefunded(state6) => BobState::BtcRefunded(*state6),
+                BobEndState::BtcEarlyRefunded(state3) => BobState::BtcEarlyRefunded(*state3),
             },
         }
     }
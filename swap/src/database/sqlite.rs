@@ -1,14 +1,18 @@
+use crate::bitcoin::{psbt_from_base64, psbt_to_base64, PartiallySignedTransaction, Txid};
 use crate::database::Swap;
 use crate::monero::{Address, TransferProof};
 use crate::protocol::{Database, State};
-use anyhow::{anyhow, Context, Result};
+use ::bitcoin::Script;
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use libp2p::{Multiaddr, PeerId};
 use sqlx::sqlite::{Sqlite, SqliteConnectOptions};
 use sqlx::{Pool, SqlitePool};
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
@@ -250,6 +254,27 @@ impl Database for SqliteDatabase {
         Ok(swap.into())
     }
 
+    async fn get_state_history(&self, swap_id: Uuid) -> Result<Vec<(String, serde_json::Value)>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+           SELECT entered_at, state
+           FROM swap_states
+           WHERE swap_id = ?
+           ORDER BY id
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| Ok((row.entered_at.clone(), serde_json::from_str(&row.state)?)))
+            .collect()
+    }
+
     async fn all(&self) -> Result<Vec<(Uuid, State)>> {
         let mut conn = self.pool.acquire().await?;
         let rows = sqlx::query!(
@@ -363,6 +388,175 @@ impl Database for SqliteDatabase {
         Ok(Some(proof))
     }
 
+    async fn insert_unsigned_psbt(
+        &self,
+        swap_id: Uuid,
+        psbt: PartiallySignedTransaction,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+        let psbt = psbt_to_base64(&psbt);
+        let exported_at = OffsetDateTime::now_utc().unix_timestamp();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO psbts (
+                swap_id,
+                unsigned_psbt,
+                exported_at
+                ) VALUES (?, ?, ?);
+        "#,
+            swap_id,
+            psbt,
+            exported_at
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_unsigned_psbt(&self, swap_id: Uuid) -> Result<Option<PartiallySignedTransaction>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+           SELECT unsigned_psbt
+           FROM psbts
+           WHERE swap_id = ?
+            "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        row.map(|row| psbt_from_base64(&row.unsigned_psbt))
+            .transpose()
+    }
+
+    async fn get_psbt_exported_at(&self, swap_id: Uuid) -> Result<Option<SystemTime>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+           SELECT exported_at
+           FROM psbts
+           WHERE swap_id = ?
+            "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        row.and_then(|row| row.exported_at)
+            .map(|exported_at| {
+                let exported_at = u64::try_from(exported_at)?;
+                Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(exported_at))
+            })
+            .transpose()
+    }
+
+    async fn insert_signed_psbt(
+        &self,
+        swap_id: Uuid,
+        psbt: PartiallySignedTransaction,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+        let psbt = psbt_to_base64(&psbt);
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE psbts
+            SET signed_psbt = ?
+            WHERE swap_id = ?
+        "#,
+            psbt,
+            swap_id
+        )
+        .execute(&mut conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            bail!("No PSBT was exported for swap {}", swap_id);
+        }
+
+        Ok(())
+    }
+
+    async fn get_signed_psbt(&self, swap_id: Uuid) -> Result<Option<PartiallySignedTransaction>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+           SELECT signed_psbt
+           FROM psbts
+           WHERE swap_id = ?
+            "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        row.and_then(|row| row.signed_psbt)
+            .map(|psbt| psbt_from_base64(&psbt))
+            .transpose()
+    }
+
+    async fn insert_script_status(
+        &self,
+        txid: Txid,
+        script: Script,
+        inclusion_height: u32,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let txid = txid.to_string();
+        let script = format!("{:x}", script);
+
+        sqlx::query!(
+            r#"
+            INSERT OR REPLACE INTO bitcoin_script_statuses (
+                txid,
+                script,
+                inclusion_height
+                ) VALUES (?, ?, ?);
+        "#,
+            txid,
+            script,
+            inclusion_height
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_script_statuses(&self) -> Result<Vec<(Txid, Script, u32)>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+           SELECT txid, script, inclusion_height
+           FROM bitcoin_script_statuses
+            "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let txid = Txid::from_str(&row.txid)?;
+                let script = Script::from_str(&row.script)?;
+                let inclusion_height = u32::try_from(row.inclusion_height)?;
+
+                Ok((txid, script, inclusion_height))
+            })
+            .collect()
+    }
+
     async fn raw_all(&self) -> Result<HashMap<Uuid, Vec<serde_json::Value>>> {
         let mut conn = self.pool.acquire().await?;
         let rows = sqlx::query!(
@@ -508,6 +702,55 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_insert_and_load_psbts() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let unsigned = PartiallySignedTransaction::from_unsigned_tx(bitcoin::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![],
+        })?;
+        let mut signed = unsigned.clone();
+        signed.inputs[0].final_script_witness = Some(bitcoin::Witness::from_vec(vec![vec![1]]));
+
+        assert!(db
+            .insert_signed_psbt(swap_id, signed.clone())
+            .await
+            .is_err());
+
+        assert_eq!(db.get_psbt_exported_at(swap_id).await?, None);
+
+        let before_export = SystemTime::now() - Duration::from_secs(1);
+        db.insert_unsigned_psbt(swap_id, unsigned.clone()).await?;
+        assert_eq!(db.get_unsigned_psbt(swap_id).await?, Some(unsigned));
+        assert!(db.get_psbt_exported_at(swap_id).await?.unwrap() >= before_export);
+        assert_eq!(db.get_signed_psbt(swap_id).await?, None);
+
+        db.insert_signed_psbt(swap_id, signed.clone()).await?;
+        assert_eq!(db.get_signed_psbt(swap_id).await?, Some(signed));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_script_statuses() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let txid =
+            Txid::from_str("0000000000000000000000000000000000000000000000000000000000000001")?;
+        let script = Script::from_str("0014e4a1a0b2a2f8f5e0bd4d7f1d6dbc0f2c0dbd5e25")?;
+
+        db.insert_script_status(txid, script.clone(), 100).await?;
+        db.insert_script_status(txid, script.clone(), 101).await?;
+
+        assert_eq!(db.get_script_statuses().await?, vec![(txid, script, 101)]);
+
+        Ok(())
+    }
+
     async fn setup_test_db() -> Result<SqliteDatabase> {
         let temp_db = tempdir().unwrap().into_path().join("tempdb");
 
@@ -28,6 +28,12 @@ impl Config {
     pub fn monero_sync_interval(&self) -> Duration {
         sync_interval(self.monero_avg_block_time)
     }
+
+    /// How long Bob waits for Alice to lock the Monero before asking her
+    /// whether she aborted the swap and is willing to let him refund early.
+    pub fn early_refund_request_interval(&self) -> Duration {
+        self.bitcoin_avg_block_time
+    }
 }
 
 pub trait GetConfig {
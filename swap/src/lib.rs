@@ -31,8 +31,10 @@ pub mod network;
 pub mod protocol;
 pub mod rpc;
 pub mod seed;
+pub mod signed;
 pub mod tor;
 pub mod tracing_ext;
+pub mod watchtower;
 
 mod monero_ext;
 
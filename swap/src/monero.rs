@@ -1,11 +1,15 @@
+pub mod daemon;
 pub mod wallet;
 mod wallet_rpc;
 
 pub use ::monero::network::Network;
 pub use ::monero::{Address, PrivateKey, PublicKey};
 pub use curve25519_dalek::scalar::Scalar;
+pub use daemon::{DaemonStatus, MoneroDaemon};
 pub use wallet::Wallet;
-pub use wallet_rpc::{WalletRpc, WalletRpcProcess};
+pub use wallet_rpc::{
+    RemoteWalletRpc, WalletRpc, WalletRpcOptions, WalletRpcProcess, WalletRpcSupervisor,
+};
 
 use crate::bitcoin;
 use anyhow::Result;
@@ -0,0 +1,489 @@
+use crate::monero::Wallet;
+use ::monero::Network;
+use anyhow::{bail, Context, Error, Result};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+
+// See: https://www.moneroworld.com/#nodes, https://monero.fail
+// We don't need any testnet nodes because we don't support testnet at all
+const MONERO_DAEMONS: [MoneroDaemon; 17] = [
+    MoneroDaemon::new("xmr-node.cakewallet.com", 18081, Network::Mainnet),
+    MoneroDaemon::new("nodex.monerujo.io", 18081, Network::Mainnet),
+    MoneroDaemon::new("node.moneroworld.com", 18089, Network::Mainnet),
+    MoneroDaemon::new("nodes.hashvault.pro", 18081, Network::Mainnet),
+    MoneroDaemon::new("p2pmd.xmrvsbeast.com", 18081, Network::Mainnet),
+    MoneroDaemon::new("node.monerodevs.org", 18089, Network::Mainnet),
+    MoneroDaemon::new("xmr-node-usa-east.cakewallet.com", 18081, Network::Mainnet),
+    MoneroDaemon::new("xmr-node-uk.cakewallet.com", 18081, Network::Mainnet),
+    MoneroDaemon::new("node.community.rino.io", 18081, Network::Mainnet),
+    MoneroDaemon::new("testingjohnross.com", 20031, Network::Mainnet),
+    MoneroDaemon::new("xmr.litepay.ch", 18081, Network::Mainnet),
+    MoneroDaemon::new("node.trocador.app", 18089, Network::Mainnet),
+    MoneroDaemon::new("stagenet.xmr-tw.org", 38081, Network::Stagenet),
+    MoneroDaemon::new("node.monerodevs.org", 38089, Network::Stagenet),
+    MoneroDaemon::new("singapore.node.xmr.pm", 38081, Network::Stagenet),
+    MoneroDaemon::new("xmr-lux.boldsuck.org", 38081, Network::Stagenet),
+    MoneroDaemon::new("stagenet.community.rino.io", 38081, Network::Stagenet),
+];
+
+/// Daemons that are more than this many blocks behind the highest daemon are
+/// not chosen.
+const MAX_HEIGHT_LAG: u64 = 2;
+
+/// How often the current daemon is checked while the wallet is in use.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(2 * 60);
+
+/// If the height of the current daemon did not increase for this long we
+/// look for a daemon that is further ahead. With a block time of 2 minutes it
+/// is very unlikely that no block is found in this time.
+const STALL_TIMEOUT: Duration = Duration::from_secs(20 * 60);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoneroDaemon {
+    address: Cow<'static, str>,
+    port: u16,
+    network: Network,
+}
+
+impl MoneroDaemon {
+    const fn new(address: &'static str, port: u16, network: Network) -> Self {
+        Self {
+            address: Cow::Borrowed(address),
+            port,
+            network,
+        }
+    }
+
+    /// Parses a daemon address of the form `<host>:<port>`.
+    pub fn from_address(address: &str, network: Network) -> Result<Self> {
+        let (host, port) = monero_rpc::monerod::parse_daemon_address(address)?;
+
+        Ok(Self {
+            address: Cow::Owned(host),
+            port,
+            network,
+        })
+    }
+
+    /// The built-in daemons for the given network.
+    pub fn defaults(network: Network) -> Vec<Self> {
+        MONERO_DAEMONS
+            .iter()
+            .filter(|daemon| daemon.network == network)
+            .cloned()
+            .collect()
+    }
+
+    pub fn monerod_client(&self) -> Result<monero_rpc::monerod::Client> {
+        monero_rpc::monerod::Client::from_daemon_address(&self.to_string())
+    }
+
+    /// Checks if the Monero daemon is available by sending a request to its `get_info` endpoint.
+    #[cfg(test)]
+    async fn is_available(&self, client: &reqwest::Client) -> Result<bool, Error> {
+        let info = self.get_info(client).await?;
+
+        Ok(info.is_healthy(self.network))
+    }
+
+    async fn get_info(&self, client: &reqwest::Client) -> Result<MoneroDaemonGetInfoResponse> {
+        let url = format!("http://{}:{}/get_info", self.address, self.port);
+        let res = client
+            .get(url)
+            .send()
+            .await
+            .context("Failed to send request to get_info endpoint")?;
+
+        let json: MoneroDaemonGetInfoResponse = res
+            .json()
+            .await
+            .context("Failed to deserialize daemon get_info response")?;
+
+        Ok(json)
+    }
+
+    /// Queries the height and health of the daemon, failures are recorded in
+    /// the returned probe.
+    pub async fn probe(&self, client: &reqwest::Client) -> Probe {
+        let start = Instant::now();
+        let result = self.get_info(client).await;
+        let latency_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+        match result {
+            Ok(info) => Probe {
+                daemon: self.to_string(),
+                healthy: info.is_healthy(self.network),
+                height: Some(info.height),
+                latency_ms: Some(latency_ms),
+                error: None,
+            },
+            Err(error) => Probe {
+                daemon: self.to_string(),
+                healthy: false,
+                height: None,
+                latency_ms: None,
+                error: Some(format!("{:#}", error)),
+            },
+        }
+    }
+}
+
+impl Display for MoneroDaemon {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.address, self.port)
+    }
+}
+
+#[derive(Deserialize)]
+struct MoneroDaemonGetInfoResponse {
+    status: String,
+    synchronized: bool,
+    mainnet: bool,
+    stagenet: bool,
+    testnet: bool,
+    #[serde(default)]
+    height: u64,
+}
+
+impl MoneroDaemonGetInfoResponse {
+    fn is_healthy(&self, network: Network) -> bool {
+        let is_status_ok = self.status == "OK";
+        let is_correct_network = match network {
+            Network::Mainnet => self.mainnet,
+            Network::Stagenet => self.stagenet,
+            Network::Testnet => self.testnet,
+        };
+
+        is_status_ok && self.synchronized && is_correct_network
+    }
+}
+
+/// The result of querying a daemon's `get_info` endpoint.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Probe {
+    pub daemon: String,
+    /// Whether the daemon is synchronized and on the expected network.
+    pub healthy: bool,
+    pub height: Option<u64>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// The daemon currently in use and the probes that led to choosing it.
+#[derive(Clone, Debug, Serialize)]
+pub struct DaemonStatus {
+    pub current: String,
+    pub probes: Vec<Probe>,
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .https_only(false)
+        .build()?)
+}
+
+/// Probes all daemons and chooses the first healthy one in the given order
+/// that is not behind the others.
+pub async fn choose_monero_daemon(daemons: &[MoneroDaemon]) -> Result<(MoneroDaemon, Vec<Probe>)> {
+    let client = http_client()?;
+    let probes = join_all(daemons.iter().map(|daemon| daemon.probe(&client))).await;
+
+    for probe in &probes {
+        match &probe.error {
+            None => tracing::info!(
+                daemon = %probe.daemon,
+                healthy = probe.healthy,
+                height = ?probe.height,
+                latency_ms = ?probe.latency_ms,
+                "Probed Monero daemon"
+            ),
+            Some(error) => {
+                tracing::debug!(daemon = %probe.daemon, %error, "Failed to connect to Monero daemon")
+            }
+        }
+    }
+
+    let daemon = match best(&probes) {
+        Some(index) => daemons[index].clone(),
+        None => bail!(
+            "No Monero daemon could be found. Please specify one manually or try again later."
+        ),
+    };
+
+    tracing::info!(%daemon, "Using Monero daemon");
+
+    Ok((daemon, probes))
+}
+
+/// Index of the first healthy probe at most [`MAX_HEIGHT_LAG`] blocks behind
+/// the highest healthy probe.
+fn best(probes: &[Probe]) -> Option<usize> {
+    let max_height = probes
+        .iter()
+        .filter(|probe| probe.healthy)
+        .filter_map(|probe| probe.height)
+        .max()?;
+
+    probes
+        .iter()
+        .position(|probe| probe.healthy && probe.height.unwrap_or(0) + MAX_HEIGHT_LAG >= max_height)
+}
+
+/// Periodically checks the daemon the wallet is connected to and switches to
+/// another one if it becomes unhealthy or stops progressing while others are
+/// further ahead.
+///
+/// Only a weak reference to the wallet is held, the watcher stops once the
+/// wallet is dropped.
+pub async fn watch_daemon(
+    wallet: Weak<Wallet>,
+    mut current: MoneroDaemon,
+    daemons: Vec<MoneroDaemon>,
+    status: Arc<RwLock<DaemonStatus>>,
+) {
+    let client = match http_client() {
+        Ok(client) => client,
+        Err(error) => {
+            tracing::warn!(
+                "Failed to create client to watch Monero daemon: {:#}",
+                error
+            );
+            return;
+        }
+    };
+
+    let mut last_height = 0;
+    let mut last_progress = Instant::now();
+
+    loop {
+        tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+
+        let wallet = match wallet.upgrade() {
+            Some(wallet) => wallet,
+            None => {
+                tracing::debug!("Monero wallet dropped, no longer watching its daemon");
+                return;
+            }
+        };
+
+        let probe = current.probe(&client).await;
+
+        if let Some(height) = probe.height {
+            if height > last_height {
+                last_height = height;
+                last_progress = Instant::now();
+            }
+        }
+
+        let stalled = last_progress.elapsed() >= STALL_TIMEOUT;
+
+        if probe.healthy && !stalled {
+            continue;
+        }
+
+        tracing::warn!(
+            daemon = %current,
+            healthy = probe.healthy,
+            height = ?probe.height,
+            error = ?probe.error,
+            "Monero daemon is unhealthy or not progressing, looking for another one"
+        );
+
+        let probes = join_all(daemons.iter().map(|daemon| daemon.probe(&client))).await;
+
+        let (candidate, candidate_height) = match best(&probes) {
+            Some(index) => (daemons[index].clone(), probes[index].height.unwrap_or(0)),
+            None => {
+                tracing::warn!("No other healthy Monero daemon found, keeping the current one");
+                continue;
+            }
+        };
+
+        if candidate == current || (probe.healthy && candidate_height <= last_height) {
+            // all daemons are at the same height, there simply was no new block
+            last_progress = Instant::now();
+            continue;
+        }
+
+        if let Err(error) = wallet.switch_daemon(&candidate).await {
+            tracing::warn!(daemon = %candidate, "Failed to switch Monero daemon: {:#}", error);
+            continue;
+        }
+
+        tracing::info!(from = %current, to = %candidate, "Switched Monero daemon");
+
+        *status.write().await = DaemonStatus {
+            current: candidate.to_string(),
+            probes,
+        };
+        current = candidate;
+        last_height = candidate_height;
+        last_progress = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract_host_and_port(address: String) -> (&'static str, u16) {
+        let parts: Vec<&str> = address.split(':').collect();
+
+        if parts.len() == 2 {
+            let host = parts[0].to_string();
+            let port = parts[1].parse::<u16>().unwrap();
+            let static_str_host: &'static str = Box::leak(host.into_boxed_str());
+            return (static_str_host, port);
+        }
+        panic!("Could not extract host and port from address: {}", address)
+    }
+
+    #[tokio::test]
+    async fn test_is_daemon_available_success() {
+        let mut server = mockito::Server::new_async().await;
+
+        let _ = server
+            .mock("GET", "/get_info")
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "status": "OK",
+                    "synchronized": true,
+                    "mainnet": true,
+                    "stagenet": false,
+                    "testnet": false
+                }
+                "#,
+            )
+            .create();
+
+        let (host, port) = extract_host_and_port(server.host_with_port());
+
+        let client = reqwest::Client::new();
+        let result = MoneroDaemon::new(host, port, Network::Mainnet)
+            .is_available(&client)
+            .await;
+
+        assert!(result.is_ok());
+        assert!(result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_is_daemon_available_wrong_network_failure() {
+        let mut server = mockito::Server::new_async().await;
+
+        let _ = server
+            .mock("GET", "/get_info")
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "status": "OK",
+                    "synchronized": true,
+                    "mainnet": true,
+                    "stagenet": false,
+                    "testnet": false
+                }
+                "#,
+            )
+            .create();
+
+        let (host, port) = extract_host_and_port(server.host_with_port());
+
+        let client = reqwest::Client::new();
+        let result = MoneroDaemon::new(host, port, Network::Stagenet)
+            .is_available(&client)
+            .await;
+
+        assert!(result.is_ok());
+        assert!(!result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_is_daemon_available_not_synced_failure() {
+        let mut server = mockito::Server::new_async().await;
+
+        let _ = server
+            .mock("GET", "/get_info")
+            .with_status(200)
+            .with_body(
+                r#"
+                {
+                    "status": "OK",
+                    "synchronized": false,
+                    "mainnet": true,
+                    "stagenet": false,
+                    "testnet": false
+                }
+                "#,
+            )
+            .create();
+
+        let (host, port) = extract_host_and_port(server.host_with_port());
+
+        let client = reqwest::Client::new();
+        let result = MoneroDaemon::new(host, port, Network::Mainnet)
+            .is_available(&client)
+            .await;
+
+        assert!(result.is_ok());
+        assert!(!result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_is_daemon_available_network_error_failure() {
+        let client = reqwest::Client::new();
+        let result = MoneroDaemon::new("does.not.exist.com", 18081, Network::Mainnet)
+            .is_available(&client)
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    fn probe(healthy: bool, height: Option<u64>) -> Probe {
+        Probe {
+            daemon: String::new(),
+            healthy,
+            height,
+            latency_ms: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn best_daemon_is_first_healthy_one_in_sync() {
+        let probes = vec![
+            probe(false, Some(110)),
+            probe(true, Some(100)),
+            probe(true, Some(109)),
+            probe(true, Some(110)),
+        ];
+
+        assert_eq!(best(&probes), Some(2));
+    }
+
+    #[test]
+    fn no_best_daemon_if_none_is_healthy() {
+        let probes = vec![probe(false, Some(110)), probe(false, None)];
+
+        assert_eq!(best(&probes), None);
+    }
+
+    #[test]
+    fn parses_daemon_address() {
+        let daemon =
+            MoneroDaemon::from_address("http://node.example.com:18089/", Network::Mainnet).unwrap();
+
+        assert_eq!(daemon.to_string(), "node.example.com:18089");
+        assert!(MoneroDaemon::from_address("node.example.com", Network::Mainnet).is_err());
+    }
+}
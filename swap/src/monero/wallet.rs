@@ -1,11 +1,13 @@
 use crate::env::Config;
 use crate::monero::{
-    Amount, InsufficientFunds, PrivateViewKey, PublicViewKey, TransferProof, TxHash,
+    Amount, InsufficientFunds, MoneroDaemon, PrivateViewKey, PublicViewKey, TransferProof, TxHash,
 };
 use ::monero::{Address, Network, PrivateKey, PublicKey};
 use anyhow::{Context, Result};
+use futures::{Future, TryFutureExt};
+use monero_rpc::monerod::MonerodRpc as _;
 use monero_rpc::wallet::{BlockHeight, MoneroWalletRpc as _, Refreshed};
-use monero_rpc::{jsonrpc, wallet};
+use monero_rpc::{jsonrpc, monerod, wallet};
 use std::ops::Div;
 use std::str::FromStr;
 use std::time::Duration;
@@ -13,6 +15,10 @@ use tokio::sync::Mutex;
 use tokio::time::Interval;
 use url::Url;
 
+/// How long calls to `monero-wallet-rpc` are retried while it is unreachable,
+/// giving its supervisor time to restart it.
+const WALLET_RPC_RESTART_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
 #[derive(Debug)]
 pub struct Wallet {
     inner: Mutex<wallet::Client>,
@@ -20,13 +26,25 @@ pub struct Wallet {
     name: String,
     main_address: monero::Address,
     sync_interval: Duration,
+    /// If set, transfers are verified against monerod directly instead of
+    /// through `monero-wallet-rpc`.
+    monerod: Option<Mutex<monerod::Client>>,
+    generated_wallet_prefix: Option<String>,
 }
 
 impl Wallet {
     /// Connect to a wallet RPC and load the given wallet by name.
     pub async fn open_or_create(url: Url, name: String, env_config: Config) -> Result<Self> {
-        let client = wallet::Client::new(url)?;
+        Self::open_or_create_with_client(wallet::Client::new(url)?, name, env_config).await
+    }
 
+    /// Like [`Wallet::open_or_create`] but with a configured client, e.g. one
+    /// that authenticates against the wallet RPC.
+    pub async fn open_or_create_with_client(
+        client: wallet::Client,
+        name: String,
+        env_config: Config,
+    ) -> Result<Self> {
         match client.open_wallet(name.clone()).await {
             Err(error) => {
                 tracing::debug!(%error, "Open wallet response error");
@@ -53,66 +71,140 @@ impl Wallet {
             name,
             main_address,
             sync_interval: env_config.monero_sync_interval(),
+            monerod: None,
+            generated_wallet_prefix: None,
         })
     }
 
-    /// Re-open the wallet using the internally stored name.
-    pub async fn re_open(&self) -> Result<()> {
+    /// Prefix the file names of wallets generated from keys, to keep them
+    /// apart from other wallets on a shared wallet RPC.
+    pub fn with_generated_wallet_prefix(self, prefix: String) -> Self {
+        Self {
+            generated_wallet_prefix: Some(prefix),
+            ..self
+        }
+    }
+
+    fn generated_wallet_file_name(&self, file_name: String) -> String {
+        match &self.generated_wallet_prefix {
+            Some(prefix) => format!("{}-{}", prefix, file_name),
+            None => file_name,
+        }
+    }
+
+    /// Verify transfers and fetch the block height by talking to monerod
+    /// directly.
+    pub fn with_monerod(self, monerod: monerod::Client) -> Self {
+        Self {
+            monerod: Some(Mutex::new(monerod)),
+            ..self
+        }
+    }
+
+    /// Points `monero-wallet-rpc` and the monerod client to another daemon.
+    pub async fn switch_daemon(&self, daemon: &MoneroDaemon) -> Result<()> {
         self.inner
             .lock()
             .await
-            .open_wallet(self.name.clone())
+            .set_daemon(daemon.to_string(), false)
             .await?;
+
+        if let Some(monerod) = &self.monerod {
+            *monerod.lock().await = daemon.monerod_client()?;
+        }
+
         Ok(())
     }
 
-    pub async fn open(&self, filename: String) -> Result<()> {
-        self.inner.lock().await.open_wallet(filename).await?;
+    /// Re-open the wallet using the internally stored name.
+    pub async fn re_open(&self) -> Result<()> {
+        self.inner
+            .lock()
+            .await
+            .open_wallet(self.name.clone())
+            .await?;
         Ok(())
     }
 
     /// Close the wallet and open (load) another wallet by generating it from
-    /// keys. The generated wallet will remain loaded.
-    pub async fn create_from_and_load(
+    /// keys, or by opening it if it was generated before. All funds are swept
+    /// to `sweep_to` and then the wallet is re-loaded using the internally
+    /// stored name.
+    ///
+    /// Unlike [`Wallet::create_from`] a failed sweep is returned, so that it can
+    /// be retried. Swaps sharing this wallet redeem one after another, as the
+    /// lock is held until the default wallet is loaded again.
+    pub async fn sweep_from_keys(
         &self,
         file_name: String,
         private_spend_key: PrivateKey,
         private_view_key: PrivateViewKey,
         restore_height: BlockHeight,
-    ) -> Result<()> {
+        sweep_to: Address,
+    ) -> Result<Vec<TxHash>> {
         let public_spend_key = PublicKey::from_private_key(&private_spend_key);
         let public_view_key = PublicKey::from_private_key(&private_view_key.into());
 
         let address = Address::standard(self.network, public_spend_key, public_view_key);
+        let file_name = self.generated_wallet_file_name(file_name);
 
         let wallet = self.inner.lock().await;
 
         // Properly close the wallet before generating the other wallet to ensure that
         // it saves its state correctly
-        let _ = wallet
-            .close_wallet()
+        let _ = retry_in_wallet(&*wallet, &self.name, || wallet.close_wallet())
             .await
             .context("Failed to close wallet")?;
 
-        let _ = wallet
-            .generate_from_keys(
-                file_name,
-                address.to_string(),
-                private_spend_key.to_string(),
-                PrivateKey::from(private_view_key).to_string(),
-                restore_height.height,
-                String::from(""),
-                true,
-            )
+        let sweep = async {
+            if let Err(error) = retry_while_unreachable(|| {
+                wallet.generate_from_keys(
+                    file_name.clone(),
+                    address.to_string(),
+                    private_spend_key.to_string(),
+                    PrivateKey::from(private_view_key).to_string(),
+                    restore_height.height,
+                    String::from(""),
+                    true,
+                )
+            })
+            .await
+            {
+                // In case we failed to refresh/sweep, when resuming the wallet might already
+                // exist! This is a very unlikely scenario, but if we don't take care of it we
+                // might not be able to ever transfer the Monero.
+                tracing::warn!("Failed to generate monero wallet from keys: {:#}", error);
+                tracing::info!(%file_name,
+                    "Falling back to trying to open the wallet if it already exists",
+                );
+                retry_while_unreachable(|| wallet.open_wallet(file_name.clone()))
+                    .await
+                    .context("Failed to open generated wallet")?;
+            }
+
+            // Ensure that the generated wallet is synced so we have a proper balance
+            refresh(&wallet, &file_name, 20).await?;
+
+            let sweep_all = retry_in_wallet(&*wallet, &file_name, || {
+                wallet.sweep_all(sweep_to.to_string())
+            })
             .await
-            .context("Failed to generate new wallet from keys")?;
+            .context("Failed to sweep Monero from generated wallet")?;
 
-        Ok(())
+            Ok::<_, anyhow::Error>(sweep_all.tx_hash_list.into_iter().map(TxHash).collect())
+        }
+        .await;
+
+        let _ = retry_while_unreachable(|| wallet.open_wallet(self.name.clone()))
+            .await
+            .context("Failed to re-open wallet")?;
+
+        sweep
     }
 
     /// Close the wallet and open (load) another wallet by generating it from
-    /// keys. The generated wallet will be opened, all funds sweeped to the
-    /// main_address and then the wallet will be re-loaded using the internally
+    /// keys. The generated wallet will be opened, all funds sweeped to
+    /// `sweep_to` and then the wallet will be re-loaded using the internally
     /// stored name.
     pub async fn create_from(
         &self,
@@ -120,23 +212,26 @@ impl Wallet {
         private_spend_key: PrivateKey,
         private_view_key: PrivateViewKey,
         restore_height: BlockHeight,
+        sweep_to: Address,
     ) -> Result<()> {
         let public_spend_key = PublicKey::from_private_key(&private_spend_key);
         let public_view_key = PublicKey::from_private_key(&private_view_key.into());
 
         let temp_wallet_address =
             Address::standard(self.network, public_spend_key, public_view_key);
+        let file_name = self.generated_wallet_file_name(file_name);
+
+        // Hold the lock until the default wallet is loaded again, so no other call
+        // operates on the generated wallet in the meantime
+        let wallet = self.inner.lock().await;
 
         // Close the default wallet before generating the other wallet to ensure that
         // it saves its state correctly
-        let _ = self.inner.lock().await.close_wallet().await?;
+        let _ = retry_in_wallet(&*wallet, &self.name, || wallet.close_wallet()).await?;
 
-        let _ = self
-            .inner
-            .lock()
-            .await
-            .generate_from_keys(
-                file_name,
+        let _ = retry_while_unreachable(|| {
+            wallet.generate_from_keys(
+                file_name.clone(),
                 temp_wallet_address.to_string(),
                 private_spend_key.to_string(),
                 PrivateKey::from(private_view_key).to_string(),
@@ -144,29 +239,28 @@ impl Wallet {
                 String::from(""),
                 true,
             )
-            .await?;
+        })
+        .await?;
 
-        // Try to send all the funds from the generated wallet to the default wallet
-        match self.refresh(3).await {
-            Ok(_) => match self
-                .inner
-                .lock()
-                .await
-                .sweep_all(self.main_address.to_string())
-                .await
+        // Try to send all the funds from the generated wallet to `sweep_to`
+        match refresh(&wallet, &file_name, 3).await {
+            Ok(_) => match retry_in_wallet(&*wallet, &file_name, || {
+                wallet.sweep_all(sweep_to.to_string())
+            })
+            .await
             {
                 Ok(sweep_all) => {
                     for tx in sweep_all.tx_hash_list {
                         tracing::info!(
                             %tx,
-                            monero_address = %self.main_address,
-                            "Monero transferred back to default wallet");
+                            monero_address = %sweep_to,
+                            "Monero swept from generated wallet");
                     }
                 }
                 Err(error) => {
                     tracing::warn!(
-                        address = %self.main_address,
-                        "Failed to transfer Monero to default wallet: {:#}", error
+                        address = %sweep_to,
+                        "Failed to sweep Monero from generated wallet: {:#}", error
                     );
                 }
             },
@@ -175,19 +269,12 @@ impl Wallet {
             }
         }
 
-        let _ = self
-            .inner
-            .lock()
-            .await
-            .open_wallet(self.name.clone())
-            .await?;
+        let _ = retry_while_unreachable(|| wallet.open_wallet(self.name.clone())).await?;
 
         Ok(())
     }
 
     pub async fn transfer(&self, request: TransferRequest) -> Result<TransferProof> {
-        let inner = self.inner.lock().await;
-
         let TransferRequest {
             public_spend_key,
             public_view_key,
@@ -197,9 +284,14 @@ impl Wallet {
         let destination_address =
             Address::standard(self.network, public_spend_key, public_view_key.into());
 
-        let res = inner
-            .transfer_single(0, amount.as_piconero(), &destination_address.to_string())
-            .await?;
+        let res = retry_while_unreachable(|| async {
+            self.inner
+                .lock()
+                .await
+                .transfer_single(0, amount.as_piconero(), &destination_address.to_string())
+                .await
+        })
+        .await?;
 
         tracing::debug!(
             %amount,
@@ -236,6 +328,20 @@ impl Wallet {
 
         let check_interval = tokio::time::interval(self.sync_interval.div(10));
 
+        if let Some(monerod) = &self.monerod {
+            wait_for_confirmations_with_monerod(
+                monerod,
+                transfer_proof,
+                address,
+                expected,
+                conf_target,
+                check_interval,
+            )
+            .await?;
+
+            return Ok(());
+        }
+
         wait_for_confirmations(
             &self.inner,
             transfer_proof,
@@ -251,24 +357,84 @@ impl Wallet {
     }
 
     pub async fn sweep_all(&self, address: Address) -> Result<Vec<TxHash>> {
-        let sweep_all = self
-            .inner
-            .lock()
-            .await
-            .sweep_all(address.to_string())
-            .await?;
+        let sweep_all = retry_while_unreachable(|| async {
+            self.inner.lock().await.sweep_all(address.to_string()).await
+        })
+        .await?;
 
         let tx_hashes = sweep_all.tx_hash_list.into_iter().map(TxHash).collect();
         Ok(tx_hashes)
     }
 
+    /// Transfers `amount` to `address`, or the whole unlocked balance if no
+    /// amount is given.
+    pub async fn withdraw(&self, address: Address, amount: Option<Amount>) -> Result<Vec<TxHash>> {
+        match amount {
+            Some(amount) => {
+                let transfer = retry_while_unreachable(|| async {
+                    self.inner
+                        .lock()
+                        .await
+                        .transfer_single(0, amount.as_piconero(), &address.to_string())
+                        .await
+                })
+                .await?;
+
+                Ok(vec![TxHash(transfer.tx_hash)])
+            }
+            None => self.sweep_all(address).await,
+        }
+    }
+
     /// Get the balance of the primary account.
     pub async fn get_balance(&self) -> Result<wallet::GetBalance> {
-        Ok(self.inner.lock().await.get_balance(0).await?)
+        Ok(
+            retry_while_unreachable(|| async { self.inner.lock().await.get_balance(0).await })
+                .await?,
+        )
     }
 
     pub async fn block_height(&self) -> Result<BlockHeight> {
-        Ok(self.inner.lock().await.get_height().await?)
+        if let Some(monerod) = &self.monerod {
+            let height = monerod.lock().await.get_block_count().await?.count;
+            return Ok(BlockHeight { height });
+        }
+
+        Ok(
+            retry_while_unreachable(|| async { self.inner.lock().await.get_height().await })
+                .await?,
+        )
+    }
+
+    /// Information about monerod, if the wallet talks to it directly.
+    pub async fn daemon_info(&self) -> Result<Option<monerod::GetInfo>> {
+        match &self.monerod {
+            Some(monerod) => Ok(Some(monerod.lock().await.get_info().await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the subaddress of the primary account of the default wallet with
+    /// the given label, creating it if it does not exist yet.
+    pub async fn subaddress(&self, label: &str) -> Result<Address> {
+        // Hold the lock across all calls so no other wallet is loaded in between
+        let wallet = self.inner.lock().await;
+
+        let _ = wallet.open_wallet(self.name.clone()).await?;
+
+        let existing = wallet
+            .get_address(0)
+            .await?
+            .addresses
+            .into_iter()
+            .find(|subaddress| subaddress.label == label);
+
+        let address = match existing {
+            Some(subaddress) => subaddress.address,
+            None => wallet.create_address(0, label.to_owned()).await?.address,
+        };
+
+        Address::from_str(&address).context("Failed to parse subaddress from monero-wallet-rpc")
     }
 
     pub fn get_main_address(&self) -> Address {
@@ -276,44 +442,145 @@ impl Wallet {
     }
 
     pub async fn refresh(&self, max_attempts: usize) -> Result<Refreshed> {
-        const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+        refresh(&*self.inner.lock().await, &self.name, max_attempts).await
+    }
+}
 
-        for i in 1..=max_attempts {
-            tracing::info!(name = %self.name, attempt=i, "Syncing Monero wallet");
+/// Syncs the wallet `name` which is currently loaded, retrying up to
+/// `max_attempts` times.
+async fn refresh(wallet: &wallet::Client, name: &str, max_attempts: usize) -> Result<Refreshed> {
+    const RETRY_INTERVAL: Duration = Duration::from_secs(1);
 
-            let result = self.inner.lock().await.refresh().await;
+    for i in 1..=max_attempts {
+        tracing::info!(%name, attempt=i, "Syncing Monero wallet");
 
-            match result {
-                Ok(refreshed) => {
-                    tracing::info!(name = %self.name, "Monero wallet synced");
-                    return Ok(refreshed);
-                }
-                Err(error) => {
-                    let attempts_left = max_attempts - i;
-
-                    // We would not want to fail here if the height is not available
-                    // as it is not critical for the operation of the wallet.
-                    // We can just log a warning and continue.
-                    let height = match self.inner.lock().await.get_height().await {
-                        Ok(height) => height.to_string(),
-                        Err(_) => {
-                            tracing::warn!(name = %self.name, "Failed to fetch Monero wallet height during sync");
-                            "unknown".to_string()
-                        }
-                    };
-
-                    tracing::warn!(attempt=i, %height, %attempts_left, name = %self.name, %error, "Failed to sync Monero wallet");
-
-                    if attempts_left == 0 {
-                        return Err(error.into());
+        let result = retry_in_wallet(wallet, name, || wallet.refresh()).await;
+
+        match result {
+            Ok(refreshed) => {
+                tracing::info!(%name, "Monero wallet synced");
+                return Ok(refreshed);
+            }
+            Err(error) => {
+                let attempts_left = max_attempts - i;
+
+                // We would not want to fail here if the height is not available
+                // as it is not critical for the operation of the wallet.
+                // We can just log a warning and continue.
+                let height = match wallet.get_height().await {
+                    Ok(height) => height.to_string(),
+                    Err(_) => {
+                        tracing::warn!(%name, "Failed to fetch Monero wallet height during sync");
+                        "unknown".to_string()
                     }
+                };
+
+                tracing::warn!(attempt=i, %height, %attempts_left, %name, %error, "Failed to sync Monero wallet");
+
+                if attempts_left == 0 {
+                    return Err(error.into());
                 }
             }
-
-            tokio::time::sleep(RETRY_INTERVAL).await;
         }
-        unreachable!("Loop should have returned by now");
+
+        tokio::time::sleep(RETRY_INTERVAL).await;
     }
+    unreachable!("Loop should have returned by now");
+}
+
+/// Retries `operation` for as long as `monero-wallet-rpc` cannot be reached or
+/// has no wallet loaded, which is the case while it is being restarted.
+///
+/// Only errors where the request was never processed are retried, so this is
+/// safe to use for operations that are not idempotent.
+async fn retry_while_unreachable<T, F, Fut>(
+    operation: F,
+) -> Result<T, jsonrpc::Error<reqwest::Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, jsonrpc::Error<reqwest::Error>>>,
+{
+    retry_while(
+        |error| is_connect_error(error) || is_no_wallet_loaded(error),
+        operation,
+    )
+    .await
+}
+
+/// Like [`retry_while_unreachable`] for operations on the wallet `name`, which
+/// is loaded again before retrying because a restarted monero-wallet-rpc only
+/// loads the default wallet.
+async fn retry_in_wallet<C, T, F, Fut>(
+    wallet: &C,
+    name: &str,
+    mut operation: F,
+) -> Result<T, jsonrpc::Error<reqwest::Error>>
+where
+    C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, jsonrpc::Error<reqwest::Error>>>,
+{
+    let mut retrying = false;
+
+    retry_while_unreachable(|| {
+        let load_wallet = std::mem::replace(&mut retrying, true);
+        let attempt = operation();
+
+        async move {
+            if load_wallet {
+                let _ = wallet.open_wallet(name.to_owned()).await?;
+            }
+
+            attempt.await
+        }
+    })
+    .await
+}
+
+fn is_connect_error(error: &jsonrpc::Error<reqwest::Error>) -> bool {
+    matches!(error, jsonrpc::Error::Client(client) if client.is_connect())
+}
+
+fn is_no_wallet_loaded(error: &jsonrpc::Error<reqwest::Error>) -> bool {
+    matches!(
+        error,
+        jsonrpc::Error::JsonRpc(jsonrpc::JsonRpcError { code: -13, .. })
+    )
+}
+
+async fn retry_while<T, F, Fut>(
+    is_transient: impl Fn(&jsonrpc::Error<reqwest::Error>) -> bool,
+    mut operation: F,
+) -> Result<T, jsonrpc::Error<reqwest::Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, jsonrpc::Error<reqwest::Error>>>,
+{
+    let backoff = backoff::ExponentialBackoff {
+        max_elapsed_time: Some(WALLET_RPC_RESTART_TIMEOUT),
+        ..backoff::ExponentialBackoff::default()
+    };
+
+    backoff::future::retry_notify(
+        backoff,
+        || {
+            operation().map_err(|error| {
+                if is_transient(&error) {
+                    backoff::Error::transient(error)
+                } else {
+                    backoff::Error::Permanent(error)
+                }
+            })
+        },
+        |error, next: Duration| {
+            tracing::warn!(
+                "monero-wallet-rpc is unavailable, retrying in {}ms. Error {:#}",
+                next.as_millis(),
+                error
+            );
+        },
+    )
+    .await
 }
 
 #[derive(Debug)]
@@ -347,16 +614,24 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
         check_interval.tick().await; // tick() at the beginning of the loop so every `continue` tick()s as well
 
         let txid = transfer_proof.tx_hash().to_string();
+
+        // A missing wallet is handled below, so only wait for a restarted
+        // monero-wallet-rpc to come back
+        let result = retry_while(is_connect_error, || async {
+            client
+                .lock()
+                .await
+                .check_tx_key(
+                    txid.clone(),
+                    transfer_proof.tx_key.to_string(),
+                    to_address.to_string(),
+                )
+                .await
+        })
+        .await;
         let client = client.lock().await;
 
-        let tx = match client
-            .check_tx_key(
-                txid.clone(),
-                transfer_proof.tx_key.to_string(),
-                to_address.to_string(),
-            )
-            .await
-        {
+        let tx = match result {
             Ok(proof) => proof,
             Err(jsonrpc::Error::JsonRpc(jsonrpc::JsonRpcError {
                 code: -1,
@@ -411,6 +686,75 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
     Ok(())
 }
 
+/// Like [`wait_for_confirmations`] but decodes the transaction fetched from
+/// monerod with the transaction key instead of asking `monero-wallet-rpc`.
+async fn wait_for_confirmations_with_monerod(
+    monerod: &Mutex<monerod::Client>,
+    transfer_proof: TransferProof,
+    to_address: Address,
+    expected: Amount,
+    conf_target: u64,
+    mut check_interval: Interval,
+) -> Result<(), InsufficientFunds> {
+    let txid = transfer_proof.tx_hash().to_string();
+    let tx_hash = match hex::decode(&txid) {
+        Ok(bytes) if bytes.len() == 32 => monero::Hash::from_slice(&bytes),
+        _ => {
+            tracing::error!(%txid, "Transfer proof contains an invalid transaction hash");
+            return Err(InsufficientFunds {
+                expected,
+                actual: Amount::ZERO,
+            });
+        }
+    };
+
+    let mut seen_confirmations = 0u64;
+
+    while seen_confirmations < conf_target {
+        check_interval.tick().await; // tick() at the beginning of the loop so every `continue` tick()s as well
+
+        let client = monerod.lock().await.clone(); // the daemon may be switched while waiting
+        let tx = match monero_wallet::transfer::check_tx_key(
+            &client,
+            tx_hash,
+            transfer_proof.tx_key,
+            to_address,
+        )
+        .await
+        {
+            Ok(tx) => tx,
+            Err(error) => {
+                tracing::debug!(
+                    %txid,
+                    "Failed to verify tx with monerod: {:#}", error
+                );
+                continue; // the transaction may not be propagated yet or the daemon is unreachable
+            }
+        };
+
+        let received = Amount::from_piconero(tx.received);
+
+        if received != expected {
+            return Err(InsufficientFunds {
+                expected,
+                actual: received,
+            });
+        }
+
+        if tx.confirmations > seen_confirmations {
+            seen_confirmations = tx.confirmations;
+            tracing::info!(
+                %txid,
+                %seen_confirmations,
+                needed_confirmations = %conf_target,
+                "Received new confirmation for Monero lock tx"
+            );
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -556,6 +900,66 @@ DEBUG swap::monero::wallet: Opening wallet `foo-wallet` because no wallet is loa
         );
     }
 
+    #[tokio::test]
+    async fn retries_while_wallet_rpc_is_unreachable() {
+        let attempts = AtomicU32::new(0);
+
+        let result = retry_while_unreachable(|| async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err(jsonrpc::Error::Client(connect_error().await));
+            }
+
+            Ok(5)
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 5);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_errors_of_processed_requests() {
+        let client = DummyClient::new(vec![
+            Err((-1, "Failed to get transaction".to_owned())),
+            Ok(CheckTxKey {
+                confirmations: 1,
+                received: 100,
+            }),
+        ]);
+
+        let result = retry_while_unreachable(|| {
+            client.check_tx_key("txid".to_owned(), "key".to_owned(), "address".to_owned())
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(client.check_tx_key_invocations.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn loads_wallet_again_before_retrying() {
+        let client = DummyClient::new(vec![
+            Err((-13, "No wallet file".to_owned())),
+            Ok(CheckTxKey {
+                confirmations: 1,
+                received: 100,
+            }),
+        ]);
+
+        let result = retry_in_wallet(&client, "foo-wallet", || {
+            client.check_tx_key("txid".to_owned(), "key".to_owned(), "address".to_owned())
+        })
+        .await;
+
+        assert_eq!(result.unwrap().confirmations, 1);
+        assert_eq!(client.check_tx_key_invocations.load(Ordering::SeqCst), 2);
+        assert_eq!(client.open_wallet_invocations.load(Ordering::SeqCst), 1);
+    }
+
+    async fn connect_error() -> reqwest::Error {
+        reqwest::get("http://127.0.0.1:1").await.unwrap_err()
+    }
+
     type ErrorCode = i64;
     type ErrorMessage = String;
 
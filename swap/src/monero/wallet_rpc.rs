@@ -1,73 +1,78 @@
+use super::daemon::{choose_monero_daemon, DaemonStatus, MoneroDaemon};
+use super::Wallet;
 use ::monero::Network;
-use anyhow::{bail, Context, Error, Result};
+use anyhow::{bail, Context, Result};
 use big_bytes::BigByte;
 use data_encoding::HEXLOWER;
-use futures::{StreamExt, TryStreamExt};
-use monero_rpc::wallet::{Client, MoneroWalletRpc as _};
+use futures::{Future, StreamExt, TryStreamExt};
+use monero_rpc::wallet::{Client, Credentials, MoneroWalletRpc as _, Version};
+use pgp::composed::{CleartextSignedMessage, Deserializable, SignedPublicKey};
+use pgp::types::KeyTrait;
 use reqwest::header::CONTENT_LENGTH;
 use reqwest::Url;
-use serde::Deserialize;
 use sha2::{Digest, Sha256};
 use std::fmt;
-use std::fmt::{Debug, Display, Formatter};
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::fs::{remove_file, OpenOptions};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use tokio_util::codec::{BytesCodec, FramedRead};
 use tokio_util::io::StreamReader;
 
-// See: https://www.moneroworld.com/#nodes, https://monero.fail
-// We don't need any testnet nodes because we don't support testnet at all
-const MONERO_DAEMONS: [MoneroDaemon; 17] = [
-    MoneroDaemon::new("xmr-node.cakewallet.com", 18081, Network::Mainnet),
-    MoneroDaemon::new("nodex.monerujo.io", 18081, Network::Mainnet),
-    MoneroDaemon::new("node.moneroworld.com", 18089, Network::Mainnet),
-    MoneroDaemon::new("nodes.hashvault.pro", 18081, Network::Mainnet),
-    MoneroDaemon::new("p2pmd.xmrvsbeast.com", 18081, Network::Mainnet),
-    MoneroDaemon::new("node.monerodevs.org", 18089, Network::Mainnet),
-    MoneroDaemon::new("xmr-node-usa-east.cakewallet.com", 18081, Network::Mainnet),
-    MoneroDaemon::new("xmr-node-uk.cakewallet.com", 18081, Network::Mainnet),
-    MoneroDaemon::new("node.community.rino.io", 18081, Network::Mainnet),
-    MoneroDaemon::new("testingjohnross.com", 20031, Network::Mainnet),
-    MoneroDaemon::new("xmr.litepay.ch", 18081, Network::Mainnet),
-    MoneroDaemon::new("node.trocador.app", 18089, Network::Mainnet),
-    MoneroDaemon::new("stagenet.xmr-tw.org", 38081, Network::Stagenet),
-    MoneroDaemon::new("node.monerodevs.org", 38089, Network::Stagenet),
-    MoneroDaemon::new("singapore.node.xmr.pm", 38081, Network::Stagenet),
-    MoneroDaemon::new("xmr-lux.boldsuck.org", 38081, Network::Stagenet),
-    MoneroDaemon::new("stagenet.community.rino.io", 38081, Network::Stagenet),
-];
-
 #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
 compile_error!("unsupported operating system");
 
+/// Where releases are downloaded from unless configured otherwise.
+const DEFAULT_DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/";
+
+/// The hashes of the latest release, signed by binaryFate.
+const HASHES_URL: &str = "https://www.getmonero.org/downloads/hashes.txt";
+
+/// binaryFate's GPG key which signs the hashes of all Monero releases, taken
+/// from `utils/gpg_keys/binaryfate.asc` in the Monero repository.
+const SIGNING_KEY: &str = include_str!("binaryfate.asc");
+
+/// Fingerprint of [`SIGNING_KEY`].
+const SIGNING_KEY_FINGERPRINT: &str = "81AC591FE9C4B65C5806AFC3F0AF4D462A0BDF92";
+
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+const ARCHIVE_PREFIX: &str = "monero-mac-x64-";
 #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
-const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-mac-x64-v0.18.3.1.tar.bz2";
+const ARCHIVE_EXTENSION: &str = "tar.bz2";
 #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
 const DOWNLOAD_HASH: &str = "7f8bd9364ef16482b418aa802a65be0e4cc660c794bb5d77b2d17bc84427883a";
 
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
-const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-mac-armv8-v0.18.3.1.tar.bz2";
+const ARCHIVE_PREFIX: &str = "monero-mac-armv8-";
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+const ARCHIVE_EXTENSION: &str = "tar.bz2";
 #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
 const DOWNLOAD_HASH: &str = "915288b023cb5811e626e10052adc6ac5323dd283c5a25b91059b0fb86a21fb6";
 
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
-const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-linux-x64-v0.18.3.1.tar.bz2";
+const ARCHIVE_PREFIX: &str = "monero-linux-x64-";
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const ARCHIVE_EXTENSION: &str = "tar.bz2";
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
 const DOWNLOAD_HASH: &str = "23af572fdfe3459b9ab97e2e9aa7e3c11021c955d6064b801a27d7e8c21ae09d";
 
 #[cfg(all(target_os = "linux", target_arch = "arm"))]
-const DOWNLOAD_URL: &str =
-    "https://downloads.getmonero.org/cli/monero-linux-armv7-v0.18.3.1.tar.bz2";
+const ARCHIVE_PREFIX: &str = "monero-linux-armv7-";
+#[cfg(all(target_os = "linux", target_arch = "arm"))]
+const ARCHIVE_EXTENSION: &str = "tar.bz2";
 #[cfg(all(target_os = "linux", target_arch = "arm"))]
 const DOWNLOAD_HASH: &str = "2ea2c8898cbab88f49423f4f6c15f2a94046cb4bbe827493dd061edc0fd5f1ca";
 
 #[cfg(target_os = "windows")]
-const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-win-x64-v0.18.3.1.zip";
+const ARCHIVE_PREFIX: &str = "monero-win-x64-";
+#[cfg(target_os = "windows")]
+const ARCHIVE_EXTENSION: &str = "zip";
 #[cfg(target_os = "windows")]
 const DOWNLOAD_HASH: &str = "35dcc4bee4caad3442659d37837e0119e4649a77f2e3b5e80dd6d9b8fc4fb6ad";
 
@@ -77,234 +82,421 @@ const PACKED_FILE: &str = "monero-wallet-rpc";
 #[cfg(target_os = "windows")]
 const PACKED_FILE: &str = "monero-wallet-rpc.exe";
 
+/// The release that is downloaded by default. [`DOWNLOAD_HASH`] is the hash of
+/// its archive, other releases are checked against the signed `hashes.txt`.
 const WALLET_RPC_VERSION: &str = "v0.18.3.1";
 
+/// Oldest wallet RPC version (major, minor) that supports all calls made during
+/// a swap.
+const MIN_WALLET_RPC_VERSION: (u32, u32) = (1, 22);
+
 #[derive(Debug, Clone, Copy, thiserror::Error)]
 #[error("monero wallet rpc executable not found in downloaded archive")]
 pub struct ExecutableNotFoundInArchive;
 
-pub struct WalletRpcProcess {
-    _child: Child,
-    port: u16,
-}
+/// How often the supervisor asks `monero-wallet-rpc` for its version.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long `monero-wallet-rpc` may take to answer a health check.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
 
-struct MoneroDaemon {
-    address: &'static str,
+/// How long `monero-wallet-rpc` may fail all health checks before it is
+/// considered hung and restarted. It does not answer while it processes a long
+/// running call, e.g. syncing a wallet generated with an old restore height, so
+/// this has to be much longer than any such call.
+const UNRESPONSIVE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+pub struct WalletRpcProcess {
+    child: Child,
     port: u16,
+    wallet_rpc: WalletRpc,
     network: Network,
+    daemon_address: String,
 }
 
-impl MoneroDaemon {
-    const fn new(address: &'static str, port: u16, network: Network) -> Self {
-        Self {
-            address,
-            port,
-            network,
-        }
+impl WalletRpcProcess {
+    pub fn endpoint(&self) -> Url {
+        Url::parse(&format!("http://127.0.0.1:{}/json_rpc", self.port))
+            .expect("Static url template is always valid")
     }
 
-    /// Checks if the Monero daemon is available by sending a request to its `get_info` endpoint.
-    async fn is_available(&self, client: &reqwest::Client) -> Result<bool, Error> {
-        let url = format!("http://{}:{}/get_info", self.address, self.port);
-        let res = client
-            .get(url)
-            .send()
-            .await
-            .context("Failed to send request to get_info endpoint")?;
+    /// Hands the process over to a background task that restarts it on the
+    /// same port whenever it exits or stops responding for
+    /// [`UNRESPONSIVE_TIMEOUT`], and re-opens the wallet afterwards.
+    ///
+    /// The process is killed once the returned supervisor is dropped.
+    pub fn supervise(
+        self,
+        wallet: Arc<Wallet>,
+        daemon_status: Option<Arc<RwLock<DaemonStatus>>>,
+    ) -> WalletRpcSupervisor {
+        let task = tokio::spawn(self.supervise_forever(wallet, daemon_status));
+
+        WalletRpcSupervisor { task }
+    }
 
-        let json: MoneroDaemonGetInfoResponse = res
-            .json()
-            .await
-            .context("Failed to deserialize daemon get_info response")?;
-
-        let is_status_ok = json.status == "OK";
-        let is_synchronized = json.synchronized;
-        let is_correct_network = match self.network {
-            Network::Mainnet => json.mainnet,
-            Network::Stagenet => json.stagenet,
-            Network::Testnet => json.testnet,
+    async fn supervise_forever(
+        mut self,
+        wallet: Arc<Wallet>,
+        daemon_status: Option<Arc<RwLock<DaemonStatus>>>,
+    ) {
+        loop {
+            let crash = self.wait_for_crash().await;
+            tracing::error!(port = %self.port, "monero-wallet-rpc crashed: {}", crash);
+
+            let _ = self.child.kill().await;
+
+            // The daemon may have been switched since the process was started
+            if let Some(status) = &daemon_status {
+                self.daemon_address = status.read().await.current.clone();
+            }
+
+            let backoff = backoff::ExponentialBackoff {
+                max_elapsed_time: None,
+                ..backoff::ExponentialBackoff::default()
+            };
+
+            let wallet_rpc = &self.wallet_rpc;
+            let daemon_address = self.daemon_address.as_str();
+            let (network, port) = (self.network, self.port);
+            let wallet = &wallet;
+
+            let restart = backoff::future::retry_notify(
+                backoff,
+                || async move {
+                    let child = wallet_rpc
+                        .spawn(network, daemon_address, port)
+                        .await
+                        .map_err(backoff::Error::transient)?;
+                    wallet.re_open().await.map_err(backoff::Error::transient)?;
+
+                    Ok(child)
+                },
+                |error, next: Duration| {
+                    tracing::warn!(
+                        "Failed to restart monero-wallet-rpc, retrying in {}ms. Error {:#}",
+                        next.as_millis(),
+                        error
+                    );
+                },
+            )
+            .await;
+
+            match restart {
+                Ok(child) => {
+                    self.child = child;
+                    tracing::info!(port = %self.port, "Restarted monero-wallet-rpc");
+                }
+                Err(error) => {
+                    tracing::error!("Giving up on restarting monero-wallet-rpc: {:#}", error);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Resolves once the process exited or did not answer health checks for
+    /// [`UNRESPONSIVE_TIMEOUT`].
+    async fn wait_for_crash(&mut self) -> Crash {
+        let client = match Client::localhost(self.port) {
+            Ok(client) => client,
+            Err(error) => return Crash::Unresponsive(error.to_string()),
         };
 
-        Ok(is_status_ok && is_synchronized && is_correct_network)
+        watch_process(
+            &mut self.child,
+            || async { client.get_version().await.map(|_| ()) },
+            HEALTH_CHECK_INTERVAL,
+            UNRESPONSIVE_TIMEOUT,
+        )
+        .await
     }
 }
 
-impl Display for MoneroDaemon {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{}", self.address, self.port)
+/// Resolves once `child` exited or `health_check` did not succeed for
+/// `unresponsive_timeout`.
+async fn watch_process<F, Fut, E>(
+    child: &mut Child,
+    mut health_check: F,
+    interval: Duration,
+    unresponsive_timeout: Duration,
+) -> Crash
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: fmt::Display,
+{
+    let mut health_checks = tokio::time::interval(interval);
+    let mut last_response = Instant::now();
+
+    loop {
+        tokio::select! {
+            status = child.wait() => {
+                return match status {
+                    Ok(status) => Crash::Exited(status.to_string()),
+                    Err(error) => Crash::Exited(error.to_string()),
+                };
+            }
+            _ = health_checks.tick() => {
+                match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, health_check()).await {
+                    Ok(Ok(())) => last_response = Instant::now(),
+                    Ok(Err(error)) => {
+                        tracing::debug!("monero-wallet-rpc health check failed: {:#}", error);
+                    }
+                    Err(_) => {
+                        tracing::debug!("monero-wallet-rpc health check timed out");
+                    }
+                }
+
+                if last_response.elapsed() >= unresponsive_timeout {
+                    return Crash::Unresponsive(format!(
+                        "no response to health checks for {}s",
+                        last_response.elapsed().as_secs()
+                    ));
+                }
+            }
+        }
     }
 }
 
-#[derive(Deserialize)]
-struct MoneroDaemonGetInfoResponse {
-    status: String,
-    synchronized: bool,
-    mainnet: bool,
-    stagenet: bool,
-    testnet: bool,
+#[derive(Debug)]
+enum Crash {
+    Exited(String),
+    Unresponsive(String),
 }
 
-/// Chooses an available Monero daemon based on the specified network.
-async fn choose_monero_daemon(network: Network) -> Result<&'static MoneroDaemon, Error> {
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(30))
-        .https_only(false)
-        .build()?;
-
-    // We only want to check for daemons that match the specified network
-    let network_matching_daemons = MONERO_DAEMONS
-        .iter()
-        .filter(|daemon| daemon.network == network);
-
-    for daemon in network_matching_daemons {
-        match daemon.is_available(&client).await {
-            Ok(true) => {
-                tracing::debug!(%daemon, "Found available Monero daemon");
-                return Ok(daemon);
-            }
-            Err(err) => {
-                tracing::debug!(%err, %daemon, "Failed to connect to Monero daemon");
-                continue;
-            }
-            Ok(false) => continue,
+impl fmt::Display for Crash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Crash::Exited(status) => write!(f, "process exited ({})", status),
+            Crash::Unresponsive(reason) => write!(f, "RPC is unresponsive ({})", reason),
         }
     }
+}
 
-    bail!("No Monero daemon could be found. Please specify one manually or try again later.")
+/// Keeps a supervised `monero-wallet-rpc` alive. Dropping it stops the
+/// supervisor and kills the process.
+pub struct WalletRpcSupervisor {
+    task: JoinHandle<()>,
 }
 
-impl WalletRpcProcess {
-    pub fn endpoint(&self) -> Url {
-        Url::parse(&format!("http://127.0.0.1:{}/json_rpc", self.port))
-            .expect("Static url template is always valid")
+impl Drop for WalletRpcSupervisor {
+    fn drop(&mut self) {
+        self.task.abort();
     }
 }
 
+/// Where to take `monero-wallet-rpc` from.
+#[derive(Debug, Clone, Default)]
+pub struct WalletRpcOptions {
+    /// A locally installed `monero-wallet-rpc` that is used instead of
+    /// downloading one.
+    pub binary: Option<PathBuf>,
+    /// Never download `monero-wallet-rpc`.
+    pub offline: bool,
+    /// The release to download instead of the default one, e.g. `v0.18.3.3`.
+    pub version: Option<String>,
+    /// Where to download releases from instead of [`DEFAULT_DOWNLOAD_URL`].
+    pub download_url: Option<Url>,
+}
+
+/// A `monero-wallet-rpc` operated by someone else which is used instead of
+/// starting one locally.
+#[derive(Debug, Clone)]
+pub struct RemoteWalletRpc {
+    pub url: Url,
+    /// Answers the digest challenge of a wallet RPC started with
+    /// `--rpc-login`.
+    pub credentials: Option<Credentials>,
+    /// Prefix of the file names of all wallets the swap opens on the remote
+    /// wallet RPC.
+    pub wallet_prefix: String,
+}
+
+#[derive(Clone)]
 pub struct WalletRpc {
     working_dir: PathBuf,
+    exec_path: PathBuf,
 }
 
 impl WalletRpc {
-    pub async fn new(working_dir: impl AsRef<Path>) -> Result<WalletRpc> {
+    pub async fn new(
+        working_dir: impl AsRef<Path>,
+        options: WalletRpcOptions,
+    ) -> Result<WalletRpc> {
         let working_dir = working_dir.as_ref();
 
         if !working_dir.exists() {
             tokio::fs::create_dir(working_dir).await?;
         }
 
+        if let Some(binary) = options.binary {
+            if !binary.exists() {
+                bail!("monero-wallet-rpc not found at {}", binary.display());
+            }
+
+            tracing::info!(path = %binary.display(), "Using user-provided monero-wallet-rpc");
+
+            return Ok(WalletRpc {
+                working_dir: working_dir.to_path_buf(),
+                exec_path: binary,
+            });
+        }
+
         let monero_wallet_rpc = WalletRpc {
             working_dir: working_dir.to_path_buf(),
+            exec_path: working_dir.join(PACKED_FILE),
         };
 
         if monero_wallet_rpc.archive_path().exists() {
             remove_file(monero_wallet_rpc.archive_path()).await?;
         }
 
+        let version = options
+            .version
+            .unwrap_or_else(|| WALLET_RPC_VERSION.to_string());
+
         // check the monero-wallet-rpc version
         let exec_path = monero_wallet_rpc.exec_path();
         tracing::debug!("RPC exec path: {}", exec_path.display());
 
         if exec_path.exists() {
             let output = Command::new(&exec_path).arg("--version").output().await?;
-            let version = String::from_utf8_lossy(&output.stdout);
-            tracing::debug!("RPC version output: {}", version);
-
-            if !version.contains(WALLET_RPC_VERSION) {
-                tracing::info!("Removing old version of monero-wallet-rpc");
-                tokio::fs::remove_file(exec_path).await?;
+            let installed_version = String::from_utf8_lossy(&output.stdout);
+            tracing::debug!("RPC version output: {}", installed_version);
+
+            if !installed_version.contains(&version) {
+                if options.offline {
+                    tracing::warn!(
+                        %version,
+                        "Installed monero-wallet-rpc is not the expected version, keeping it because downloads are disabled"
+                    );
+                } else {
+                    tracing::info!("Removing old version of monero-wallet-rpc");
+                    tokio::fs::remove_file(exec_path).await?;
+                }
             }
         }
 
         // if monero-wallet-rpc doesn't exist then download it
         if !monero_wallet_rpc.exec_path().exists() {
-            let mut options = OpenOptions::new();
-            let mut file = options
-                .read(true)
-                .write(true)
-                .create_new(true)
-                .open(monero_wallet_rpc.archive_path())
-                .await?;
-
-            let response = reqwest::get(DOWNLOAD_URL).await?;
-
-            let content_length = response.headers()[CONTENT_LENGTH]
-                .to_str()
-                .context("Failed to convert content-length to string")?
-                .parse::<u64>()?;
-
-            tracing::info!(
-                progress="0%",
-                size=%content_length.big_byte(2),
-                download_url=DOWNLOAD_URL,
-                "Downloading monero-wallet-rpc",
-            );
+            if options.offline {
+                bail!(
+                    "monero-wallet-rpc is not installed in {} and downloads are disabled, provide a locally installed monero-wallet-rpc instead",
+                    working_dir.display()
+                );
+            }
 
-            let mut hasher = Sha256::new();
+            let file_name = format!("{}{}.{}", ARCHIVE_PREFIX, version, ARCHIVE_EXTENSION);
+            let mut download_url = match options.download_url {
+                Some(download_url) => download_url,
+                None => Url::parse(DEFAULT_DOWNLOAD_URL)?,
+            };
+            // Without a trailing slash, joining would replace the last path segment
+            if !download_url.path().ends_with('/') {
+                let path = format!("{}/", download_url.path());
+                download_url.set_path(&path);
+            }
+            let url = download_url
+                .join(&file_name)
+                .context("Failed to build monero-wallet-rpc download url")?;
 
-            let byte_stream = response
-                .bytes_stream()
-                .map_ok(|bytes| {
-                    hasher.update(&bytes);
-                    bytes
-                })
-                .map_err(|err| std::io::Error::new(ErrorKind::Other, err));
+            let expected_hash = expected_hash(&file_name, &version).await?;
 
-            #[cfg(not(target_os = "windows"))]
-            let mut stream = FramedRead::new(
-                async_compression::tokio::bufread::BzDecoder::new(StreamReader::new(byte_stream)),
-                BytesCodec::new(),
-            )
-            .map_ok(|bytes| bytes.freeze());
+            monero_wallet_rpc.download(url, &expected_hash).await?;
 
-            #[cfg(target_os = "windows")]
-            let mut stream = FramedRead::new(StreamReader::new(byte_stream), BytesCodec::new())
-                .map_ok(|bytes| bytes.freeze());
-
-            let (mut received, mut notified) = (0, 0);
-            while let Some(chunk) = stream.next().await {
-                let bytes = chunk?;
-                received += bytes.len();
-                // the stream is decompressed as it is downloaded
-                // file is compressed approx 3:1 in bz format
-                let total = 3 * content_length;
-                let percent = 100 * received as u64 / total;
-                if percent != notified && percent % 10 == 0 {
-                    tracing::info!(
-                        progress=format!("{}%", percent),
-                        size=%content_length.big_byte(2),
-                        download_url=DOWNLOAD_URL,
-                        "Downloading monero-wallet-rpc",
-                    );
-                    notified = percent;
-                }
-                file.write_all(&bytes).await?;
-            }
+            tracing::debug!("Extracting archive");
+            Self::extract_archive(&monero_wallet_rpc).await?;
+        }
+        Ok(monero_wallet_rpc)
+    }
 
-            tracing::info!(
-                progress="100%",
-                size=%content_length.big_byte(2),
-                download_url=DOWNLOAD_URL,
-                "Downloading monero-wallet-rpc",
-            );
+    async fn download(&self, url: Url, expected_hash: &str) -> Result<()> {
+        let mut options = OpenOptions::new();
+        let mut file = options
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(self.archive_path())
+            .await?;
 
-            let result = hasher.finalize();
-            let result_hash = HEXLOWER.encode(result.as_ref());
-            if result_hash != DOWNLOAD_HASH {
-                bail!(
-                    "SHA256 of download ({}) does not match expected ({})!",
-                    result_hash,
-                    DOWNLOAD_HASH
+        let response = reqwest::get(url.clone()).await?.error_for_status()?;
+
+        let content_length = response.headers()[CONTENT_LENGTH]
+            .to_str()
+            .context("Failed to convert content-length to string")?
+            .parse::<u64>()?;
+
+        tracing::info!(
+            progress="0%",
+            size=%content_length.big_byte(2),
+            download_url=%url,
+            "Downloading monero-wallet-rpc",
+        );
+
+        let mut hasher = Sha256::new();
+
+        let byte_stream = response
+            .bytes_stream()
+            .map_ok(|bytes| {
+                hasher.update(&bytes);
+                bytes
+            })
+            .map_err(|err| std::io::Error::new(ErrorKind::Other, err));
+
+        #[cfg(not(target_os = "windows"))]
+        let mut stream = FramedRead::new(
+            async_compression::tokio::bufread::BzDecoder::new(StreamReader::new(byte_stream)),
+            BytesCodec::new(),
+        )
+        .map_ok(|bytes| bytes.freeze());
+
+        #[cfg(target_os = "windows")]
+        let mut stream = FramedRead::new(StreamReader::new(byte_stream), BytesCodec::new())
+            .map_ok(|bytes| bytes.freeze());
+
+        let (mut received, mut notified) = (0, 0);
+        while let Some(chunk) = stream.next().await {
+            let bytes = chunk?;
+            received += bytes.len();
+            // the stream is decompressed as it is downloaded
+            // file is compressed approx 3:1 in bz format
+            let total = 3 * content_length;
+            let percent = 100 * received as u64 / total;
+            if percent != notified && percent % 10 == 0 {
+                tracing::info!(
+                    progress=format!("{}%", percent),
+                    size=%content_length.big_byte(2),
+                    download_url=%url,
+                    "Downloading monero-wallet-rpc",
                 );
-            } else {
-                tracing::debug!("Hashes match");
+                notified = percent;
             }
+            file.write_all(&bytes).await?;
+        }
 
-            file.flush().await?;
+        tracing::info!(
+            progress="100%",
+            size=%content_length.big_byte(2),
+            download_url=%url,
+            "Downloading monero-wallet-rpc",
+        );
 
-            tracing::debug!("Extracting archive");
-            Self::extract_archive(&monero_wallet_rpc).await?;
+        let result = hasher.finalize();
+        let result_hash = HEXLOWER.encode(result.as_ref());
+        if result_hash != expected_hash {
+            bail!(
+                "SHA256 of download ({}) does not match expected ({})!",
+                result_hash,
+                expected_hash
+            );
+        } else {
+            tracing::debug!("Hashes match");
         }
-        Ok(monero_wallet_rpc)
+
+        file.flush().await?;
+
+        Ok(())
     }
 
     pub async fn run(
@@ -319,9 +511,26 @@ impl WalletRpc {
 
         let daemon_address = match daemon_address {
             Some(daemon_address) => daemon_address,
-            None => choose_monero_daemon(network).await?.to_string(),
+            None => {
+                let (daemon, _) = choose_monero_daemon(&MoneroDaemon::defaults(network)).await?;
+                daemon.to_string()
+            }
         };
 
+        let child = self.spawn(network, &daemon_address, port).await?;
+
+        Ok(WalletRpcProcess {
+            child,
+            port,
+            wallet_rpc: self.clone(),
+            network,
+            daemon_address,
+        })
+    }
+
+    /// Starts `monero-wallet-rpc` on the given port and waits until it answers
+    /// requests.
+    async fn spawn(&self, network: Network, daemon_address: &str, port: u16) -> Result<Child> {
         tracing::debug!(
             %daemon_address,
             %port,
@@ -379,12 +588,10 @@ impl WalletRpc {
         }
 
         // Send a json rpc request to make sure monero_wallet_rpc is ready
-        Client::localhost(port)?.get_version().await?;
+        let version = Client::localhost(port)?.get_version().await?;
+        ensure_compatible(version)?;
 
-        Ok(WalletRpcProcess {
-            _child: child,
-            port,
-        })
+        Ok(child)
     }
 
     fn archive_path(&self) -> PathBuf {
@@ -392,7 +599,7 @@ impl WalletRpc {
     }
 
     fn exec_path(&self) -> PathBuf {
-        self.working_dir.join(PACKED_FILE)
+        self.exec_path.clone()
     }
 
     #[cfg(not(target_os = "windows"))]
@@ -433,7 +640,6 @@ impl WalletRpc {
     #[cfg(target_os = "windows")]
     async fn extract_archive(monero_wallet_rpc: &Self) -> Result<()> {
         use std::fs::File;
-        use tokio::task::JoinHandle;
         use zip::ZipArchive;
 
         let archive_path = monero_wallet_rpc.archive_path();
@@ -462,122 +668,270 @@ impl WalletRpc {
     }
 }
 
+/// Returns the SHA-256 of the release archive `file_name`.
+///
+/// The hash of the default release is compiled in. Any other release must be
+/// listed in the `hashes.txt` of the latest release, which has to be signed by
+/// [`SIGNING_KEY`].
+async fn expected_hash(file_name: &str, version: &str) -> Result<String> {
+    if version == WALLET_RPC_VERSION {
+        return Ok(DOWNLOAD_HASH.to_string());
+    }
+
+    let hashes = reqwest::get(HASHES_URL)
+        .await?
+        .error_for_status()?
+        .text()
+        .await
+        .context("Failed to fetch the hashes of Monero releases")?;
+
+    signed_hash(&hashes, SIGNING_KEY, SIGNING_KEY_FINGERPRINT, file_name)
+}
+
+/// Looks up the hash of `file_name` in the clear-signed `hashes`, which must be
+/// signed by `signing_key` with the given fingerprint.
+fn signed_hash(
+    hashes: &str,
+    signing_key: &str,
+    fingerprint: &str,
+    file_name: &str,
+) -> Result<String> {
+    let hashes = verify_signed_hashes(hashes, signing_key, fingerprint)?;
+    tracing::debug!("Signature of Monero release hashes is valid");
+
+    find_hash(&hashes, file_name).with_context(|| {
+        format!(
+            "{} is not listed in the signed hashes of Monero releases",
+            file_name
+        )
+    })
+}
+
+/// Verifies that the clear-signed `hashes` are signed by `signing_key` and
+/// returns the signed text.
+fn verify_signed_hashes(hashes: &str, signing_key: &str, fingerprint: &str) -> Result<String> {
+    let (signing_key, _) =
+        SignedPublicKey::from_string(signing_key).context("Failed to parse signing key")?;
+
+    let actual_fingerprint = hex::encode_upper(signing_key.fingerprint());
+    if actual_fingerprint != fingerprint {
+        bail!(
+            "Signing key has fingerprint {}, expected {}",
+            actual_fingerprint,
+            fingerprint
+        );
+    }
+
+    let (message, _) =
+        CleartextSignedMessage::from_string(hashes).context("Failed to parse hashes.txt")?;
+
+    let is_signed = message.verify(&signing_key).is_ok()
+        || signing_key
+            .public_subkeys
+            .iter()
+            .any(|subkey| message.verify(subkey).is_ok());
+    if !is_signed {
+        bail!("hashes.txt is not signed by the key signing Monero releases");
+    }
+
+    Ok(message.signed_text())
+}
+
+/// Finds the hash of `file_name` in lines of the form `<file name>, <hash>`.
+fn find_hash(hashes: &str, file_name: &str) -> Option<String> {
+    hashes.lines().find_map(|line| {
+        let mut fields = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|field| !field.is_empty());
+
+        let name = fields.next()?;
+        let hash = fields.next()?;
+
+        (name == file_name && hash.len() == 64).then(|| hash.to_lowercase())
+    })
+}
+
+fn ensure_compatible(version: Version) -> Result<()> {
+    let major = version.version >> 16;
+    let minor = version.version & 0xffff;
+    let (min_major, min_minor) = MIN_WALLET_RPC_VERSION;
+
+    if major != min_major || minor < min_minor {
+        bail!(
+            "monero-wallet-rpc has RPC version {}.{} but {}.{} or a newer minor version is required",
+            major,
+            minor,
+            min_major,
+            min_minor
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn extract_host_and_port(address: String) -> (&'static str, u16) {
-        let parts: Vec<&str> = address.split(':').collect();
+    const HASHES: &str = "## CLI
+monero-linux-armv7-v0.18.3.1.tar.bz2, 2ea2c8898cbab88f49423f4f6c15f2a94046cb4bbe827493dd061edc0fd5f1ca
+monero-linux-x64-v0.18.3.1.tar.bz2, 23af572fdfe3459b9ab97e2e9aa7e3c11021c955d6064b801a27d7e8c21ae09d
+";
 
-        if parts.len() == 2 {
-            let host = parts[0].to_string();
-            let port = parts[1].parse::<u16>().unwrap();
-            let static_str_host: &'static str = Box::leak(host.into_boxed_str());
-            return (static_str_host, port);
-        }
-        panic!("Could not extract host and port from address: {}", address)
+    #[test]
+    fn finds_hash_of_release_archive() {
+        assert_eq!(
+            find_hash(HASHES, "monero-linux-x64-v0.18.3.1.tar.bz2").as_deref(),
+            Some("23af572fdfe3459b9ab97e2e9aa7e3c11021c955d6064b801a27d7e8c21ae09d")
+        );
+        assert_eq!(
+            find_hash(HASHES, "monero-linux-x64-v0.18.3.3.tar.bz2"),
+            None
+        );
+        assert_eq!(find_hash(HASHES, "monero-linux-x64"), None);
     }
 
+    #[cfg(not(target_os = "windows"))]
     #[tokio::test]
-    async fn test_is_daemon_available_success() {
-        let mut server = mockito::Server::new_async().await;
-
-        let _ = server
-            .mock("GET", "/get_info")
-            .with_status(200)
-            .with_body(
-                r#"
-                {
-                    "status": "OK",
-                    "synchronized": true,
-                    "mainnet": true,
-                    "stagenet": false,
-                    "testnet": false
-                }
-                "#,
-            )
-            .create();
-
-        let (host, port) = extract_host_and_port(server.host_with_port());
-
-        let client = reqwest::Client::new();
-        let result = MoneroDaemon::new(host, port, Network::Mainnet)
-            .is_available(&client)
-            .await;
-
-        assert!(result.is_ok());
-        assert!(result.unwrap());
+    async fn restarts_exited_process() {
+        let mut child = Command::new("sleep").arg("0.1").spawn().unwrap();
+
+        let crash = watch_process(
+            &mut child,
+            || async { Ok::<_, anyhow::Error>(()) },
+            Duration::from_millis(10),
+            Duration::from_secs(60),
+        )
+        .await;
+
+        assert!(matches!(crash, Crash::Exited(_)));
     }
 
+    #[cfg(not(target_os = "windows"))]
     #[tokio::test]
-    async fn test_is_daemon_available_wrong_network_failure() {
-        let mut server = mockito::Server::new_async().await;
-
-        let _ = server
-            .mock("GET", "/get_info")
-            .with_status(200)
-            .with_body(
-                r#"
-                {
-                    "status": "OK",
-                    "synchronized": true,
-                    "mainnet": true,
-                    "stagenet": false,
-                    "testnet": false
+    async fn does_not_restart_process_that_is_busy_for_a_while() {
+        let mut child = Command::new("sleep")
+            .arg("10")
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let mut health_checks = 0;
+
+        let watch = watch_process(
+            &mut child,
+            || {
+                health_checks += 1;
+                let busy = health_checks <= 5;
+                async move {
+                    if busy {
+                        anyhow::bail!("busy")
+                    }
+                    Ok(())
                 }
-                "#,
-            )
-            .create();
+            },
+            Duration::from_millis(10),
+            Duration::from_millis(200),
+        );
 
-        let (host, port) = extract_host_and_port(server.host_with_port());
+        assert!(tokio::time::timeout(Duration::from_millis(500), watch)
+            .await
+            .is_err());
+    }
 
-        let client = reqwest::Client::new();
-        let result = MoneroDaemon::new(host, port, Network::Stagenet)
-            .is_available(&client)
-            .await;
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn restarts_process_that_stays_unresponsive() {
+        let mut child = Command::new("sleep")
+            .arg("10")
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+
+        let crash = watch_process(
+            &mut child,
+            || async { Err::<(), _>(anyhow::anyhow!("unresponsive")) },
+            Duration::from_millis(10),
+            Duration::from_millis(200),
+        )
+        .await;
+
+        assert!(matches!(crash, Crash::Unresponsive(_)));
+    }
 
-        assert!(result.is_ok());
-        assert!(!result.unwrap());
+    const TEST_SIGNING_KEY: &str = include_str!("test_data/release_signing_key.asc");
+    const TEST_SIGNING_KEY_FINGERPRINT: &str = "87117F89558B391630239464BFE7F5F3D733FD0F";
+    const SIGNED_HASHES: &str = include_str!("test_data/hashes.txt.asc");
+
+    #[test]
+    fn finds_hash_in_validly_signed_hashes() {
+        let hash = signed_hash(
+            SIGNED_HASHES,
+            TEST_SIGNING_KEY,
+            TEST_SIGNING_KEY_FINGERPRINT,
+            "monero-linux-x64-v0.0.0.1.tar.bz2",
+        )
+        .unwrap();
+
+        assert_eq!(
+            hash,
+            "5609f728403e197bb255ef50c62aeabb1f93b09f7b7c379903440b65cd4319cb"
+        );
     }
 
-    #[tokio::test]
-    async fn test_is_daemon_available_not_synced_failure() {
-        let mut server = mockito::Server::new_async().await;
-
-        let _ = server
-            .mock("GET", "/get_info")
-            .with_status(200)
-            .with_body(
-                r#"
-                {
-                    "status": "OK",
-                    "synchronized": false,
-                    "mainnet": true,
-                    "stagenet": false,
-                    "testnet": false
-                }
-                "#,
-            )
-            .create();
+    #[test]
+    fn rejects_tampered_hashes() {
+        let tampered = SIGNED_HASHES.replace("5609f728", "0609f728");
+
+        assert!(signed_hash(
+            &tampered,
+            TEST_SIGNING_KEY,
+            TEST_SIGNING_KEY_FINGERPRINT,
+            "monero-linux-x64-v0.0.0.1.tar.bz2",
+        )
+        .is_err());
+    }
 
-        let (host, port) = extract_host_and_port(server.host_with_port());
+    #[test]
+    fn rejects_tampered_signature() {
+        let tampered = SIGNED_HASHES.replace("/Q/IiAf/", "/Q/IiAg/");
+        assert_ne!(tampered, SIGNED_HASHES);
 
-        let client = reqwest::Client::new();
-        let result = MoneroDaemon::new(host, port, Network::Mainnet)
-            .is_available(&client)
-            .await;
+        assert!(
+            verify_signed_hashes(&tampered, TEST_SIGNING_KEY, TEST_SIGNING_KEY_FINGERPRINT)
+                .is_err()
+        );
+    }
 
-        assert!(result.is_ok());
-        assert!(!result.unwrap());
+    #[test]
+    fn rejects_hashes_signed_by_another_key() {
+        assert!(
+            verify_signed_hashes(SIGNED_HASHES, TEST_SIGNING_KEY, SIGNING_KEY_FINGERPRINT).is_err()
+        );
     }
 
-    #[tokio::test]
-    async fn test_is_daemon_available_network_error_failure() {
-        let client = reqwest::Client::new();
-        let result = MoneroDaemon::new("does.not.exist.com", 18081, Network::Mainnet)
-            .is_available(&client)
-            .await;
+    #[test]
+    fn rejects_release_missing_from_signed_hashes() {
+        let error = signed_hash(
+            SIGNED_HASHES,
+            TEST_SIGNING_KEY,
+            TEST_SIGNING_KEY_FINGERPRINT,
+            "monero-linux-x64-v0.0.0.2.tar.bz2",
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("is not listed"));
+    }
 
-        assert!(result.is_err());
+    #[test]
+    fn rejects_incompatible_wallet_rpc_versions() {
+        assert!(ensure_compatible(Version {
+            version: (1 << 16) | 26
+        })
+        .is_ok());
+        assert!(ensure_compatible(Version {
+            version: (1 << 16) | 21
+        })
+        .is_err());
+        assert!(ensure_compatible(Version { version: 2 << 16 }).is_err());
     }
 }
@@ -1,6 +1,8 @@
 mod impl_from_rr_event;
 
 pub mod cbor_request_response;
+pub mod compatibility;
+pub mod cooperative_early_refund;
 pub mod cooperative_xmr_redeem_after_punish;
 pub mod encrypted_signature;
 pub mod json_pull_codec;
@@ -0,0 +1,204 @@
+//! Checks whether a peer speaks the protocols needed to swap with it.
+//!
+//! Every node advertises the protocols it accepts through `identify`. A
+//! protocol is supported by a peer if the peer advertises any of the versions
+//! we speak, which allows several versions of a protocol to be supported side
+//! by side while nodes upgrade. Optional protocols are features that are only
+//! needed on some paths of a swap, a peer without them can still be swapped
+//! with.
+
+use crate::network::{
+    cooperative_early_refund, cooperative_xmr_redeem_after_punish, encrypted_signature, quote,
+    swap_setup,
+};
+use std::fmt;
+
+/// The protocol version advertised through `identify`.
+pub const PROTOCOL_VERSION: &str = "/comit/xmr/btc/1.0.0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Protocol {
+    pub name: &'static str,
+    /// All versions of the protocol we speak, newest first.
+    pub versions: &'static [&'static str],
+    /// Whether a swap can not be started without the protocol.
+    pub required: bool,
+}
+
+pub const COOPERATIVE_XMR_REDEEM: &str = "cooperative xmr redeem";
+pub const COOPERATIVE_EARLY_REFUND: &str = "cooperative early refund";
+
+/// The protocols Bob needs Alice to accept.
+pub const ASB_PROTOCOLS: &[Protocol] = &[
+    Protocol {
+        name: "quote",
+        versions: &[quote::SIGNED_PROTOCOL, quote::PROTOCOL],
+        required: true,
+    },
+    Protocol {
+        name: "swap setup",
+        versions: &[
+            swap_setup::protocol::PROTOCOL,
+            swap_setup::protocol::PROTOCOL_V1,
+        ],
+        required: true,
+    },
+    Protocol {
+        name: "encrypted signature",
+        versions: &[encrypted_signature::PROTOCOL],
+        required: true,
+    },
+    Protocol {
+        name: COOPERATIVE_XMR_REDEEM,
+        versions: &[cooperative_xmr_redeem_after_punish::PROTOCOL],
+        required: false,
+    },
+    Protocol {
+        name: COOPERATIVE_EARLY_REFUND,
+        versions: &[cooperative_early_refund::PROTOCOL],
+        required: false,
+    },
+];
+
+impl Protocol {
+    fn is_supported_by(&self, advertised: &[String]) -> bool {
+        self.versions
+            .iter()
+            .any(|version| advertised.iter().any(|protocol| protocol == version))
+    }
+}
+
+/// The result of comparing the protocols a peer advertises with the
+/// protocols we need.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compatibility {
+    pub agent_version: String,
+    pub missing_protocols: Vec<&'static str>,
+    pub missing_features: Vec<&'static str>,
+}
+
+impl Compatibility {
+    pub fn check(agent_version: String, advertised: &[String], needed: &[Protocol]) -> Self {
+        let missing = |required: bool| {
+            needed
+                .iter()
+                .filter(|protocol| protocol.required == required)
+                .filter(|protocol| !protocol.is_supported_by(advertised))
+                .map(|protocol| protocol.name)
+                .collect()
+        };
+
+        Self {
+            agent_version,
+            missing_protocols: missing(true),
+            missing_features: missing(false),
+        }
+    }
+
+    /// Whether a swap can be run with the peer.
+    pub fn is_compatible(&self) -> bool {
+        self.missing_protocols.is_empty()
+    }
+
+    /// Whether the peer advertised the protocol with the given name.
+    pub fn supports(&self, name: &str) -> bool {
+        !self.missing_protocols.contains(&name) && !self.missing_features.contains(&name)
+    }
+}
+
+impl fmt::Display for Compatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_compatible() {
+            return write!(
+                f,
+                "peer running {} is incompatible, it does not support: {}",
+                self.agent_version,
+                self.missing_protocols.join(", ")
+            );
+        }
+
+        if !self.missing_features.is_empty() {
+            return write!(
+                f,
+                "peer running {} is compatible, but does not support: {}",
+                self.agent_version,
+                self.missing_features.join(", ")
+            );
+        }
+
+        write!(f, "peer running {} is compatible", self.agent_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_versions() -> Vec<String> {
+        ASB_PROTOCOLS
+            .iter()
+            .flat_map(|protocol| protocol.versions.iter().map(|v| v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn peer_with_all_protocols_is_compatible() {
+        let compatibility =
+            Compatibility::check("asb/0.13.0".to_owned(), &all_versions(), ASB_PROTOCOLS);
+
+        assert!(compatibility.is_compatible());
+        assert!(compatibility.missing_features.is_empty());
+    }
+
+    #[test]
+    fn peer_without_optional_protocol_is_compatible_but_misses_feature() {
+        let advertised = all_versions()
+            .into_iter()
+            .filter(|protocol| protocol != cooperative_early_refund::PROTOCOL)
+            .collect::<Vec<_>>();
+
+        let compatibility =
+            Compatibility::check("asb/0.12.0".to_owned(), &advertised, ASB_PROTOCOLS);
+
+        assert!(compatibility.is_compatible());
+        assert_eq!(
+            compatibility.missing_features,
+            vec![COOPERATIVE_EARLY_REFUND]
+        );
+        assert!(!compatibility.supports(COOPERATIVE_EARLY_REFUND));
+        assert!(compatibility.supports(COOPERATIVE_XMR_REDEEM));
+    }
+
+    #[test]
+    fn peer_with_previous_swap_setup_version_is_compatible() {
+        let advertised = all_versions()
+            .into_iter()
+            .filter(|protocol| protocol != swap_setup::protocol::PROTOCOL)
+            .collect::<Vec<_>>();
+
+        let compatibility =
+            Compatibility::check("asb/0.13.0".to_owned(), &advertised, ASB_PROTOCOLS);
+
+        assert!(compatibility.is_compatible());
+    }
+
+    #[test]
+    fn peer_with_other_protocol_version_is_incompatible() {
+        let advertised = all_versions()
+            .into_iter()
+            .map(|protocol| {
+                if protocol.starts_with("/comit/xmr/btc/swap_setup/") {
+                    "/comit/xmr/btc/swap_setup/3.0.0".to_owned()
+                } else {
+                    protocol
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let compatibility =
+            Compatibility::check("asb/1.0.0".to_owned(), &advertised, ASB_PROTOCOLS);
+
+        assert!(!compatibility.is_compatible());
+        assert_eq!(compatibility.missing_protocols, vec!["swap setup"]);
+    }
+}
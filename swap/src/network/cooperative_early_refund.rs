@@ -0,0 +1,113 @@
+use crate::network::cbor_request_response::CborCodec;
+use crate::{asb, bitcoin, cli};
+use libp2p::core::ProtocolName;
+use libp2p::request_response::{
+    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
+    RequestResponseMessage,
+};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const PROTOCOL: &str = "/comit/xmr/btc/cooperative_early_refund/1.0.0";
+type OutEvent = RequestResponseEvent<Request, Response>;
+type Message = RequestResponseMessage<Request, Response>;
+
+pub type Behaviour = RequestResponse<CborCodec<CooperativeEarlyRefundProtocol, Request, Response>>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CooperativeEarlyRefundProtocol;
+
+impl ProtocolName for CooperativeEarlyRefundProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        PROTOCOL.as_bytes()
+    }
+}
+
+#[derive(Debug, thiserror::Error, Clone, Serialize, Deserialize)]
+pub enum CooperativeEarlyRefundRejectReason {
+    #[error("Alice does not have a record of the swap")]
+    UnknownSwap,
+    #[error("Alice rejected the request because it deemed it malicious")]
+    MaliciousRequest,
+    #[error("Alice has not aborted the swap and may still lock the Monero")]
+    SwapInvalidState,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub swap_id: Uuid,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Response {
+    Fullfilled {
+        swap_id: Uuid,
+        tx_early_refund_sig: bitcoin::Signature,
+    },
+    Rejected {
+        swap_id: Uuid,
+        reason: CooperativeEarlyRefundRejectReason,
+    },
+}
+
+pub fn alice() -> Behaviour {
+    Behaviour::new(
+        CborCodec::default(),
+        vec![(CooperativeEarlyRefundProtocol, ProtocolSupport::Inbound)],
+        RequestResponseConfig::default(),
+    )
+}
+
+pub fn bob() -> Behaviour {
+    Behaviour::new(
+        CborCodec::default(),
+        vec![(CooperativeEarlyRefundProtocol, ProtocolSupport::Outbound)],
+        RequestResponseConfig::default(),
+    )
+}
+
+impl From<(PeerId, Message)> for asb::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request {
+                request, channel, ..
+            } => Self::CooperativeEarlyRefundRequested {
+                swap_id: request.swap_id,
+                channel,
+                peer,
+            },
+            Message::Response { .. } => Self::unexpected_response(peer),
+        }
+    }
+}
+
+crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+
+impl From<(PeerId, Message)> for cli::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request { .. } => Self::unexpected_request(peer),
+            Message::Response {
+                response,
+                request_id,
+            } => match response {
+                Response::Fullfilled {
+                    swap_id,
+                    tx_early_refund_sig,
+                } => Self::CooperativeEarlyRefundFulfilled {
+                    id: request_id,
+                    swap_id,
+                    tx_early_refund_sig,
+                },
+                Response::Rejected { swap_id, reason } => Self::CooperativeEarlyRefundRejected {
+                    id: request_id,
+                    swap_id,
+                    reason,
+                },
+            },
+        }
+    }
+}
+
+crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
@@ -50,7 +50,11 @@ macro_rules! impl_from_rr_event {
                                     peer,
                                 }
                             }
-                            UnsupportedProtocols => Self::Other, // TODO: Report this and disconnected / ban the peer?
+                            // Optional protocols are not supported by every peer, the required ones are checked once the peer identified itself
+                            UnsupportedProtocols => {
+                                tracing::warn!(%peer, "{} is not supported by the peer, it may run an incompatible version", $protocol);
+                                Self::Other
+                            }
                             DialFailure => {
                                 Self::Failure {
                                     error: anyhow!("{} failed because we failed to dial", $protocol),
@@ -1,25 +1,47 @@
 use crate::network::json_pull_codec::JsonPullCodec;
+use crate::network::swap_setup::TimelockBounds;
+use crate::signed::Signed;
 use crate::{asb, bitcoin, cli};
+use anyhow::{bail, Context, Result};
 use libp2p::core::ProtocolName;
+use libp2p::identity::Keypair;
 use libp2p::request_response::{
     ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
     RequestResponseMessage,
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
 
-const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/1.0.0";
-pub type OutEvent = RequestResponseEvent<(), BidQuote>;
-pub type Message = RequestResponseMessage<(), BidQuote>;
+pub const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/1.0.0";
+/// Version 2 of the quote protocol, quotes are signed and expire.
+pub const SIGNED_PROTOCOL: &str = "/comit/xmr/btc/bid-quote/2.0.0";
+/// Used in errors, request-response failures do not tell which of the
+/// versions was negotiated.
+const PROTOCOL_FAMILY: &str = "/comit/xmr/btc/bid-quote";
+pub type OutEvent = RequestResponseEvent<(), SignedBidQuote>;
+pub type Message = RequestResponseMessage<(), SignedBidQuote>;
 
-pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, BidQuote>>;
+pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, SignedBidQuote>>;
 
-#[derive(Debug, Clone, Copy, Default)]
-pub struct BidQuoteProtocol;
+/// How long the ASB honours the price of a signed quote.
+pub const QUOTE_VALIDITY: Duration = Duration::from_secs(2 * 60);
+
+/// Both versions share the same message, the signature is simply ignored by
+/// peers that only speak version 1.
+#[derive(Debug, Clone, Copy)]
+pub enum BidQuoteProtocol {
+    V1,
+    V2,
+}
 
 impl ProtocolName for BidQuoteProtocol {
     fn protocol_name(&self) -> &[u8] {
-        PROTOCOL.as_bytes()
+        match self {
+            BidQuoteProtocol::V1 => PROTOCOL.as_bytes(),
+            BidQuoteProtocol::V2 => SIGNED_PROTOCOL.as_bytes(),
+        }
     }
 }
 
@@ -35,6 +57,121 @@ pub struct BidQuote {
     /// The maximum quantity the maker is willing to buy.
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub max_quantity: bitcoin::Amount,
+    /// The timelocks the maker is willing to agree to. Makers that do not
+    /// advertise bounds only accept the timelocks of the network.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelock_bounds: Option<TimelockBounds>,
+}
+
+/// A quote as it is sent by the ASB.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignedBidQuote {
+    #[serde(flatten)]
+    pub quote: BidQuote,
+    /// Absent if the ASB does not sign its quotes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<QuoteSignature>,
+}
+
+/// Binds the ASB to the price of a quote until the quote expires.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QuoteSignature {
+    /// Identifies the quote when setting up a swap, also serves as nonce.
+    pub id: Uuid,
+    /// Unix timestamp in seconds until which the price is honoured.
+    pub expires_at: u64,
+    /// The quote, id and expiry as they were signed by the ASB.
+    #[serde(flatten)]
+    pub signed: Signed,
+}
+
+/// The terms the ASB commits to when signing a quote.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct QuoteTerms {
+    quote: BidQuote,
+    id: Uuid,
+    expires_at: u64,
+    /// The peer the quote was issued to, in base58.
+    requester: String,
+}
+
+const SIGNATURE_DOMAIN: &str = "xmr-btc-swap/bid-quote";
+
+fn unix_timestamp() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+impl SignedBidQuote {
+    pub fn unsigned(quote: BidQuote) -> Self {
+        Self {
+            quote,
+            signature: None,
+        }
+    }
+
+    /// Signs the quote with the libp2p identity of the ASB for the requesting
+    /// peer.
+    pub fn sign(
+        quote: BidQuote,
+        identity: &Keypair,
+        validity: Duration,
+        requester: PeerId,
+    ) -> Result<Self> {
+        let id = Uuid::new_v4();
+        let expires_at = unix_timestamp()? + validity.as_secs();
+
+        let terms = QuoteTerms {
+            quote,
+            id,
+            expires_at,
+            requester: requester.to_base58(),
+        };
+        let signed =
+            Signed::sign(SIGNATURE_DOMAIN, &terms, identity).context("Failed to sign quote")?;
+
+        Ok(Self {
+            quote,
+            signature: Some(QuoteSignature {
+                id,
+                expires_at,
+                signed,
+            }),
+        })
+    }
+
+    /// Checks that the quote is signed by the given peer for the requester and
+    /// returns the signature.
+    ///
+    /// Expired quotes are valid, they only prove what price was advertised.
+    pub fn verify(&self, peer_id: PeerId, requester: PeerId) -> Result<&QuoteSignature> {
+        let signature = self.signature.as_ref().context("Quote is not signed")?;
+
+        let (signer, terms) = signature
+            .signed
+            .verify::<QuoteTerms>(SIGNATURE_DOMAIN)
+            .context("Quote signature is invalid")?;
+        if signer != peer_id {
+            bail!("Quote was signed by {} instead of {}", signer, peer_id);
+        }
+
+        let advertised = QuoteTerms {
+            quote: self.quote,
+            id: signature.id,
+            expires_at: signature.expires_at,
+            requester: requester.to_base58(),
+        };
+        if terms != advertised {
+            bail!("Quote does not match the signed terms");
+        }
+
+        Ok(signature)
+    }
+}
+
+impl QuoteSignature {
+    pub fn is_expired(&self) -> Result<bool> {
+        Ok(unix_timestamp()? >= self.expires_at)
+    }
 }
 
 #[derive(Clone, Copy, Debug, thiserror::Error)]
@@ -48,7 +185,10 @@ pub struct ZeroQuoteReceived;
 pub fn asb() -> Behaviour {
     Behaviour::new(
         JsonPullCodec::default(),
-        vec![(BidQuoteProtocol, ProtocolSupport::Inbound)],
+        vec![
+            (BidQuoteProtocol::V2, ProtocolSupport::Inbound),
+            (BidQuoteProtocol::V1, ProtocolSupport::Inbound),
+        ],
         RequestResponseConfig::default(),
     )
 }
@@ -60,7 +200,10 @@ pub fn asb() -> Behaviour {
 pub fn cli() -> Behaviour {
     Behaviour::new(
         JsonPullCodec::default(),
-        vec![(BidQuoteProtocol, ProtocolSupport::Outbound)],
+        vec![
+            (BidQuoteProtocol::V2, ProtocolSupport::Outbound),
+            (BidQuoteProtocol::V1, ProtocolSupport::Outbound),
+        ],
         RequestResponseConfig::default(),
     )
 }
@@ -73,7 +216,7 @@ impl From<(PeerId, Message)> for asb::OutEvent {
         }
     }
 }
-crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL_FAMILY);
 
 impl From<(PeerId, Message)> for cli::OutEvent {
     fn from((peer, message): (PeerId, Message)) -> Self {
@@ -89,4 +232,93 @@ impl From<(PeerId, Message)> for cli::OutEvent {
         }
     }
 }
-crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
+crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL_FAMILY);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote() -> BidQuote {
+        BidQuote {
+            price: bitcoin::Amount::from_sat(500_000),
+            min_quantity: bitcoin::Amount::from_sat(10_000),
+            max_quantity: bitcoin::Amount::from_sat(1_000_000),
+            timelock_bounds: None,
+        }
+    }
+
+    #[test]
+    fn signed_quote_verifies_for_signer() {
+        let identity = Keypair::generate_ed25519();
+        let requester = PeerId::random();
+        let signed = SignedBidQuote::sign(quote(), &identity, QUOTE_VALIDITY, requester).unwrap();
+
+        let signature = signed
+            .verify(identity.public().to_peer_id(), requester)
+            .unwrap();
+
+        assert!(!signature.is_expired().unwrap());
+    }
+
+    #[test]
+    fn signed_quote_does_not_verify_for_other_peer() {
+        let identity = Keypair::generate_ed25519();
+        let requester = PeerId::random();
+        let signed = SignedBidQuote::sign(quote(), &identity, QUOTE_VALIDITY, requester).unwrap();
+
+        let other = Keypair::generate_ed25519().public().to_peer_id();
+
+        assert!(signed.verify(other, requester).is_err());
+    }
+
+    #[test]
+    fn signed_quote_does_not_verify_for_other_requester() {
+        let identity = Keypair::generate_ed25519();
+        let signed =
+            SignedBidQuote::sign(quote(), &identity, QUOTE_VALIDITY, PeerId::random()).unwrap();
+
+        assert!(signed
+            .verify(identity.public().to_peer_id(), PeerId::random())
+            .is_err());
+    }
+
+    #[test]
+    fn tampered_quote_does_not_verify() {
+        let identity = Keypair::generate_ed25519();
+        let requester = PeerId::random();
+        let mut signed =
+            SignedBidQuote::sign(quote(), &identity, QUOTE_VALIDITY, requester).unwrap();
+        signed.quote.price = bitcoin::Amount::from_sat(1);
+
+        assert!(signed
+            .verify(identity.public().to_peer_id(), requester)
+            .is_err());
+    }
+
+    #[test]
+    fn quote_with_tampered_id_does_not_verify() {
+        let identity = Keypair::generate_ed25519();
+        let requester = PeerId::random();
+        let mut signed =
+            SignedBidQuote::sign(quote(), &identity, QUOTE_VALIDITY, requester).unwrap();
+        signed.signature.as_mut().unwrap().id = Uuid::new_v4();
+
+        assert!(signed
+            .verify(identity.public().to_peer_id(), requester)
+            .is_err());
+    }
+
+    #[test]
+    fn signed_quote_can_be_read_as_plain_quote() {
+        let identity = Keypair::generate_ed25519();
+        let signed =
+            SignedBidQuote::sign(quote(), &identity, QUOTE_VALIDITY, PeerId::random()).unwrap();
+
+        let json = serde_json::to_string(&signed).unwrap();
+        let plain = serde_json::from_str::<BidQuote>(&json).unwrap();
+        let roundtrip = serde_json::from_str::<SignedBidQuote>(&json).unwrap();
+
+        assert_eq!(plain, quote());
+        assert_eq!(roundtrip, signed);
+    }
+}
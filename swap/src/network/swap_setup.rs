@@ -1,9 +1,11 @@
-use crate::monero;
-use anyhow::{Context, Result};
+use crate::bitcoin::{CancelTimelock, PunishTimelock};
+use crate::{env, monero};
+use anyhow::{bail, Context, Result};
 use libp2p::core::upgrade;
 use libp2p::swarm::NegotiatedSubstream;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 pub mod alice;
 pub mod bob;
@@ -12,29 +14,67 @@ pub const BUF_SIZE: usize = 1024 * 1024;
 
 pub mod protocol {
     use futures::future;
-    use libp2p::core::upgrade::{from_fn, FromFnUpgrade};
-    use libp2p::core::Endpoint;
+    use libp2p::core::{InboundUpgrade, OutboundUpgrade, ProtocolName, UpgradeInfo};
     use libp2p::swarm::NegotiatedSubstream;
     use void::Void;
 
+    /// Adds proposed timelocks and signed quotes to the spot price request.
+    pub const PROTOCOL: &str = "/comit/xmr/btc/swap_setup/2.0.0";
+    /// Spoken with peers that do not support [`PROTOCOL`] yet.
+    pub const PROTOCOL_V1: &str = "/comit/xmr/btc/swap_setup/1.0.0";
+
     pub fn new() -> SwapSetup {
-        from_fn(
-            b"/comit/xmr/btc/swap_setup/1.0.0",
-            Box::new(|socket, _| future::ready(Ok(socket))),
-        )
+        SwapSetup
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Version {
+        V1,
+        V2,
     }
 
-    pub type SwapSetup = FromFnUpgrade<
-        &'static [u8],
-        Box<
-            dyn Fn(
-                    NegotiatedSubstream,
-                    Endpoint,
-                ) -> future::Ready<Result<NegotiatedSubstream, Void>>
-                + Send
-                + 'static,
-        >,
-    >;
+    impl ProtocolName for Version {
+        fn protocol_name(&self) -> &[u8] {
+            match self {
+                Version::V1 => PROTOCOL_V1.as_bytes(),
+                Version::V2 => PROTOCOL.as_bytes(),
+            }
+        }
+    }
+
+    /// Negotiates the newest version both peers speak and hands it out
+    /// together with the substream.
+    #[derive(Debug, Clone, Copy)]
+    pub struct SwapSetup;
+
+    impl UpgradeInfo for SwapSetup {
+        type Info = Version;
+        type InfoIter = std::array::IntoIter<Version, 2>;
+
+        fn protocol_info(&self) -> Self::InfoIter {
+            [Version::V2, Version::V1].into_iter()
+        }
+    }
+
+    impl InboundUpgrade<NegotiatedSubstream> for SwapSetup {
+        type Output = (NegotiatedSubstream, Version);
+        type Error = Void;
+        type Future = future::Ready<Result<Self::Output, Self::Error>>;
+
+        fn upgrade_inbound(self, socket: NegotiatedSubstream, version: Version) -> Self::Future {
+            future::ready(Ok((socket, version)))
+        }
+    }
+
+    impl OutboundUpgrade<NegotiatedSubstream> for SwapSetup {
+        type Output = (NegotiatedSubstream, Version);
+        type Error = Void;
+        type Future = future::Ready<Result<Self::Output, Self::Error>>;
+
+        fn upgrade_outbound(self, socket: NegotiatedSubstream, version: Version) -> Self::Future {
+            future::ready(Ok((socket, version)))
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,11 +85,119 @@ pub struct BlockchainNetwork {
     pub monero: monero::Network,
 }
 
+/// The timelocks of the Bitcoin transactions of a swap.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timelocks {
+    pub cancel: CancelTimelock,
+    pub punish: PunishTimelock,
+}
+
+impl From<env::Config> for Timelocks {
+    fn from(env_config: env::Config) -> Self {
+        Self {
+            cancel: env_config.bitcoin_cancel_timelock,
+            punish: env_config.bitcoin_punish_timelock,
+        }
+    }
+}
+
+/// The shortest cancel timelock, in blocks, that can be agreed to. A shorter
+/// one leaves Alice no time to redeem before Bob can cancel.
+pub const MIN_CANCEL_TIMELOCK: CancelTimelock = CancelTimelock::new(6);
+
+/// The shortest punish timelock, in blocks, that can be agreed to. A shorter
+/// one leaves Bob no time to refund before Alice can punish.
+pub const MIN_PUNISH_TIMELOCK: PunishTimelock = PunishTimelock::new(6);
+
+impl Timelocks {
+    /// Fails if a timelock is shorter than [`MIN_CANCEL_TIMELOCK`] or
+    /// [`MIN_PUNISH_TIMELOCK`].
+    pub fn ensure_above_minimum(&self) -> Result<()> {
+        if self.cancel < MIN_CANCEL_TIMELOCK {
+            bail!(
+                "Cancel timelock of {} blocks is shorter than the minimum of {} blocks",
+                u32::from(self.cancel),
+                u32::from(MIN_CANCEL_TIMELOCK)
+            );
+        }
+
+        if self.punish < MIN_PUNISH_TIMELOCK {
+            bail!(
+                "Punish timelock of {} blocks is shorter than the minimum of {} blocks",
+                u32::from(self.punish),
+                u32::from(MIN_PUNISH_TIMELOCK)
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// The timelocks a maker is willing to agree to. All bounds are inclusive.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TimelockBounds {
+    pub min_cancel: CancelTimelock,
+    pub max_cancel: CancelTimelock,
+    pub min_punish: PunishTimelock,
+    pub max_punish: PunishTimelock,
+}
+
+impl TimelockBounds {
+    /// Bounds that only allow the given timelocks.
+    pub fn fixed(timelocks: Timelocks) -> Self {
+        Self {
+            min_cancel: timelocks.cancel,
+            max_cancel: timelocks.cancel,
+            min_punish: timelocks.punish,
+            max_punish: timelocks.punish,
+        }
+    }
+
+    pub fn contains(&self, timelocks: Timelocks) -> bool {
+        (self.min_cancel..=self.max_cancel).contains(&timelocks.cancel)
+            && (self.min_punish..=self.max_punish).contains(&timelocks.punish)
+    }
+
+    /// Fails if a lower bound exceeds its upper bound or lies below the
+    /// minimum timelocks.
+    pub fn validate(&self) -> Result<()> {
+        if self.min_cancel > self.max_cancel {
+            bail!(
+                "Minimum cancel timelock of {} blocks exceeds the maximum of {} blocks",
+                u32::from(self.min_cancel),
+                u32::from(self.max_cancel)
+            );
+        }
+
+        if self.min_punish > self.max_punish {
+            bail!(
+                "Minimum punish timelock of {} blocks exceeds the maximum of {} blocks",
+                u32::from(self.min_punish),
+                u32::from(self.max_punish)
+            );
+        }
+
+        Timelocks {
+            cancel: self.min_cancel,
+            punish: self.min_punish,
+        }
+        .ensure_above_minimum()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SpotPriceRequest {
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub btc: bitcoin::Amount,
     pub blockchain_network: BlockchainNetwork,
+    /// The timelocks proposed by Bob. If absent, the timelocks of the network
+    /// are used. Only part of [`protocol::PROTOCOL`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelocks: Option<Timelocks>,
+    /// The signed quote whose price Bob wants to swap at. If absent, the
+    /// latest price is used. Only part of [`protocol::PROTOCOL`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<Uuid>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -58,6 +206,22 @@ pub enum SpotPriceResponse {
     Error(SpotPriceError),
 }
 
+impl SpotPriceResponse {
+    /// Replaces errors that peers speaking the given version cannot decode.
+    pub fn for_version(self, version: protocol::Version) -> Self {
+        match (self, version) {
+            (
+                SpotPriceResponse::Error(
+                    SpotPriceError::TimelocksOutOfBounds { .. }
+                    | SpotPriceError::QuoteExpired { .. },
+                ),
+                protocol::Version::V1,
+            ) => SpotPriceResponse::Error(SpotPriceError::Other),
+            (response, _) => response,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum SpotPriceError {
     NoSwapsAccepted,
@@ -81,6 +245,13 @@ pub enum SpotPriceError {
         cli: BlockchainNetwork,
         asb: BlockchainNetwork,
     },
+    TimelocksOutOfBounds {
+        proposed: Timelocks,
+        bounds: TimelockBounds,
+    },
+    QuoteExpired {
+        quote_id: Uuid,
+    },
     /// To be used for errors that cannot be explained on the CLI side (e.g.
     /// rate update problems on the seller side)
     Other,
@@ -112,3 +283,34 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_unknown_to_v1_peers_are_replaced() {
+        let response = SpotPriceResponse::Error(SpotPriceError::QuoteExpired {
+            quote_id: Uuid::new_v4(),
+        });
+
+        assert!(matches!(
+            response.clone().for_version(protocol::Version::V1),
+            SpotPriceResponse::Error(SpotPriceError::Other)
+        ));
+        assert!(matches!(
+            response.for_version(protocol::Version::V2),
+            SpotPriceResponse::Error(SpotPriceError::QuoteExpired { .. })
+        ));
+    }
+
+    #[test]
+    fn errors_known_to_v1_peers_are_kept() {
+        let response = SpotPriceResponse::Error(SpotPriceError::NoSwapsAccepted);
+
+        assert!(matches!(
+            response.for_version(protocol::Version::V1),
+            SpotPriceResponse::Error(SpotPriceError::NoSwapsAccepted)
+        ));
+    }
+}
@@ -1,8 +1,9 @@
-use crate::asb::LatestRate;
+use crate::asb::{LatestRate, Rate};
 use crate::monero::Amount;
 use crate::network::swap_setup;
 use crate::network::swap_setup::{
     protocol, BlockchainNetwork, SpotPriceError, SpotPriceRequest, SpotPriceResponse,
+    TimelockBounds, Timelocks,
 };
 use crate::protocol::alice::{State0, State3};
 use crate::protocol::{Message0, Message2, Message4};
@@ -28,7 +29,8 @@ use void::Void;
 #[allow(clippy::large_enum_variant)]
 pub enum OutEvent {
     Initiated {
-        send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>,
+        peer_id: PeerId,
+        send_wallet_snapshot: bmrng::RequestReceiver<WalletSnapshotRequest, WalletSnapshot>,
     },
     Completed {
         peer_id: PeerId,
@@ -41,6 +43,9 @@ pub enum OutEvent {
     },
 }
 
+/// The amount Bob wants to swap and the signed quote he wants to swap at.
+pub type WalletSnapshotRequest = (bitcoin::Amount, Option<Uuid>);
+
 #[derive(Debug)]
 pub struct WalletSnapshot {
     balance: monero_rpc::wallet::GetBalance,
@@ -53,6 +58,9 @@ pub struct WalletSnapshot {
 
     redeem_fee: bitcoin::Amount,
     punish_fee: bitcoin::Amount,
+
+    /// The price of the quote Bob referenced, if it is still honoured.
+    quoted_price: Option<bitcoin::Amount>,
 }
 
 impl WalletSnapshot {
@@ -84,16 +92,26 @@ impl WalletSnapshot {
             punish_address,
             redeem_fee,
             punish_fee,
+            quoted_price: None,
         })
     }
+
+    pub fn with_quoted_price(self, quoted_price: Option<bitcoin::Amount>) -> Self {
+        Self {
+            quoted_price,
+            ..self
+        }
+    }
 }
 
 impl From<OutEvent> for asb::OutEvent {
     fn from(event: OutEvent) -> Self {
         match event {
             OutEvent::Initiated {
+                peer_id,
                 send_wallet_snapshot,
             } => asb::OutEvent::SwapSetupInitiated {
+                peer_id,
                 send_wallet_snapshot,
             },
             OutEvent::Completed {
@@ -118,6 +136,7 @@ pub struct Behaviour<LR> {
     events: VecDeque<OutEvent>,
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
+    timelock_bounds: TimelockBounds,
     env_config: env::Config,
 
     latest_rate: LR,
@@ -128,6 +147,7 @@ impl<LR> Behaviour<LR> {
     pub fn new(
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
+        timelock_bounds: TimelockBounds,
         env_config: env::Config,
         latest_rate: LR,
         resume_only: bool,
@@ -136,6 +156,7 @@ impl<LR> Behaviour<LR> {
             events: Default::default(),
             min_buy,
             max_buy,
+            timelock_bounds,
             env_config,
             latest_rate,
             resume_only,
@@ -154,6 +175,7 @@ where
         Handler::new(
             self.min_buy,
             self.max_buy,
+            self.timelock_bounds,
             self.env_config,
             self.latest_rate.clone(),
             self.resume_only,
@@ -172,6 +194,7 @@ where
         match event {
             HandlerOutEvent::Initiated(send_wallet_snapshot) => {
                 self.events.push_back(OutEvent::Initiated {
+                    peer_id,
                     send_wallet_snapshot,
                 })
             }
@@ -209,6 +232,7 @@ pub struct Handler<LR> {
 
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
+    timelock_bounds: TimelockBounds,
     env_config: env::Config,
 
     latest_rate: LR,
@@ -222,6 +246,7 @@ impl<LR> Handler<LR> {
     fn new(
         min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
+        timelock_bounds: TimelockBounds,
         env_config: env::Config,
         latest_rate: LR,
         resume_only: bool,
@@ -231,6 +256,7 @@ impl<LR> Handler<LR> {
             events: Default::default(),
             min_buy,
             max_buy,
+            timelock_bounds,
             env_config,
             latest_rate,
             resume_only,
@@ -243,7 +269,7 @@ impl<LR> Handler<LR> {
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum HandlerOutEvent {
-    Initiated(bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>),
+    Initiated(bmrng::RequestReceiver<WalletSnapshotRequest, WalletSnapshot>),
     Completed(Result<(Uuid, State3)>),
 }
 
@@ -265,18 +291,19 @@ where
 
     fn inject_fully_negotiated_inbound(
         &mut self,
-        mut substream: NegotiatedSubstream,
+        (mut substream, version): (NegotiatedSubstream, protocol::Version),
         _: Self::InboundOpenInfo,
     ) {
         self.keep_alive = KeepAlive::Yes;
 
-        let (sender, receiver) = bmrng::channel_with_timeout::<bitcoin::Amount, WalletSnapshot>(
+        let (sender, receiver) = bmrng::channel_with_timeout::<WalletSnapshotRequest, WalletSnapshot>(
             1,
             Duration::from_secs(5),
         );
         let resume_only = self.resume_only;
         let min_buy = self.min_buy;
         let max_buy = self.max_buy;
+        let timelock_bounds = self.timelock_bounds;
         let latest_rate = self.latest_rate.latest_rate();
         let env_config = self.env_config;
 
@@ -284,9 +311,17 @@ where
             let request = swap_setup::read_cbor_message::<SpotPriceRequest>(&mut substream)
                 .await
                 .context("Failed to read spot price request")?;
+            let request = match version {
+                protocol::Version::V1 => SpotPriceRequest {
+                    timelocks: None,
+                    quote_id: None,
+                    ..request
+                },
+                protocol::Version::V2 => request,
+            };
 
             let wallet_snapshot = sender
-                .send_receive(request.btc)
+                .send_receive((request.btc, request.quote_id))
                 .await
                 .context("Failed to receive wallet snapshot")?;
 
@@ -309,6 +344,17 @@ where
                     });
                 }
 
+                let timelocks = request
+                    .timelocks
+                    .unwrap_or_else(|| Timelocks::from(env_config));
+
+                if !timelock_bounds.contains(timelocks) {
+                    return Err(Error::TimelocksOutOfBounds {
+                        proposed: timelocks,
+                        bounds: timelock_bounds,
+                    });
+                }
+
                 let btc = request.btc;
 
                 if btc < min_buy {
@@ -325,10 +371,22 @@ where
                     });
                 }
 
-                let rate = latest_rate.map_err(|e| Error::LatestRateFetchFailed(Box::new(e)))?;
-                let xmr = rate
-                    .sell_quote(btc)
-                    .map_err(Error::SellQuoteCalculationFailed)?;
+                let xmr = match request.quote_id {
+                    Some(quote_id) => {
+                        let price = wallet_snapshot
+                            .quoted_price
+                            .ok_or(Error::QuoteExpired { quote_id })?;
+
+                        Rate::quote(price, btc).map_err(Error::SellQuoteCalculationFailed)?
+                    }
+                    None => {
+                        let rate =
+                            latest_rate.map_err(|e| Error::LatestRateFetchFailed(Box::new(e)))?;
+
+                        rate.sell_quote(btc)
+                            .map_err(Error::SellQuoteCalculationFailed)?
+                    }
+                };
 
                 let unlocked = Amount::from_piconero(wallet_snapshot.balance.unlocked_balance);
                 if unlocked < xmr + wallet_snapshot.lock_fee {
@@ -338,24 +396,25 @@ where
                     });
                 }
 
-                Ok(xmr)
+                Ok((xmr, timelocks))
             };
 
             let result = validate.await;
 
             swap_setup::write_cbor_message(
                 &mut substream,
-                SpotPriceResponse::from_result_ref(&result),
+                SpotPriceResponse::from_result_ref(&result).for_version(version),
             )
             .await
             .context("Failed to write spot price response")?;
 
-            let xmr = result?;
+            let (xmr, timelocks) = result?;
 
             let state0 = State0::new(
                 request.btc,
                 xmr,
-                env_config,
+                timelocks.cancel,
+                timelocks.punish,
                 wallet_snapshot.redeem_address,
                 wallet_snapshot.punish_address,
                 wallet_snapshot.redeem_fee,
@@ -465,9 +524,9 @@ where
 }
 
 impl SpotPriceResponse {
-    pub fn from_result_ref(result: &Result<monero::Amount, Error>) -> Self {
+    pub fn from_result_ref(result: &Result<(monero::Amount, Timelocks), Error>) -> Self {
         match result {
-            Ok(amount) => SpotPriceResponse::Xmr(*amount),
+            Ok((amount, _)) => SpotPriceResponse::Xmr(*amount),
             Err(error) => SpotPriceResponse::Error(error.to_error_response()),
         }
     }
@@ -501,6 +560,13 @@ pub enum Error {
         cli: BlockchainNetwork,
         asb: BlockchainNetwork,
     },
+    #[error("Proposed timelocks {proposed:?} are outside of the accepted bounds {bounds:?}")]
+    TimelocksOutOfBounds {
+        proposed: Timelocks,
+        bounds: TimelockBounds,
+    },
+    #[error("Quote {quote_id} expired, was already used or was not issued to this peer")]
+    QuoteExpired { quote_id: Uuid },
 }
 
 impl Error {
@@ -522,6 +588,15 @@ impl Error {
                     asb: *asb,
                 }
             }
+            Error::TimelocksOutOfBounds { proposed, bounds } => {
+                SpotPriceError::TimelocksOutOfBounds {
+                    proposed: *proposed,
+                    bounds: *bounds,
+                }
+            }
+            Error::QuoteExpired { quote_id } => SpotPriceError::QuoteExpired {
+                quote_id: *quote_id,
+            },
             Error::LatestRateFetchFailed(_) | Error::SellQuoteCalculationFailed(_) => {
                 SpotPriceError::Other
             }
@@ -1,6 +1,7 @@
+use crate::asb::Rate;
 use crate::network::swap_setup::{
     protocol, read_cbor_message, write_cbor_message, BlockchainNetwork, SpotPriceError,
-    SpotPriceRequest, SpotPriceResponse,
+    SpotPriceRequest, SpotPriceResponse, TimelockBounds, Timelocks,
 };
 use crate::protocol::bob::{State0, State2};
 use crate::protocol::{Message1, Message3};
@@ -124,6 +125,17 @@ pub struct NewSwap {
     pub tx_refund_fee: bitcoin::Amount,
     pub tx_cancel_fee: bitcoin::Amount,
     pub bitcoin_refund_address: bitcoin::Address,
+    /// The timelocks to propose, the ones of the network are used if `None`.
+    pub timelocks: Option<Timelocks>,
+    /// The signed quote to swap at, the latest price is used if `None`.
+    pub quote: Option<AcceptedQuote>,
+}
+
+/// A signed quote whose price Bob wants to swap at.
+#[derive(Debug, Clone, Copy)]
+pub struct AcceptedQuote {
+    pub id: Uuid,
+    pub price: bitcoin::Amount,
 }
 
 #[derive(Debug)]
@@ -148,13 +160,29 @@ impl ProtocolsHandler for Handler {
 
     fn inject_fully_negotiated_outbound(
         &mut self,
-        mut substream: NegotiatedSubstream,
+        (mut substream, version): (NegotiatedSubstream, protocol::Version),
         info: Self::OutboundOpenInfo,
     ) {
         let bitcoin_wallet = self.bitcoin_wallet.clone();
         let env_config = self.env_config;
 
         let protocol = tokio::time::timeout(self.timeout, async move {
+            let timelocks = info
+                .timelocks
+                .unwrap_or_else(|| Timelocks::from(env_config));
+
+            // Sellers speaking the first version ignore both and use their own timelocks and the
+            // latest price
+            if version == protocol::Version::V1 {
+                if timelocks != Timelocks::from(env_config) {
+                    return Err(anyhow::Error::from(Error::TimelocksNotSupported));
+                }
+
+                if info.quote.is_some() {
+                    return Err(anyhow::Error::from(Error::QuoteNotSupported));
+                }
+            }
+
             write_cbor_message(
                 &mut substream,
                 SpotPriceRequest {
@@ -163,19 +191,34 @@ impl ProtocolsHandler for Handler {
                         bitcoin: env_config.bitcoin_network,
                         monero: env_config.monero_network,
                     },
+                    timelocks: info.timelocks,
+                    quote_id: info.quote.map(|quote| quote.id),
                 },
             )
             .await?;
 
             let xmr = Result::from(read_cbor_message::<SpotPriceResponse>(&mut substream).await?)?;
 
+            // The seller is bound to the price of the quote it signed
+            if let Some(quote) = info.quote {
+                let quoted = Rate::quote(quote.price, info.btc)?;
+
+                if xmr != quoted {
+                    return Err(anyhow::Error::from(Error::QuotePriceMismatch {
+                        quote_id: quote.id,
+                        quoted,
+                        offered: xmr,
+                    }));
+                }
+            }
+
             let state0 = State0::new(
                 info.swap_id,
                 &mut rand::thread_rng(),
                 info.btc,
                 xmr,
-                env_config.bitcoin_cancel_timelock,
-                env_config.bitcoin_punish_timelock,
+                timelocks.cancel,
+                timelocks.punish,
                 info.bitcoin_refund_address,
                 env_config.monero_finality_confirmations,
                 info.tx_refund_fee,
@@ -284,6 +327,28 @@ pub enum Error {
         asb: BlockchainNetwork,
     },
 
+    #[error("Seller refused the proposed timelocks {proposed:?} because they are outside of the accepted bounds {bounds:?}")]
+    TimelocksOutOfBounds {
+        proposed: Timelocks,
+        bounds: TimelockBounds,
+    },
+
+    #[error("Seller no longer honours the price of quote {quote_id} because it expired, please request a new quote")]
+    QuoteExpired { quote_id: Uuid },
+
+    #[error("Seller runs an older version that does not support proposing timelocks")]
+    TimelocksNotSupported,
+
+    #[error("Seller runs an older version that does not support swapping at a signed quote")]
+    QuoteNotSupported,
+
+    #[error("Seller offered {offered} instead of the {quoted} of quote {quote_id}")]
+    QuotePriceMismatch {
+        quote_id: Uuid,
+        quoted: monero::Amount,
+        offered: monero::Amount,
+    },
+
     #[error("Failed to complete swap setup within {seconds}s")]
     Timeout { seconds: u64 },
 
@@ -307,6 +372,10 @@ impl From<SpotPriceError> for Error {
             SpotPriceError::BlockchainNetworkMismatch { cli, asb } => {
                 Error::BlockchainNetworkMismatch { cli, asb }
             }
+            SpotPriceError::TimelocksOutOfBounds { proposed, bounds } => {
+                Error::TimelocksOutOfBounds { proposed, bounds }
+            }
+            SpotPriceError::QuoteExpired { quote_id } => Error::QuoteExpired { quote_id },
             SpotPriceError::Other => Error::Other,
         }
     }
@@ -1,6 +1,7 @@
 use crate::asb::{LatestRate, RendezvousNode};
 use crate::libp2p_ext::MultiAddrExt;
 use crate::network::rendezvous::XmrBtcNamespace;
+use crate::network::swap_setup::TimelockBounds;
 use crate::seed::Seed;
 use crate::{asb, bitcoin, cli, env, tor};
 use anyhow::Result;
@@ -13,6 +14,7 @@ pub fn asb<LR>(
     seed: &Seed,
     min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
+    timelock_bounds: TimelockBounds,
     latest_rate: LR,
     resume_only: bool,
     env_config: env::Config,
@@ -38,6 +40,7 @@ where
     let behaviour = asb::Behaviour::new(
         min_buy,
         max_buy,
+        timelock_bounds,
         latest_rate,
         resume_only,
         env_config,
@@ -9,7 +9,7 @@ use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-const PROTOCOL: &str = "/comit/xmr/btc/transfer_proof/1.0.0";
+pub const PROTOCOL: &str = "/comit/xmr/btc/transfer_proof/1.0.0";
 type OutEvent = RequestResponseEvent<Request, ()>;
 type Message = RequestResponseMessage<Request, ()>;
 
@@ -13,10 +13,13 @@ use sigma_fun::ext::dl_secp256k1_ed25519_eq::{CrossCurveDLEQ, CrossCurveDLEQProo
 use sigma_fun::HashTranscript;
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::fmt;
+use std::time::SystemTime;
 use uuid::Uuid;
 
 pub mod alice;
 pub mod bob;
+pub mod rescan;
 
 pub static CROSS_CURVE_PROOF_SYSTEM: Lazy<
     CrossCurveDLEQ<HashTranscript<Sha256, rand_chacha::ChaCha20Rng>>,
@@ -88,6 +91,29 @@ impl State {
             State::Bob(state) => bob_is_complete(state),
         }
     }
+
+    pub fn tx_lock(&self) -> Option<&bitcoin::TxLock> {
+        match self {
+            State::Alice(state) => state.tx_lock(),
+            State::Bob(state) => state.tx_lock(),
+        }
+    }
+
+    pub fn own_bitcoin_addresses(&self) -> Vec<bitcoin::Address> {
+        match self {
+            State::Alice(state) => state.own_bitcoin_addresses(),
+            State::Bob(state) => state.own_bitcoin_addresses(),
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Alice(state) => state.fmt(f),
+            State::Bob(state) => state.fmt(f),
+        }
+    }
 }
 
 impl From<AliceState> for State {
@@ -144,6 +170,9 @@ pub trait Database {
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()>;
     async fn get_state(&self, swap_id: Uuid) -> Result<State>;
     async fn get_states(&self, swap_id: Uuid) -> Result<Vec<State>>;
+    /// Every state of the swap as it is stored, together with the time it was
+    /// entered.
+    async fn get_state_history(&self, swap_id: Uuid) -> Result<Vec<(String, serde_json::Value)>>;
     async fn all(&self) -> Result<Vec<(Uuid, State)>>;
     async fn raw_all(&self) -> Result<HashMap<Uuid, Vec<serde_json::Value>>>;
     async fn insert_buffered_transfer_proof(
@@ -155,4 +184,90 @@ pub trait Database {
         &self,
         swap_id: Uuid,
     ) -> Result<Option<monero::TransferProof>>;
+    async fn insert_unsigned_psbt(
+        &self,
+        swap_id: Uuid,
+        psbt: bitcoin::PartiallySignedTransaction,
+    ) -> Result<()>;
+    async fn get_unsigned_psbt(
+        &self,
+        swap_id: Uuid,
+    ) -> Result<Option<bitcoin::PartiallySignedTransaction>>;
+    /// When the unsigned PSBT was exported, `None` if it was exported before
+    /// this was recorded.
+    async fn get_psbt_exported_at(&self, swap_id: Uuid) -> Result<Option<SystemTime>>;
+    async fn insert_signed_psbt(
+        &self,
+        swap_id: Uuid,
+        psbt: bitcoin::PartiallySignedTransaction,
+    ) -> Result<()>;
+    async fn get_signed_psbt(
+        &self,
+        swap_id: Uuid,
+    ) -> Result<Option<bitcoin::PartiallySignedTransaction>>;
+    async fn insert_script_status(
+        &self,
+        txid: bitcoin::Txid,
+        script: ::bitcoin::Script,
+        inclusion_height: u32,
+    ) -> Result<()>;
+    async fn get_script_statuses(&self) -> Result<Vec<(bitcoin::Txid, ::bitcoin::Script, u32)>>;
+}
+
+/// Runs the swap setup of Alice and Bob against test wallets. Returns their
+/// states once Bob is about to lock the Bitcoin.
+#[cfg(test)]
+pub(crate) async fn swap_setup_states() -> (alice::State3, bob::State2) {
+    use crate::bitcoin::wallet::WalletBuilder;
+    use crate::bitcoin::{TxPunish, TxRedeem};
+    use crate::env::{GetConfig, Regtest};
+    use rand::rngs::OsRng;
+
+    let alice_wallet = WalletBuilder::new(bitcoin::Amount::ONE_BTC.to_sat()).build();
+    let bob_wallet = WalletBuilder::new(bitcoin::Amount::ONE_BTC.to_sat()).build();
+    let spending_fee = bitcoin::Amount::from_sat(1_000);
+    let btc_amount = bitcoin::Amount::from_sat(500_000);
+    let xmr_amount = monero::Amount::from_piconero(10_000);
+    let config = Regtest::get_config();
+
+    let alice_state0 = alice::State0::new(
+        btc_amount,
+        xmr_amount,
+        config.bitcoin_cancel_timelock,
+        config.bitcoin_punish_timelock,
+        alice_wallet.new_address().await.unwrap(),
+        alice_wallet.new_address().await.unwrap(),
+        alice_wallet
+            .estimate_fee(TxRedeem::weight(), btc_amount)
+            .await
+            .unwrap(),
+        alice_wallet
+            .estimate_fee(TxPunish::weight(), btc_amount)
+            .await
+            .unwrap(),
+        &mut OsRng,
+    );
+    let bob_state0 = bob::State0::new(
+        Uuid::new_v4(),
+        &mut OsRng,
+        btc_amount,
+        xmr_amount,
+        config.bitcoin_cancel_timelock,
+        config.bitcoin_punish_timelock,
+        bob_wallet.new_address().await.unwrap(),
+        config.monero_finality_confirmations,
+        spending_fee,
+        spending_fee,
+    );
+
+    let (_, alice_state1) = alice_state0.receive(bob_state0.next_message()).unwrap();
+    let bob_state1 = bob_state0
+        .receive(&bob_wallet, alice_state1.next_message())
+        .await
+        .unwrap();
+    let alice_state2 = alice_state1.receive(bob_state1.next_message()).unwrap();
+    let bob_state2 = bob_state1.receive(alice_state2.next_message()).unwrap();
+    let alice_state3 = alice_state2.receive(bob_state2.next_message()).unwrap();
+
+    (alice_state3, bob_state2)
 }
@@ -1,8 +1,7 @@
 use crate::bitcoin::{
     current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxPunish, TxRedeem, TxRefund, Txid,
+    TxEarlyRefund, TxPunish, TxRedeem, TxRefund, Txid,
 };
-use crate::env::Config;
 use crate::monero::wallet::{TransferRequest, WatchRequest};
 use crate::monero::TransferProof;
 use crate::monero_ext::ScalarExt;
@@ -80,6 +79,20 @@ pub enum AliceState {
     SafelyAborted,
 }
 
+impl AliceState {
+    /// The amount of Monero locked in a swap that was cancelled and may
+    /// still be refunded to us.
+    pub fn pending_xmr_refund(&self) -> Option<monero::Amount> {
+        match self {
+            AliceState::CancelTimelockExpired { state3, .. }
+            | AliceState::BtcCancelled { state3, .. }
+            | AliceState::BtcPunishable { state3, .. }
+            | AliceState::BtcRefunded { state3, .. } => Some(state3.xmr),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for AliceState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -109,6 +122,40 @@ impl fmt::Display for AliceState {
     }
 }
 
+impl AliceState {
+    pub(crate) fn state3(&self) -> Option<&State3> {
+        match self {
+            AliceState::Started { state3 }
+            | AliceState::BtcLockTransactionSeen { state3 }
+            | AliceState::BtcLocked { state3 }
+            | AliceState::XmrLockTransactionSent { state3, .. }
+            | AliceState::XmrLocked { state3, .. }
+            | AliceState::XmrLockTransferProofSent { state3, .. }
+            | AliceState::EncSigLearned { state3, .. }
+            | AliceState::BtcRedeemTransactionPublished { state3 }
+            | AliceState::BtcCancelled { state3, .. }
+            | AliceState::BtcRefunded { state3, .. }
+            | AliceState::BtcPunishable { state3, .. }
+            | AliceState::CancelTimelockExpired { state3, .. }
+            | AliceState::BtcPunished { state3 } => Some(state3),
+            AliceState::BtcRedeemed | AliceState::XmrRefunded | AliceState::SafelyAborted => None,
+        }
+    }
+
+    /// The Bitcoin lock transaction of this swap, if it is still known.
+    pub fn tx_lock(&self) -> Option<&bitcoin::TxLock> {
+        self.state3().map(|state3| &state3.tx_lock)
+    }
+
+    /// Addresses of our own Bitcoin wallet that this swap pays out to.
+    pub fn own_bitcoin_addresses(&self) -> Vec<bitcoin::Address> {
+        match self.state3() {
+            Some(state3) => vec![state3.redeem_address.clone(), state3.punish_address.clone()],
+            None => vec![],
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     a: bitcoin::SecretKey,
@@ -132,7 +179,8 @@ impl State0 {
     pub fn new<R>(
         btc: bitcoin::Amount,
         xmr: monero::Amount,
-        env_config: Config,
+        cancel_timelock: CancelTimelock,
+        punish_timelock: PunishTimelock,
         redeem_address: bitcoin::Address,
         punish_address: bitcoin::Address,
         tx_redeem_fee: bitcoin::Amount,
@@ -161,8 +209,8 @@ impl State0 {
             punish_address,
             btc,
             xmr,
-            cancel_timelock: env_config.bitcoin_cancel_timelock,
-            punish_timelock: env_config.bitcoin_punish_timelock,
+            cancel_timelock,
+            punish_timelock,
             tx_redeem_fee,
             tx_punish_fee,
         }
@@ -472,6 +520,19 @@ impl State3 {
         TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee)
     }
 
+    pub fn tx_early_refund(&self) -> TxEarlyRefund {
+        TxEarlyRefund::new(&self.tx_lock, &self.refund_address, self.tx_refund_fee)
+    }
+
+    /// Signs the transaction that returns Bob's Bitcoin without waiting for
+    /// the cancel timelock.
+    ///
+    /// Only hand this signature out once the swap has been safely aborted,
+    /// i.e. if we never locked the Monero and never will.
+    pub fn sign_early_refund(&self) -> bitcoin::Signature {
+        self.a.sign(self.tx_early_refund().digest())
+    }
+
     pub fn extract_monero_private_key(
         &self,
         published_refund_tx: bitcoin::Transaction,
@@ -509,7 +570,7 @@ impl State3 {
         &self,
         monero_wallet: &monero::Wallet,
         monero_wallet_restore_blockheight: BlockHeight,
-        file_name: String,
+        swap_id: Uuid,
         spend_key: monero::PrivateKey,
         transfer_proof: TransferProof,
     ) -> Result<()> {
@@ -521,12 +582,16 @@ impl State3 {
             .watch_for_transfer(self.lock_xmr_watch_request(transfer_proof, 10))
             .await?;
 
+        // Every refund goes to its own subaddress to not link swaps on-chain
+        let refund_address = monero_wallet.subaddress(&swap_id.to_string()).await?;
+
         monero_wallet
             .create_from(
-                file_name,
+                swap_id.to_string(),
                 spend_key,
                 view_key,
                 monero_wallet_restore_blockheight,
+                refund_address,
             )
             .await?;
 
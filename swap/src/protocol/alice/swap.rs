@@ -6,9 +6,11 @@ use crate::asb::{EventLoopHandle, LatestRate};
 use crate::bitcoin::ExpiredTimelocks;
 use crate::env::Config;
 use crate::protocol::alice::{AliceState, Swap};
+use crate::protocol::{Database, State};
 use crate::{bitcoin, monero};
 use anyhow::{bail, Context, Result};
 use backoff::ExponentialBackoffBuilder;
+use std::sync::Arc;
 use tokio::select;
 use tokio::time::timeout;
 use uuid::Uuid;
@@ -36,6 +38,7 @@ where
             swap.swap_id,
             current_state,
             &mut swap.event_loop_handle,
+            swap.db.clone(),
             swap.bitcoin_wallet.as_ref(),
             swap.monero_wallet.as_ref(),
             &swap.env_config,
@@ -43,6 +46,16 @@ where
         )
         .await?;
 
+        // Do not overwrite an abort by another process before the Monero is locked
+        if matches!(
+            current_state,
+            AliceState::BtcLockTransactionSeen { .. } | AliceState::BtcLocked { .. }
+        ) && swap_safely_aborted(swap.swap_id, swap.db.as_ref()).await?
+        {
+            tracing::info!("Swap was safely aborted");
+            return Ok(AliceState::SafelyAborted);
+        }
+
         swap.db
             .insert_latest_state(swap.swap_id, current_state.clone().into())
             .await?;
@@ -55,6 +68,7 @@ async fn next_state<LR>(
     swap_id: Uuid,
     state: AliceState,
     event_loop_handle: &mut EventLoopHandle,
+    db: Arc<dyn Database + Send + Sync>,
     bitcoin_wallet: &bitcoin::Wallet,
     monero_wallet: &monero::Wallet,
     env_config: &Config,
@@ -129,6 +143,16 @@ where
             let result = backoff::future::retry_notify(
                 backoff,
                 || async {
+                    // The swap can be safely aborted by another process while we are retrying, once it
+                    // is aborted Bob may receive our signature for the early refund transaction.
+                    if swap_safely_aborted(swap_id, db.as_ref())
+                        .await
+                        .map_err(backoff::Error::transient)?
+                    {
+                        tracing::info!("Swap was safely aborted, not locking the Monero");
+                        return Ok(None);
+                    }
+
                     match state3.expired_timelocks(bitcoin_wallet).await {
                         Ok(ExpiredTimelocks::None { .. }) => {
                             // Record the current monero wallet block height so we don't have to scan from
@@ -389,7 +413,7 @@ where
                 .refund_xmr(
                     monero_wallet,
                     monero_wallet_restore_blockheight,
-                    swap_id.to_string(),
+                    swap_id,
                     spend_key,
                     transfer_proof,
                 )
@@ -449,3 +473,10 @@ pub(crate) fn is_complete(state: &AliceState) -> bool {
             | AliceState::SafelyAborted
     )
 }
+
+async fn swap_safely_aborted(swap_id: Uuid, db: &(dyn Database + Send + Sync)) -> Result<bool> {
+    Ok(matches!(
+        db.get_state(swap_id).await?,
+        State::Alice(AliceState::SafelyAborted)
+    ))
+}
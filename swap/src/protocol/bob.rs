@@ -3,6 +3,8 @@ use std::sync::Arc;
 use anyhow::Result;
 use uuid::Uuid;
 
+use crate::network::quote::SignedBidQuote;
+use crate::network::swap_setup::Timelocks;
 use crate::protocol::Database;
 use crate::{bitcoin, cli, env, monero};
 
@@ -10,6 +12,7 @@ pub use self::state::*;
 pub use self::swap::{run, run_until};
 use std::convert::TryInto;
 
+pub mod preflight;
 pub mod state;
 pub mod swap;
 
@@ -22,6 +25,8 @@ pub struct Swap {
     pub env_config: env::Config,
     pub id: Uuid,
     pub monero_receive_address: monero::Address,
+    /// Whether to lock the Bitcoin without running the pre-flight checks.
+    pub skip_preflight: bool,
 }
 
 impl Swap {
@@ -36,11 +41,15 @@ impl Swap {
         monero_receive_address: monero::Address,
         bitcoin_change_address: bitcoin::Address,
         btc_amount: bitcoin::Amount,
+        timelocks: Option<Timelocks>,
+        quote: Option<SignedBidQuote>,
     ) -> Self {
         Self {
             state: BobState::Started {
                 btc_amount,
                 change_address: bitcoin_change_address,
+                timelocks,
+                quote,
             },
             event_loop_handle,
             db,
@@ -49,6 +58,7 @@ impl Swap {
             env_config,
             id,
             monero_receive_address,
+            skip_preflight: false,
         }
     }
 
@@ -73,6 +83,15 @@ impl Swap {
             env_config,
             id,
             monero_receive_address,
+            skip_preflight: false,
         })
     }
+
+    /// Lock the Bitcoin even if the pre-flight checks fail.
+    pub fn with_skip_preflight(self, skip_preflight: bool) -> Self {
+        Self {
+            skip_preflight,
+            ..self
+        }
+    }
 }
@@ -0,0 +1,484 @@
+//! Checks Bob runs before locking his Bitcoin.
+//!
+//! Once the Bitcoin is locked, getting it back depends on our nodes following
+//! the right chains, on the seller locking the Monero in time and on the
+//! cancel and refund transactions being mined. The checks make sure these
+//! hold before any funds are at stake. A failed check fails the swap, the
+//! checks can be skipped with `--skip-preflight`.
+
+use crate::bitcoin::wallet::ElectrumChain;
+use crate::bitcoin::{CancelTimelock, TxCancel, TxRefund};
+use crate::protocol::bob::{BobState, State2};
+use crate::protocol::{Database, State};
+use crate::{bitcoin, env, monero};
+use ::bitcoin::blockdata::constants::genesis_block;
+use anyhow::{bail, Context, Result};
+use monero_rpc::monerod::GetInfo;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// The latest block of the Electrum server must not be older than this. On
+/// average a block is found every 10 minutes, a gap of two hours is extremely
+/// unlikely.
+const MAX_TIP_AGE: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// Blocks needed after the Monero is locked to send the encrypted signature to
+/// the seller and for the seller to redeem before the cancel timelock expires.
+const REDEEM_MARGIN_BLOCKS: u32 = 3;
+
+/// Outputs below this amount are not relayed by the network.
+const DUST_AMOUNT: u64 = 546;
+
+/// Runs all checks and fails if any of them fails.
+pub async fn check(
+    swap_id: Uuid,
+    state2: &State2,
+    db: Arc<dyn Database + Send + Sync>,
+    bitcoin_wallet: &bitcoin::Wallet,
+    monero_wallet: &monero::Wallet,
+    env_config: env::Config,
+) -> Result<()> {
+    let results = [
+        (
+            "nodes",
+            check_nodes(bitcoin_wallet, monero_wallet, env_config).await,
+        ),
+        (
+            "time before cancel timelock",
+            check_time_before_cancel(
+                state2.cancel_timelock,
+                blocks_until_redeemed(
+                    bitcoin_wallet.target_block(),
+                    state2.min_monero_confirmations,
+                    env_config,
+                ),
+            ),
+        ),
+        ("seller record", check_seller(swap_id, db).await),
+        (
+            "cancel and refund fees",
+            check_refund_fees(state2, bitcoin_wallet).await,
+        ),
+    ];
+
+    let mut failures = Vec::new();
+    for (name, result) in results {
+        match result {
+            Ok(()) => tracing::info!(%swap_id, check = name, "Pre-flight check passed"),
+            Err(error) => failures.push(format!("{}: {:#}", name, error)),
+        }
+    }
+
+    if !failures.is_empty() {
+        bail!(
+            "Pre-flight checks failed, the Bitcoin was not locked. Resume the swap with --skip-preflight to lock it anyway. {}",
+            failures.join("; ")
+        );
+    }
+
+    Ok(())
+}
+
+async fn check_nodes(
+    bitcoin_wallet: &bitcoin::Wallet,
+    monero_wallet: &monero::Wallet,
+    env_config: env::Config,
+) -> Result<()> {
+    let chain = bitcoin_wallet.electrum_chain().await?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    check_electrum_chain(env_config.bitcoin_network, &chain, now)?;
+
+    match monero_wallet.daemon_info().await? {
+        Some(info) => check_monerod(env_config, &info)?,
+        None => tracing::debug!("Not connected to monerod directly, skipping its pre-flight check"),
+    }
+
+    Ok(())
+}
+
+fn check_electrum_chain(network: bitcoin::Network, chain: &ElectrumChain, now: u64) -> Result<()> {
+    if chain.genesis_hash != genesis_block(network).block_hash() {
+        bail!("Electrum server is not on the Bitcoin {} network", network);
+    }
+
+    // Blocks are only mined on demand on regtest
+    if network != bitcoin::Network::Regtest {
+        let age = now.saturating_sub(u64::from(chain.tip_time));
+
+        if age > MAX_TIP_AGE.as_secs() {
+            bail!(
+                "Electrum server is not synced, its latest block {} is {} minutes old",
+                chain.tip_height,
+                age / 60
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn check_monerod(env_config: env::Config, info: &GetInfo) -> Result<()> {
+    match info.network() {
+        Some(network) if network == env_config.monero_network => {}
+        // Local test chains are used together with a Bitcoin regtest chain
+        None if env_config.bitcoin_network == bitcoin::Network::Regtest => {}
+        _ => bail!(
+            "monerod is on {} but the swap is on the Monero {:?} network",
+            info.nettype,
+            env_config.monero_network
+        ),
+    }
+
+    if !info.is_synced() {
+        bail!(
+            "monerod is not synced, it is at height {} of {}",
+            info.height,
+            info.target_height
+        );
+    }
+
+    Ok(())
+}
+
+/// The number of Bitcoin blocks it takes from broadcasting the lock
+/// transaction until the seller can redeem.
+///
+/// The fee of the lock transaction is estimated at the current fee levels to
+/// be mined within `target_block` blocks.
+fn blocks_until_redeemed(
+    target_block: usize,
+    min_monero_confirmations: u64,
+    env_config: env::Config,
+) -> u32 {
+    let target_block = u32::try_from(target_block).unwrap_or(u32::MAX);
+
+    let monero_confirmation_secs = env_config
+        .monero_avg_block_time
+        .as_secs()
+        .saturating_mul(min_monero_confirmations);
+    let bitcoin_block_secs = env_config.bitcoin_avg_block_time.as_secs().max(1);
+    let monero_confirmation_blocks =
+        u32::try_from((monero_confirmation_secs + bitcoin_block_secs - 1) / bitcoin_block_secs)
+            .unwrap_or(u32::MAX);
+
+    target_block
+        .saturating_add(env_config.bitcoin_finality_confirmations)
+        .saturating_add(monero_confirmation_blocks)
+        .saturating_add(REDEEM_MARGIN_BLOCKS)
+}
+
+fn check_time_before_cancel(cancel_timelock: CancelTimelock, required_blocks: u32) -> Result<()> {
+    if u32::from(cancel_timelock) < required_blocks {
+        bail!(
+            "the cancel timelock of {} expires before the swap can be completed, which takes about {} blocks",
+            cancel_timelock,
+            required_blocks
+        );
+    }
+
+    Ok(())
+}
+
+async fn check_seller(swap_id: Uuid, db: Arc<dyn Database + Send + Sync>) -> Result<()> {
+    let record = seller_record(swap_id, db)
+        .await
+        .context("Failed to load previous swaps with the seller")?;
+
+    if record.is_unknown() {
+        tracing::info!(%swap_id, "No previous swaps with the seller in which the Bitcoin was locked");
+        return Ok(());
+    }
+
+    if !record.is_timely() {
+        bail!(
+            "the seller did not lock the Monero in {} of {} previous swaps",
+            record.xmr_not_locked,
+            record.xmr_not_locked + record.xmr_locked
+        );
+    }
+
+    Ok(())
+}
+
+async fn seller_record(swap_id: Uuid, db: Arc<dyn Database + Send + Sync>) -> Result<SellerRecord> {
+    let seller = db.get_peer_id(swap_id).await?;
+    let mut record = SellerRecord::default();
+
+    for (other_swap_id, state) in db.all().await? {
+        if other_swap_id == swap_id || !matches!(state, State::Bob(_)) {
+            continue;
+        }
+
+        match db.get_peer_id(other_swap_id).await {
+            Ok(peer_id) if peer_id == seller => {}
+            _ => continue,
+        }
+
+        let history = db
+            .get_states(other_swap_id)
+            .await?
+            .into_iter()
+            .filter_map(|state| state.try_into().ok())
+            .collect::<Vec<BobState>>();
+
+        record.add(&history);
+    }
+
+    Ok(record)
+}
+
+/// How the seller behaved in previous swaps in which we locked the Bitcoin.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct SellerRecord {
+    /// Swaps in which the seller locked the Monero.
+    xmr_locked: usize,
+    /// Swaps that were cancelled without the seller ever locking the Monero.
+    xmr_not_locked: usize,
+}
+
+impl SellerRecord {
+    fn add(&mut self, history: &[BobState]) {
+        let btc_locked = history
+            .iter()
+            .any(|state| matches!(state, BobState::BtcLocked { .. }));
+        let xmr_locked = history.iter().any(|state| {
+            matches!(
+                state,
+                BobState::XmrLockProofReceived { .. } | BobState::XmrLocked(..)
+            )
+        });
+        let cancelled = history.iter().any(|state| {
+            matches!(
+                state,
+                BobState::CancelTimelockExpired(..)
+                    | BobState::BtcCancelled(..)
+                    | BobState::BtcRefunded(..)
+            )
+        });
+
+        if xmr_locked {
+            self.xmr_locked += 1;
+        } else if btc_locked && cancelled {
+            self.xmr_not_locked += 1;
+        }
+    }
+
+    fn is_unknown(&self) -> bool {
+        self.xmr_locked == 0 && self.xmr_not_locked == 0
+    }
+
+    /// We only wait for the Monero until the cancel timelock expires, a seller
+    /// that locked the Monero therefore locked it in time.
+    fn is_timely(&self) -> bool {
+        self.xmr_not_locked <= self.xmr_locked
+    }
+}
+
+async fn check_refund_fees(state2: &State2, bitcoin_wallet: &bitcoin::Wallet) -> Result<()> {
+    let lock_amount = state2.tx_lock.lock_amount();
+    let projected_cancel_fee = bitcoin_wallet
+        .projected_fee(TxCancel::weight(), lock_amount)
+        .await?;
+    let projected_refund_fee = bitcoin_wallet
+        .projected_fee(TxRefund::weight(), lock_amount)
+        .await?;
+
+    check_fees(
+        lock_amount,
+        (state2.tx_cancel_fee, projected_cancel_fee),
+        (state2.tx_refund_fee, projected_refund_fee),
+    )
+}
+
+/// The fees of `TxCancel` and `TxRefund` are fixed during swap setup and
+/// bounded relative to the lock amount. They fail the check if the current fee
+/// levels exceed them by more than a quarter, small movements between the
+/// setup and the lock are tolerated.
+fn check_fees(
+    lock_amount: bitcoin::Amount,
+    (tx_cancel_fee, projected_cancel_fee): (bitcoin::Amount, bitcoin::Amount),
+    (tx_refund_fee, projected_refund_fee): (bitcoin::Amount, bitcoin::Amount),
+) -> Result<()> {
+    for (name, fee, projected_fee) in [
+        ("TxCancel", tx_cancel_fee, projected_cancel_fee),
+        ("TxRefund", tx_refund_fee, projected_refund_fee),
+    ] {
+        if projected_fee.to_sat().saturating_mul(4) > fee.to_sat().saturating_mul(5) {
+            bail!(
+                "{} pays a fee of {} but needs {} at the current fee levels, which is not affordable for a lock amount of {}",
+                name,
+                fee,
+                projected_fee,
+                lock_amount
+            );
+        }
+    }
+
+    let refunded = lock_amount
+        .checked_sub(tx_cancel_fee + tx_refund_fee)
+        .filter(|refunded| refunded.to_sat() > DUST_AMOUNT);
+
+    if refunded.is_none() {
+        bail!(
+            "the fees of TxCancel and TxRefund leave nothing of the lock amount of {}",
+            lock_amount
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::GetConfig;
+    use monero_rpc::wallet::BlockHeight;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn chain(network: bitcoin::Network, tip_age: Duration) -> ElectrumChain {
+        ElectrumChain {
+            genesis_hash: genesis_block(network).block_hash(),
+            tip_height: 800_000,
+            tip_time: u32::try_from(NOW - tip_age.as_secs()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn electrum_on_other_network_fails() {
+        let chain = chain(bitcoin::Network::Testnet, Duration::from_secs(60));
+
+        assert!(check_electrum_chain(bitcoin::Network::Bitcoin, &chain, NOW).is_err());
+    }
+
+    #[test]
+    fn electrum_with_old_tip_fails() {
+        let synced = chain(bitcoin::Network::Bitcoin, Duration::from_secs(30 * 60));
+        let behind = chain(bitcoin::Network::Bitcoin, Duration::from_secs(3 * 60 * 60));
+
+        assert!(check_electrum_chain(bitcoin::Network::Bitcoin, &synced, NOW).is_ok());
+        assert!(check_electrum_chain(bitcoin::Network::Bitcoin, &behind, NOW).is_err());
+    }
+
+    #[test]
+    fn mainnet_leaves_time_before_cancel_timelock() {
+        let env_config = env::Mainnet::get_config();
+
+        let required = blocks_until_redeemed(1, 10, env_config);
+
+        assert_eq!(required, 7);
+        assert!(check_time_before_cancel(env_config.bitcoin_cancel_timelock, required).is_ok());
+        assert!(check_time_before_cancel(CancelTimelock::new(6), required).is_err());
+    }
+
+    #[tokio::test]
+    async fn seller_record_counts_cancelled_swaps_without_xmr_lock() {
+        let (_, state2) = crate::protocol::swap_setup_states().await;
+        let (state3, _) = state2.clone().lock_btc().await.unwrap();
+        let restore_height = BlockHeight { height: 0 };
+        let state4 = state3.clone().xmr_locked(restore_height);
+        let state6 = state3.cancel(restore_height);
+        let btc_locked = BobState::BtcLocked {
+            state3: state3.clone(),
+            monero_wallet_restore_blockheight: restore_height,
+        };
+
+        let aborted = [
+            BobState::SwapSetupCompleted(state2.clone()),
+            BobState::SafelyAborted,
+        ];
+        let cancelled_without_xmr_lock = [
+            BobState::SwapSetupCompleted(state2.clone()),
+            btc_locked.clone(),
+            BobState::CancelTimelockExpired(state6.clone()),
+            BobState::BtcCancelled(state6.clone()),
+            BobState::BtcRefunded(state6),
+        ];
+        let redeemed = [
+            BobState::SwapSetupCompleted(state2),
+            btc_locked,
+            BobState::XmrLocked(state4.clone()),
+            BobState::EncSigSent(state4),
+            BobState::XmrRedeemed {
+                tx_lock_id: state3.tx_lock_id(),
+            },
+        ];
+
+        let mut record = SellerRecord::default();
+
+        record.add(&aborted);
+        assert!(record.is_unknown());
+
+        record.add(&cancelled_without_xmr_lock);
+        assert_eq!(
+            record,
+            SellerRecord {
+                xmr_locked: 0,
+                xmr_not_locked: 1
+            }
+        );
+        assert!(!record.is_timely());
+
+        record.add(&redeemed);
+        assert_eq!(
+            record,
+            SellerRecord {
+                xmr_locked: 1,
+                xmr_not_locked: 1
+            }
+        );
+        assert!(record.is_timely());
+    }
+
+    #[test]
+    fn fees_above_current_levels_pass() {
+        let result = check_fees(
+            bitcoin::Amount::from_sat(100_000),
+            (
+                bitcoin::Amount::from_sat(1_000),
+                bitcoin::Amount::from_sat(1_100),
+            ),
+            (
+                bitcoin::Amount::from_sat(1_000),
+                bitcoin::Amount::from_sat(900),
+            ),
+        );
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn capped_fees_below_current_levels_fail() {
+        let result = check_fees(
+            bitcoin::Amount::from_sat(100_000),
+            (
+                bitcoin::Amount::from_sat(3_000),
+                bitcoin::Amount::from_sat(10_000),
+            ),
+            (
+                bitcoin::Amount::from_sat(3_000),
+                bitcoin::Amount::from_sat(10_000),
+            ),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn fees_consuming_lock_amount_fail() {
+        let result = check_fees(
+            bitcoin::Amount::from_sat(2_000),
+            (
+                bitcoin::Amount::from_sat(1_000),
+                bitcoin::Amount::from_sat(1_000),
+            ),
+            (
+                bitcoin::Amount::from_sat(1_000),
+                bitcoin::Amount::from_sat(1_000),
+            ),
+        );
+
+        assert!(result.is_err());
+    }
+}
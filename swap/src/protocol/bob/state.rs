@@ -1,13 +1,16 @@
 use crate::bitcoin::wallet::{EstimateFeeRate, Subscription};
 use crate::bitcoin::{
     self, current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxLock, Txid,
+    TxEarlyRefund, TxLock, Txid,
 };
 use crate::monero;
 use crate::monero::wallet::WatchRequest;
 use crate::monero::{monero_private_key, TransferProof};
 use crate::monero_ext::ScalarExt;
+use crate::network::quote::SignedBidQuote;
+use crate::network::swap_setup::Timelocks;
 use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
+use crate::watchtower;
 use anyhow::{anyhow, bail, Context, Result};
 use bdk::database::BatchDatabase;
 use ecdsa_fun::adaptor::{Adaptor, HashTranscript};
@@ -27,6 +30,12 @@ pub enum BobState {
         #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
         btc_amount: bitcoin::Amount,
         change_address: bitcoin::Address,
+        /// The timelocks to propose to Alice, if they differ from the ones of
+        /// the network.
+        timelocks: Option<Timelocks>,
+        /// The quote the swap was started at, its price is honoured by Alice
+        /// if it is signed and did not expire.
+        quote: Option<SignedBidQuote>,
     },
     SwapSetupCompleted(State2),
     BtcLocked {
@@ -44,6 +53,9 @@ pub enum BobState {
     CancelTimelockExpired(State6),
     BtcCancelled(State6),
     BtcRefunded(State6),
+    /// Alice aborted the swap before locking the Monero and signed the early
+    /// refund transaction.
+    BtcEarlyRefunded(State3),
     XmrRedeemed {
         tx_lock_id: bitcoin::Txid,
     },
@@ -69,6 +81,7 @@ impl fmt::Display for BobState {
             BobState::CancelTimelockExpired(..) => write!(f, "cancel timelock is expired"),
             BobState::BtcCancelled(..) => write!(f, "btc is cancelled"),
             BobState::BtcRefunded(..) => write!(f, "btc is refunded"),
+            BobState::BtcEarlyRefunded(..) => write!(f, "btc is refunded early"),
             BobState::XmrRedeemed { .. } => write!(f, "xmr is redeemed"),
             BobState::BtcPunished { .. } => write!(f, "btc is punished"),
             BobState::SafelyAborted => write!(f, "safely aborted"),
@@ -76,6 +89,51 @@ impl fmt::Display for BobState {
     }
 }
 
+impl BobState {
+    /// The Bitcoin lock transaction of this swap, if it was constructed
+    /// already.
+    pub fn tx_lock(&self) -> Option<&TxLock> {
+        match self {
+            BobState::SwapSetupCompleted(state2) => Some(&state2.tx_lock),
+            BobState::BtcLocked { state3, .. }
+            | BobState::XmrLockProofReceived { state: state3, .. }
+            | BobState::BtcEarlyRefunded(state3) => Some(&state3.tx_lock),
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => Some(&state4.tx_lock),
+            BobState::BtcRedeemed(state5) => Some(&state5.tx_lock),
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6)
+            | BobState::BtcPunished { state: state6, .. } => Some(&state6.tx_lock),
+            BobState::Started { .. } | BobState::XmrRedeemed { .. } | BobState::SafelyAborted => {
+                None
+            }
+        }
+    }
+
+    /// Addresses of our own Bitcoin wallet that this swap pays out to.
+    pub fn own_bitcoin_addresses(&self) -> Vec<bitcoin::Address> {
+        match self {
+            BobState::Started { change_address, .. } => vec![change_address.clone()],
+            BobState::SwapSetupCompleted(state2) => vec![state2.refund_address.clone()],
+            BobState::BtcLocked { state3, .. }
+            | BobState::XmrLockProofReceived { state: state3, .. }
+            | BobState::BtcEarlyRefunded(state3) => {
+                vec![state3.refund_address.clone()]
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                vec![state4.refund_address.clone()]
+            }
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6)
+            | BobState::BtcPunished { state: state6, .. } => vec![state6.refund_address.clone()],
+            BobState::BtcRedeemed(..) | BobState::XmrRedeemed { .. } | BobState::SafelyAborted => {
+                vec![]
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     swap_id: Uuid,
@@ -298,7 +356,7 @@ pub struct State2 {
     pub tx_lock: bitcoin::TxLock,
     tx_cancel_sig_a: Signature,
     tx_refund_encsig: bitcoin::EncryptedSignature,
-    min_monero_confirmations: u64,
+    pub min_monero_confirmations: u64,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_redeem_fee: bitcoin::Amount,
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
@@ -444,6 +502,30 @@ impl State3 {
         self.tx_lock.txid()
     }
 
+    pub fn tx_early_refund(&self) -> TxEarlyRefund {
+        TxEarlyRefund::new(&self.tx_lock, &self.refund_address, self.tx_refund_fee)
+    }
+
+    pub fn signed_early_refund_transaction(
+        &self,
+        tx_early_refund_sig_a: Signature,
+    ) -> Result<Transaction> {
+        self.tx_early_refund()
+            .complete_as_bob(self.A, self.b.clone(), tx_early_refund_sig_a)
+    }
+
+    pub async fn publish_early_refund_btc(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+        tx_early_refund_sig_a: Signature,
+    ) -> Result<(Txid, Subscription)> {
+        let signed_tx_early_refund = self.signed_early_refund_transaction(tx_early_refund_sig_a)?;
+
+        bitcoin_wallet
+            .broadcast(signed_tx_early_refund, "early_refund")
+            .await
+    }
+
     pub async fn expired_timelock(
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
@@ -631,29 +713,15 @@ impl State5 {
         let (spend_key, view_key) = self.xmr_keys();
 
         tracing::info!(%wallet_file_name, "Generating and opening Monero wallet from the extracted keys to redeem the Monero");
-        if let Err(e) = monero_wallet
-            .create_from_and_load(
-                wallet_file_name.clone(),
+        let tx_hashes = monero_wallet
+            .sweep_from_keys(
+                wallet_file_name,
                 spend_key,
                 view_key,
                 self.monero_wallet_restore_blockheight,
+                monero_receive_address,
             )
-            .await
-        {
-            // In case we failed to refresh/sweep, when resuming the wallet might already
-            // exist! This is a very unlikely scenario, but if we don't take care of it we
-            // might not be able to ever transfer the Monero.
-            tracing::warn!("Failed to generate monero wallet from keys: {:#}", e);
-            tracing::info!(%wallet_file_name,
-                "Falling back to trying to open the wallet if it already exists",
-            );
-            monero_wallet.open(wallet_file_name).await?;
-        }
-
-        // Ensure that the generated wallet is synced so we have a proper balance
-        monero_wallet.refresh(20).await?;
-        // Sweep (transfer all funds) to the given address
-        let tx_hashes = monero_wallet.sweep_all(monero_receive_address).await?;
+            .await?;
         for tx_hash in tx_hashes {
             tracing::info!(%monero_receive_address, txid=%tx_hash.0, "Successfully transferred XMR to wallet");
         }
@@ -739,7 +807,20 @@ impl State6 {
 
     pub async fn publish_refund_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<()> {
         let signed_tx_refund = self.signed_refund_transaction()?;
-        bitcoin_wallet.broadcast(signed_tx_refund, "refund").await?;
+        let tx_refund = (signed_tx_refund.txid(), self.refund_address.script_pubkey());
+
+        if let Err(error) = bitcoin_wallet.broadcast(signed_tx_refund, "refund").await {
+            // A watchtower may have published the refund already
+            if !bitcoin_wallet
+                .status_of_script(&tx_refund)
+                .await?
+                .has_been_seen()
+            {
+                return Err(error);
+            }
+
+            tracing::info!(txid = %tx_refund.0, "Bitcoin refund transaction was already published");
+        }
 
         Ok(())
     }
@@ -760,6 +841,35 @@ impl State6 {
         Ok(signed_tx_refund)
     }
 
+    /// The cancel and refund transactions, signed by both parties, and the
+    /// ids and timelocks a watchtower needs to cancel and refund the swap
+    /// while Bob is offline.
+    ///
+    /// The signed refund transaction reveals `s_b` to Alice, the bundle must
+    /// never reach her.
+    pub fn watchtower_bundle(&self, swap_id: Uuid) -> Result<watchtower::Bundle> {
+        let tx_cancel = self
+            .construct_tx_cancel()?
+            .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
+            .context("Failed to complete Bitcoin cancel transaction")?;
+        let tx_refund = self.signed_refund_transaction()?;
+
+        Ok(watchtower::Bundle {
+            swap_id,
+            network: self.refund_address.network,
+            tx_lock_id: self.tx_lock.txid(),
+            lock_amount: self.tx_lock.lock_amount(),
+            lock_output_descriptor: self.tx_lock.output_descriptor().clone(),
+            alice_public_key: self.A,
+            bob_public_key: self.b.public(),
+            cancel_timelock: self.cancel_timelock,
+            punish_timelock: self.punish_timelock,
+            tx_cancel,
+            tx_refund,
+            refund_address: self.refund_address.clone(),
+        })
+    }
+
     pub fn tx_lock_id(&self) -> bitcoin::Txid {
         self.tx_lock.txid()
     }
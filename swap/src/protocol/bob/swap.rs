@@ -1,19 +1,28 @@
 use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxRefund};
 use crate::cli::EventLoopHandle;
 use crate::network::cooperative_xmr_redeem_after_punish::Response::{Fullfilled, Rejected};
-use crate::network::swap_setup::bob::NewSwap;
+use crate::network::quote::SignedBidQuote;
+use crate::network::swap_setup::bob::{AcceptedQuote, NewSwap};
+use crate::network::{compatibility, cooperative_early_refund};
+use crate::protocol::bob::preflight;
 use crate::protocol::bob::state::*;
 use crate::protocol::{bob, Database};
-use crate::{bitcoin, monero};
+use crate::{bitcoin, env, monero};
 use anyhow::{bail, Context, Result};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::select;
 use uuid::Uuid;
 
+const PSBT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
 pub fn is_complete(state: &BobState) -> bool {
     matches!(
         state,
-        BobState::BtcRefunded(..) | BobState::XmrRedeemed { .. } | BobState::SafelyAborted
+        BobState::BtcRefunded(..)
+            | BobState::BtcEarlyRefunded(..)
+            | BobState::XmrRedeemed { .. }
+            | BobState::SafelyAborted
     )
 }
 
@@ -48,6 +57,8 @@ pub async fn run_until(
             swap.bitcoin_wallet.as_ref(),
             swap.monero_wallet.as_ref(),
             swap.monero_receive_address,
+            swap.env_config,
+            swap.skip_preflight,
         )
         .await?;
 
@@ -65,6 +76,7 @@ pub async fn run_until(
     Ok(current_state)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn next_state(
     swap_id: Uuid,
     state: BobState,
@@ -73,6 +85,8 @@ async fn next_state(
     bitcoin_wallet: &bitcoin::Wallet,
     monero_wallet: &monero::Wallet,
     monero_receive_address: monero::Address,
+    env_config: env::Config,
+    skip_preflight: bool,
 ) -> Result<BobState> {
     tracing::debug!(%state, "Advancing state");
 
@@ -80,7 +94,28 @@ async fn next_state(
         BobState::Started {
             btc_amount,
             change_address,
+            timelocks,
+            quote,
         } => {
+            // Waiting for funds or resuming can take longer than the quote is valid
+            let quote = match quote {
+                Some(SignedBidQuote {
+                    quote,
+                    signature: Some(signature),
+                }) => {
+                    if signature.is_expired()? {
+                        tracing::warn!(quote_id = %signature.id, "Quote expired, swapping at the latest price of the seller");
+                        None
+                    } else {
+                        Some(AcceptedQuote {
+                            id: signature.id,
+                            price: quote.price,
+                        })
+                    }
+                }
+                _ => None,
+            };
+
             let tx_refund_fee = bitcoin_wallet
                 .estimate_fee(TxRefund::weight(), btc_amount)
                 .await?;
@@ -95,6 +130,8 @@ async fn next_state(
                     tx_refund_fee,
                     tx_cancel_fee,
                     bitcoin_refund_address: change_address,
+                    timelocks,
+                    quote,
                 })
                 .await?;
 
@@ -103,6 +140,20 @@ async fn next_state(
             BobState::SwapSetupCompleted(state2)
         }
         BobState::SwapSetupCompleted(state2) => {
+            if skip_preflight {
+                tracing::warn!(%swap_id, "Skipping the pre-flight checks before locking the Bitcoin");
+            } else {
+                preflight::check(
+                    swap_id,
+                    &state2,
+                    db.clone(),
+                    bitcoin_wallet,
+                    monero_wallet,
+                    env_config,
+                )
+                .await?;
+            }
+
             // Record the current monero wallet block height so we don't have to scan from
             // block 0 once we create the redeem wallet.
             // This has to be done **before** the Bitcoin is locked in order to ensure that
@@ -116,10 +167,27 @@ async fn next_state(
 
             // Alice and Bob have exchanged info
             let (state3, tx_lock) = state2.lock_btc().await?;
-            let signed_tx = bitcoin_wallet
-                .sign_and_finalize(tx_lock.clone().into())
+            let signed_tx = if bitcoin_wallet.is_watch_only() {
+                let signed_tx = wait_for_signed_psbt(
+                    swap_id,
+                    db.clone(),
+                    bitcoin_wallet,
+                    tx_lock.clone().into(),
+                    env_config,
+                )
                 .await
-                .context("Failed to sign Bitcoin lock transaction")?;
+                .context("Failed to obtain signed Bitcoin lock transaction")?;
+
+                match signed_tx {
+                    Some(signed_tx) => signed_tx,
+                    None => return Ok(BobState::SafelyAborted),
+                }
+            } else {
+                bitcoin_wallet
+                    .sign_and_finalize(tx_lock.clone().into())
+                    .await
+                    .context("Failed to sign Bitcoin lock transaction")?
+            };
             let (..) = bitcoin_wallet.broadcast(signed_tx, "lock").await?;
 
             BobState::BtcLocked {
@@ -154,29 +222,47 @@ async fn next_state(
                     });
                 }
 
-                let transfer_proof_watcher = event_loop_handle.recv_transfer_proof();
                 let cancel_timelock_expires =
                     tx_lock_status.wait_until_confirmed_with(state3.cancel_timelock);
+                tokio::pin!(cancel_timelock_expires);
+
+                // Retries stay within this state so a declined request does not save it again
+                loop {
+                    select! {
+                        transfer_proof = event_loop_handle.recv_transfer_proof() => {
+                            let transfer_proof = transfer_proof?;
+
+                            tracing::info!(txid = %transfer_proof.tx_hash(), "Alice locked Monero");
+
+                            return Ok(BobState::XmrLockProofReceived {
+                                state: state3,
+                                lock_transfer_proof: transfer_proof,
+                                monero_wallet_restore_blockheight
+                            });
+                        },
+                        result = &mut cancel_timelock_expires => {
+                            result?;
+                            tracing::info!("Alice took too long to lock Monero, cancelling the swap");
+
+                            let state4 = state3.cancel(monero_wallet_restore_blockheight);
+                            return Ok(BobState::CancelTimelockExpired(state4));
+                        },
+                        _ = tokio::time::sleep(env_config.early_refund_request_interval()) => {},
+                    }
 
-                select! {
-                    transfer_proof = transfer_proof_watcher => {
-                        let transfer_proof = transfer_proof?;
-
-                        tracing::info!(txid = %transfer_proof.tx_hash(), "Alice locked Monero");
-
-                        BobState::XmrLockProofReceived {
-                            state: state3,
-                            lock_transfer_proof: transfer_proof,
-                            monero_wallet_restore_blockheight
+                    match event_loop_handle.alice_supports(compatibility::COOPERATIVE_EARLY_REFUND)
+                    {
+                        Some(true) => {}
+                        Some(false) => continue,
+                        None => {
+                            tracing::debug!("Alice has not identified herself yet, not asking for an early refund");
+                            continue;
                         }
-                    },
-                    result = cancel_timelock_expires => {
-                        result?;
-                        tracing::info!("Alice took too long to lock Monero, cancelling the swap");
+                    }
 
-                        let state4 = state3.cancel(monero_wallet_restore_blockheight);
-                        BobState::CancelTimelockExpired(state4)
-                    },
+                    if early_refund(swap_id, &state3, event_loop_handle, bitcoin_wallet).await {
+                        return Ok(BobState::BtcEarlyRefunded(state3));
+                    }
                 }
             } else {
                 let state4 = state3.cancel(monero_wallet_restore_blockheight);
@@ -309,6 +395,7 @@ async fn next_state(
             }
         }
         BobState::BtcRefunded(state4) => BobState::BtcRefunded(state4),
+        BobState::BtcEarlyRefunded(state3) => BobState::BtcEarlyRefunded(state3),
         BobState::BtcPunished { state, tx_lock_id } => {
             tracing::info!("Attempting to cooperatively redeem XMR after being punished");
             let response = event_loop_handle
@@ -359,3 +446,114 @@ async fn next_state(
         BobState::XmrRedeemed { tx_lock_id } => BobState::XmrRedeemed { tx_lock_id },
     })
 }
+
+/// Hands the unsigned lock transaction to the user and waits until the PSBT
+/// signed by the external wallet is imported.
+///
+/// Alice aborts the swap if she does not see the lock transaction within
+/// `bitcoin_lock_mempool_timeout` of the swap setup. The signed transaction is
+/// therefore only broadcast within the first half of that window, counted from
+/// the export of the PSBT, so it still reaches her in time. Returns `None` once
+/// that deadline passed, the swap has to be aborted without locking the
+/// Bitcoin.
+async fn wait_for_signed_psbt(
+    swap_id: Uuid,
+    db: Arc<dyn Database + Send + Sync>,
+    bitcoin_wallet: &bitcoin::Wallet,
+    psbt: bitcoin::PartiallySignedTransaction,
+    env_config: env::Config,
+) -> Result<Option<bitcoin::Transaction>> {
+    if db.get_unsigned_psbt(swap_id).await?.is_none() {
+        db.insert_unsigned_psbt(swap_id, psbt.clone()).await?;
+    }
+
+    // PSBTs exported before the export time was recorded are treated as expired
+    let signing_window = env_config.bitcoin_lock_mempool_timeout / 2;
+    let deadline = db
+        .get_psbt_exported_at(swap_id)
+        .await?
+        .map(|exported_at| exported_at + signing_window);
+
+    tracing::info!(
+        psbt = %bitcoin::psbt_to_base64(&psbt),
+        minutes = %signing_window.as_secs_f64() / 60.0,
+        "Sign the Bitcoin lock transaction with your external wallet and import it using `swap import-psbt --swap-id {} --psbt <signed psbt>` within the given minutes. Do not broadcast it yourself, the swap is aborted if it is not imported in time",
+        swap_id
+    );
+
+    loop {
+        let remaining = match deadline
+            .and_then(|deadline| deadline.duration_since(SystemTime::now()).ok())
+        {
+            Some(remaining) => remaining,
+            None => {
+                tracing::warn!(
+                    "The signed Bitcoin lock transaction was not imported in time, aborting the swap. Do not broadcast it, the seller no longer waits for it"
+                );
+                return Ok(None);
+            }
+        };
+
+        if let Some(signed_psbt) = db.get_signed_psbt(swap_id).await? {
+            return bitcoin_wallet.finalize_psbt(signed_psbt).await.map(Some);
+        }
+
+        tokio::time::sleep(PSBT_POLL_INTERVAL.min(remaining)).await;
+    }
+}
+
+/// Asks Alice whether she aborted the swap and publishes the early refund
+/// transaction if she did. Returns whether the Bitcoin was refunded.
+async fn early_refund(
+    swap_id: Uuid,
+    state3: &State3,
+    event_loop_handle: &mut EventLoopHandle,
+    bitcoin_wallet: &bitcoin::Wallet,
+) -> bool {
+    tracing::info!("Alice has not locked Monero yet, asking whether she aborted the swap");
+
+    let tx_early_refund_sig = match event_loop_handle
+        .request_cooperative_early_refund(swap_id)
+        .await
+    {
+        Ok(cooperative_early_refund::Response::Fullfilled {
+            tx_early_refund_sig,
+            ..
+        }) => tx_early_refund_sig,
+        Ok(cooperative_early_refund::Response::Rejected { reason, .. }) => {
+            tracing::debug!(%reason, "Alice declined to refund early");
+            return false;
+        }
+        Err(error) => {
+            tracing::debug!("Failed to request early refund: {:#}", error);
+            return false;
+        }
+    };
+
+    let (txid, subscription) = match state3
+        .publish_early_refund_btc(bitcoin_wallet, tx_early_refund_sig)
+        .await
+    {
+        Ok(published) => published,
+        Err(error) => {
+            tracing::warn!("Failed to publish early refund transaction: {:#}", error);
+            return false;
+        }
+    };
+
+    tracing::info!(%txid, "Alice aborted the swap, waiting for the early refund transaction to be confirmed");
+
+    match subscription.wait_until_final().await {
+        Ok(()) => {
+            tracing::info!(%txid, "Refunded our Bitcoin early");
+            true
+        }
+        Err(error) => {
+            tracing::warn!(
+                "Failed to wait for the early refund transaction to be confirmed: {:#}",
+                error
+            );
+            false
+        }
+    }
+}
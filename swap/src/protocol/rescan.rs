@@ -0,0 +1,134 @@
+use crate::asb::recovery::cancel::cancel;
+use crate::asb::recovery::punish::punish;
+use crate::asb::recovery::redeem::{redeem, Finality};
+use crate::bitcoin::wallet::ScriptStatus;
+use crate::bitcoin::{self, ExpiredTimelocks, Txid};
+use crate::cli::cancel_and_refund::cancel_and_refund;
+use crate::protocol::alice::AliceState;
+use crate::protocol::{Database, State};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// A swap whose Bitcoin lock output may still hold funds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LockedSwap {
+    pub swap_id: Uuid,
+    pub state: String,
+    pub tx_lock_id: Txid,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub amount: bitcoin::Amount,
+}
+
+/// Collects the addresses of our own Bitcoin wallet that were used by the
+/// swaps in the database, so a rescan can be extended far enough to find
+/// funds paid out to them.
+pub async fn swap_addresses(db: &(dyn Database + Send + Sync)) -> Result<Vec<bitcoin::Address>> {
+    let mut addresses = Vec::new();
+
+    for (swap_id, _) in db.all().await? {
+        for state in db.get_states(swap_id).await? {
+            addresses.extend(state.own_bitcoin_addresses());
+        }
+    }
+
+    addresses.sort_by_key(|address| address.to_string());
+    addresses.dedup();
+
+    Ok(addresses)
+}
+
+/// Returns all unfinished swaps whose Bitcoin lock transaction was published.
+///
+/// Lock transactions confirmed before `min_lock_height` are skipped. This only
+/// filters the swaps, the wallet sync always covers the full history.
+pub async fn locked_swaps(
+    db: &(dyn Database + Send + Sync),
+    bitcoin_wallet: &bitcoin::Wallet,
+    min_lock_height: Option<u32>,
+) -> Result<Vec<LockedSwap>> {
+    let latest_block = u32::from(bitcoin_wallet.latest_block_height().await);
+    let mut locked_swaps = Vec::new();
+
+    for (swap_id, state) in db.all().await? {
+        if state.swap_finished() {
+            continue;
+        }
+
+        let tx_lock = match state.tx_lock() {
+            Some(tx_lock) => tx_lock,
+            None => continue,
+        };
+
+        match bitcoin_wallet.status_of_script(tx_lock).await? {
+            ScriptStatus::Unseen | ScriptStatus::Retrying => continue,
+            ScriptStatus::InMempool => {}
+            ScriptStatus::Confirmed(confirmed) => {
+                let inclusion_height = (latest_block + 1).saturating_sub(confirmed.confirmations());
+
+                if matches!(min_lock_height, Some(height) if inclusion_height < height) {
+                    tracing::debug!(%swap_id, %inclusion_height, "Skipping swap locked before minimum lock height");
+                    continue;
+                }
+            }
+        }
+
+        locked_swaps.push(LockedSwap {
+            swap_id,
+            state: state.to_string(),
+            tx_lock_id: tx_lock.txid(),
+            amount: tx_lock.lock_amount(),
+        });
+    }
+
+    Ok(locked_swaps)
+}
+
+/// Moves the Bitcoin of a swap out of its lock output if the state of the swap
+/// and the expired timelocks allow it, and returns the new state.
+///
+/// Alice redeems if she learned Bob's encrypted signature, and cancels or
+/// punishes once the respective timelock expired. Bob cancels and refunds once
+/// the cancel timelock expired.
+pub async fn recover(
+    swap_id: Uuid,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    db: Arc<dyn Database + Send + Sync>,
+) -> Result<State> {
+    match db.get_state(swap_id).await? {
+        State::Alice(state) => recover_alice(swap_id, state, bitcoin_wallet, db)
+            .await
+            .map(State::from),
+        State::Bob(_) => cancel_and_refund(swap_id, bitcoin_wallet, db)
+            .await
+            .map(State::from),
+    }
+}
+
+async fn recover_alice(
+    swap_id: Uuid,
+    state: AliceState,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    db: Arc<dyn Database>,
+) -> Result<AliceState> {
+    if let AliceState::EncSigLearned { .. } = state {
+        let (_, state) = redeem(swap_id, bitcoin_wallet, db, Finality::NotAwait).await?;
+        return Ok(state);
+    }
+
+    let state3 = state
+        .state3()
+        .with_context(|| format!("Swap is in state {} without a Bitcoin lock", state))?;
+
+    let (_, state) = match state3.expired_timelocks(&bitcoin_wallet).await? {
+        ExpiredTimelocks::None { blocks_left } => bail!(
+            "Cannot recover the Bitcoin before the cancel timelock expires in {} blocks",
+            blocks_left
+        ),
+        ExpiredTimelocks::Cancel { .. } => cancel(swap_id, bitcoin_wallet, db).await?,
+        ExpiredTimelocks::Punish => punish(swap_id, bitcoin_wallet, db).await?,
+    };
+
+    Ok(state)
+}
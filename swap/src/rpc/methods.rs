@@ -1,6 +1,6 @@
 use crate::api::request::{Method, Request};
 use crate::api::Context;
-use crate::bitcoin::bitcoin_address;
+use crate::bitcoin::{bitcoin_address, CancelTimelock, PunishTimelock};
 use crate::monero::monero_address;
 use crate::{bitcoin, monero};
 use anyhow::Result;
@@ -19,6 +19,10 @@ pub fn register_modules(context: Arc<Context>) -> Result<RpcModule<Arc<Context>>
         execute_request(params, Method::SuspendCurrentSwap, &context).await
     })?;
 
+    module.register_async_method("get_monero_daemon", |params, context| async move {
+        execute_request(params, Method::GetMoneroDaemon, &context).await
+    })?;
+
     module.register_async_method("get_swap_info", |params_raw, context| async move {
         let params: HashMap<String, serde_json::Value> = params_raw.parse()?;
 
@@ -66,7 +70,22 @@ pub fn register_modules(context: Arc<Context>) -> Result<RpcModule<Arc<Context>>
         let swap_id = as_uuid(swap_id)
             .ok_or_else(|| jsonrpsee_core::Error::Custom("Could not parse swap_id".to_string()))?;
 
-        execute_request(params_raw, Method::Resume { swap_id }, &context).await
+        let skip_preflight = match params.get("skip_preflight") {
+            Some(skip_preflight) => skip_preflight.as_bool().ok_or_else(|| {
+                jsonrpsee_core::Error::Custom("skip_preflight is not a boolean".to_string())
+            })?,
+            None => false,
+        };
+
+        execute_request(
+            params_raw,
+            Method::Resume {
+                swap_id,
+                skip_preflight,
+            },
+            &context,
+        )
+        .await
     })?;
 
     module.register_async_method("cancel_refund_swap", |params_raw, context| async move {
@@ -99,6 +118,38 @@ pub fn register_modules(context: Arc<Context>) -> Result<RpcModule<Arc<Context>>
         },
     )?;
 
+    module.register_async_method("export_psbt", |params_raw, context| async move {
+        let params: HashMap<String, serde_json::Value> = params_raw.parse()?;
+
+        let swap_id = params
+            .get("swap_id")
+            .ok_or_else(|| jsonrpsee_core::Error::Custom("Does not contain swap_id".to_string()))?;
+
+        let swap_id = as_uuid(swap_id)
+            .ok_or_else(|| jsonrpsee_core::Error::Custom("Could not parse swap_id".to_string()))?;
+
+        execute_request(params_raw, Method::ExportPsbt { swap_id }, &context).await
+    })?;
+
+    module.register_async_method("import_psbt", |params_raw, context| async move {
+        let params: HashMap<String, serde_json::Value> = params_raw.parse()?;
+
+        let swap_id = params
+            .get("swap_id")
+            .ok_or_else(|| jsonrpsee_core::Error::Custom("Does not contain swap_id".to_string()))?;
+
+        let swap_id = as_uuid(swap_id)
+            .ok_or_else(|| jsonrpsee_core::Error::Custom("Could not parse swap_id".to_string()))?;
+
+        let psbt = params
+            .get("psbt")
+            .and_then(|psbt| psbt.as_str())
+            .ok_or_else(|| jsonrpsee_core::Error::Custom("Does not contain psbt".to_string()))?
+            .to_string();
+
+        execute_request(params_raw, Method::ImportPsbt { swap_id, psbt }, &context).await
+    })?;
+
     module.register_async_method("withdraw_btc", |params_raw, context| async move {
         let params: HashMap<String, String> = params_raw.parse()?;
 
@@ -172,6 +223,31 @@ pub fn register_modules(context: Arc<Context>) -> Result<RpcModule<Arc<Context>>
             })?)
             .map_err(|err| jsonrpsee_core::Error::Custom(err.to_string()))?;
 
+        let bitcoin_cancel_timelock = params
+            .get("bitcoin_cancel_timelock")
+            .map(|blocks| u32::from_str(blocks).map(CancelTimelock::new))
+            .transpose()
+            .map_err(|_| {
+                jsonrpsee_core::Error::Custom("Unable to parse bitcoin_cancel_timelock".to_string())
+            })?;
+
+        let bitcoin_punish_timelock = params
+            .get("bitcoin_punish_timelock")
+            .map(|blocks| u32::from_str(blocks).map(PunishTimelock::new))
+            .transpose()
+            .map_err(|_| {
+                jsonrpsee_core::Error::Custom("Unable to parse bitcoin_punish_timelock".to_string())
+            })?;
+
+        let skip_preflight = params
+            .get("skip_preflight")
+            .map(|skip_preflight| bool::from_str(skip_preflight))
+            .transpose()
+            .map_err(|_| {
+                jsonrpsee_core::Error::Custom("Unable to parse skip_preflight".to_string())
+            })?
+            .unwrap_or(false);
+
         execute_request(
             params_raw,
             Method::BuyXmr {
@@ -179,6 +255,9 @@ pub fn register_modules(context: Arc<Context>) -> Result<RpcModule<Arc<Context>>
                 monero_receive_address,
                 seller,
                 swap_id: Uuid::new_v4(),
+                bitcoin_cancel_timelock,
+                bitcoin_punish_timelock,
+                skip_preflight,
             },
             &context,
         )
@@ -0,0 +1,127 @@
+//! Content signed with the libp2p identity of a peer.
+//!
+//! The signature covers the payload exactly as it is transmitted, prefixed
+//! with a domain that separates the different uses of an identity. The
+//! payload is only parsed after its signature was checked, it is never
+//! re-serialized for verification.
+
+use anyhow::{bail, Context, Result};
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Signed {
+    /// The JSON encoded content, exactly as it was signed.
+    #[serde(with = "hex_bytes")]
+    pub payload: Vec<u8>,
+    /// The protobuf encoded libp2p public key of the signer.
+    #[serde(with = "hex_bytes")]
+    pub public_key: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl Signed {
+    /// Serializes the content once and signs the resulting bytes.
+    pub fn sign<T>(domain: &str, content: &T, identity: &Keypair) -> Result<Self>
+    where
+        T: Serialize,
+    {
+        let payload = serde_json::to_vec(content).context("Failed to serialize signed content")?;
+        let signature = identity
+            .sign(&message(domain, &payload))
+            .context("Failed to sign content")?;
+
+        Ok(Self {
+            payload,
+            public_key: identity.public().to_protobuf_encoding(),
+            signature,
+        })
+    }
+
+    /// Checks the signature over the payload and returns the signer together
+    /// with the parsed content.
+    pub fn verify<T>(&self, domain: &str) -> Result<(PeerId, T)>
+    where
+        T: DeserializeOwned,
+    {
+        let public_key = PublicKey::from_protobuf_encoding(&self.public_key)
+            .context("Failed to decode public key of signer")?;
+        if !public_key.verify(&message(domain, &self.payload), &self.signature) {
+            bail!("Signature is invalid");
+        }
+
+        let content =
+            serde_json::from_slice(&self.payload).context("Failed to parse signed content")?;
+
+        Ok((public_key.to_peer_id(), content))
+    }
+}
+
+/// The domain is terminated by a zero byte, which cannot be part of it.
+fn message(domain: &str, payload: &[u8]) -> Vec<u8> {
+    [domain.as_bytes(), &[0], payload].concat()
+}
+
+mod hex_bytes {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+        hex::decode(hex).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const DOMAIN: &str = "xmr-btc-swap/test";
+
+    #[test]
+    fn verifies_the_payload_as_it_was_signed() {
+        let identity = Keypair::generate_ed25519();
+        let payload = br#"{ "b": 2,   "a": 1 }"#.to_vec();
+        let signed = Signed {
+            public_key: identity.public().to_protobuf_encoding(),
+            signature: identity.sign(&message(DOMAIN, &payload)).unwrap(),
+            payload,
+        };
+
+        let (signer, content) = signed.verify::<Value>(DOMAIN).unwrap();
+
+        assert_eq!(signer, identity.public().to_peer_id());
+        assert_eq!(content, json!({ "a": 1, "b": 2 }));
+    }
+
+    #[test]
+    fn tampered_payload_does_not_verify() {
+        let identity = Keypair::generate_ed25519();
+        let mut signed = Signed::sign(DOMAIN, &json!({ "a": 1 }), &identity).unwrap();
+        signed.payload = serde_json::to_vec(&json!({ "a": 2 })).unwrap();
+
+        assert!(signed.verify::<Value>(DOMAIN).is_err());
+    }
+
+    #[test]
+    fn signature_does_not_verify_in_other_domain() {
+        let identity = Keypair::generate_ed25519();
+        let signed = Signed::sign(DOMAIN, &json!({ "a": 1 }), &identity).unwrap();
+
+        assert!(signed.verify::<Value>("xmr-btc-swap/other").is_err());
+    }
+}
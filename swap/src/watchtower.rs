@@ -0,0 +1,411 @@
+//! Cancels and refunds a swap on behalf of Bob while his CLI is offline.
+//!
+//! Bob hands the watchtower a [`Bundle`] with the cancel and refund
+//! transactions, both already signed by Alice and Bob, and the ids and
+//! timelocks needed to follow the swap. The watchtower publishes the cancel
+//! transaction once the cancel timelock expired and the refund transaction
+//! right after it.
+//!
+//! The signed refund transaction reveals Bob's Monero key share to Alice, who
+//! holds the matching encrypted signature. A bundle must therefore never reach
+//! Alice: if she got hold of it before redeeming, she could take both the
+//! Bitcoin and the Monero. The watchtower itself cannot learn the key share
+//! and holds no private keys.
+
+pub mod command;
+
+use crate::bitcoin::{
+    self, build_shared_output_descriptor, extract_ecdsa_sig, verify_sig, CancelTimelock, PublicKey,
+    PunishTimelock, Transaction, Txid,
+};
+use crate::env;
+use ::bitcoin::util::sighash::SighashCache;
+use ::bitcoin::EcdsaSighashType;
+use anyhow::{bail, Context, Result};
+use bdk::miniscript::Descriptor;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::Instrument;
+use url::Url;
+use uuid::Uuid;
+
+/// How often the bundle directory is checked for new bundles.
+const BUNDLE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long to wait before watching a bundle again after an error.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Everything the watchtower needs to cancel and refund a swap.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub swap_id: Uuid,
+    #[serde(with = "crate::bitcoin::network")]
+    pub network: bitcoin::Network,
+    pub tx_lock_id: Txid,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub lock_amount: bitcoin::Amount,
+    #[serde_as(as = "DisplayFromStr")]
+    pub lock_output_descriptor: Descriptor<::bitcoin::PublicKey>,
+    /// The keys `lock_output_descriptor` is built from.
+    pub alice_public_key: PublicKey,
+    pub bob_public_key: PublicKey,
+    pub cancel_timelock: CancelTimelock,
+    pub punish_timelock: PunishTimelock,
+    /// The cancel transaction signed by Alice and Bob.
+    pub tx_cancel: Transaction,
+    /// Bob's refund transaction signed by Alice and Bob, spends `tx_cancel`.
+    pub tx_refund: Transaction,
+    /// The address the refund transaction pays to.
+    pub refund_address: bitcoin::Address,
+}
+
+impl Bundle {
+    pub fn tx_lock(&self) -> (Txid, ::bitcoin::Script) {
+        (self.tx_lock_id, self.lock_output_descriptor.script_pubkey())
+    }
+
+    pub fn tx_cancel(&self) -> (Txid, ::bitcoin::Script) {
+        (
+            self.tx_cancel.txid(),
+            self.tx_cancel.output[0].script_pubkey.clone(),
+        )
+    }
+
+    pub fn tx_refund(&self) -> (Txid, ::bitcoin::Script) {
+        (self.tx_refund.txid(), self.refund_address.script_pubkey())
+    }
+
+    /// Checks that the transactions of the bundle belong together and that
+    /// the cancel transaction is signed by both keys of the lock output.
+    pub fn validate(&self) -> Result<()> {
+        let spends = |tx: &Transaction, txid: Txid| match tx.input.as_slice() {
+            [input] => input.previous_output.txid == txid,
+            _ => false,
+        };
+
+        if !spends(&self.tx_cancel, self.tx_lock_id) {
+            bail!("Cancel transaction does not spend the lock transaction");
+        }
+        if !spends(&self.tx_refund, self.tx_cancel.txid()) {
+            bail!("Refund transaction does not spend the cancel transaction");
+        }
+        if !self
+            .tx_refund
+            .output
+            .iter()
+            .any(|output| output.script_pubkey == self.refund_address.script_pubkey())
+        {
+            bail!("Refund transaction does not pay to the refund address");
+        }
+
+        let descriptor = build_shared_output_descriptor(
+            self.alice_public_key.into(),
+            self.bob_public_key.into(),
+        )?;
+        if descriptor != self.lock_output_descriptor {
+            bail!("Lock output descriptor is not built from the keys of the bundle");
+        }
+        self.validate_cancel_signatures()?;
+
+        if self.refund_address.network != self.network {
+            bail!(
+                "Refund address is for Bitcoin network {}, but the bundle is for {}",
+                self.refund_address.network,
+                self.network
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The cancel transaction is only valid if Alice and Bob both signed it
+    /// for the lock output, otherwise the watchtower could never publish it.
+    fn validate_cancel_signatures(&self) -> Result<()> {
+        let script = self.lock_output_descriptor.script_code()?;
+        let digest = SighashCache::new(&self.tx_cancel)
+            .segwit_signature_hash(0, &script, self.lock_amount.to_sat(), EcdsaSighashType::All)
+            .context("Failed to compute signature hash of cancel transaction")?;
+
+        let witness = self.tx_cancel.input[0].witness.to_vec();
+        let sigs = match witness.as_slice() {
+            [sig_1, sig_2, witness_script] if witness_script == script.as_bytes() => [sig_1, sig_2]
+                .into_iter()
+                .map(|sig| extract_ecdsa_sig(sig))
+                .collect::<Result<Vec<_>>>()?,
+            _ => bail!("Cancel transaction is not signed for the lock output"),
+        };
+
+        for (party, key) in [
+            ("Alice", self.alice_public_key),
+            ("Bob", self.bob_public_key),
+        ] {
+            if !sigs
+                .iter()
+                .any(|sig| verify_sig(&key, &digest, sig).is_ok())
+            {
+                bail!("Cancel transaction is not signed by {}", party);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// What happened to a swap the watchtower was watching.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// The refund transaction was published and it is final.
+    Refunded(Txid),
+    /// The lock output was spent by something other than the cancel
+    /// transaction, e.g. Alice redeemed or Bob refunded early.
+    LockSpent,
+    /// The cancel output was spent by something other than the refund
+    /// transaction, i.e. Alice punished Bob.
+    CancelSpent,
+}
+
+/// Opens a watch-only wallet that only knows the lock output of the bundle.
+pub async fn open_wallet(
+    electrum_rpc_url: Url,
+    data_dir: &Path,
+    bundle: &Bundle,
+    env_config: env::Config,
+) -> Result<bitcoin::Wallet> {
+    bitcoin::Wallet::new_watch_only(
+        electrum_rpc_url,
+        data_dir.join("wallets").join(bundle.swap_id.to_string()),
+        &bundle.lock_output_descriptor.to_string(),
+        env_config,
+        1,
+    )
+    .await
+}
+
+/// Waits for the cancel timelock of the swap to expire, publishes the cancel
+/// transaction of the bundle and then its refund transaction.
+///
+/// Transactions that were already published, e.g. by Bob himself, are not
+/// published again.
+pub async fn watch(bundle: &Bundle, bitcoin_wallet: &bitcoin::Wallet) -> Result<Outcome> {
+    if !bitcoin_wallet
+        .status_of_script(&bundle.tx_refund())
+        .await?
+        .has_been_seen()
+    {
+        if !bitcoin_wallet
+            .status_of_script(&bundle.tx_cancel())
+            .await?
+            .has_been_seen()
+        {
+            tracing::info!(cancel_timelock = %bundle.cancel_timelock, "Waiting for cancel timelock to expire");
+
+            bitcoin_wallet
+                .subscribe_to(bundle.tx_lock())
+                .await
+                .wait_until_confirmed_with(bundle.cancel_timelock)
+                .await?;
+
+            if let Err(error) = bitcoin_wallet
+                .broadcast(bundle.tx_cancel.clone(), "cancel")
+                .await
+            {
+                let cancel_seen = bitcoin_wallet
+                    .status_of_script(&bundle.tx_cancel())
+                    .await?
+                    .has_been_seen();
+
+                if !cancel_seen {
+                    if bitcoin_wallet.is_spent(&bundle.tx_lock()).await? {
+                        return Ok(Outcome::LockSpent);
+                    }
+
+                    return Err(error);
+                }
+            }
+        }
+
+        bitcoin_wallet
+            .subscribe_to(bundle.tx_cancel())
+            .await
+            .wait_until_seen()
+            .await?;
+
+        tracing::info!("Swap cancelled, publishing the refund transaction");
+
+        if let Err(error) = bitcoin_wallet
+            .broadcast(bundle.tx_refund.clone(), "refund")
+            .await
+        {
+            let refund_seen = bitcoin_wallet
+                .status_of_script(&bundle.tx_refund())
+                .await?
+                .has_been_seen();
+
+            if !refund_seen {
+                if bitcoin_wallet.is_spent(&bundle.tx_cancel()).await? {
+                    return Ok(Outcome::CancelSpent);
+                }
+
+                return Err(error);
+            }
+        }
+    }
+
+    bitcoin_wallet
+        .subscribe_to(bundle.tx_refund())
+        .await
+        .wait_until_final()
+        .await?;
+
+    Ok(Outcome::Refunded(bundle.tx_refund.txid()))
+}
+
+fn bundle_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("bundles")
+}
+
+fn finished_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("finished")
+}
+
+fn bundle_file_name(swap_id: Uuid) -> String {
+    format!("{}.json", swap_id)
+}
+
+/// Stores the bundle so it is watched by [`run`].
+pub async fn add_bundle(data_dir: &Path, bundle: &Bundle, env_config: env::Config) -> Result<()> {
+    bundle.validate()?;
+
+    if bundle.network != env_config.bitcoin_network {
+        bail!(
+            "Bundle is for Bitcoin network {}, but the watchtower runs on {}",
+            bundle.network,
+            env_config.bitcoin_network
+        );
+    }
+
+    let dir = bundle_dir(data_dir);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let path = dir.join(bundle_file_name(bundle.swap_id));
+    tokio::fs::write(&path, serde_json::to_vec_pretty(bundle)?)
+        .await
+        .with_context(|| format!("Failed to write bundle to {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Reads all bundles that are still being watched.
+pub async fn load_bundles(data_dir: &Path) -> Result<Vec<Bundle>> {
+    let dir = bundle_dir(data_dir);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut bundles = vec![];
+    let mut entries = tokio::fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path
+            .extension()
+            .map_or(true, |extension| extension != "json")
+        {
+            continue;
+        }
+
+        let bundle = tokio::fs::read(&path).await?;
+        let bundle = serde_json::from_slice(&bundle)
+            .with_context(|| format!("Failed to read bundle from {}", path.display()))?;
+        bundles.push(bundle);
+    }
+
+    Ok(bundles)
+}
+
+/// Moves the bundle out of the set of watched bundles.
+async fn finish_bundle(data_dir: &Path, swap_id: Uuid) -> Result<()> {
+    let dir = finished_dir(data_dir);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    tokio::fs::rename(
+        bundle_dir(data_dir).join(bundle_file_name(swap_id)),
+        dir.join(bundle_file_name(swap_id)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Watches all stored bundles until they are finished. Bundles added while
+/// running are picked up within [`BUNDLE_POLL_INTERVAL`].
+pub async fn run(electrum_rpc_url: Url, data_dir: PathBuf, env_config: env::Config) -> Result<()> {
+    let mut watched = HashSet::new();
+
+    loop {
+        for bundle in load_bundles(&data_dir).await? {
+            if !watched.insert(bundle.swap_id) {
+                continue;
+            }
+
+            let electrum_rpc_url = electrum_rpc_url.clone();
+            let data_dir = data_dir.clone();
+            let swap_id = bundle.swap_id;
+
+            tokio::spawn(
+                async move {
+                    loop {
+                        let result = async {
+                            let bitcoin_wallet = open_wallet(
+                                electrum_rpc_url.clone(),
+                                &data_dir,
+                                &bundle,
+                                env_config,
+                            )
+                            .await?;
+
+                            watch(&bundle, &bitcoin_wallet).await
+                        }
+                        .await;
+
+                        match result {
+                            Ok(outcome) => {
+                                match outcome {
+                                    Outcome::Refunded(txid) => {
+                                        tracing::info!(%txid, "Swap refunded")
+                                    }
+                                    Outcome::LockSpent => {
+                                        tracing::info!("Bitcoin lock output was spent without cancelling, nothing left to do")
+                                    }
+                                    Outcome::CancelSpent => {
+                                        tracing::warn!("Bitcoin cancel output was spent without refunding, Bob was punished")
+                                    }
+                                }
+
+                                if let Err(error) = finish_bundle(&data_dir, swap_id).await {
+                                    tracing::warn!("Failed to move finished bundle: {:#}", error);
+                                }
+
+                                return;
+                            }
+                            Err(error) => {
+                                tracing::warn!(
+                                    "Failed to watch swap, retrying in {}s: {:#}",
+                                    RETRY_INTERVAL.as_secs(),
+                                    error
+                                );
+                                tokio::time::sleep(RETRY_INTERVAL).await;
+                            }
+                        }
+                    }
+                }
+                .instrument(tracing::info_span!("swap", %swap_id)),
+            );
+        }
+
+        tokio::time::sleep(BUNDLE_POLL_INTERVAL).await;
+    }
+}
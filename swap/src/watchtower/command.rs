@@ -0,0 +1,182 @@
+use crate::cli::command::{DEFAULT_ELECTRUM_RPC_URL, DEFAULT_ELECTRUM_RPC_URL_TESTNET};
+use crate::env;
+use crate::env::GetConfig;
+use crate::fs::system_data_dir;
+use anyhow::Result;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+use url::Url;
+
+pub fn parse_args<I, T>(raw_args: I) -> Result<Arguments>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = RawArguments::clap().get_matches_from_safe(raw_args)?;
+    let args = RawArguments::from_clap(&matches);
+
+    let testnet = args.testnet;
+
+    let cmd = match args.cmd {
+        RawCommand::Run { electrum_rpc_url } => Command::Run {
+            electrum_rpc_url: match electrum_rpc_url {
+                Some(url) => url,
+                None if testnet => Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)?,
+                None => Url::from_str(DEFAULT_ELECTRUM_RPC_URL)?,
+            },
+        },
+        RawCommand::Add { bundle } => Command::Add { bundle },
+        RawCommand::List => Command::List,
+    };
+
+    Ok(Arguments {
+        json: args.json,
+        data_dir: data_dir(args.data, testnet)?,
+        env_config: env_config(testnet),
+        cmd,
+    })
+}
+
+fn data_dir(data: Option<PathBuf>, testnet: bool) -> Result<PathBuf> {
+    let base_dir = match data {
+        Some(data) => data,
+        None => system_data_dir()?.join("watchtower"),
+    };
+
+    let sub_directory = if testnet { "testnet" } else { "mainnet" };
+
+    Ok(base_dir.join(sub_directory))
+}
+
+fn env_config(is_testnet: bool) -> env::Config {
+    if is_testnet {
+        env::Testnet::get_config()
+    } else {
+        env::Mainnet::get_config()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Arguments {
+    pub json: bool,
+    pub data_dir: PathBuf,
+    pub env_config: env::Config,
+    pub cmd: Command,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Run { electrum_rpc_url: Url },
+    Add { bundle: PathBuf },
+    List,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+#[structopt(
+    name = "swap-watchtower",
+    about = "Cancels and refunds swaps on behalf of an offline CLI",
+    author,
+    version = env!("VERGEN_GIT_DESCRIBE")
+)]
+pub struct RawArguments {
+    #[structopt(long, help = "Watch swaps on testnet", global = true)]
+    pub testnet: bool,
+
+    #[structopt(
+        short,
+        long = "json",
+        help = "Outputs all logs in JSON format instead of plain text"
+    )]
+    pub json: bool,
+
+    #[structopt(
+        short,
+        long = "--data-base-dir",
+        help = "The base data directory for the stored bundles, mainnet and testnet use a separate sub directory"
+    )]
+    pub data: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    pub cmd: RawCommand,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub enum RawCommand {
+    #[structopt(
+        about = "Watch all stored bundles and publish their cancel transactions when due."
+    )]
+    Run {
+        #[structopt(
+            long = "electrum-rpc",
+            help = "Provide the Bitcoin Electrum RPC URL",
+            parse(try_from_str = Url::from_str)
+        )]
+        electrum_rpc_url: Option<Url>,
+    },
+    #[structopt(
+        about = "Store a bundle exported with `swap export-watchtower-bundle` so it is watched."
+    )]
+    Add {
+        #[structopt(long = "bundle", help = "Path to the bundle file", parse(from_os_str))]
+        bundle: PathBuf,
+    },
+    #[structopt(about = "Prints the swaps that are currently watched.")]
+    List,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINARY_NAME: &str = "swap-watchtower";
+
+    #[test]
+    fn given_run_without_electrum_url_uses_default() {
+        let args = parse_args(vec![BINARY_NAME, "run"]).unwrap();
+
+        assert_eq!(
+            args.cmd,
+            Command::Run {
+                electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap()
+            }
+        );
+        assert_eq!(args.env_config, env::Mainnet::get_config());
+    }
+
+    #[test]
+    fn given_testnet_uses_testnet_defaults() {
+        let args = parse_args(vec![BINARY_NAME, "--testnet", "run"]).unwrap();
+
+        assert_eq!(
+            args.cmd,
+            Command::Run {
+                electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET).unwrap()
+            }
+        );
+        assert_eq!(args.env_config, env::Testnet::get_config());
+        assert!(args.data_dir.ends_with("testnet"));
+    }
+
+    #[test]
+    fn given_custom_data_dir_uses_network_sub_directory() {
+        let args = parse_args(vec![
+            BINARY_NAME,
+            "--data-base-dir",
+            "/tmp/watchtower",
+            "add",
+            "--bundle",
+            "bundle.json",
+        ])
+        .unwrap();
+
+        assert_eq!(args.data_dir, PathBuf::from("/tmp/watchtower/mainnet"));
+        assert_eq!(
+            args.cmd,
+            Command::Add {
+                bundle: PathBuf::from("bundle.json")
+            }
+        );
+    }
+}
@@ -0,0 +1,50 @@
+pub mod harness;
+
+use harness::SlowCancelConfig;
+use std::time::Duration;
+use swap::asb::{safely_abort, FixedRate};
+use swap::protocol::alice::AliceState;
+use swap::protocol::{alice, bob, State};
+
+/// The swap is safely aborted by another process while Alice's swap task is
+/// still running. The task has to notice the abort instead of locking the
+/// Monero, because Bob can now refund early.
+#[tokio::test]
+async fn given_swap_aborted_while_alice_runs_alice_does_not_lock_xmr() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, _bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap = tokio::spawn(bob::run(bob_swap));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap_id = alice_swap.swap_id;
+        let alice_db = alice_swap.db.clone();
+        let alice_swap = tokio::spawn(alice::run(alice_swap, FixedRate::default()));
+
+        // The first state Alice saves is the one after seeing the lock transaction
+        loop {
+            if let Ok(State::Alice(AliceState::BtcLockTransactionSeen { .. })) =
+                alice_db.get_state(alice_swap_id).await
+            {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let aborted_state = safely_abort(alice_swap_id, alice_db.clone()).await?;
+        assert!(matches!(aborted_state, AliceState::SafelyAborted));
+
+        let alice_state = alice_swap.await??;
+        ctx.assert_alice_aborted(alice_state).await;
+        assert!(matches!(
+            alice_db.get_state(alice_swap_id).await?,
+            State::Alice(AliceState::SafelyAborted)
+        ));
+
+        let bob_state = bob_swap.await??;
+        ctx.assert_bob_early_refunded(bob_state).await;
+
+        Ok(())
+    })
+    .await;
+}
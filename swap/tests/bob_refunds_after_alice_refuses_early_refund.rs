@@ -0,0 +1,36 @@
+pub mod harness;
+
+use harness::alice_run_until::is_btc_locked;
+use harness::FastCancelConfig;
+use std::convert::TryInto;
+use swap::asb::FixedRate;
+use swap::protocol::alice::AliceState;
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob, Database};
+
+/// Alice stops before locking the Monero but does not abort the swap. She
+/// refuses to sign the early refund transaction, so Bob keeps waiting and
+/// refunds through the cancel transaction once the cancel timelock expired.
+#[tokio::test]
+async fn given_alice_does_not_abort_bob_refunds_after_cancel_timelock() {
+    harness::setup_test(FastCancelConfig, |mut ctx| async move {
+        let (bob_swap, _bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap = tokio::spawn(bob::run(bob_swap));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap_id = alice_swap.swap_id;
+        let alice_db = alice_swap.db.clone();
+        let alice_state = alice::run_until(alice_swap, is_btc_locked, FixedRate::default()).await?;
+        assert!(matches!(alice_state, AliceState::BtcLocked { .. }));
+
+        let bob_state = bob_swap.await??;
+        assert!(matches!(bob_state, BobState::BtcRefunded(..)));
+        ctx.assert_bob_refunded(bob_state).await;
+
+        let alice_state: AliceState = alice_db.get_state(alice_swap_id).await?.try_into()?;
+        assert!(matches!(alice_state, AliceState::BtcLocked { .. }));
+
+        Ok(())
+    })
+    .await;
+}
@@ -0,0 +1,33 @@
+pub mod harness;
+
+use harness::alice_run_until::is_btc_locked;
+use harness::SlowCancelConfig;
+use swap::asb::{safely_abort, FixedRate};
+use swap::protocol::alice::AliceState;
+use swap::protocol::{alice, bob};
+
+/// Alice aborts the swap before locking the Monero. Bob asks her to sign the
+/// early refund transaction and takes back his Bitcoin without waiting for the
+/// cancel timelock.
+#[tokio::test]
+async fn given_alice_aborts_before_xmr_lock_bob_refunds_early() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, _bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap = tokio::spawn(bob::run(bob_swap));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap_id = alice_swap.swap_id;
+        let alice_db = alice_swap.db.clone();
+        let alice_state = alice::run_until(alice_swap, is_btc_locked, FixedRate::default()).await?;
+        assert!(matches!(alice_state, AliceState::BtcLocked { .. }));
+
+        let alice_state = safely_abort(alice_swap_id, alice_db).await?;
+        assert!(matches!(alice_state, AliceState::SafelyAborted));
+
+        let bob_state = bob_swap.await??;
+        ctx.assert_bob_early_refunded(bob_state).await;
+
+        Ok(())
+    })
+    .await;
+}
@@ -20,6 +20,7 @@ use swap::database::{AccessMode, SqliteDatabase};
 use swap::env::{Config, GetConfig};
 use swap::fs::ensure_directory_exists;
 use swap::network::rendezvous::XmrBtcNamespace;
+use swap::network::swap_setup::{TimelockBounds, Timelocks};
 use swap::network::swarm;
 use swap::protocol::alice::{AliceState, Swap};
 use swap::protocol::bob::BobState;
@@ -135,6 +136,7 @@ where
         bob_starting_balances,
         bob_bitcoin_wallet,
         bob_monero_wallet,
+        electrum_rpc_url: electrum_rpc_url(electrs_rpc_port),
     };
 
     testfn(test).await.unwrap()
@@ -241,11 +243,13 @@ async fn start_alice(
     let max_buy = bitcoin::Amount::from_sat(u64::MAX);
     let latest_rate = FixedRate::default();
     let resume_only = false;
+    let timelock_bounds = TimelockBounds::fixed(Timelocks::from(env_config));
 
     let mut swarm = swarm::asb(
         seed,
         min_buy,
         max_buy,
+        timelock_bounds,
         latest_rate,
         resume_only,
         env_config,
@@ -257,6 +261,7 @@ async fn start_alice(
 
     let (event_loop, swap_handle) = asb::EventLoop::new(
         swarm,
+        seed.derive_libp2p_identity(),
         env_config,
         bitcoin_wallet,
         monero_wallet,
@@ -264,6 +269,7 @@ async fn start_alice(
         FixedRate::default(),
         min_buy,
         max_buy,
+        timelock_bounds,
         None,
     )
     .unwrap();
@@ -305,13 +311,8 @@ async fn init_test_wallets(
     .await
     .unwrap();
 
-    let electrum_rpc_url = {
-        let input = format!("tcp://@localhost:{}", electrum_rpc_port);
-        Url::parse(&input).unwrap()
-    };
-
     let btc_wallet = swap::bitcoin::Wallet::new(
-        electrum_rpc_url,
+        electrum_rpc_url(electrum_rpc_port),
         datadir,
         seed.derive_extended_private_key(env_config.bitcoin_network)
             .expect("Could not create extended private key from seed"),
@@ -483,11 +484,64 @@ impl BobParams {
             self.monero_wallet.get_main_address(),
             self.bitcoin_wallet.new_address().await?,
             btc_amount,
+            None,
+            None,
         );
 
         Ok((swap, event_loop))
     }
 
+    /// Creates a swap that runs through the event loop of an existing swap.
+    pub async fn new_parallel_swap(
+        &self,
+        event_loop_handle: &cli::EventLoopHandle,
+        btc_amount: bitcoin::Amount,
+    ) -> Result<bob::Swap> {
+        let swap_id = Uuid::new_v4();
+
+        let db = Arc::new(SqliteDatabase::open(&self.db_path, AccessMode::ReadWrite).await?);
+        db.insert_peer_id(swap_id, self.alice_peer_id).await?;
+
+        let handle = event_loop_handle.new_swap(swap_id).await?;
+
+        Ok(bob::Swap::new(
+            db,
+            swap_id,
+            self.bitcoin_wallet.clone(),
+            self.monero_wallet.clone(),
+            self.env_config,
+            handle,
+            self.monero_wallet.get_main_address(),
+            self.bitcoin_wallet.new_address().await?,
+            btc_amount,
+            None,
+            None,
+        ))
+    }
+
+    /// Resumes a swap from the database through the event loop of another
+    /// swap.
+    pub async fn parallel_swap_from_db(
+        &self,
+        event_loop_handle: &cli::EventLoopHandle,
+        swap_id: Uuid,
+    ) -> Result<bob::Swap> {
+        let db = Arc::new(SqliteDatabase::open(&self.db_path, AccessMode::ReadWrite).await?);
+
+        let handle = event_loop_handle.new_swap(swap_id).await?;
+
+        bob::Swap::from_db(
+            db,
+            swap_id,
+            self.bitcoin_wallet.clone(),
+            self.monero_wallet.clone(),
+            self.env_config,
+            handle,
+            self.monero_wallet.get_main_address(),
+        )
+        .await
+    }
+
     pub async fn new_eventloop(
         &self,
         swap_id: Uuid,
@@ -551,9 +605,19 @@ pub struct TestContext {
     bob_starting_balances: StartingBalances,
     bob_bitcoin_wallet: Arc<bitcoin::Wallet>,
     bob_monero_wallet: Arc<monero::Wallet>,
+
+    electrum_rpc_url: Url,
 }
 
 impl TestContext {
+    pub fn electrum_rpc_url(&self) -> Url {
+        self.electrum_rpc_url.clone()
+    }
+
+    pub fn env_config(&self) -> Config {
+        self.env_config
+    }
+
     pub async fn get_bob_context(self) -> api::Context {
         api::Context::for_harness(
             self.bob_params.seed,
@@ -600,6 +664,26 @@ impl TestContext {
         (swap, BobApplicationHandle(join_handle))
     }
 
+    /// Starts another swap that shares the event loop of `bob_swap`.
+    pub async fn bob_parallel_swap(&mut self, bob_swap: &bob::Swap) -> bob::Swap {
+        self.bob_params
+            .new_parallel_swap(&bob_swap.event_loop_handle, self.btc_amount)
+            .await
+            .unwrap()
+    }
+
+    /// Resumes the swap with `swap_id` on the event loop of `bob_swap`.
+    pub async fn bob_parallel_swap_from_db(
+        &mut self,
+        bob_swap: &bob::Swap,
+        swap_id: Uuid,
+    ) -> bob::Swap {
+        self.bob_params
+            .parallel_swap_from_db(&bob_swap.event_loop_handle, swap_id)
+            .await
+            .unwrap()
+    }
+
     pub async fn stop_and_resume_bob_from_db(
         &mut self,
         join_handle: BobApplicationHandle,
@@ -655,6 +739,18 @@ impl TestContext {
         .unwrap();
     }
 
+    pub async fn assert_alice_aborted(&self, state: AliceState) {
+        assert!(matches!(state, AliceState::SafelyAborted));
+
+        assert_eventual_balance(
+            self.alice_monero_wallet.as_ref(),
+            Ordering::Equal,
+            self.alice_starting_balances.xmr,
+        )
+        .await
+        .unwrap();
+    }
+
     pub async fn assert_alice_punished(&self, state: AliceState) {
         assert!(matches!(state, AliceState::BtcPunished { .. }));
 
@@ -696,6 +792,19 @@ impl TestContext {
         .unwrap();
     }
 
+    /// Asserts that Bob received the Monero of all swaps he ran in parallel.
+    /// Each swap pays a fee for sweeping the Monero, so receiving more than
+    /// the amount of all but one swap proves that all of them arrived.
+    pub async fn assert_bob_redeemed_parallel_swaps(&self, swaps: u64) {
+        assert_eventual_balance(
+            self.bob_monero_wallet.as_ref(),
+            Ordering::Greater,
+            self.bob_redeemed_xmr_balance() + self.xmr_amount * (swaps - 1),
+        )
+        .await
+        .unwrap();
+    }
+
     pub async fn assert_bob_refunded(&self, state: BobState) {
         self.bob_bitcoin_wallet.sync().await.unwrap();
 
@@ -737,6 +846,44 @@ impl TestContext {
         .unwrap();
     }
 
+    pub async fn assert_bob_early_refunded(&self, state: BobState) {
+        self.bob_bitcoin_wallet.sync().await.unwrap();
+
+        let lock_tx_id = if let BobState::BtcEarlyRefunded(state3) = state {
+            state3.tx_lock_id()
+        } else {
+            panic!("Bob is not in btc early refunded state: {:?}", state);
+        };
+        let lock_tx_bitcoin_fee = self
+            .bob_bitcoin_wallet
+            .transaction_fee(lock_tx_id)
+            .await
+            .unwrap();
+
+        let btc_balance_after_swap = self.bob_bitcoin_wallet.balance().await.unwrap();
+
+        // The early refund transaction pays the same fee as the refund
+        // transaction
+        let refund_fee = self
+            .alice_bitcoin_wallet
+            .estimate_fee(TxRefund::weight(), self.btc_amount)
+            .await
+            .expect("To estimate fee correctly");
+
+        let bob_refunded_early = btc_balance_after_swap
+            == self.bob_starting_balances.btc - lock_tx_bitcoin_fee - refund_fee;
+
+        assert!(bob_refunded_early);
+
+        assert_eventual_balance(
+            self.bob_monero_wallet.as_ref(),
+            Ordering::Equal,
+            self.bob_refunded_xmr_balance(),
+        )
+        .await
+        .unwrap();
+    }
+
     pub async fn assert_bob_punished(&self, state: BobState) {
         assert_eventual_balance(
             self.bob_bitcoin_wallet.as_ref(),
@@ -963,6 +1110,11 @@ async fn init_bitcoind(node_url: Url, spendable_quantity: u32) -> Result<Client>
     Ok(bitcoind_client)
 }
 
+fn electrum_rpc_url(port: u16) -> Url {
+    let input = format!("tcp://@localhost:{}", port);
+    Url::parse(&input).unwrap()
+}
+
 /// Send Bitcoin to the specified address, limited to the spendable bitcoin
 /// quantity.
 pub async fn mint(node_url: Url, address: bitcoin::Address, amount: bitcoin::Amount) -> Result<()> {
@@ -994,6 +1146,10 @@ struct Containers<'a> {
 pub mod alice_run_until {
     use swap::protocol::alice::AliceState;
 
+    pub fn is_btc_locked(state: &AliceState) -> bool {
+        matches!(state, AliceState::BtcLocked { .. })
+    }
+
     pub fn is_xmr_lock_transaction_sent(state: &AliceState) -> bool {
         matches!(state, AliceState::XmrLockTransactionSent { .. })
     }
@@ -0,0 +1,74 @@
+pub mod harness;
+
+use harness::bob_run_until::{is_btc_locked, is_xmr_locked};
+use harness::SlowCancelConfig;
+use swap::asb::FixedRate;
+use swap::protocol::alice::AliceState;
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob};
+
+/// Bob runs two swaps with Alice over the same event loop. The transfer proofs
+/// and encrypted signatures are routed to the swap they belong to, and both
+/// swaps redeem their Monero through the shared Monero wallet at the same
+/// time.
+#[tokio::test]
+async fn parallel_bobs_share_event_loop() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap_1, bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_1_id = bob_swap_1.id;
+        let bob_swap_2 = ctx.bob_parallel_swap(&bob_swap_1).await;
+        let bob_swap_2_id = bob_swap_2.id;
+
+        // Lock the bitcoin of the first swap before the second one is set up,
+        // otherwise both lock transactions spend the same outputs
+        let bob_swap_1 = tokio::spawn(bob::run_until(bob_swap_1, is_btc_locked));
+        let alice_swap_1 = ctx.alice_next_swap().await;
+        let alice_swap_1 = tokio::spawn(alice::run(alice_swap_1, FixedRate::default()));
+
+        let bob_state_1 = bob_swap_1.await??;
+        assert!(matches!(bob_state_1, BobState::BtcLocked { .. }));
+
+        let bob_swap_1 = ctx
+            .bob_parallel_swap_from_db(&bob_swap_2, bob_swap_1_id)
+            .await;
+        bob_swap_2.bitcoin_wallet.sync().await?;
+
+        let bob_swap_1 = tokio::spawn(bob::run_until(bob_swap_1, is_xmr_locked));
+        let bob_swap_2 = tokio::spawn(bob::run_until(bob_swap_2, is_xmr_locked));
+
+        let alice_swap_2 = ctx.alice_next_swap().await;
+        let alice_swap_2 = tokio::spawn(alice::run(alice_swap_2, FixedRate::default()));
+
+        let bob_state_1 = bob_swap_1.await??;
+        assert!(matches!(bob_state_1, BobState::XmrLocked { .. }));
+        let bob_state_2 = bob_swap_2.await??;
+        assert!(matches!(bob_state_2, BobState::XmrLocked { .. }));
+
+        // Continue both swaps at once, so that they redeem the Monero at the
+        // same time
+        let (bob_swap_1, _bob_join_handle) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_1_id)
+            .await;
+        let bob_swap_2 = ctx
+            .bob_parallel_swap_from_db(&bob_swap_1, bob_swap_2_id)
+            .await;
+
+        let bob_swap_1 = tokio::spawn(bob::run(bob_swap_1));
+        let bob_swap_2 = tokio::spawn(bob::run(bob_swap_2));
+
+        let bob_state_1 = bob_swap_1.await??;
+        assert!(matches!(bob_state_1, BobState::XmrRedeemed { .. }));
+        let bob_state_2 = bob_swap_2.await??;
+        assert!(matches!(bob_state_2, BobState::XmrRedeemed { .. }));
+
+        ctx.assert_bob_redeemed_parallel_swaps(2).await;
+
+        let alice_state_1 = alice_swap_1.await??;
+        assert!(matches!(alice_state_1, AliceState::BtcRedeemed { .. }));
+        let alice_state_2 = alice_swap_2.await??;
+        assert!(matches!(alice_state_2, AliceState::BtcRedeemed { .. }));
+
+        Ok(())
+    })
+    .await;
+}
@@ -406,7 +406,10 @@ mod test {
                     .listen_for_swap_force_suspension()
                     .await
                     .unwrap();
-                ctx.swap_lock.release_swap_lock().await.unwrap();
+                ctx.swap_lock
+                    .release_swap_lock(Uuid::parse_str(SWAP_ID).unwrap())
+                    .await
+                    .unwrap();
             });
 
             let response: HashMap<String, String> = client
@@ -0,0 +1,71 @@
+pub mod harness;
+
+use harness::alice_run_until::is_xmr_lock_transaction_sent;
+use harness::bob_run_until::is_xmr_locked;
+use harness::FastCancelConfig;
+use swap::asb::FixedRate;
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob};
+use swap::watchtower::{self, Outcome};
+use tempfile::tempdir;
+
+/// Bob locks Btc and Alice locks Xmr. Bob hands his bundle to the watchtower
+/// and goes offline. The watchtower cancels once the cancel timelock expired
+/// and refunds. Bob comes back online and finds the swap refunded.
+#[tokio::test]
+async fn watchtower_refunds_after_bob_goes_offline() {
+    harness::setup_test(FastCancelConfig, |mut ctx| async move {
+        let (bob_swap, bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+        let bob_swap = tokio::spawn(bob::run_until(bob_swap, is_xmr_locked));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run_until(
+            alice_swap,
+            is_xmr_lock_transaction_sent,
+            FixedRate::default(),
+        ));
+
+        let bob_state = bob_swap.await??;
+        let state6 = if let BobState::XmrLocked(state4) = bob_state {
+            state4.cancel()
+        } else {
+            panic!("Bob in unexpected state {}", bob_state);
+        };
+        let bundle = state6.watchtower_bundle(bob_swap_id)?;
+        bob_join_handle.abort();
+
+        bundle.validate()?;
+        let mut tampered = bundle.clone();
+        tampered.tx_refund = bundle.tx_cancel.clone();
+        assert!(tampered.validate().is_err());
+        let mut tampered = bundle.clone();
+        tampered.alice_public_key = tampered.bob_public_key;
+        assert!(tampered.validate().is_err());
+        let mut tampered = bundle.clone();
+        tampered.lock_amount = bundle.lock_amount + swap::bitcoin::Amount::from_sat(1);
+        assert!(tampered.validate().is_err());
+
+        alice_swap.await??;
+
+        let data_dir = tempdir()?;
+        let watchtower_wallet = watchtower::open_wallet(
+            ctx.electrum_rpc_url(),
+            data_dir.path(),
+            &bundle,
+            ctx.env_config(),
+        )
+        .await?;
+        let outcome = watchtower::watch(&bundle, &watchtower_wallet).await?;
+        assert_eq!(outcome, Outcome::Refunded(bundle.tx_refund.txid()));
+
+        let (bob_swap, _) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+            .await;
+        let bob_state = bob::run(bob_swap).await?;
+        ctx.assert_bob_refunded(bob_state).await;
+
+        Ok(())
+    })
+    .await;
+}